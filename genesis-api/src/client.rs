@@ -2,6 +2,7 @@ use crate::proto::metrics::NodeSystemMetrics;
 use crate::proto::metrics::metrics_service_client::MetricsServiceClient;
 use crate::proto::types::{CpuMetrics, DiskMetrics, DiskMetricsItem, MemMetrics, SystemMetrics};
 
+#[allow(dead_code)]
 mod proto;
 
 #[tokio::main]
@@ -12,3 +12,38 @@ pub fn _default_username() -> String {
 pub fn _default_recording_path() -> String {
     "./".to_owned()
 }
+
+#[inline]
+pub const fn _default_keepalive_interval() -> u64 {
+    30
+}
+
+#[inline]
+pub const fn _default_keepalive_max() -> usize {
+    3
+}
+
+#[inline]
+pub const fn _default_reconnect_attempts() -> u32 {
+    5
+}
+
+#[inline]
+pub const fn _default_reconnect_backoff() -> u64 {
+    1000
+}
+
+#[inline]
+pub const fn _default_reconnect_max_backoff() -> u64 {
+    30000
+}
+
+#[inline]
+pub const fn _default_telnet_port() -> u16 {
+    23
+}
+
+#[inline]
+pub const fn _default_telnet_connect_timeout() -> u64 {
+    10
+}
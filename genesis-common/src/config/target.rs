@@ -1,6 +1,7 @@
 use super::defaults::*;
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 #[derive(Debug, Default, Deserialize, Serialize, Builder, Clone)]
 #[builder(setter(into))]
 pub struct TargetSSHOptions {
@@ -12,10 +13,295 @@ pub struct TargetSSHOptions {
     #[serde(default)]
     #[builder(default)]
     pub allow_insecure_algos: Option<bool>,
+    /// 算法配置, 未指定预置集时按 `allow_insecure_algos` 选择
+    #[serde(default)]
+    #[builder(default)]
+    pub algorithms: SSHAlgorithms,
     #[serde(default)]
     pub auth: SSHTargetAuth,
     #[serde(default)]
     pub pty_request: PtyRequest,
+    #[serde(default)]
+    #[builder(default)]
+    pub host_key_policy: HostKeyPolicy,
+    /// 跳板机列表, 按顺序依次连接
+    #[serde(default)]
+    #[builder(default)]
+    pub jump_hosts: Vec<SSHJumpHost>,
+    #[serde(default)]
+    #[builder(default)]
+    pub keepalive: SSHKeepalive,
+    #[serde(default)]
+    #[builder(default)]
+    pub reconnect: ReconnectPolicy,
+    /// 代理转发, 需按资产显式开启
+    #[serde(default)]
+    #[builder(default)]
+    pub agent_forward: SSHAgentForward,
+    /// 按方向限速, 默认不限制
+    #[serde(default)]
+    #[builder(default)]
+    pub rate_limit: SSHRateLimit,
+    /// 会话环境变量, 在请求shell前发送
+    #[serde(default)]
+    #[builder(default)]
+    pub env: SSHEnvironment,
+    /// 命令提权, 默认不提权
+    #[serde(default)]
+    #[builder(default)]
+    pub elevation: SSHElevation,
+}
+
+/// 提权方式
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum ElevationMode {
+    #[default]
+    #[serde(rename = "none")]
+    None,
+    /// `sudo cmd`, 密码为登录账户密码
+    #[serde(rename = "sudo")]
+    Sudo,
+    /// `sudo -i cmd`, 以目标用户的登录shell执行
+    #[serde(rename = "sudo-i")]
+    SudoLogin,
+    /// `su - user -c cmd`, 密码为目标用户密码
+    #[serde(rename = "su")]
+    Su,
+}
+
+/// 命令提权, 密码在出现密码提示时自动应答, 不回显至终端及录像
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SSHElevation {
+    pub mode: ElevationMode,
+    /// 目标用户, 为空时为root
+    pub user: Option<String>,
+    /// 提权密码, 与登录凭证分开保存
+    pub password: Option<String>,
+}
+
+impl SSHElevation {
+    /// 目标用户名仅允许 `[A-Za-z0-9._-]` 且不以 `-` 开头, 避免拼接提权命令时被解析为选项或shell语法
+    pub fn is_valid_user(user: &str) -> bool {
+        !user.is_empty()
+            && !user.starts_with('-')
+            && user
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+    }
+}
+
+/// 会话带宽限制, 令牌桶实现, 单位为字节/秒, 0 表示不限制
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SSHRateLimit {
+    /// 发往目标
+    pub upload: u64,
+    /// 目标返回
+    pub download: u64,
+    /// 突发容量, 0 时等同于一秒的速率
+    pub burst: u64,
+}
+
+/// 会话环境变量, 以env请求发送, 服务端仅接受 AcceptEnv 中的变量
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SSHEnvironment {
+    /// 变量名及值, 如 LANG、TZ、PS1、HISTFILE
+    pub vars: BTreeMap<String, String>,
+    /// 与服务端 AcceptEnv 一致的变量名, 支持 `*` 通配, 其余变量不发送env请求
+    pub accept_env: Vec<String>,
+    /// 被拒绝或未在 `accept_env` 中的变量, 在shell就绪后以 export 设置
+    pub inline_fallback: bool,
+}
+
+impl Default for SSHEnvironment {
+    fn default() -> Self {
+        // 与 OpenSSH 默认的 `AcceptEnv LANG LC_*` 一致
+        Self {
+            vars: BTreeMap::new(),
+            accept_env: vec!["LANG".to_string(), "LC_*".to_string()],
+            inline_fallback: true,
+        }
+    }
+}
+
+impl SSHEnvironment {
+    /// 合并变量, 同名时覆盖
+    pub fn merge(&mut self, vars: &BTreeMap<String, String>) {
+        self.vars
+            .extend(vars.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+
+    /// 变量名是否匹配 `accept_env`
+    pub fn accepts(&self, name: &str) -> bool {
+        self.accept_env
+            .iter()
+            .any(|pattern| match pattern.split_once('*') {
+                Some((prefix, suffix)) => {
+                    name.len() >= prefix.len() + suffix.len()
+                        && name.starts_with(prefix)
+                        && name.ends_with(suffix)
+                }
+                None => pattern == name,
+            })
+    }
+}
+
+/// telnet 目标, 登录由用户在终端中完成
+#[derive(Debug, Default, Deserialize, Serialize, Builder, Clone)]
+#[builder(setter(into))]
+pub struct TargetTelnetOptions {
+    pub host: String,
+    #[serde(default = "_default_telnet_port")]
+    pub port: u16,
+    #[serde(default)]
+    #[builder(default)]
+    pub pty_request: PtyRequest,
+    /// 连接超时, 单位秒, 0 表示不限制
+    #[serde(default = "_default_telnet_connect_timeout")]
+    #[builder(default = "_default_telnet_connect_timeout()")]
+    pub connect_timeout: u64,
+}
+
+/// ssh-agent 转发, 目标主机可经转发的代理继续登录其他主机
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SSHAgentForward {
+    pub enabled: bool,
+    /// 本地代理socket, 为空时使用 `SSH_AUTH_SOCK`
+    pub socket: Option<String>,
+}
+
+/// 预置算法集
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum AlgorithmProfile {
+    /// 仅现代算法, 不含sha1及cbc
+    #[serde(rename = "modern")]
+    Modern,
+    /// 兼容老旧设备, 包含sha1、cbc等弱算法
+    #[serde(rename = "compat")]
+    Compat,
+    /// 仅使用NIST曲线、aes及sha2系列算法
+    #[serde(rename = "fips-like")]
+    FipsLike,
+}
+
+/// ssh算法配置, 非空列表覆盖预置集中对应的算法, 按优先级排列
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SSHAlgorithms {
+    pub profile: Option<AlgorithmProfile>,
+    pub kex: Vec<String>,
+    pub cipher: Vec<String>,
+    pub mac: Vec<String>,
+    pub host_key: Vec<String>,
+    pub compression: Vec<String>,
+}
+
+/// 保活配置, 连续 `max` 次未收到保活响应时视为连接断开
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct SSHKeepalive {
+    /// 保活间隔(秒), 0为关闭
+    #[serde(default = "_default_keepalive_interval")]
+    pub interval: u64,
+    /// 允许未响应的保活次数
+    #[serde(default = "_default_keepalive_max")]
+    pub max: usize,
+}
+
+impl Default for SSHKeepalive {
+    fn default() -> Self {
+        Self {
+            interval: _default_keepalive_interval(),
+            max: _default_keepalive_max(),
+        }
+    }
+}
+
+/// 断线重连策略, 默认关闭
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 最大重连次数
+    #[serde(default = "_default_reconnect_attempts")]
+    pub max_attempts: u32,
+    /// 首次重连等待(毫秒), 之后每次翻倍
+    #[serde(default = "_default_reconnect_backoff")]
+    pub backoff: u64,
+    /// 重连等待上限(毫秒)
+    #[serde(default = "_default_reconnect_max_backoff")]
+    pub max_backoff: u64,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_attempts: _default_reconnect_attempts(),
+            backoff: _default_reconnect_backoff(),
+            max_backoff: _default_reconnect_max_backoff(),
+        }
+    }
+}
+
+/// 跳板机
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct SSHJumpHost {
+    pub host: String,
+    #[serde(default = "_default_ssh_port")]
+    pub port: u16,
+    #[serde(default = "_default_username")]
+    pub username: String,
+    #[serde(default)]
+    pub auth: SSHTargetAuth,
+    #[serde(default)]
+    pub host_key_policy: HostKeyPolicy,
+}
+
+impl TargetSSHOptions {
+    /// 按连接顺序返回每一跳的连接参数, 最后一跳为目标主机
+    pub fn hops(&self) -> Vec<TargetSSHOptions> {
+        let mut hops = self
+            .jump_hosts
+            .iter()
+            .map(|jump| TargetSSHOptions {
+                host: jump.host.clone(),
+                port: jump.port,
+                username: jump.username.clone(),
+                allow_insecure_algos: self.allow_insecure_algos,
+                algorithms: self.algorithms.clone(),
+                keepalive: self.keepalive.clone(),
+                auth: jump.auth.clone(),
+                host_key_policy: jump.host_key_policy,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        hops.push(TargetSSHOptions {
+            jump_hosts: vec![],
+            ..self.clone()
+        });
+        hops
+    }
+}
+
+/// 主机密钥校验策略
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum HostKeyPolicy {
+    /// 首次连接时自动记录主机密钥, 密钥变更时由交互调用方确认后替换, 无人应答则拒绝
+    #[default]
+    #[serde(rename = "tofu")]
+    Tofu,
+    /// 首次连接时由交互调用方确认, 确认后记录, 拒绝变更的密钥
+    #[serde(rename = "ask")]
+    Ask,
+    /// 仅允许已记录的主机密钥
+    #[serde(rename = "strict")]
+    Strict,
+    /// 自动记录未知主机密钥, 拒绝变更的密钥
+    #[serde(rename = "accept-new")]
+    AcceptNew,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -38,6 +324,15 @@ impl Default for PtyRequest {
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
 #[serde(untagged)]
 pub enum SSHTargetAuth {
+    /// 需先于密码认证匹配, 依赖必填的 `responses` 字段区分
+    #[serde(rename = "keyboard-interactive")]
+    KeyboardInteractive(SshTargetKeyboardInteractiveAuth),
+    /// 需先于公钥认证匹配, 依赖必填的 `certificate` 字段区分
+    #[serde(rename = "certificate")]
+    Certificate(SshTargetCertificateAuth),
+    /// 需先于密码及公钥认证匹配, 依赖必填的 `agent_socket` 字段区分
+    #[serde(rename = "agent")]
+    Agent(SshTargetAgentAuth),
     #[serde(rename = "password")]
     Password(SshTargetPasswordAuth),
     #[serde(rename = "publickey")]
@@ -55,5 +350,54 @@ pub struct SshTargetPasswordAuth {
     pub password: String,
 }
 
+/// keyboard-interactive 认证, 按提示语匹配预置应答, 未匹配的提示转交调用方
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct SshTargetKeyboardInteractiveAuth {
+    /// 用于应答包含 `password` 的提示
+    #[serde(default)]
+    pub password: Option<String>,
+    pub responses: Vec<SshPromptResponse>,
+}
+
+/// 提示语包含 `prompt` (忽略大小写) 时以 `response` 应答
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct SshPromptResponse {
+    pub prompt: String,
+    pub response: String,
+}
+
+/// OpenSSH 用户证书认证
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct SshTargetCertificateAuth {
+    /// 证书对应的私钥, OpenSSH 格式
+    pub private_key: String,
+    /// 证书内容, 如 `ssh-ed25519-cert-v01@openssh.com AAAA...`
+    pub certificate: String,
+    /// 签发证书的凭证, 连接时证书即将失效则据此重新签发
+    #[serde(default)]
+    pub credential_id: Option<String>,
+}
+
+/// 使用本地 ssh-agent 中的密钥认证
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct SshTargetAgentAuth {
+    /// 代理socket, 为空时使用 `SSH_AUTH_SOCK`
+    pub agent_socket: String,
+    /// 仅尝试指定指纹(SHA256)的密钥, 为空时尝试全部
+    #[serde(default)]
+    pub fingerprints: Vec<String>,
+}
+
+/// 公钥认证, `private_key` 与 `private_key_path` 二选一, 优先使用 `private_key`
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Default)]
-pub struct SshTargetPublicKeyAuth {}
+pub struct SshTargetPublicKeyAuth {
+    /// 私钥内容, 支持 OpenSSH/PEM/PKCS#8 格式
+    #[serde(default)]
+    pub private_key: Option<String>,
+    /// 私钥文件路径
+    #[serde(default)]
+    pub private_key_path: Option<String>,
+    /// 私钥密码
+    #[serde(default)]
+    pub passphrase: Option<String>,
+}
@@ -6,4 +6,6 @@ pub enum NotifyEnum {
     INIT,
     SUCCESS,
     ERROR(String),
+    /// shell退出码
+    EXIT(u32),
 }
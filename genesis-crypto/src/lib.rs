@@ -6,6 +6,9 @@ mod argon;
 mod base64;
 mod blake3;
 mod ed25519;
+mod ssh_ca;
+
+pub use ssh_ca::{SessionCertificate, SshCa, UserCertRequest};
 
 pub trait Crypto: SingerCrypto + VerifyCrypto {}
 
@@ -0,0 +1,191 @@
+use crate::ed25519::Ed25519;
+use rand::rngs::OsRng;
+use russh::keys::ssh_key::certificate::{Builder, CertType};
+use russh::keys::ssh_key::private::Ed25519Keypair;
+use russh::keys::ssh_key::{Certificate, LineEnding, PrivateKey, PublicKey};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 允许时钟偏差, 证书生效时间提前
+const CLOCK_SKEW: Duration = Duration::from_secs(60);
+
+/// 默认授予的证书扩展, 与 ssh-keygen 一致
+const DEFAULT_EXTENSIONS: [&str; 5] = [
+    "permit-X11-forwarding",
+    "permit-agent-forwarding",
+    "permit-port-forwarding",
+    "permit-pty",
+    "permit-user-rc",
+];
+
+/// 用户证书签发参数
+#[derive(Debug, Clone)]
+pub struct UserCertRequest {
+    /// 证书标识, 记录在目标主机的认证日志中
+    pub key_id: String,
+    /// 允许登录的账户
+    pub principals: Vec<String>,
+    /// 有效期
+    pub validity: Duration,
+    /// 允许的客户端地址, CIDR格式
+    pub source_address: Vec<String>,
+    /// 强制执行的命令
+    pub force_command: Option<String>,
+    /// 为空时授予默认扩展
+    pub extensions: Vec<String>,
+}
+
+impl UserCertRequest {
+    pub fn new(
+        key_id: impl Into<String>,
+        principal: impl Into<String>,
+        validity: Duration,
+    ) -> Self {
+        Self {
+            key_id: key_id.into(),
+            principals: vec![principal.into()],
+            validity,
+            source_address: vec![],
+            force_command: None,
+            extensions: vec![],
+        }
+    }
+}
+
+/// 会话证书, 私钥及证书均为 OpenSSH 格式
+#[derive(Debug, Clone)]
+pub struct SessionCertificate {
+    pub private_key: String,
+    pub certificate: String,
+    /// 证书失效时间, unix时间戳(秒)
+    pub valid_before: u64,
+}
+
+/// 基于 ed25519 的 OpenSSH 证书颁发机构
+pub struct SshCa {
+    key: PrivateKey,
+}
+
+impl SshCa {
+    /// 生成新的CA密钥
+    pub fn generate() -> anyhow::Result<Self> {
+        let (sk, _) = Ed25519::generate()?;
+        Self::try_new(sk)
+    }
+
+    /// 由 ed25519 私钥种子创建
+    pub fn try_new(sk: impl AsRef<[u8]>) -> anyhow::Result<Self> {
+        let seed: [u8; 32] = sk
+            .as_ref()
+            .get(..32)
+            .ok_or_else(|| anyhow::anyhow!("ed25519 seed must be 32 bytes"))?
+            .try_into()?;
+        let keypair = Ed25519Keypair::from_seed(&seed);
+        Ok(Self {
+            key: PrivateKey::from(keypair),
+        })
+    }
+
+    /// 由 OpenSSH 格式私钥创建, 如 `ssh-keygen -t ed25519` 生成的密钥
+    pub fn from_openssh(pem: impl AsRef<[u8]>) -> anyhow::Result<Self> {
+        let key = PrivateKey::from_openssh(pem)?;
+        if key.is_encrypted() {
+            anyhow::bail!("encrypted ca key is not supported");
+        }
+        Ok(Self { key })
+    }
+
+    pub fn to_openssh(&self) -> anyhow::Result<String> {
+        Ok(self.key.to_openssh(LineEnding::LF)?.to_string())
+    }
+
+    /// CA公钥, 用于目标主机的 `TrustedUserCAKeys`
+    pub fn public_key(&self) -> anyhow::Result<String> {
+        Ok(self.key.public_key().to_openssh()?)
+    }
+
+    /// 为指定公钥签发用户证书
+    pub fn issue(
+        &self,
+        public_key: &PublicKey,
+        request: &UserCertRequest,
+    ) -> anyhow::Result<Certificate> {
+        if request.principals.is_empty() {
+            anyhow::bail!("certificate principals is empty");
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        let valid_after = now.saturating_sub(CLOCK_SKEW).as_secs();
+        let valid_before = (now + request.validity).as_secs();
+        let mut builder =
+            Builder::new_with_random_nonce(&mut OsRng, public_key, valid_after, valid_before)?;
+        builder
+            .serial(rand::random())?
+            .key_id(request.key_id.clone())?
+            .cert_type(CertType::User)?;
+        for principal in &request.principals {
+            builder.valid_principal(principal.clone())?;
+        }
+        if let Some(command) = &request.force_command {
+            builder.critical_option("force-command", command.clone())?;
+        }
+        if !request.source_address.is_empty() {
+            builder.critical_option("source-address", request.source_address.join(","))?;
+        }
+        if request.extensions.is_empty() {
+            for extension in DEFAULT_EXTENSIONS {
+                builder.extension(extension, "")?;
+            }
+        } else {
+            for extension in &request.extensions {
+                builder.extension(extension.clone(), "")?;
+            }
+        }
+        Ok(builder.sign(&self.key)?)
+    }
+
+    /// 生成临时密钥并签发证书, 每个会话使用独立的密钥
+    pub fn issue_session(&self, request: &UserCertRequest) -> anyhow::Result<SessionCertificate> {
+        let key = PrivateKey::from(Ed25519Keypair::random(&mut OsRng));
+        let certificate = self.issue(key.public_key(), request)?;
+        Ok(SessionCertificate {
+            private_key: key.to_openssh(LineEnding::LF)?.to_string(),
+            valid_before: certificate.valid_before(),
+            certificate: certificate.to_openssh()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use russh::keys::ssh_key::HashAlg;
+
+    #[test]
+    fn test_issue_session_certificate() {
+        let ca = SshCa::generate().unwrap();
+        let mut request = UserCertRequest::new("genesis-test", "root", Duration::from_secs(300));
+        request.source_address = vec!["10.0.0.0/8".into()];
+        request.force_command = Some("/usr/bin/true".into());
+        let session = ca.issue_session(&request).unwrap();
+
+        let certificate = Certificate::from_openssh(&session.certificate).unwrap();
+        let ca_key = PublicKey::from_openssh(&ca.public_key().unwrap()).unwrap();
+        certificate
+            .validate([&ca_key.fingerprint(HashAlg::Sha256)])
+            .unwrap();
+        assert_eq!(certificate.valid_principals(), ["root".to_string()]);
+        assert_eq!(
+            certificate.critical_options().get("source-address"),
+            Some(&"10.0.0.0/8".to_string())
+        );
+        assert_eq!(
+            certificate.critical_options().get("force-command"),
+            Some(&"/usr/bin/true".to_string())
+        );
+        let key = PrivateKey::from_openssh(&session.private_key).unwrap();
+        assert_eq!(key.public_key().key_data(), certificate.public_key());
+
+        // CA密钥可导出后还原
+        let restored = SshCa::from_openssh(ca.to_openssh().unwrap()).unwrap();
+        assert_eq!(restored.public_key().unwrap(), ca.public_key().unwrap());
+    }
+}
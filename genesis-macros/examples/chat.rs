@@ -7,7 +7,6 @@ use futures::stream::SplitSink;
 use std::fmt;
 use std::fmt::Formatter;
 use std::net::SocketAddr;
-use std::os::macos::raw::stat;
 use std::sync::Arc;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::mpsc;
@@ -0,0 +1,187 @@
+//! 从命令输出中提取变量, 供后续节点以 `${node<id>.<name>}` 引用
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+/// 变量引用, 如 `${node1.pid}` 引用节点 `1` 的变量 `pid`
+pub static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\$\{node([^.}\s]+)\.([A-Za-z_][A-Za-z0-9_]*)\}").unwrap());
+
+/// 节点变量
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Capture {
+    /// 正则命名分组, 每个匹配到的分组保存为同名变量
+    Regex { pattern: String },
+    /// 按行及列截取, 列以空白分隔, 下标从0开始, 负数从末尾计数, 未指定列时取整行
+    Slice {
+        name: String,
+        line: i64,
+        #[serde(default)]
+        column: Option<i64>,
+    },
+    /// 输出为json时按路径取值, 如 `data.items.0.id`
+    Json { name: String, path: String },
+}
+
+/// 变量在 `global_params` 中的键
+pub(crate) fn var_key(node_id: &str, name: &str) -> String {
+    format!("node-{node_id}-var-{name}")
+}
+
+impl Capture {
+    /// 声明的变量名
+    pub fn names(&self) -> anyhow::Result<Vec<String>> {
+        match self {
+            Capture::Regex { pattern } => Ok(Regex::new(pattern)?
+                .capture_names()
+                .flatten()
+                .map(str::to_string)
+                .collect()),
+            Capture::Slice { name, .. } | Capture::Json { name, .. } => Ok(vec![name.clone()]),
+        }
+    }
+
+    /// 提取变量, 未匹配时不产生变量
+    pub fn extract(&self, output: &str) -> anyhow::Result<Vec<(String, String)>> {
+        let vars = match self {
+            Capture::Regex { pattern } => {
+                let re = Regex::new(pattern)?;
+                let Some(caps) = re.captures(output) else {
+                    return Ok(vec![]);
+                };
+                re.capture_names()
+                    .flatten()
+                    .filter_map(|name| {
+                        caps.name(name)
+                            .map(|m| (name.to_string(), m.as_str().to_string()))
+                    })
+                    .collect()
+            }
+            Capture::Slice { name, line, column } => {
+                let lines = output.lines().collect::<Vec<_>>();
+                let value = index(&lines, *line).and_then(|line| match column {
+                    Some(column) => {
+                        let columns = line.split_whitespace().collect::<Vec<_>>();
+                        index(&columns, *column)
+                    }
+                    None => Some(line.trim()),
+                });
+                value
+                    .map(|value| (name.clone(), value.to_string()))
+                    .into_iter()
+                    .collect()
+            }
+            Capture::Json { name, path } => {
+                let value = serde_json::from_str::<serde_json::Value>(output.trim())?;
+                let found =
+                    path.split('.')
+                        .filter(|key| !key.is_empty())
+                        .try_fold(&value, |value, key| match value {
+                            serde_json::Value::Array(items) => {
+                                key.parse::<usize>().ok().and_then(|i| items.get(i))
+                            }
+                            value => value.get(key),
+                        });
+                found
+                    .map(|value| match value {
+                        serde_json::Value::String(s) => s.clone(),
+                        value => value.to_string(),
+                    })
+                    .map(|value| (name.clone(), value))
+                    .into_iter()
+                    .collect()
+            }
+        };
+        Ok(vars)
+    }
+}
+
+fn index<'a>(items: &[&'a str], i: i64) -> Option<&'a str> {
+    let i = if i < 0 {
+        items.len().checked_sub(i.unsigned_abs() as usize)?
+    } else {
+        i as usize
+    };
+    items.get(i).copied()
+}
+
+/// 替换命令中的变量引用, 存在未定义的变量时返回其引用
+pub(crate) fn interpolate(cmd: &str, params: &HashMap<String, String>) -> Result<String, String> {
+    let mut missing = None;
+    let result = PLACEHOLDER.replace_all(cmd, |caps: &Captures| {
+        match params.get(&var_key(&caps[1], &caps[2])) {
+            Some(value) => value.clone(),
+            None => {
+                missing.get_or_insert_with(|| caps[0].to_string());
+                caps[0].to_string()
+            }
+        }
+    });
+    match missing {
+        Some(placeholder) => Err(placeholder),
+        None => Ok(result.into_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract() {
+        let output = "UID   PID  CMD\nroot  123  nginx\nroot  456  sshd";
+        let capture = Capture::Regex {
+            pattern: r"root\s+(?P<pid>\d+)\s+(?P<cmd>sshd)".to_string(),
+        };
+        assert_eq!(
+            capture.extract(output).unwrap(),
+            vec![
+                ("pid".to_string(), "456".to_string()),
+                ("cmd".to_string(), "sshd".to_string())
+            ]
+        );
+        let capture = Capture::Slice {
+            name: "pid".to_string(),
+            line: -1,
+            column: Some(1),
+        };
+        assert_eq!(
+            capture.extract(output).unwrap(),
+            vec![("pid".to_string(), "456".to_string())]
+        );
+        let capture = Capture::Slice {
+            name: "pid".to_string(),
+            line: 5,
+            column: None,
+        };
+        assert!(capture.extract(output).unwrap().is_empty());
+        let capture = Capture::Json {
+            name: "id".to_string(),
+            path: "items.1.id".to_string(),
+        };
+        assert_eq!(
+            capture
+                .extract(r#"{"items":[{"id":1},{"id":"b"}]}"#)
+                .unwrap(),
+            vec![("id".to_string(), "b".to_string())]
+        );
+        assert!(capture.extract("not json").is_err());
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let params = HashMap::from([(var_key("1", "pid"), "456".to_string())]);
+        assert_eq!(
+            interpolate("kill ${node1.pid} && echo ${HOME}", &params),
+            Ok("kill 456 && echo ${HOME}".to_string())
+        );
+        assert_eq!(
+            interpolate("kill ${node2.pid}", &params),
+            Err("${node2.pid}".to_string())
+        );
+    }
+}
@@ -9,4 +9,14 @@ pub enum PreMatchTypeEnum {
     Contains,
     NotContains,
     NotEq,
+    /// 匹配退出码, 需启用shell集成标记
+    ExitCode,
+    NotExitCode,
+}
+
+impl PreMatchTypeEnum {
+    /// 匹配退出码而非命令输出
+    pub fn is_exit_code(&self) -> bool {
+        matches!(self, Self::ExitCode | Self::NotExitCode)
+    }
 }
@@ -27,6 +27,23 @@ pub(crate) fn regex(source: &str, target: &str) -> anyhow::Result<bool> {
     anyhow::Ok(re.is_match(source))
 }
 
+/// 按匹配方式比较命令输出
+pub(crate) fn match_item(
+    match_type: &PreMatchTypeEnum,
+    source: &str,
+    target: &str,
+) -> anyhow::Result<bool> {
+    match match_type {
+        PreMatchTypeEnum::Eq => eq(source, target),
+        PreMatchTypeEnum::Reg => string::regex(source, target),
+        PreMatchTypeEnum::Contains => contains(source, target),
+        PreMatchTypeEnum::NotContains => not_contains(source, target),
+        PreMatchTypeEnum::NotEq => not_eq(source, target),
+        PreMatchTypeEnum::ExitCode => eq(source.trim(), target.trim()),
+        PreMatchTypeEnum::NotExitCode => not_eq(source.trim(), target.trim()),
+    }
+}
+
 pub fn cmd_string_match(
     match_type: PreMatchTypeEnum,
     node_id: String,
@@ -35,21 +52,17 @@ pub fn cmd_string_match(
     Arc::new(move |data: Arc<RwLock<HashMap<String, String>>>| {
         let match_type = match_type.clone();
         let item_value = value.clone();
-        let key = format!("node-{node_id}-cmd-output");
+        let key = match match_type.is_exit_code() {
+            true => format!("node-{node_id}-exit-code"),
+            false => format!("node-{node_id}-cmd-output"),
+        };
         Box::pin(async move {
             let data_read = data.read().await;
             let s = match data_read.get(&key) {
                 Some(v) => v,
                 None => return anyhow::Ok(false), // 确保返回 Result<bool>
             };
-            let result = match match_type {
-                PreMatchTypeEnum::Eq => eq(s.as_str(), &item_value),
-                PreMatchTypeEnum::Reg => string::regex(s.as_str(), &item_value),
-                PreMatchTypeEnum::Contains => contains(s.as_str(), &item_value),
-                PreMatchTypeEnum::NotContains => not_contains(s.as_str(), &item_value),
-                PreMatchTypeEnum::NotEq => not_eq(s.as_str(), &item_value),
-            };
-            result
+            match_item(&match_type, s, &item_value)
         })
     })
 }
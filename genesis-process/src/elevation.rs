@@ -0,0 +1,170 @@
+//! 命令提权, 识别输出中的密码提示并以提权密码应答
+
+use std::sync::LazyLock;
+
+use genesis_common::ElevationMode;
+use regex::Regex;
+
+/// 当前行以密码提示结尾, 如 `[sudo] password for root: `、`Password: `、`密码：`
+static PASSWORD_PROMPT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)(password|passphrase|密码)[^\n]*[:：]\s*$").unwrap());
+
+static ESCAPE_SEQUENCE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]|\x1b\][^\x07]*\x07|\r").unwrap());
+
+/// 密码被拒绝时的输出
+const REJECTED: &[&str] = &[
+    "sorry, try again",
+    "incorrect password",
+    "authentication failure",
+    "鉴定故障",
+    "认证失败",
+];
+
+/// 仅保留最近的输出用于匹配
+const TAIL_LIMIT: usize = 512;
+
+/// 按提权方式包装命令
+///
+/// 含管道、重定向等shell语法的命令经 `sh -c` 整体提权, 否则sudo仅作用于第一条命令;
+/// 简单命令保持原样, 以兼容仅授权了具体命令的sudoers规则
+pub(crate) fn elevate_command(mode: ElevationMode, user: Option<&str>, cmd: &str) -> String {
+    // 目标用户保存时已校验, 拼接时仍按单个参数引用
+    let user = user.filter(|u| !u.is_empty()).map(quote);
+    let cmd = match mode {
+        ElevationMode::Sudo | ElevationMode::SudoLogin
+            if cmd.contains([';', '&', '|', '<', '>', '`', '$', '\n']) =>
+        {
+            format!("sh -c {}", quote(cmd))
+        }
+        ElevationMode::Su => quote(cmd),
+        _ => cmd.to_string(),
+    };
+    match mode {
+        ElevationMode::None => cmd,
+        ElevationMode::Sudo => match user {
+            Some(user) => format!("sudo -u {user} {cmd}"),
+            None => format!("sudo {cmd}"),
+        },
+        ElevationMode::SudoLogin => match user {
+            Some(user) => format!("sudo -i -u {user} {cmd}"),
+            None => format!("sudo -i {cmd}"),
+        },
+        ElevationMode::Su => format!("su - {} -c {cmd}", user.as_deref().unwrap_or("root")),
+    }
+}
+
+/// 以单引号引用为shell的单个参数
+fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ElevationEvent {
+    /// 出现密码提示, 需应答
+    Prompt,
+    /// 应答后密码被拒绝
+    Rejected,
+}
+
+/// 跟踪单条提权命令的输出, 仅应答一次密码提示
+#[derive(Debug, Default)]
+pub(crate) struct ElevationWatcher {
+    tail: String,
+    answered: bool,
+}
+
+impl ElevationWatcher {
+    pub(crate) fn feed(&mut self, data: &[u8]) -> Option<ElevationEvent> {
+        let text = String::from_utf8_lossy(data);
+        self.tail.push_str(&ESCAPE_SEQUENCE.replace_all(&text, ""));
+        if self.tail.len() > TAIL_LIMIT {
+            let mut start = self.tail.len() - TAIL_LIMIT;
+            while !self.tail.is_char_boundary(start) {
+                start += 1;
+            }
+            self.tail.drain(..start);
+        }
+        let lower = self.tail.to_lowercase();
+        if self.answered && REJECTED.iter().any(|r| lower.contains(r)) {
+            return Some(ElevationEvent::Rejected);
+        }
+        if !PASSWORD_PROMPT.is_match(&self.tail) {
+            return None;
+        }
+        self.tail.clear();
+        if std::mem::replace(&mut self.answered, true) {
+            // 再次提示输入密码
+            Some(ElevationEvent::Rejected)
+        } else {
+            Some(ElevationEvent::Prompt)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elevate_command() {
+        assert_eq!(
+            elevate_command(ElevationMode::None, Some("app"), "id"),
+            "id"
+        );
+        assert_eq!(elevate_command(ElevationMode::Sudo, None, "id"), "sudo id");
+        assert_eq!(
+            elevate_command(ElevationMode::SudoLogin, Some("app"), "id"),
+            "sudo -i -u 'app' id"
+        );
+        assert_eq!(
+            elevate_command(ElevationMode::Sudo, Some("app; reboot"), "id"),
+            "sudo -u 'app; reboot' id"
+        );
+        assert_eq!(
+            elevate_command(ElevationMode::Su, Some("a'b"), "id"),
+            r"su - 'a'\''b' -c 'id'"
+        );
+        assert_eq!(
+            elevate_command(ElevationMode::Sudo, None, "cd /tmp && rm 'a b'"),
+            r"sudo sh -c 'cd /tmp && rm '\''a b'\'''"
+        );
+        assert_eq!(
+            elevate_command(ElevationMode::Su, None, "echo 'a'"),
+            r"su - root -c 'echo '\''a'\'''"
+        );
+    }
+
+    #[test]
+    fn test_elevation_watcher() {
+        let mut watcher = ElevationWatcher::default();
+        assert_eq!(watcher.feed(b"sudo id\r\n"), None);
+        assert_eq!(
+            watcher.feed(b"[sudo] password for ro"),
+            None,
+            "incomplete prompt"
+        );
+        assert_eq!(watcher.feed(b"ot: "), Some(ElevationEvent::Prompt));
+        assert_eq!(watcher.feed(b"\r\nuid=0(root)\r\n"), None);
+
+        let mut watcher = ElevationWatcher::default();
+        assert_eq!(
+            watcher.feed(b"\x1b[1mPassword:\x1b[0m "),
+            Some(ElevationEvent::Prompt)
+        );
+        assert_eq!(
+            watcher.feed(b"\r\nsu: Authentication failure\r\n"),
+            Some(ElevationEvent::Rejected)
+        );
+
+        let mut watcher = ElevationWatcher::default();
+        assert_eq!(
+            watcher.feed("密码：".as_bytes()),
+            Some(ElevationEvent::Prompt)
+        );
+        assert_eq!(
+            watcher.feed("\r\n密码：".as_bytes()),
+            Some(ElevationEvent::Rejected)
+        );
+    }
+}
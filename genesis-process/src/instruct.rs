@@ -1,7 +1,12 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
 
+use crate::capture::Capture;
 use crate::common::em::PreMatchTypeEnum;
 use futures_util::future::BoxFuture;
+use genesis_common::ElevationMode;
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 use validator::Validate;
@@ -25,6 +30,19 @@ pub struct Core {
     pub des: String,
     pub cmd: String,
     pub expire: u64,
+    /// 提权方式, 为空时使用目标配置
+    #[serde(default)]
+    pub elevation: Option<ElevationMode>,
+}
+/// 汇合节点等待分支的方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JoinMode {
+    /// 等待全部分支, 任一分支失败时停止执行
+    #[default]
+    All,
+    /// 任一分支到达即继续, 其余分支被中止
+    Any,
 }
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Position {
@@ -38,6 +56,15 @@ pub struct Node {
     pub core: Core,
     pub post: Option<Post>,
     pub position: Position,
+    /// 从命令输出中提取的变量, 后续节点以 `${node<id>.<name>}` 引用
+    #[serde(default)]
+    pub captures: Vec<Capture>,
+    /// 并行执行所有满足条件的子节点, 每个分支使用独立的shell通道及录像
+    #[serde(default)]
+    pub fork: bool,
+    /// 汇合节点, 分支执行至此结束, 由发起分支的shell继续执行
+    #[serde(default)]
+    pub join: Option<JoinMode>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,6 +79,15 @@ pub struct InData {
     pub nodes: Vec<Node>,
     #[validate(length(min = 1, message = "edges is empty"))]
     pub edges: Vec<Edge>,
+    /// shell会话环境变量, 覆盖资产配置中的同名变量
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// 声明的执行参数, 命令中以 `${name}` 引用, 执行时替换
+    #[serde(default)]
+    pub params: Vec<String>,
+    /// 注入shell集成标记, 以标记判断命令结束并获取退出码
+    #[serde(default)]
+    pub shell_integration: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -75,6 +111,7 @@ impl Graph {
     }
     // 根据 edges 构建图
     pub async fn build_from_edges(&mut self, in_data: InData) {
+        let root = in_data.root_id().map(str::to_string);
         let mut node_map: HashMap<String, Arc<Mutex<Execute>>> = HashMap::new();
 
         // 创建所有节点，并将它们放入 node_map，保持节点之间的引用关系
@@ -100,11 +137,7 @@ impl Graph {
             }
         }
         // 将所有节点收集到 nodes 中
-        self.nodes = node_map
-            .into_iter()
-            .filter(|(id, _)| id == "1")
-            .map(|(_, node)| Arc::clone(&node))
-            .next();
+        self.nodes = root.and_then(|id| node_map.remove(&id));
     }
 
     // 打印图的结构，递归遍历每个节点及其子节点
@@ -0,0 +1,189 @@
+//! shell集成标记, 以 OSC 133 (FinalTerm) 标记命令的开始、结束及退出码
+//!
+//! 命令前后注入 `printf`, 回显中只有转义文本, 仅命令实际执行时输出标记
+
+use std::sync::LazyLock;
+
+use bytes::{Bytes, BytesMut};
+use regex::Regex;
+
+const MARKER_PREFIX: &[u8] = b"\x1b]133;";
+/// 标记过长时视为普通数据
+const MARKER_LIMIT: usize = 64;
+const START_WRAPPER: &str = r"printf '\033]133;C\007'; ";
+const END_WRAPPER: &str = r"; printf '\033]133;D;%s\007' $?";
+
+static ESCAPE_SEQUENCE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\x1b\[[0-9;?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(\x07|\x1b\\)|\x1b[()][0-9A-Za-z]|\x1b[=>]|\r")
+        .unwrap()
+});
+
+/// 包装命令, 执行前后输出标记
+pub(crate) fn wrap_command(cmd: &str) -> String {
+    // 末尾的 `;` 及后台执行的 `&` 之后不能再接 `;`
+    let cmd = cmd.trim_end().trim_end_matches(';').trim_end();
+    let end = if cmd.ends_with('&') && !cmd.ends_with("&&") {
+        END_WRAPPER.trim_start_matches(';')
+    } else {
+        END_WRAPPER
+    };
+    format!("{START_WRAPPER}{cmd}{end}")
+}
+
+/// 从回显的输入中还原命令
+pub(crate) fn unwrap_command(input: &str) -> String {
+    let input = input.trim();
+    let input = input
+        .split_once(START_WRAPPER.trim_end())
+        .map_or(input, |(_, cmd)| cmd);
+    let input = input
+        .rsplit_once(END_WRAPPER.trim_start_matches(';').trim_start())
+        .map_or(input, |(cmd, _)| cmd);
+    input.trim().trim_end_matches(';').trim().to_string()
+}
+
+/// 去除终端控制序列, 保留文本
+pub(crate) fn plain_text(data: &[u8]) -> String {
+    ESCAPE_SEQUENCE
+        .replace_all(&String::from_utf8_lossy(data), "")
+        .trim()
+        .to_string()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Marker {
+    /// 命令开始输出
+    Start,
+    /// 命令结束及退出码
+    End(Option<i32>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Segment {
+    Data(Bytes),
+    Marker(Marker),
+}
+
+/// 从输出流中拆分标记, 跨数据包的标记保留至下次解析
+#[derive(Debug, Default)]
+pub(crate) struct MarkerScanner {
+    pending: BytesMut,
+}
+
+impl MarkerScanner {
+    pub(crate) fn feed(&mut self, data: &[u8]) -> Vec<Segment> {
+        self.pending.extend_from_slice(data);
+        let mut segments = vec![];
+        loop {
+            let buf = &self.pending[..];
+            let Some(start) = find(buf, MARKER_PREFIX) else {
+                // 末尾可能是标记前缀的一部分
+                let keep = (1..MARKER_PREFIX.len().min(buf.len() + 1))
+                    .rev()
+                    .find(|len| buf.ends_with(&MARKER_PREFIX[..*len]))
+                    .unwrap_or(0);
+                let data = self.pending.split_to(buf.len() - keep).freeze();
+                push_data(&mut segments, data);
+                break;
+            };
+            let body = &buf[start + MARKER_PREFIX.len()..];
+            let end = body
+                .iter()
+                .position(|b| *b == 0x07)
+                .map(|i| (i, 1))
+                .or_else(|| find(body, b"\x1b\\").map(|i| (i, 2)));
+            let Some((end, terminator)) = end else {
+                if buf.len() - start > MARKER_LIMIT {
+                    let data = self.pending.split_to(start + MARKER_PREFIX.len()).freeze();
+                    push_data(&mut segments, data);
+                    continue;
+                }
+                let data = self.pending.split_to(start).freeze();
+                push_data(&mut segments, data);
+                break;
+            };
+            let marker = parse_marker(&body[..end]);
+            let data = self.pending.split_to(start).freeze();
+            push_data(&mut segments, data);
+            let _ = self
+                .pending
+                .split_to(MARKER_PREFIX.len() + end + terminator);
+            // 提示符等其余标记忽略
+            segments.extend(marker.map(Segment::Marker));
+        }
+        segments
+    }
+}
+
+fn push_data(segments: &mut Vec<Segment>, data: Bytes) {
+    if !data.is_empty() {
+        segments.push(Segment::Data(data));
+    }
+}
+
+fn parse_marker(body: &[u8]) -> Option<Marker> {
+    let body = String::from_utf8_lossy(body);
+    let mut parts = body.split(';');
+    match parts.next()? {
+        "C" => Some(Marker::Start),
+        "D" => Some(Marker::End(parts.next().and_then(|code| code.parse().ok()))),
+        _ => None,
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_command() {
+        let wrapped = wrap_command("ls -l; pwd");
+        assert_eq!(
+            wrapped,
+            r"printf '\033]133;C\007'; ls -l; pwd; printf '\033]133;D;%s\007' $?"
+        );
+        assert_eq!(unwrap_command(&wrapped), "ls -l; pwd");
+        assert_eq!(wrap_command("pwd; "), wrap_command("pwd"));
+        let wrapped = wrap_command("sleep 5 &");
+        assert_eq!(
+            wrapped,
+            r"printf '\033]133;C\007'; sleep 5 & printf '\033]133;D;%s\007' $?"
+        );
+        assert_eq!(unwrap_command(&wrapped), "sleep 5 &");
+        assert_eq!(unwrap_command("pwd"), "pwd");
+    }
+
+    #[test]
+    fn test_marker_scanner() {
+        let mut scanner = MarkerScanner::default();
+        assert_eq!(
+            scanner.feed(b"\r\n\x1b]133;C\x07/root\r\n\x1b]13"),
+            vec![
+                Segment::Data(Bytes::from_static(b"\r\n")),
+                Segment::Marker(Marker::Start),
+                Segment::Data(Bytes::from_static(b"/root\r\n")),
+            ]
+        );
+        assert_eq!(
+            scanner.feed(b"3;D;127\x07\x1b]133;A\x1b\\# "),
+            vec![
+                Segment::Marker(Marker::End(Some(127))),
+                Segment::Data(Bytes::from_static(b"# ")),
+            ]
+        );
+        assert_eq!(
+            scanner.feed(b"\x1b]0;title\x07"),
+            vec![Segment::Data(Bytes::from_static(b"\x1b]0;title\x07"))]
+        );
+        assert_eq!(
+            plain_text(b"\x1b[1;31mfailed\x1b[0m\r\n\x1b]0;t\x07done\r\n"),
+            "failed\ndone"
+        );
+    }
+}
@@ -1,20 +1,28 @@
 //! process
 
+mod capture;
 mod common;
+mod elevation;
+#[allow(dead_code)]
 mod error;
 pub mod guacamole;
 mod instruct;
+mod integration;
 mod pipe;
 mod process;
 mod recording;
 mod ssh;
+#[allow(dead_code)]
 mod sshm;
 mod types;
+mod validation;
 
+pub use capture::Capture;
 pub use instruct::*;
 pub use pipe::*;
 pub use process::*;
 pub use ssh::*;
+pub use validation::*;
 #[cfg(test)]
 mod tests {
 
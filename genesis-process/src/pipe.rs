@@ -1,3 +1,4 @@
+use crate::integration::{plain_text, unwrap_command, Marker, MarkerScanner, Segment};
 use bytes::{Bytes, BytesMut};
 use std::iter::once;
 use std::sync::atomic::Ordering;
@@ -5,10 +6,9 @@ use std::sync::Arc;
 use std::time::Duration;
 use tokio::select;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
-use tokio::sync::watch::{self, Receiver};
 use tokio::sync::{broadcast, Mutex, RwLock};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error};
+use tracing::debug;
 
 /// cmd execute state enum
 #[derive(Clone)]
@@ -35,6 +35,8 @@ pub enum PipeState {
 pub struct PipeCmd {
     pub input: String,
     pub output: String,
+    /// 退出码, 仅启用shell集成标记时存在
+    pub exit_code: Option<i32>,
 }
 
 #[derive(Debug)]
@@ -62,6 +64,7 @@ pub struct PipeManger {
     alternate_mode: Arc<RwLock<bool>>,
     ps1_char: Arc<Vec<char>>,
     counter: Arc<std::sync::atomic::AtomicUsize>,
+    shell_integration: bool,
 }
 
 impl PipeManger {
@@ -69,6 +72,11 @@ impl PipeManger {
         self.ps1_char = Arc::new(chars);
         self
     }
+    /// 以shell集成标记判断命令结束, 不再依赖提示符
+    pub fn with_shell_integration(&mut self, enabled: bool) -> &mut Self {
+        self.shell_integration = enabled;
+        self
+    }
     pub fn new(wait_times: u8, uniq_id: String) -> Self {
         Self {
             wait_times,
@@ -81,6 +89,7 @@ impl PipeManger {
             alternate_mode: Arc::new(RwLock::new(false)),
             ps1_char: Arc::new(vec!['#', '$', '>']),
             counter: Arc::new(Default::default()),
+            shell_integration: false,
         }
     }
 
@@ -218,6 +227,9 @@ impl PipeManger {
         let output = self.out_buf.clone();
         let alternate_mode = self.alternate_mode.clone();
         let mut buffer = BytesMut::new();
+        let mut scanner = MarkerScanner::default();
+        // 标记开始后的命令及原始输出
+        let mut marked: Option<(String, BytesMut)> = None;
         'ro: loop {
             select! {
                 rb = out_io_reader.recv() => match rb {
@@ -239,48 +251,86 @@ impl PipeManger {
                             .filter(|e| !e.is_empty())
                             .collect();
                         // loop
-                        for data in parts.iter() {
-                            let mut par = parser.lock().await;
-                            par.process(data);
-                            if par.screen().alternate_screen() {
-                                //VIM等界面
-                                let mut x = alternate_mode.write().await;
-                                *x = true;
-                                continue 'ro;
-                            }
-                            // 判断当前接受状态,若是输入状态,则写到input
-                            // 若是输出状态,则写入到output
-                            match *(stat.read().await) {
-                                PipeState::In => {
-                                    input.lock().await.process(data);
-                                },
-                                PipeState::Out => {
-                                    output.lock().await.process(data);
-                                },
-                            }
-                            buffer.extend_from_slice(data);
-                            if let Some(p1) = self.extract_command_after_bell(&buffer){
-                                self.counter.store(0,Ordering::SeqCst);
-                                *(ps1.write().await) = String::from_utf8_lossy(p1).to_string();
-                                // 打印ps1,并清空
-                                *(stat.write().await) = PipeState::In;
-                                buffer.clear();
-
-                                let mut input = input.lock().await;
-                                let cmd_input = input.screen().contents().trim().to_string();
-                                if cmd_input.is_empty() {
-                                    output.lock().await.process(b"\x1b[2J");
+                        for part in parts.iter() {
+                            let segments = match self.shell_integration {
+                                true => scanner.feed(part),
+                                false => vec![Segment::Data(part.clone())],
+                            };
+                            for segment in segments {
+                                let data = match segment {
+                                    Segment::Data(data) => data,
+                                    Segment::Marker(Marker::Start) => {
+                                        let mut input = input.lock().await;
+                                        let cmd_input = unwrap_command(&input.screen().contents());
+                                        input.process(b"\x1b[2J");
+                                        output.lock().await.process(b"\x1b[2J");
+                                        marked = Some((cmd_input, BytesMut::new()));
+                                        continue;
+                                    }
+                                    Segment::Marker(Marker::End(exit_code)) => {
+                                        if let Some((cmd_input, out)) = marked.take() {
+                                            output.lock().await.process(b"\x1b[2J");
+                                            let _ = state_sender.send(ExecuteState::ExecutedCmd(PipeCmd{
+                                                input: cmd_input,
+                                                output: plain_text(&out),
+                                                exit_code,
+                                            }));
+                                        }
+                                        continue;
+                                    }
+                                };
+                                let data = &data;
+                                if let Some((_, out)) = marked.as_mut() {
+                                    out.extend_from_slice(data);
+                                }
+                                let mut par = parser.lock().await;
+                                par.process(data);
+                                if par.screen().alternate_screen() {
+                                    //VIM等界面
+                                    let mut x = alternate_mode.write().await;
+                                    *x = true;
+                                    continue 'ro;
+                                }
+                                // 判断当前接受状态,若是输入状态,则写到input
+                                // 若是输出状态,则写入到output
+                                match *(stat.read().await) {
+                                    PipeState::In => {
+                                        input.lock().await.process(data);
+                                    },
+                                    PipeState::Out => {
+                                        output.lock().await.process(data);
+                                    },
+                                }
+                                buffer.extend_from_slice(data);
+                                // 命令执行中, 输出中的提示符字符不代表命令结束
+                                if marked.is_some() {
+                                    buffer.clear();
                                     continue;
-                                }else{
-                                    input.process(b"\x1b[2J");
-                                    let mut output = output.lock().await;
-                                    let ps1_value = ps1.read().await;
-                                    let cmd_out = output.screen().contents().replace(ps1_value.as_str(), "").trim().to_string();
-                                    output.process(b"\x1b[2J");
-                                    let _ = state_sender.send(ExecuteState::ExecutedCmd(PipeCmd{
-                                        input: cmd_input,
-                                        output: cmd_out,
-                                    }));
+                                }
+                                if let Some(p1) = self.extract_command_after_bell(&buffer){
+                                    self.counter.store(0,Ordering::SeqCst);
+                                    *(ps1.write().await) = String::from_utf8_lossy(p1).to_string();
+                                    // 打印ps1,并清空
+                                    *(stat.write().await) = PipeState::In;
+                                    buffer.clear();
+
+                                    let mut input = input.lock().await;
+                                    let cmd_input = input.screen().contents().trim().to_string();
+                                    if cmd_input.is_empty() {
+                                        output.lock().await.process(b"\x1b[2J");
+                                        continue;
+                                    }else{
+                                        input.process(b"\x1b[2J");
+                                        let mut output = output.lock().await;
+                                        let ps1_value = ps1.read().await;
+                                        let cmd_out = output.screen().contents().replace(ps1_value.as_str(), "").trim().to_string();
+                                        output.process(b"\x1b[2J");
+                                        let _ = state_sender.send(ExecuteState::ExecutedCmd(PipeCmd{
+                                            input: cmd_input,
+                                            output: cmd_out,
+                                            exit_code: None,
+                                        }));
+                                    }
                                 }
                             }
                         }
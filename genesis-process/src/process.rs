@@ -1,13 +1,22 @@
 //! process
 
+use crate::capture::{interpolate, var_key, Capture};
 use crate::common::string;
+use crate::elevation::{elevate_command, ElevationEvent, ElevationWatcher};
+use crate::integration::wrap_command;
 use crate::recording::{Recorder, RecorderBuilder};
 use crate::types::AsyncMatchFn;
-use crate::{Execute, ExecuteState, Item, Pipe, PipeManger, PipeState};
+use crate::{Execute, ExecuteState, Item, JoinMode, Node, Pipe, PipeManger, PipeState};
 use bytes::Bytes;
-use genesis_common::{EventSubscription, NotifyEnum, TargetSSHOptions, TaskStatusEnum};
-use genesis_ssh::start_ssh_connect;
-use std::collections::HashMap;
+use futures::future::BoxFuture;
+use futures::stream::FuturesUnordered;
+use futures::{FutureExt, StreamExt};
+use genesis_common::{
+    ElevationMode, EventHub, EventSubscription, NotifyEnum, SSHElevation, TargetSSHOptions,
+    TaskStatusEnum,
+};
+use genesis_ssh::start_pooled_ssh_connect;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::io::Write;
 use std::{sync::Arc, time::Duration};
 use tokio::sync::broadcast;
@@ -34,6 +43,8 @@ pub struct ProcessManger {
     recorder: Arc<Mutex<Option<Recorder>>>,
     pub uniq_id: String,
     pub ssh_cmd_wait_times: u8,
+    /// 以shell集成标记判断命令结束并获取退出码
+    pub shell_integration: bool,
     pub execute: Arc<Mutex<Execute>>,
     pub abort_rc: watch::Receiver<bool>,
     pub broadcast_sender: broadcast::Sender<ExecuteState>,
@@ -43,7 +54,23 @@ pub struct ProcessManger {
     execute_info: Arc<Mutex<Option<String>>>,
     cmd_expire_time: Arc<Mutex<Option<Instant>>>,
     global_params: Arc<RwLock<HashMap<String, String>>>,
+    elevation: SSHElevation,
     ctx: CancellationToken,
+    // 分支执行所需
+    recorder_builder: Option<RecorderBuilder>,
+    ssh_option: Option<TargetSSHOptions>,
+    branch: bool,
+    joined: Mutex<Option<Arc<Mutex<Execute>>>>,
+}
+
+/// 已打开的shell通道
+struct Shell {
+    hub: EventHub<Bytes>,
+    sender: UnboundedSender<Bytes>,
+    raw_sender: UnboundedSender<Bytes>,
+    manager: Arc<PipeManger>,
+    /// 通道关闭时发送端释放
+    closed: watch::Receiver<NotifyEnum>,
 }
 
 impl ProcessManger {
@@ -58,11 +85,17 @@ impl ProcessManger {
             broadcast_receiver,
             abort_sc,
             ssh_cmd_wait_times: 100,
+            shell_integration: false,
             recorder: Arc::new(Mutex::new(None)),
             cmd_expire_time: Arc::new(Mutex::new(None)),
             execute_info: Arc::new(Mutex::new(None)),
             global_params: Arc::new(RwLock::new(HashMap::new())),
+            elevation: SSHElevation::default(),
             ctx: CancellationToken::new(),
+            recorder_builder: None,
+            ssh_option: None,
+            branch: false,
+            joined: Mutex::new(None),
         })
     }
 
@@ -70,6 +103,23 @@ impl ProcessManger {
         self.global_params.write().await.insert(key, value);
     }
 
+    /// 保存命令输出并提取节点变量
+    async fn insert_cmd_output(&self, node_id: &str, captures: &[Capture], content: &str) {
+        let mut params = self.global_params.write().await;
+        params.insert(format!("node-{node_id}-cmd-output"), content.to_string());
+        for capture in captures {
+            match capture.extract(content) {
+                Ok(vars) => params.extend(
+                    vars.into_iter()
+                        .map(|(name, value)| (var_key(node_id, &name), value)),
+                ),
+                Err(e) => {
+                    debug!(session_id=%self.uniq_id,"node:{} capture {:?} failed: {}", node_id, capture, e)
+                }
+            }
+        }
+    }
+
     pub fn with_recorder_param(
         mut self,
         save_path: &str,
@@ -77,14 +127,15 @@ impl ProcessManger {
         height: u32,
         width: u32,
     ) -> anyhow::Result<Self> {
-        let recorder = RecorderBuilder::default()
-            .uniq(&self.uniq_id)
+        let mut builder = RecorderBuilder::default();
+        builder
             .path(save_path)
             .term(term)
             .height(height)
-            .width(width)
-            .build()?;
+            .width(width);
+        let recorder = builder.clone().uniq(&self.uniq_id).build()?;
         self.recorder = Arc::new(Mutex::new(Some(recorder)));
+        self.recorder_builder = Some(builder);
         anyhow::Ok(self)
     }
 
@@ -96,6 +147,10 @@ impl ProcessManger {
         self.ssh_cmd_wait_times = times;
         self
     }
+    pub fn with_shell_integration(&mut self, enabled: bool) -> &mut Self {
+        self.shell_integration = enabled;
+        self
+    }
     pub fn register_state_watcher(&self) -> broadcast::Receiver<ExecuteState> {
         self.broadcast_receiver.resubscribe()
     }
@@ -208,7 +263,45 @@ impl ProcessManger {
         uuid: Uuid,
         ssh_option: TargetSSHOptions,
     ) -> anyhow::Result<genesis_common::TaskStatusEnum> {
-        let (hub, sender, notify) = start_ssh_connect(uuid, ssh_option).await?;
+        self.elevation = ssh_option.elevation.clone();
+        self.ssh_option = Some(ssh_option.clone());
+        // step1-3. connect & start interactive
+        let shell = self.open_shell(uuid, ssh_option).await?;
+        let mut closed = shell.closed;
+        let recording = shell.hub.subscribe(|_| true).await;
+        // step5. cmd & recording process
+        let _ = tokio::join!(
+            self.do_cmd_process(
+                shell.sender,
+                shell.raw_sender,
+                shell.manager.out_buf.clone(),
+                shell.manager.state.clone()
+            ),
+            async {
+                // shell关闭后结束录像
+                select! {
+                    _ = self.do_recording(recording) => {},
+                    _ = closed.wait_for(|_| false) => {},
+                }
+            }
+        );
+        // step6. stop type check
+        let old = self.abort_rc.clone();
+        if *old.borrow() {
+            self.get_execute_info()
+                .await
+                .map(|s| anyhow::bail!(s))
+                .unwrap_or(anyhow::Ok(TaskStatusEnum::ManualStop))
+        } else {
+            self.stop_process();
+            debug!(session_id=%self.uniq_id,"end execute:{}", self.uniq_id);
+            anyhow::Ok(TaskStatusEnum::Success)
+        }
+    }
+
+    async fn open_shell(&self, uuid: Uuid, ssh_option: TargetSSHOptions) -> anyhow::Result<Shell> {
+        let (hub, sender, _, notify) = start_pooled_ssh_connect(uuid, ssh_option).await?;
+        let closed = notify.clone();
         // step1. wait until ssh connected
         self.wait_ssh_state(notify).await?;
         // step2. Two-way binary stream copy
@@ -216,8 +309,11 @@ impl ProcessManger {
         let (sc, in_rc) = unbounded_channel::<Bytes>();
         let (psc, _) = unbounded_channel::<Bytes>();
         let in_pipe = Pipe::new(psc, in_rc);
+        // 提权密码直接写入ssh通道, 不经过命令解析
+        let raw_sender = sender.clone();
         let out_pipe = Pipe::new(sender, receiver.unbox());
-        let manager = PipeManger::new(self.ssh_cmd_wait_times, self.uniq_id.clone());
+        let mut manager = PipeManger::new(self.ssh_cmd_wait_times, self.uniq_id.clone());
+        manager.with_shell_integration(self.shell_integration);
         // step3.start interactive
         let new_manager = Arc::new(manager);
         let _ = new_manager
@@ -229,28 +325,136 @@ impl ProcessManger {
                 self.ctx.clone(),
             )
             .await;
-        // step5. cmd & recording process
-        let _ = tokio::join!(
-            self.do_cmd_process(sc, new_manager.out_buf.clone(), new_manager.state.clone()),
-            self.do_recording(hub.subscribe(|_| true).await)
-        );
-        // step6. stop type check
-        let old = self.abort_rc.clone();
-        if *old.borrow() {
-            self.get_execute_info()
-                .await
-                .map(|s| anyhow::bail!(s))
-                .unwrap_or(anyhow::Ok(TaskStatusEnum::ManualStop))
-        } else {
-            self.stop_process();
-            debug!(session_id=%self.uniq_id,"end execute:{}", self.uniq_id);
-            anyhow::Ok(TaskStatusEnum::Success)
+        anyhow::Ok(Shell {
+            hub,
+            sender: sc,
+            raw_sender,
+            manager: new_manager,
+            closed,
+        })
+    }
+
+    /// 分支执行器, 共享全局参数, 拥有独立的中止信号、状态广播及录像
+    fn branch(&self, node_id: &str, execute: Arc<Mutex<Execute>>) -> ProcessManger {
+        let (abort_sc, abort_rc) = watch::channel(false);
+        let (broadcast_sender, broadcast_receiver) = broadcast::channel::<ExecuteState>(2048);
+        Self {
+            uniq_id: format!("{}-{}", self.uniq_id, node_id),
+            execute,
+            abort_rc,
+            broadcast_sender,
+            broadcast_receiver,
+            abort_sc,
+            ssh_cmd_wait_times: self.ssh_cmd_wait_times,
+            shell_integration: self.shell_integration,
+            recorder: Arc::new(Mutex::new(None)),
+            cmd_expire_time: Arc::new(Mutex::new(None)),
+            execute_info: Arc::new(Mutex::new(None)),
+            global_params: self.global_params.clone(),
+            elevation: self.elevation.clone(),
+            ctx: CancellationToken::new(),
+            recorder_builder: self.recorder_builder.clone(),
+            ssh_option: self.ssh_option.clone(),
+            branch: true,
+            joined: Mutex::new(None),
+        }
+    }
+
+    /// 在新的shell通道中执行分支, 返回到达的汇合节点
+    fn run_branch(self) -> BoxFuture<'static, anyhow::Result<Option<Arc<Mutex<Execute>>>>> {
+        async move {
+            let option = self
+                .ssh_option
+                .clone()
+                .ok_or_else(|| anyhow::anyhow!("ssh option is not set"))?;
+            let shell = self.open_shell(Uuid::new_v4(), option).await?;
+            if let Some(builder) = &self.recorder_builder {
+                let recorder = builder.clone().uniq(&self.uniq_id).build()?;
+                let receiver = shell.hub.subscribe(|_| true).await;
+                recorder.start_spawn(self.ctx.clone(), receiver.unbox());
+            }
+            debug!(session_id=%self.uniq_id,"start branch");
+            self.do_cmd_process(
+                shell.sender,
+                shell.raw_sender,
+                shell.manager.out_buf.clone(),
+                shell.manager.state.clone(),
+            )
+            .await;
+            // 关闭分支的shell通道及录像
+            self.ctx.cancel();
+            if *self.abort_rc.borrow() {
+                let info = self.get_execute_info().await;
+                anyhow::bail!(info.unwrap_or("branch stopped".to_string()));
+            }
+            anyhow::Ok(self.joined.lock().await.take())
+        }
+        .boxed()
+    }
+
+    /// 并行执行分支, 按汇合方式等待, 返回需继续执行的汇合节点
+    async fn run_branches(
+        &self,
+        executes: Vec<Arc<Mutex<Execute>>>,
+    ) -> anyhow::Result<Option<Arc<Mutex<Execute>>>> {
+        let mode = find_join_mode(&executes).await.unwrap_or_default();
+        let mut aborts = vec![];
+        let mut branches = FuturesUnordered::new();
+        for execute in executes {
+            let node_id = execute.lock().await.node.id.clone();
+            let branch = self.branch(&node_id, execute);
+            aborts.push(branch.get_abort_sc());
+            branches.push(branch.run_branch().map(move |r| (node_id, r)));
+        }
+        // 中止时同时中止所有分支
+        let stop = aborts.clone();
+        let mut abort_rc = self.abort_rc.clone();
+        let forward = tokio::spawn(async move {
+            if abort_rc.wait_for(|abort| *abort).await.is_ok() {
+                stop.iter().for_each(|sc| {
+                    let _ = sc.send(true);
+                });
+            }
+        });
+        let mut joined = None;
+        let mut succeeded = false;
+        let mut failed = None;
+        while let Some((node_id, result)) = branches.next().await {
+            match result {
+                Ok(reached) => {
+                    debug!(session_id=%self.uniq_id,"branch node:{} finished", node_id);
+                    succeeded = true;
+                    joined = joined.or(reached);
+                    if mode == JoinMode::Any && joined.is_some() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    error!(session_id=%self.uniq_id,"branch node:{} failed: {}", node_id, e);
+                    failed.get_or_insert(format!("branch node:{node_id} failed: {e}"));
+                    if mode == JoinMode::All {
+                        break;
+                    }
+                }
+            }
+        }
+        // 中止其余分支并等待其关闭通道
+        aborts.iter().for_each(|sc| {
+            let _ = sc.send(true);
+        });
+        while branches.next().await.is_some() {}
+        forward.abort();
+        match (mode, failed) {
+            (JoinMode::All, Some(e)) => anyhow::bail!(e),
+            (JoinMode::Any, Some(e)) if !succeeded => anyhow::bail!(e),
+            _ => anyhow::Ok(joined),
         }
     }
 
     async fn do_cmd_process(
         &self,
         sc: UnboundedSender<Bytes>,
+        raw_sender: UnboundedSender<Bytes>,
         res: Arc<Mutex<vt100::Parser>>,
         state: Arc<RwLock<PipeState>>,
     ) {
@@ -258,6 +462,8 @@ impl ProcessManger {
         // 初始数据发送
         cmd_sender.send(self.execute.clone()).unwrap();
         let mut abort_execute_cmd = self.abort_rc.clone();
+        // 分支汇合后在本shell继续执行的汇合节点
+        let mut resume_join = None;
         loop {
             select! {
                 flag = abort_execute_cmd.changed() => match flag {
@@ -275,9 +481,33 @@ impl ProcessManger {
                 ma = cmd_executor.recv() => match ma {
                         Some(execute) => {
                         let exe = execute.lock().await.clone();
+                        // 分支到达汇合节点后结束, 由发起分支的shell执行
+                        if self.branch && exe.node.join.is_some() && resume_join.as_ref() != Some(&exe.node.id) {
+                            debug!(session_id=%self.uniq_id,"branch reach join node:{}", exe.node.id);
+                            *self.joined.lock().await = Some(execute);
+                            return;
+                        }
                         let execute_node_info = format!("node[id:{} des:{}]",exe.node.id,exe.node.core.des);
-                        let mut cmd = exe.node.core.cmd.clone();
                         let node_id = exe.node.id.clone();
+                        // 替换前序节点变量
+                        let interpolated = interpolate(&exe.node.core.cmd, &*self.global_params.read().await);
+                        let mut cmd = match interpolated {
+                            Ok(cmd) => cmd,
+                            Err(placeholder) => {
+                                error!(session_id=%self.uniq_id,"undefined variable {} in {}", placeholder, execute_node_info);
+                                self.set_execute_info(format!("undefined variable {placeholder} for {execute_node_info}")).await;
+                                self.stop_process();
+                                break;
+                            }
+                        };
+                        let mode = exe.node.core.elevation.unwrap_or(self.elevation.mode);
+                        if mode != ElevationMode::None {
+                            cmd = elevate_command(mode, self.elevation.user.as_deref(), cmd.trim_end_matches('\r'));
+                            self.watch_elevation(execute_node_info.clone(), raw_sender.clone());
+                        }
+                        if self.shell_integration {
+                            cmd = wrap_command(cmd.trim_end_matches('\r'));
+                        }
                         if !cmd.ends_with('\r') {
                             cmd.push('\r');
                         }
@@ -294,15 +524,42 @@ impl ProcessManger {
                         if exe.children.is_empty() {
                             return;
                         }
+                        let node = exe.node.clone();
+                        let fork = node.fork;
                         let execute_fns = self.do_next_match(exe).await;
+                        // 分支节点先收集所有满足条件的子节点
+                        let (fork_sender, mut fork_executor) = unbounded_channel();
+                        let next_sender = if fork { &fork_sender } else { &cmd_sender };
                         //存在子节点,等待子节点匹配
-                        if self.cmd_wait_loop(&node_id,res.clone(),state.clone(),&execute_fns,&cmd_sender).await{
+                        if self.cmd_wait_loop(&node,res.clone(),state.clone(),&execute_fns,next_sender).await{
                             // 超时记录
                             self.set_execute_info(format!("execute expired for node:{execute_node_info}")).await;
                             // 发送停止信号
                             self.stop_process();
                             break;
                         }
+                        if !fork {
+                            continue;
+                        }
+                        let mut executes = vec![];
+                        while let Ok(execute) = fork_executor.try_recv() {
+                            executes.push(execute);
+                        }
+                        if executes.is_empty() {
+                            continue;
+                        }
+                        match self.run_branches(executes).await {
+                            Ok(Some(join)) => {
+                                resume_join = Some(join.lock().await.node.id.clone());
+                                let _ = cmd_sender.send(join);
+                            }
+                            Ok(None) => return,
+                            Err(e) => {
+                                self.set_execute_info(format!("{e} for {execute_node_info}")).await;
+                                self.stop_process();
+                                break;
+                            }
+                        }
                     }
                     None => {
                         self.stop_process();
@@ -315,9 +572,49 @@ impl ProcessManger {
         debug!(session_id=%self.uniq_id,"do_cmd_process end");
     }
 
+    /// 跟踪提权命令的输出直至命令结束, 应答密码提示, 密码被拒绝时中断命令并停止执行
+    fn watch_elevation(&self, node_info: String, raw_sender: UnboundedSender<Bytes>) {
+        let mut states = self.register_state_watcher();
+        let mut abort_rc = self.abort_rc.clone();
+        let abort_sc = self.abort_sc.clone();
+        let execute_info = self.execute_info.clone();
+        let password = self.elevation.password.clone();
+        let uniq_id = self.uniq_id.clone();
+        tokio::spawn(async move {
+            let mut watcher = ElevationWatcher::default();
+            loop {
+                let state = select! {
+                    _ = abort_rc.wait_for(|abort| *abort) => break,
+                    state = states.recv() => state,
+                };
+                let event = match state {
+                    Ok(ExecuteState::ExecutedBytes(data)) => watcher.feed(&data),
+                    Ok(ExecuteState::ExecutedCmd(_)) | Ok(ExecuteState::End(_)) => break,
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let reason = match (event, &password) {
+                    (None, _) => continue,
+                    (Some(ElevationEvent::Prompt), Some(password)) => {
+                        debug!(session_id=%uniq_id,"answer elevation prompt for {}", node_info);
+                        let _ = raw_sender.send(Bytes::from(format!("{password}\r")));
+                        continue;
+                    }
+                    (Some(ElevationEvent::Prompt), None) => "elevation password is not configured",
+                    (Some(ElevationEvent::Rejected), _) => "elevation password rejected",
+                };
+                error!(session_id=%uniq_id,"{} for {}", reason, node_info);
+                let _ = raw_sender.send(Bytes::from_static(b"\x03"));
+                *execute_info.lock().await = Some(format!("{reason} for {node_info}"));
+                let _ = abort_sc.send(true);
+                break;
+            }
+        });
+    }
+
     async fn cmd_wait_loop(
         &self,
-        node_id: &str,
+        node: &Node,
         res: Arc<Mutex<vt100::Parser>>,
         state: Arc<RwLock<PipeState>>,
         execute_fns: &RwLock<Vec<ExecuteFns>>,
@@ -345,11 +642,14 @@ impl ProcessManger {
                         expired = true;
                         break;
                     }
+                    // 启用shell集成标记时仅在命令结束后匹配
+                    if self.shell_integration {
+                        continue;
+                    }
                     let content = &res.lock().await.screen().contents(); // 获取屏幕内容
                     debug!(session_id=%self.uniq_id,"receive content: {}", content);
-                    self.insert_global_params(
-                                format!("node-{node_id}-cmd-output"),content.clone()).await;
-                    match self.process_execute_fns(execute_fns, cmd_sender, &state).await{
+                    self.insert_cmd_output(&node.id, &node.captures, content).await;
+                    match self.process_execute_fns(execute_fns, cmd_sender, &state, node.fork).await{
                         Ok(_) => {
                             debug!(session_id=%self.uniq_id,"time stop loop");
                             break;
@@ -368,9 +668,11 @@ impl ProcessManger {
                             }
                             let content = md.output.clone();
                             debug!(session_id=%self.uniq_id,"receive cmd: {:?}", md);
-                            self.insert_global_params(
-                                format!("node-{node_id}-cmd-output"),content.clone()).await;
-                            match self.process_execute_fns(execute_fns, cmd_sender, &state).await{
+                            self.insert_cmd_output(&node.id, &node.captures, &content).await;
+                            if let Some(code) = md.exit_code {
+                                self.insert_global_params(format!("node-{}-exit-code", node.id), code.to_string()).await;
+                            }
+                            match self.process_execute_fns(execute_fns, cmd_sender, &state, node.fork).await{
                                 Ok(_) => {
                                     debug!(session_id=%self.uniq_id,"cmd stop loop");
                                     break;
@@ -400,22 +702,30 @@ impl ProcessManger {
         execute_fns: &RwLock<Vec<ExecuteFns>>,
         cmd_sender: &UnboundedSender<Arc<Mutex<Execute>>>,
         state: &RwLock<PipeState>,
+        fork: bool,
     ) -> anyhow::Result<()> {
         let efn = execute_fns.read().await;
         if efn.is_empty() {
             return anyhow::Ok(());
         }
+        let mut matched = false;
         for fnn in efn.iter() {
             if self.check_conditions(&fnn.fns).await {
                 // 发送命令
                 cmd_sender.send(fnn.execute.clone())?;
-                // 更新状态
-                *state.write().await = PipeState::In;
-                // 如果找到匹配的条件，跳出循环
-                return anyhow::Ok(());
+                matched = true;
+                // 如果找到匹配的条件，跳出循环, 分支节点发送全部匹配的子节点
+                if !fork {
+                    break;
+                }
             }
         }
-        anyhow::bail!("not match any branch")
+        if !matched {
+            anyhow::bail!("not match any branch")
+        }
+        // 更新状态
+        *state.write().await = PipeState::In;
+        anyhow::Ok(())
     }
 
     fn item_build(&self, node_id: &str, item: Item) -> AsyncMatchFn {
@@ -466,6 +776,23 @@ impl ProcessManger {
     }
 }
 
+/// 分支之后首个汇合节点的汇合方式
+async fn find_join_mode(executes: &[Arc<Mutex<Execute>>]) -> Option<JoinMode> {
+    let mut seen = HashSet::new();
+    let mut queue = executes.iter().cloned().collect::<VecDeque<_>>();
+    while let Some(execute) = queue.pop_front() {
+        let exe = execute.lock().await;
+        if !seen.insert(exe.node.id.clone()) {
+            continue;
+        }
+        if exe.node.join.is_some() {
+            return exe.node.join;
+        }
+        queue.extend(exe.children.iter().cloned());
+    }
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use std::{iter::once, time::Duration};
@@ -482,7 +809,8 @@ mod tests {
 
     use super::*;
     use crate::common::em::PreMatchTypeEnum;
-    use crate::{Graph, InData};
+    use crate::{Graph, InData, JoinMode};
+    use genesis_ssh::testing::{FakeShell, TestServer};
     #[tokio::test]
     #[ignore]
     async fn test_graph_building() {
@@ -500,9 +828,13 @@ mod tests {
                         des: "判断是否是home目录".to_string(),
                         cmd: "pwd".to_string(),
                         expire: 0,
+                        elevation: None,
                     },
                     post: None,
                     position: Position::default(),
+                    captures: vec![],
+                    fork: false,
+                    join: None,
                 },
                 Node {
                     id: "2".to_string(),
@@ -516,9 +848,13 @@ mod tests {
                         des: "home目录执行密码变更".to_string(),
                         cmd: "passwd".to_string(),
                         expire: 0,
+                        elevation: None,
                     },
                     post: None,
                     position: Position::default(),
+                    captures: vec![],
+                    fork: false,
+                    join: None,
                 },
                 Node {
                     id: "3".to_string(),
@@ -532,9 +868,13 @@ mod tests {
                         des: "root目录直接退出".to_string(),
                         cmd: "exit".to_string(),
                         expire: 0,
+                        elevation: None,
                     },
                     post: None,
                     position: Position::default(),
+                    captures: vec![],
+                    fork: false,
+                    join: None,
                 },
                 Node {
                     id: "4".to_string(),
@@ -548,9 +888,13 @@ mod tests {
                         des: "输入当前密码".to_string(),
                         cmd: old_password.to_string(),
                         expire: 0,
+                        elevation: None,
                     },
                     post: None,
                     position: Position::default(),
+                    captures: vec![],
+                    fork: false,
+                    join: None,
                 },
                 Node {
                     id: "5".to_string(),
@@ -564,9 +908,13 @@ mod tests {
                         des: "输入新密码".to_string(),
                         cmd: new_password.to_string(),
                         expire: 0,
+                        elevation: None,
                     },
                     post: None,
                     position: Position::default(),
+                    captures: vec![],
+                    fork: false,
+                    join: None,
                 },
                 Node {
                     id: "6".to_string(),
@@ -580,9 +928,13 @@ mod tests {
                         des: "确认新密码".to_string(),
                         cmd: new_password.to_string(),
                         expire: 0,
+                        elevation: None,
                     },
                     post: None,
                     position: Position::default(),
+                    captures: vec![],
+                    fork: false,
+                    join: None,
                 },
                 Node {
                     id: "7".to_string(),
@@ -596,9 +948,13 @@ mod tests {
                         des: "退出".to_string(),
                         cmd: "exit".to_string(),
                         expire: 0,
+                        elevation: None,
                     },
                     post: None,
                     position: Position::default(),
+                    captures: vec![],
+                    fork: false,
+                    join: None,
                 },
             ],
             edges: vec![
@@ -627,6 +983,9 @@ mod tests {
                     target: "7".to_string(),
                 },
             ],
+            env: Default::default(),
+            params: vec![],
+            shell_integration: false,
         };
         let mut graph = Graph::new();
         graph.build_from_edges(in_data).await;
@@ -658,7 +1017,7 @@ mod tests {
         let out_pipe = Pipe::new(sender, receiver.unbox());
         let ctx = CancellationToken::new();
         let manager = PipeManger::default();
-        let (abort_sc, abort_rc) = watch::channel(false);
+        let (abort_sc, _abort_rc) = watch::channel(false);
         let (scc, _rc) = broadcast::channel(16);
         let new_manager = Arc::new(manager);
         let ma = new_manager.do_interactive(in_pipe, out_pipe, scc.clone(), ctx);
@@ -707,4 +1066,376 @@ mod tests {
 
     #[tokio::test]
     async fn test_continue() {}
+
+    fn node(id: &str, cmd: &str, pre: Option<&str>) -> Node {
+        Node {
+            id: id.to_string(),
+            pre: pre.map(|value| Pre {
+                list: vec![Item {
+                    value: value.to_string(),
+                    match_type: PreMatchTypeEnum::Contains,
+                }],
+            }),
+            core: Core {
+                des: cmd.to_string(),
+                cmd: cmd.to_string(),
+                expire: 10,
+                elevation: None,
+            },
+            post: None,
+            position: Position::default(),
+            captures: vec![],
+            fork: false,
+            join: None,
+        }
+    }
+
+    fn edge(source: &str, target: &str) -> Edge {
+        Edge {
+            source: source.to_string(),
+            target: target.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pipe_manager_prompt() {
+        let server = TestServer::start(FakeShell::new().command("pwd", "/root", 0))
+            .await
+            .unwrap();
+        let (hub, sender, _) = start_ssh_connect(Uuid::new_v4(), server.ssh_options())
+            .await
+            .unwrap();
+        let receiver = hub.subscribe(|_| true).await;
+        let (sc, in_rc) = unbounded_channel::<Bytes>();
+        let (psc, _prc) = unbounded_channel::<Bytes>();
+        let in_pipe = Pipe::new(psc, in_rc);
+        let out_pipe = Pipe::new(sender, receiver.unbox());
+        let (scc, mut states) = broadcast::channel(64);
+        let ctx = CancellationToken::new();
+        let manager = Arc::new(PipeManger::new(50, "test".to_string()));
+        manager
+            .clone()
+            .do_interactive(in_pipe, out_pipe, scc, ctx.clone())
+            .await
+            .unwrap();
+        sc.send(Bytes::from_static(b"pwd\r")).unwrap();
+        let cmd = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                if let Ok(ExecuteState::ExecutedCmd(cmd)) = states.recv().await {
+                    return cmd;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        ctx.cancel();
+        assert_eq!(cmd.input, "pwd");
+        assert!(cmd.output.starts_with("/root\n"));
+        assert!(manager.ps1.read().await.contains("[root@genesis ~]#"));
+    }
+
+    #[tokio::test]
+    async fn test_process_graph_with_test_server() {
+        let server = TestServer::start(
+            FakeShell::new()
+                .command("pwd", "/root", 0)
+                .command("whoami", "root", 0),
+        )
+        .await
+        .unwrap();
+        let in_data = InData {
+            nodes: vec![
+                node("1", "pwd", None),
+                node("2", "whoami", Some("/root")),
+                node("3", "false", Some("/home")),
+                node("4", "exit", Some("root")),
+            ],
+            edges: vec![edge("1", "2"), edge("1", "3"), edge("2", "4")],
+            env: Default::default(),
+            params: vec![],
+            shell_integration: false,
+        };
+        let mut graph = Graph::new();
+        graph.build_from_edges(in_data).await;
+        let execute = graph.start_node().await.unwrap();
+        let mut pm = ProcessManger::new("test".to_string(), execute).unwrap();
+        pm.with_ssh_cmd_wait_times(50);
+        let mut states = pm.register_state_watcher();
+        let status = tokio::time::timeout(
+            Duration::from_secs(30),
+            pm.run(Uuid::new_v4(), server.ssh_options()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(matches!(status, TaskStatusEnum::Success));
+        let mut inputs = vec![];
+        while let Ok(state) = states.try_recv() {
+            if let ExecuteState::ExecutedCmd(cmd) = state {
+                inputs.push(cmd.input);
+            }
+        }
+        assert_eq!(inputs, vec!["pwd".to_string(), "whoami".to_string()]);
+    }
+
+    async fn run_elevated(password: &str) -> (anyhow::Result<TaskStatusEnum>, Vec<Bytes>) {
+        let server = TestServer::start(
+            FakeShell::new()
+                .with_sudo_password("secret")
+                .command("whoami", "root", 0),
+        )
+        .await
+        .unwrap();
+        let mut first = node("1", "whoami", None);
+        first.core.elevation = Some(ElevationMode::Sudo);
+        let in_data = InData {
+            nodes: vec![first, node("2", "exit", Some("root"))],
+            edges: vec![edge("1", "2")],
+            env: Default::default(),
+            params: vec![],
+            shell_integration: false,
+        };
+        let mut graph = Graph::new();
+        graph.build_from_edges(in_data).await;
+        let execute = graph.start_node().await.unwrap();
+        let mut pm = ProcessManger::new("test".to_string(), execute).unwrap();
+        pm.with_ssh_cmd_wait_times(50);
+        let mut states = pm.register_state_watcher();
+        let mut option = server.ssh_options();
+        option.elevation.password = Some(password.to_string());
+        let result = tokio::time::timeout(Duration::from_secs(30), pm.run(Uuid::new_v4(), option))
+            .await
+            .unwrap();
+        let mut output = vec![];
+        while let Ok(state) = states.try_recv() {
+            if let ExecuteState::ExecutedBytes(bytes) = state {
+                output.push(bytes);
+            }
+        }
+        (result, output)
+    }
+
+    #[tokio::test]
+    async fn test_process_elevation_with_test_server() {
+        let (result, output) = run_elevated("secret").await;
+        assert!(matches!(result, Ok(TaskStatusEnum::Success)));
+        let output = output.concat();
+        let output = String::from_utf8_lossy(&output);
+        assert!(output.contains("[sudo] password for root: "));
+        assert!(!output.contains("secret"), "password echoed: {output:?}");
+
+        let (result, _) = run_elevated("wrong").await;
+        let error = result.err().map(|e| e.to_string()).unwrap_or_default();
+        assert!(error.contains("elevation password rejected"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn test_process_captures_with_test_server() {
+        let server = TestServer::start(
+            FakeShell::new()
+                .command("cat app.pid", "pid: 4242", 0)
+                .command("kill 4242", "killed", 0),
+        )
+        .await
+        .unwrap();
+        let mut first = node("1", "cat app.pid", None);
+        first.captures = vec![Capture::Regex {
+            pattern: r"pid: (?P<pid>\d+)".to_string(),
+        }];
+        let in_data = InData {
+            nodes: vec![
+                first,
+                node("2", "kill ${node1.pid}", Some("pid")),
+                node("3", "exit", Some("killed")),
+            ],
+            edges: vec![edge("1", "2"), edge("2", "3")],
+            env: Default::default(),
+            params: vec![],
+            shell_integration: false,
+        };
+        let mut graph = Graph::new();
+        graph.build_from_edges(in_data).await;
+        let execute = graph.start_node().await.unwrap();
+        let mut pm = ProcessManger::new("test".to_string(), execute).unwrap();
+        pm.with_ssh_cmd_wait_times(50);
+        let mut states = pm.register_state_watcher();
+        let status = tokio::time::timeout(
+            Duration::from_secs(30),
+            pm.run(Uuid::new_v4(), server.ssh_options()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(matches!(status, TaskStatusEnum::Success));
+        let mut inputs = vec![];
+        while let Ok(state) = states.try_recv() {
+            if let ExecuteState::ExecutedCmd(cmd) = state {
+                inputs.push(cmd.input);
+            }
+        }
+        assert_eq!(
+            inputs,
+            vec!["cat app.pid".to_string(), "kill 4242".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_process_shell_integration_with_test_server() {
+        let server = TestServer::start(
+            FakeShell::new()
+                .command("tail app.log", "\x1b[31merror\x1b[0m #\nsecond line", 0)
+                .command("false", "", 1),
+        )
+        .await
+        .unwrap();
+        let exit_code = |id: &str, cmd: &str, code: &str| {
+            let mut node = node(id, cmd, Some(code));
+            node.pre.as_mut().unwrap().list[0].match_type = PreMatchTypeEnum::ExitCode;
+            node
+        };
+        let in_data = InData {
+            nodes: vec![
+                node("1", "tail app.log", None),
+                node("2", "false", Some("second line")),
+                exit_code("3", "echo failed", "1"),
+                exit_code("4", "echo ok", "0"),
+                node("5", "exit", Some("failed")),
+            ],
+            edges: vec![
+                edge("1", "2"),
+                edge("2", "4"),
+                edge("2", "3"),
+                edge("3", "5"),
+            ],
+            env: Default::default(),
+            params: vec![],
+            shell_integration: true,
+        };
+        let mut graph = Graph::new();
+        graph.build_from_edges(in_data).await;
+        let execute = graph.start_node().await.unwrap();
+        let mut pm = ProcessManger::new("test".to_string(), execute).unwrap();
+        pm.with_ssh_cmd_wait_times(50).with_shell_integration(true);
+        let mut states = pm.register_state_watcher();
+        let status = tokio::time::timeout(
+            Duration::from_secs(30),
+            pm.run(Uuid::new_v4(), server.ssh_options()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(matches!(status, TaskStatusEnum::Success));
+        let mut cmds = vec![];
+        while let Ok(state) = states.try_recv() {
+            if let ExecuteState::ExecutedCmd(cmd) = state {
+                cmds.push(cmd);
+            }
+        }
+        // 输出中的提示符字符不影响命令边界
+        assert_eq!(cmds[0].output, "error #\nsecond line");
+        assert_eq!(
+            cmds.iter()
+                .map(|cmd| (cmd.input.as_str(), cmd.exit_code))
+                .collect::<Vec<_>>(),
+            vec![
+                ("tail app.log", Some(0)),
+                ("false", Some(1)),
+                ("echo failed", Some(0))
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_process_fork_join_with_test_server() {
+        let server = TestServer::start(
+            FakeShell::new()
+                .command("pwd", "/root", 0)
+                .command("whoami", "root", 0)
+                .command("hostname", "genesis", 0),
+        )
+        .await
+        .unwrap();
+        let mut fork = node("1", "pwd", None);
+        fork.fork = true;
+        let mut join = node("5", "echo ${node2.user} ${node3.host}", None);
+        join.join = Some(JoinMode::All);
+        let mut whoami = node("2", "whoami", Some("/root"));
+        whoami.captures = vec![Capture::Slice {
+            name: "user".to_string(),
+            line: 0,
+            column: None,
+        }];
+        let mut hostname = node("3", "hostname", Some("/root"));
+        hostname.captures = vec![Capture::Regex {
+            pattern: r"(?P<host>genesis)".to_string(),
+        }];
+        let in_data = InData {
+            nodes: vec![
+                fork,
+                whoami,
+                hostname,
+                node("4", "false", Some("/home")),
+                join,
+                node("6", "exit", Some("root genesis")),
+            ],
+            edges: vec![
+                edge("1", "2"),
+                edge("1", "3"),
+                edge("1", "4"),
+                edge("2", "5"),
+                edge("3", "5"),
+                edge("5", "6"),
+            ],
+            env: Default::default(),
+            params: vec![],
+            shell_integration: false,
+        };
+        assert!(in_data.validate_graph().is_empty());
+        let mut graph = Graph::new();
+        graph.build_from_edges(in_data).await;
+        let execute = graph.start_node().await.unwrap();
+        let path = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let path = path.to_str().unwrap();
+        let mut pm = ProcessManger::new("fork".to_string(), execute)
+            .unwrap()
+            .with_recorder_param(path, "xterm", 24, 80)
+            .unwrap();
+        pm.with_ssh_cmd_wait_times(50);
+        let mut states = pm.register_state_watcher();
+        let status = tokio::time::timeout(
+            Duration::from_secs(30),
+            pm.run(Uuid::new_v4(), server.ssh_options()),
+        )
+        .await
+        .unwrap();
+        assert!(
+            matches!(status, Ok(TaskStatusEnum::Success)),
+            "{:?}",
+            status.err()
+        );
+        let mut inputs = vec![];
+        while let Ok(state) = states.try_recv() {
+            if let ExecuteState::ExecutedCmd(cmd) = state {
+                inputs.push(cmd.input);
+            }
+        }
+        // 分支命令在各自的shell中执行
+        assert_eq!(
+            inputs,
+            vec!["pwd".to_string(), "echo root genesis".to_string()]
+        );
+        for uniq in ["fork", "fork-2", "fork-3"] {
+            let cast = std::path::Path::new(path)
+                .join("ssh")
+                .join(uniq)
+                .join("recording.cast");
+            assert!(cast.exists(), "{cast:?}");
+        }
+        let branch =
+            std::fs::read_to_string(std::path::Path::new(path).join("ssh/fork-3/recording.cast"))
+                .unwrap();
+        assert!(branch.contains("genesis"));
+        assert!(!branch.contains("whoami"));
+        let _ = std::fs::remove_dir_all(path);
+    }
 }
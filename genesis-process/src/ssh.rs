@@ -1,7 +1,7 @@
 use std::{io::Write, sync::Arc, time::Duration};
 
 use bytes::Bytes;
-use genesis_common::{EventSubscription, TargetSSHOptions};
+use genesis_common::{EventHub, EventSubscription, NotifyEnum, TargetSSHOptions};
 use genesis_ssh::{start_ssh_connect_with_state, ServerExtraEnum};
 use tokio::{
     select,
@@ -25,6 +25,7 @@ pub struct SSHProcessManager {
     abort_rc: watch::Receiver<bool>,
     recorder: Arc<Mutex<Option<Recorder>>>,
     ps1_char: Vec<char>,
+    notify_rc: Option<watch::Receiver<NotifyEnum>>,
 }
 
 impl SSHProcessManager {
@@ -37,6 +38,7 @@ impl SSHProcessManager {
             ssh_cmd_wait_times: 50,
             recorder: Arc::new(Mutex::new(None)),
             ps1_char: vec!['#', '$', '>'],
+            notify_rc: None,
         }
     }
     pub fn with_ps1_char(&mut self, chars: Vec<char>) -> &mut Self {
@@ -51,7 +53,7 @@ impl SSHProcessManager {
         width: u32,
     ) -> anyhow::Result<Self> {
         let recorder = RecorderBuilder::default()
-            .uniq(&self.uniq_id.to_string())
+            .uniq(self.uniq_id.to_string())
             .path(save_path)
             .term(term)
             .height(height)
@@ -75,6 +77,11 @@ impl SSHProcessManager {
         self.abort_rc.clone()
     }
 
+    /// 连接状态, shell退出后为退出码, 仅连接建立后存在
+    pub fn get_notify_rc(&self) -> Option<watch::Receiver<NotifyEnum>> {
+        self.notify_rc.clone()
+    }
+
     /// stop process
     pub fn stop_process(&self) {
         match self.abort_sc.send(true) {
@@ -153,8 +160,34 @@ impl SSHProcessManager {
         broadcast::Receiver<ExecuteState>,
         UnboundedSender<ServerExtraEnum>,
     )> {
-        let (hub, sender, see) =
-            start_ssh_connect_with_state(self.uniq_id, ssh_option, None).await?;
+        let connected = start_ssh_connect_with_state(self.uniq_id, ssh_option, None).await?;
+        anyhow::Ok(self.attach(connected).await)
+    }
+
+    /// 接管已建立的ssh或telnet连接, 录像及命令解析一致
+    pub async fn attach(
+        &mut self,
+        (hub, sender, see, notify): (
+            EventHub<Bytes>,
+            UnboundedSender<Bytes>,
+            UnboundedSender<ServerExtraEnum>,
+            watch::Receiver<NotifyEnum>,
+        ),
+    ) -> (
+        UnboundedSender<Bytes>,
+        broadcast::Receiver<ExecuteState>,
+        UnboundedSender<ServerExtraEnum>,
+    ) {
+        self.notify_rc = Some(notify);
+        let (sc, broadcast_receiver) = self.do_process(hub, sender).await;
+        (sc, broadcast_receiver, see)
+    }
+
+    async fn do_process(
+        &mut self,
+        hub: EventHub<Bytes>,
+        sender: UnboundedSender<Bytes>,
+    ) -> (UnboundedSender<Bytes>, broadcast::Receiver<ExecuteState>) {
         // step2. Two-way binary stream copy
         let receiver = hub.subscribe(|_| true).await;
         let (sc, in_rc) = unbounded_channel::<Bytes>();
@@ -177,6 +210,6 @@ impl SSHProcessManager {
             .await;
         // step5. cmd & recording process
         self.do_recording(hub.subscribe(|_| true).await).await;
-        anyhow::Ok((sc, broadcast_receiver, see))
+        (sc, broadcast_receiver)
     }
 }
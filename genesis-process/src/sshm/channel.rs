@@ -108,7 +108,7 @@ impl ChannelManager {
         UnboundedSender<ServerExtraEnum>,
     )> {
         // step1. 建立ssh连接
-        let (hub, sender, see) =
+        let (hub, sender, see, _) =
             start_ssh_connect_with_state(self.uniq_id, ssh_option, Some(self.ctx.clone())).await?;
         // step2. 创建双向拷贝通道
         let receiver = hub.subscribe(|_| true).await;
@@ -131,9 +131,7 @@ impl ChannelManager {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use genesis_common::{
-        PtyRequest, SSHTargetAuth, SshTargetPasswordAuth, TargetSSHOptionsBuilder,
-    };
+    use genesis_ssh::testing::{FakeShell, TestServer};
     use tokio::time;
     #[tokio::test]
     async fn test_ssh_channel_run() -> anyhow::Result<()> {
@@ -145,16 +143,8 @@ mod tests {
             .wait_times(5)
             .build()?;
         // step2. ssh options
-        let ssh_options = TargetSSHOptionsBuilder::default()
-            .host("10.0.1.52")
-            .port(22u16)
-            .username("root")
-            .auth(SSHTargetAuth::Password(SshTargetPasswordAuth {
-                password: "1qaz2wsx".to_string(),
-            }))
-            .pty_request(PtyRequest::default())
-            .build()
-            .expect("unable to build channel manager");
+        let server = TestServer::start(FakeShell::new()).await?;
+        let ssh_options = server.ssh_options();
         let (sender, mut receiver, _) = cm.run(ssh_options).await?;
         let sender_ctx = ctx.clone();
         tokio::spawn(async move {
@@ -0,0 +1,633 @@
+//! 指令图校验及模拟执行
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::capture::{interpolate, var_key, PLACEHOLDER};
+use crate::common::em::PreMatchTypeEnum;
+use crate::common::string::match_item;
+use crate::{InData, Node};
+
+/// 命令中的任意 `${...}` 引用
+static ANY_PLACEHOLDER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\$\{([^}]*)\}").unwrap());
+
+/// 模拟执行的最大步数
+const MAX_DRY_RUN_STEPS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiagnosticLevel {
+    /// 无法执行, 保存时拒绝
+    Error,
+    /// 可执行, 但可能不符合预期
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiagnosticKind {
+    MissingRoot,
+    DuplicateNode,
+    DanglingEdge,
+    Cycle,
+    Unreachable,
+    InvalidRegex,
+    UndeclaredPlaceholder,
+    ExitCodeUnavailable,
+}
+
+/// 指令图校验结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub level: DiagnosticLevel,
+    pub kind: DiagnosticKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node: Option<String>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn error(kind: DiagnosticKind, node: Option<&str>, message: String) -> Self {
+        Self {
+            level: DiagnosticLevel::Error,
+            kind,
+            node: node.map(str::to_string),
+            message,
+        }
+    }
+
+    fn warning(kind: DiagnosticKind, node: Option<&str>, message: String) -> Self {
+        Self {
+            level: DiagnosticLevel::Warning,
+            ..Self::error(kind, node, message)
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.level == DiagnosticLevel::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.node {
+            Some(node) => write!(f, "node[id:{}] {}", node, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+/// 模拟执行的单个节点
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRunStep {
+    pub node: String,
+    /// 所属分支的起始节点
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// 替换变量后的命令
+    pub cmd: String,
+    pub output: String,
+    /// 本节点提取的变量
+    pub vars: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DryRunEnd {
+    /// 执行至叶子节点
+    Finished,
+    /// 没有匹配的子节点, 实际执行时将等待至超时
+    NoMatch,
+    /// 命令引用了未定义的变量
+    UndefinedVariable,
+    /// 超过最大步数
+    StepLimit,
+    /// 图存在错误, 未执行
+    Invalid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRun {
+    pub diagnostics: Vec<Diagnostic>,
+    pub steps: Vec<DryRunStep>,
+    pub end: DryRunEnd,
+}
+
+impl InData {
+    /// 根节点, 优先取没有入边的 `1` 号节点, 否则取唯一没有入边的节点
+    pub fn root_id(&self) -> Option<&str> {
+        let targets = self
+            .edges
+            .iter()
+            .map(|e| e.target.as_str())
+            .collect::<HashSet<_>>();
+        let roots = self
+            .nodes
+            .iter()
+            .map(|n| n.id.as_str())
+            .filter(|id| !targets.contains(id))
+            .collect::<Vec<_>>();
+        match roots.as_slice() {
+            [root] => Some(root),
+            roots => roots.iter().find(|id| **id == "1").copied(),
+        }
+    }
+
+    fn children(&self) -> HashMap<&str, Vec<&str>> {
+        let ids = self
+            .nodes
+            .iter()
+            .map(|n| n.id.as_str())
+            .collect::<HashSet<_>>();
+        let mut children: HashMap<&str, Vec<&str>> = HashMap::new();
+        for edge in &self.edges {
+            if ids.contains(edge.source.as_str()) && ids.contains(edge.target.as_str()) {
+                children
+                    .entry(edge.source.as_str())
+                    .or_default()
+                    .push(edge.target.as_str());
+            }
+        }
+        children
+    }
+
+    /// 校验指令图, 返回全部问题
+    pub fn validate_graph(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+        let mut ids = HashSet::new();
+        for node in &self.nodes {
+            if !ids.insert(node.id.as_str()) {
+                diagnostics.push(Diagnostic::error(
+                    DiagnosticKind::DuplicateNode,
+                    Some(&node.id),
+                    "duplicate node id".to_string(),
+                ));
+            }
+        }
+        for edge in &self.edges {
+            for id in [&edge.source, &edge.target] {
+                if !ids.contains(id.as_str()) {
+                    diagnostics.push(Diagnostic::error(
+                        DiagnosticKind::DanglingEdge,
+                        None,
+                        format!(
+                            "edge {} -> {} refers to unknown node {}",
+                            edge.source, edge.target, id
+                        ),
+                    ));
+                }
+            }
+        }
+        let children = self.children();
+        match self.root_id() {
+            Some(root) => {
+                let reachable = reachable(&children, root);
+                for node in &self.nodes {
+                    if !reachable.contains(node.id.as_str()) {
+                        diagnostics.push(Diagnostic::warning(
+                            DiagnosticKind::Unreachable,
+                            Some(&node.id),
+                            "node is unreachable from root".to_string(),
+                        ));
+                    }
+                }
+            }
+            None => diagnostics.push(Diagnostic::error(
+                DiagnosticKind::MissingRoot,
+                None,
+                "no unique root node without incoming edges".to_string(),
+            )),
+        }
+        if let Some(cycle) = find_cycle(&self.nodes, &children) {
+            diagnostics.push(Diagnostic::error(
+                DiagnosticKind::Cycle,
+                Some(cycle[0]),
+                format!("cycle detected: {}", cycle.join(" -> ")),
+            ));
+        }
+        for node in &self.nodes {
+            self.validate_node(node, &children, &mut diagnostics);
+        }
+        diagnostics
+    }
+
+    fn validate_node(
+        &self,
+        node: &Node,
+        children: &HashMap<&str, Vec<&str>>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let id = Some(node.id.as_str());
+        let items = node.pre.iter().flat_map(|p| &p.list);
+        let items = items.chain(node.post.iter().flat_map(|p| &p.list));
+        for item in items {
+            if item.match_type.is_exit_code() && !self.shell_integration {
+                diagnostics.push(Diagnostic::warning(
+                    DiagnosticKind::ExitCodeUnavailable,
+                    id,
+                    "exit code match requires shell integration".to_string(),
+                ));
+            }
+            if matches!(item.match_type, PreMatchTypeEnum::Reg) {
+                if let Err(e) = Regex::new(&item.value) {
+                    diagnostics.push(Diagnostic::error(
+                        DiagnosticKind::InvalidRegex,
+                        id,
+                        format!("invalid match regex {:?}: {}", item.value, e),
+                    ));
+                }
+            }
+        }
+        for capture in &node.captures {
+            if let Err(e) = capture.names() {
+                diagnostics.push(Diagnostic::error(
+                    DiagnosticKind::InvalidRegex,
+                    id,
+                    format!("invalid capture regex: {e}"),
+                ));
+            }
+        }
+        for caps in ANY_PLACEHOLDER.captures_iter(&node.core.cmd) {
+            let placeholder = &caps[0];
+            if let Some(var) = PLACEHOLDER.captures(placeholder) {
+                let (source, name) = (&var[1], &var[2]);
+                let declared = self.nodes.iter().any(|n| {
+                    n.id == source
+                        && n.captures
+                            .iter()
+                            .filter_map(|c| c.names().ok())
+                            .any(|names| names.iter().any(|n| n == name))
+                });
+                if !declared {
+                    diagnostics.push(Diagnostic::error(
+                        DiagnosticKind::UndeclaredPlaceholder,
+                        id,
+                        format!("{placeholder} is not captured by node {source}"),
+                    ));
+                } else if !reachable(children, source).contains(node.id.as_str())
+                    || source == node.id
+                {
+                    diagnostics.push(Diagnostic::error(
+                        DiagnosticKind::UndeclaredPlaceholder,
+                        id,
+                        format!("{placeholder} refers to node {source} which does not run before"),
+                    ));
+                }
+            } else if !self.params.iter().any(|p| p == &caps[1]) && !self.env.contains_key(&caps[1])
+            {
+                diagnostics.push(Diagnostic::warning(
+                    DiagnosticKind::UndeclaredPlaceholder,
+                    id,
+                    format!("{placeholder} is not a declared param"),
+                ));
+            }
+        }
+    }
+
+    /// 以模拟输出执行指令图, 未提供输出的节点视为输出为空, 退出码视为0
+    pub fn dry_run(&self, outputs: &HashMap<String, String>) -> DryRun {
+        let diagnostics = self.validate_graph();
+        let mut steps = vec![];
+        let root = self.root_id();
+        let end = match root {
+            Some(root) if !diagnostics.iter().any(Diagnostic::is_error) => {
+                self.simulate(root, outputs, &mut steps)
+            }
+            _ => DryRunEnd::Invalid,
+        };
+        DryRun {
+            diagnostics,
+            steps,
+            end,
+        }
+    }
+
+    fn simulate(
+        &self,
+        root: &str,
+        outputs: &HashMap<String, String>,
+        steps: &mut Vec<DryRunStep>,
+    ) -> DryRunEnd {
+        let mut simulation = Simulation {
+            nodes: self.nodes.iter().map(|n| (n.id.as_str(), n)).collect(),
+            children: self.children(),
+            outputs,
+            params: HashMap::new(),
+            steps,
+        };
+        match simulation.walk(simulation.nodes[root], None) {
+            Ok(_) => DryRunEnd::Finished,
+            Err(end) => end,
+        }
+    }
+}
+
+struct Simulation<'a> {
+    nodes: HashMap<&'a str, &'a Node>,
+    children: HashMap<&'a str, Vec<&'a str>>,
+    outputs: &'a HashMap<String, String>,
+    params: HashMap<String, String>,
+    steps: &'a mut Vec<DryRunStep>,
+}
+
+impl<'a> Simulation<'a> {
+    /// 从节点开始执行, 分支内到达汇合节点时返回该节点, 分支按顺序模拟
+    fn walk(
+        &mut self,
+        start: &'a Node,
+        branch: Option<&str>,
+    ) -> Result<Option<&'a Node>, DryRunEnd> {
+        let mut current = start;
+        let mut resume_join = None;
+        loop {
+            if branch.is_some() && current.join.is_some() && resume_join != Some(&current.id) {
+                return Ok(Some(current));
+            }
+            if self.steps.len() >= MAX_DRY_RUN_STEPS {
+                return Err(DryRunEnd::StepLimit);
+            }
+            let Ok(cmd) = interpolate(&current.core.cmd, &self.params) else {
+                return Err(DryRunEnd::UndefinedVariable);
+            };
+            let output = self.outputs.get(&current.id).cloned().unwrap_or_default();
+            let vars = current
+                .captures
+                .iter()
+                .filter_map(|c| c.extract(&output).ok())
+                .flatten()
+                .collect::<BTreeMap<_, _>>();
+            self.params.extend(
+                vars.iter()
+                    .map(|(name, value)| (var_key(&current.id, name), value.clone())),
+            );
+            self.steps.push(DryRunStep {
+                node: current.id.clone(),
+                branch: branch.map(str::to_string),
+                cmd,
+                output: output.clone(),
+                vars,
+            });
+            let Some(next) = self.children.get(current.id.as_str()) else {
+                return Ok(None);
+            };
+            // 与执行时一致, 取第一个条件全部满足的子节点, 分支节点取全部
+            let mut matched = next.iter().map(|id| self.nodes[id]).filter(|child| {
+                child.pre.iter().flat_map(|p| &p.list).all(|item| {
+                    let source = match item.match_type.is_exit_code() {
+                        true => "0",
+                        false => &output,
+                    };
+                    match_item(&item.match_type, source, &item.value).unwrap_or(false)
+                })
+            });
+            if !current.fork {
+                match matched.next() {
+                    Some(child) => current = child,
+                    None => return Err(DryRunEnd::NoMatch),
+                }
+                continue;
+            }
+            let matched = matched.collect::<Vec<_>>();
+            if matched.is_empty() {
+                return Err(DryRunEnd::NoMatch);
+            }
+            let mut joined = None;
+            for child in matched {
+                joined = joined.or(self.walk(child, Some(&child.id))?);
+            }
+            match joined {
+                Some(join) => {
+                    resume_join = Some(&join.id);
+                    current = join;
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+fn reachable<'a>(children: &HashMap<&'a str, Vec<&'a str>>, root: &'a str) -> HashSet<&'a str> {
+    let mut seen = HashSet::from([root]);
+    let mut queue = VecDeque::from([root]);
+    while let Some(id) = queue.pop_front() {
+        for child in children.get(id).into_iter().flatten() {
+            if seen.insert(child) {
+                queue.push_back(child);
+            }
+        }
+    }
+    seen
+}
+
+/// 查找一个环, 返回环上的节点, 首尾相同
+fn find_cycle<'a>(
+    nodes: &'a [Node],
+    children: &HashMap<&'a str, Vec<&'a str>>,
+) -> Option<Vec<&'a str>> {
+    let mut done = HashSet::new();
+    for node in nodes {
+        let mut path: Vec<&str> = vec![];
+        // (节点, 下一个待访问的子节点下标)
+        let mut stack = vec![(node.id.as_str(), 0)];
+        while let Some((id, index)) = stack.last_mut() {
+            let id = *id;
+            if *index == 0 {
+                if done.contains(id) {
+                    stack.pop();
+                    continue;
+                }
+                path.push(id);
+            }
+            match children.get(id).and_then(|c| c.get(*index)) {
+                Some(child) => {
+                    *index += 1;
+                    if let Some(start) = path.iter().position(|p| p == child) {
+                        let mut cycle = path[start..].to_vec();
+                        cycle.push(child);
+                        return Some(cycle);
+                    }
+                    stack.push((child, 0));
+                }
+                None => {
+                    done.insert(id);
+                    path.pop();
+                    stack.pop();
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Capture, Core, Edge, Item, JoinMode, Position, Pre};
+
+    fn node(id: &str, cmd: &str, pre: Option<(PreMatchTypeEnum, &str)>) -> Node {
+        Node {
+            id: id.to_string(),
+            pre: pre.map(|(match_type, value)| Pre {
+                list: vec![Item {
+                    value: value.to_string(),
+                    match_type,
+                }],
+            }),
+            core: Core {
+                des: cmd.to_string(),
+                cmd: cmd.to_string(),
+                expire: 10,
+                elevation: None,
+            },
+            post: None,
+            position: Position::default(),
+            captures: vec![],
+            fork: false,
+            join: None,
+        }
+    }
+
+    fn in_data(nodes: Vec<Node>, edges: &[(&str, &str)]) -> InData {
+        InData {
+            nodes,
+            edges: edges
+                .iter()
+                .map(|(source, target)| Edge {
+                    source: source.to_string(),
+                    target: target.to_string(),
+                })
+                .collect(),
+            env: Default::default(),
+            params: vec![],
+            shell_integration: false,
+        }
+    }
+
+    fn kinds(data: &InData) -> Vec<(DiagnosticKind, Option<String>)> {
+        data.validate_graph()
+            .into_iter()
+            .map(|d| (d.kind, d.node))
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_graph() {
+        let data = in_data(
+            vec![
+                node("a", "pwd", None),
+                node("b", "ls", Some((PreMatchTypeEnum::Reg, "(unclosed"))),
+                node("c", "echo ${TARGET} ${node9.pid}", None),
+                node("d", "whoami", None),
+            ],
+            &[("a", "b"), ("b", "c"), ("c", "b"), ("a", "x")],
+        );
+        assert_eq!(data.root_id(), None);
+        assert_eq!(
+            kinds(&data),
+            vec![
+                (DiagnosticKind::DanglingEdge, None),
+                (DiagnosticKind::MissingRoot, None),
+                (DiagnosticKind::Cycle, Some("b".to_string())),
+                (DiagnosticKind::InvalidRegex, Some("b".to_string())),
+                (DiagnosticKind::UndeclaredPlaceholder, Some("c".to_string())),
+                (DiagnosticKind::UndeclaredPlaceholder, Some("c".to_string())),
+            ]
+        );
+        let mut data = in_data(
+            vec![node("1", "pwd", None), node("2", "ls", None)],
+            &[("2", "1")],
+        );
+        assert_eq!(data.root_id(), Some("2"));
+        data.edges.clear();
+        assert_eq!(data.root_id(), Some("1"));
+        assert_eq!(
+            kinds(&data),
+            vec![(DiagnosticKind::Unreachable, Some("2".to_string()))]
+        );
+        let mut data = in_data(
+            vec![
+                node("1", "false", None),
+                node("2", "echo failed", Some((PreMatchTypeEnum::ExitCode, "1"))),
+            ],
+            &[("1", "2")],
+        );
+        assert_eq!(
+            kinds(&data),
+            vec![(DiagnosticKind::ExitCodeUnavailable, Some("2".to_string()))]
+        );
+        data.shell_integration = true;
+        assert!(kinds(&data).is_empty());
+        assert_eq!(data.dry_run(&HashMap::new()).end, DryRunEnd::NoMatch);
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let mut first = node("1", "cat app.pid", None);
+        first.captures = vec![Capture::Regex {
+            pattern: r"pid: (?P<pid>\d+)".to_string(),
+        }];
+        let data = in_data(
+            vec![
+                first,
+                node(
+                    "2",
+                    "kill ${node1.pid}",
+                    Some((PreMatchTypeEnum::Contains, "pid")),
+                ),
+                node(
+                    "3",
+                    "start",
+                    Some((PreMatchTypeEnum::Contains, "no such file")),
+                ),
+                node("4", "exit", Some((PreMatchTypeEnum::Eq, "killed"))),
+            ],
+            &[("1", "2"), ("1", "3"), ("2", "4")],
+        );
+        let outputs = HashMap::from([
+            ("1".to_string(), "pid: 4242".to_string()),
+            ("2".to_string(), "killed".to_string()),
+        ]);
+        let run = data.dry_run(&outputs);
+        assert!(run.diagnostics.is_empty(), "{:?}", run.diagnostics);
+        assert_eq!(run.end, DryRunEnd::Finished);
+        let cmds = run.steps.iter().map(|s| s.cmd.as_str()).collect::<Vec<_>>();
+        assert_eq!(cmds, vec!["cat app.pid", "kill 4242", "exit"]);
+        assert_eq!(run.steps[0].vars["pid"], "4242");
+
+        let run = data.dry_run(&HashMap::new());
+        assert_eq!(run.end, DryRunEnd::NoMatch);
+        assert_eq!(run.steps.len(), 1);
+
+        let mut fork = node("1", "pwd", None);
+        fork.fork = true;
+        let mut join = node("4", "echo done", None);
+        join.join = Some(JoinMode::All);
+        let data = in_data(
+            vec![
+                fork,
+                node("2", "whoami", None),
+                node("3", "hostname", None),
+                join,
+            ],
+            &[("1", "2"), ("1", "3"), ("2", "4"), ("3", "4")],
+        );
+        let run = data.dry_run(&HashMap::new());
+        assert_eq!(run.end, DryRunEnd::Finished);
+        let steps = run
+            .steps
+            .iter()
+            .map(|s| (s.node.as_str(), s.branch.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            steps,
+            vec![("1", None), ("2", Some("2")), ("3", Some("3")), ("4", None)]
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use std::path::PathBuf;
+
+use genesis_common::{SessionId, SshTargetAgentAuth, TargetSSHOptions};
+use russh::client::{AuthResult, Handle, Msg};
+use russh::keys::agent::client::AgentClient;
+use russh::keys::{HashAlg, PublicKey};
+use russh::{Channel, MethodSet};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+use tracing::{debug, info, warn};
+
+use super::handler::ClientHandler;
+use crate::ConnectionError;
+
+/// 代理协议消息长度上限
+const MAX_AGENT_MESSAGE: usize = 256 * 1024;
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+const SSH_AGENT_FAILURE: u8 = 5;
+
+/// 代理socket路径, 为空时使用 `SSH_AUTH_SOCK`
+pub fn agent_socket(socket: Option<&str>) -> Result<PathBuf, ConnectionError> {
+    match socket.filter(|s| !s.is_empty()) {
+        Some(socket) => Ok(PathBuf::from(socket)),
+        None => std::env::var("SSH_AUTH_SOCK")
+            .map(PathBuf::from)
+            .map_err(|_| ConnectionError::Agent("SSH_AUTH_SOCK is not set".into())),
+    }
+}
+
+/// 密钥指纹, 用于审计
+pub fn key_fingerprint(key: &PublicKey) -> String {
+    key.fingerprint(HashAlg::Sha256).to_string()
+}
+
+/// 依次尝试代理中的密钥, 记录认证成功的密钥指纹
+pub(crate) async fn authenticate_with_agent(
+    session: &mut Handle<ClientHandler>,
+    ssh_options: &TargetSSHOptions,
+    auth: &SshTargetAgentAuth,
+) -> Result<AuthResult, ConnectionError> {
+    let socket = agent_socket(Some(&auth.agent_socket))?;
+    let mut agent = AgentClient::connect_uds(&socket)
+        .await
+        .map_err(|e| ConnectionError::Agent(format!("{}: {}", socket.display(), e)))?;
+    let identities = agent
+        .request_identities()
+        .await
+        .map_err(|e| ConnectionError::Agent(e.to_string()))?;
+    let mut response = AuthResult::Failure {
+        remaining_methods: MethodSet::empty(),
+        partial_success: false,
+    };
+    for key in identities {
+        let fingerprint = key_fingerprint(&key);
+        if !auth.fingerprints.is_empty() && !auth.fingerprints.contains(&fingerprint) {
+            continue;
+        }
+        // rsa 密钥需协商签名算法, 其余类型忽略
+        let hash_alg = if key.algorithm().is_rsa() {
+            session.best_supported_rsa_hash().await?.flatten()
+        } else {
+            None
+        };
+        response = session
+            .authenticate_publickey_with(ssh_options.username.clone(), key, hash_alg, &mut agent)
+            .await
+            .map_err(|e| ConnectionError::Agent(e.to_string()))?;
+        if response.success() {
+            info!(
+                host = %ssh_options.host,
+                port = ssh_options.port,
+                username = %ssh_options.username,
+                %fingerprint,
+                "Authenticated with agent key"
+            );
+            break;
+        }
+        debug!(host=%ssh_options.host, %fingerprint, "Agent key rejected");
+    }
+    Ok(response)
+}
+
+/// 转发目标主机打开的代理通道至本地代理, 并记录签名使用的密钥指纹
+pub(crate) async fn forward_agent_channel(
+    channel: Channel<Msg>,
+    socket: PathBuf,
+    target: String,
+    session_id: SessionId,
+) -> anyhow::Result<()> {
+    let agent = UnixStream::connect(&socket).await?;
+    relay_agent(channel.into_stream(), agent, &target, session_id).await
+}
+
+/// 仅转发列出密钥及签名请求, 其余请求直接回复失败
+async fn relay_agent<S, A>(
+    mut stream: S,
+    mut agent: A,
+    target: &str,
+    session_id: SessionId,
+) -> anyhow::Result<()>
+where
+    S: AsyncReadExt + AsyncWriteExt + Unpin,
+    A: AsyncReadExt + AsyncWriteExt + Unpin,
+{
+    loop {
+        let Some(request) = read_message(&mut stream).await? else {
+            break;
+        };
+        match request.first() {
+            Some(&SSH_AGENTC_SIGN_REQUEST) => match sign_request_key(&request) {
+                Some(key) => info!(
+                    session=%session_id,
+                    %target,
+                    fingerprint = %key_fingerprint(&key),
+                    "Forwarded agent sign request"
+                ),
+                None => {
+                    warn!(session=%session_id, %target, "Forwarded agent sign request with invalid key")
+                }
+            },
+            Some(&SSH_AGENTC_REQUEST_IDENTITIES) => {
+                debug!(session=%session_id, %target, "Forwarded agent identities request")
+            }
+            // 目标主机不可增删或锁定本地代理中的密钥
+            other => {
+                warn!(session=%session_id, %target, request = ?other, "Refused agent request");
+                write_message(&mut stream, &[SSH_AGENT_FAILURE]).await?;
+                continue;
+            }
+        }
+        write_message(&mut agent, &request).await?;
+        let Some(reply) = read_message(&mut agent).await? else {
+            break;
+        };
+        write_message(&mut stream, &reply).await?;
+    }
+    debug!(session=%session_id, %target, "Agent forward channel closed");
+    Ok(())
+}
+
+async fn read_message<R: AsyncReadExt + Unpin>(reader: &mut R) -> anyhow::Result<Option<Vec<u8>>> {
+    let len = match reader.read_u32().await {
+        Ok(len) => len as usize,
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if len > MAX_AGENT_MESSAGE {
+        anyhow::bail!("agent message too large: {}", len);
+    }
+    let mut buf = vec![0; len];
+    reader.read_exact(&mut buf).await?;
+    Ok(Some(buf))
+}
+
+async fn write_message<W: AsyncWriteExt + Unpin>(
+    writer: &mut W,
+    message: &[u8],
+) -> anyhow::Result<()> {
+    writer.write_u32(message.len() as u32).await?;
+    writer.write_all(message).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// 解析签名请求中的公钥: byte type, string key_blob, string data, uint32 flags
+fn sign_request_key(request: &[u8]) -> Option<PublicKey> {
+    let len = u32::from_be_bytes(request.get(1..5)?.try_into().ok()?) as usize;
+    let blob = request.get(5..5 + len)?;
+    PublicKey::from_bytes(blob).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_sign_request_key() {
+        let key = PublicKey::from_openssh(
+            "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAILh0U0W4gkUM0JohvQyrAKed92ofcE2s+Wba+mp0D+gU",
+        )
+        .unwrap();
+        let blob = key.to_bytes().unwrap();
+        let mut request = vec![SSH_AGENTC_SIGN_REQUEST];
+        request.extend_from_slice(&(blob.len() as u32).to_be_bytes());
+        request.extend_from_slice(&blob);
+        request.extend_from_slice(&4u32.to_be_bytes());
+        request.extend_from_slice(b"data");
+        request.extend_from_slice(&0u32.to_be_bytes());
+        let parsed = sign_request_key(&request).unwrap();
+        assert_eq!(key_fingerprint(&parsed), key_fingerprint(&key));
+        assert!(sign_request_key(&request[..8]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_relay_agent_refuses_key_management() -> anyhow::Result<()> {
+        const SSH_AGENTC_REMOVE_ALL_IDENTITIES: u8 = 19;
+        const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+        let (mut remote, stream) = tokio::io::duplex(1024);
+        let (agent, mut local) = tokio::io::duplex(1024);
+        let relay = tokio::spawn(relay_agent(stream, agent, "test", Uuid::new_v4()));
+
+        write_message(&mut remote, &[SSH_AGENTC_REMOVE_ALL_IDENTITIES]).await?;
+        assert_eq!(
+            read_message(&mut remote).await?,
+            Some(vec![SSH_AGENT_FAILURE])
+        );
+
+        write_message(&mut remote, &[SSH_AGENTC_REQUEST_IDENTITIES]).await?;
+        assert_eq!(
+            read_message(&mut local).await?,
+            Some(vec![SSH_AGENTC_REQUEST_IDENTITIES])
+        );
+        let answer = [SSH_AGENT_IDENTITIES_ANSWER, 0, 0, 0, 0];
+        write_message(&mut local, &answer).await?;
+        assert_eq!(read_message(&mut remote).await?, Some(answer.to_vec()));
+
+        drop(remote);
+        relay.await??;
+        Ok(())
+    }
+}
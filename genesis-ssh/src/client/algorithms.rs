@@ -0,0 +1,651 @@
+//! ssh算法配置及协商结果
+
+use std::borrow::Cow;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, LazyLock, RwLock};
+use std::task::{Context, Poll};
+
+use async_trait::async_trait;
+use genesis_common::{AlgorithmProfile, SessionId, TargetSSHOptions};
+use russh::keys::{Algorithm, EcdsaCurve, HashAlg};
+use russh::{cipher, compression, kex, mac, Preferred};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use super::ConnectionError;
+
+/// 读取服务端 KEXINIT 时最多缓存的数据量
+const MAX_KEXINIT_BUFFER: usize = 64 * 1024;
+
+/// 追加在kex列表末尾的扩展协商标识
+const KEX_EXTENSIONS: &[kex::Name] = &[
+    kex::EXTENSION_SUPPORT_AS_CLIENT,
+    kex::EXTENSION_OPENSSH_STRICT_KEX_AS_CLIENT,
+];
+
+const MODERN_KEX: &[kex::Name] = &[
+    kex::CURVE25519,
+    kex::CURVE25519_PRE_RFC_8731,
+    kex::ECDH_SHA2_NISTP256,
+    kex::ECDH_SHA2_NISTP384,
+    kex::ECDH_SHA2_NISTP521,
+    kex::DH_GEX_SHA256,
+    kex::DH_G16_SHA512,
+    kex::DH_G14_SHA256,
+];
+
+const COMPAT_KEX: &[kex::Name] = &[
+    kex::CURVE25519,
+    kex::CURVE25519_PRE_RFC_8731,
+    kex::ECDH_SHA2_NISTP256,
+    kex::ECDH_SHA2_NISTP384,
+    kex::ECDH_SHA2_NISTP521,
+    kex::DH_GEX_SHA256,
+    kex::DH_G16_SHA512,
+    kex::DH_G14_SHA256,
+    kex::DH_GEX_SHA1,
+    kex::DH_G14_SHA1,
+    kex::DH_G1_SHA1,
+];
+
+const FIPS_KEX: &[kex::Name] = &[
+    kex::ECDH_SHA2_NISTP256,
+    kex::ECDH_SHA2_NISTP384,
+    kex::ECDH_SHA2_NISTP521,
+    kex::DH_GEX_SHA256,
+    kex::DH_G16_SHA512,
+    kex::DH_G14_SHA256,
+];
+
+const MODERN_CIPHER: &[cipher::Name] = &[
+    cipher::CHACHA20_POLY1305,
+    cipher::AES_256_GCM,
+    cipher::AES_128_GCM,
+    cipher::AES_256_CTR,
+    cipher::AES_192_CTR,
+    cipher::AES_128_CTR,
+];
+
+const COMPAT_CIPHER: &[cipher::Name] = &[
+    cipher::CHACHA20_POLY1305,
+    cipher::AES_256_GCM,
+    cipher::AES_128_GCM,
+    cipher::AES_256_CTR,
+    cipher::AES_192_CTR,
+    cipher::AES_128_CTR,
+    cipher::AES_256_CBC,
+    cipher::AES_192_CBC,
+    cipher::AES_128_CBC,
+];
+
+const FIPS_CIPHER: &[cipher::Name] = &[
+    cipher::AES_256_GCM,
+    cipher::AES_128_GCM,
+    cipher::AES_256_CTR,
+    cipher::AES_192_CTR,
+    cipher::AES_128_CTR,
+];
+
+const MODERN_MAC: &[mac::Name] = &[
+    mac::HMAC_SHA512_ETM,
+    mac::HMAC_SHA256_ETM,
+    mac::HMAC_SHA512,
+    mac::HMAC_SHA256,
+];
+
+const COMPAT_MAC: &[mac::Name] = &[
+    mac::HMAC_SHA512_ETM,
+    mac::HMAC_SHA256_ETM,
+    mac::HMAC_SHA512,
+    mac::HMAC_SHA256,
+    mac::HMAC_SHA1_ETM,
+    mac::HMAC_SHA1,
+];
+
+const MODERN_KEY: &[Algorithm] = &[
+    Algorithm::Ed25519,
+    Algorithm::Ecdsa {
+        curve: EcdsaCurve::NistP256,
+    },
+    Algorithm::Ecdsa {
+        curve: EcdsaCurve::NistP384,
+    },
+    Algorithm::Ecdsa {
+        curve: EcdsaCurve::NistP521,
+    },
+    Algorithm::Rsa {
+        hash: Some(HashAlg::Sha512),
+    },
+    Algorithm::Rsa {
+        hash: Some(HashAlg::Sha256),
+    },
+];
+
+const COMPAT_KEY: &[Algorithm] = &[
+    Algorithm::Ed25519,
+    Algorithm::Ecdsa {
+        curve: EcdsaCurve::NistP256,
+    },
+    Algorithm::Ecdsa {
+        curve: EcdsaCurve::NistP384,
+    },
+    Algorithm::Ecdsa {
+        curve: EcdsaCurve::NistP521,
+    },
+    Algorithm::Rsa {
+        hash: Some(HashAlg::Sha512),
+    },
+    Algorithm::Rsa {
+        hash: Some(HashAlg::Sha256),
+    },
+    Algorithm::Rsa { hash: None },
+];
+
+const FIPS_KEY: &[Algorithm] = &[
+    Algorithm::Ecdsa {
+        curve: EcdsaCurve::NistP256,
+    },
+    Algorithm::Ecdsa {
+        curve: EcdsaCurve::NistP384,
+    },
+    Algorithm::Ecdsa {
+        curve: EcdsaCurve::NistP521,
+    },
+    Algorithm::Rsa {
+        hash: Some(HashAlg::Sha512),
+    },
+    Algorithm::Rsa {
+        hash: Some(HashAlg::Sha256),
+    },
+];
+
+fn profile_preferred(profile: AlgorithmProfile) -> Preferred {
+    let (kex, key, cipher, mac) = match profile {
+        AlgorithmProfile::Modern => (MODERN_KEX, MODERN_KEY, MODERN_CIPHER, MODERN_MAC),
+        AlgorithmProfile::Compat => (COMPAT_KEX, COMPAT_KEY, COMPAT_CIPHER, COMPAT_MAC),
+        AlgorithmProfile::FipsLike => (FIPS_KEX, FIPS_KEY, FIPS_CIPHER, MODERN_MAC),
+    };
+    Preferred {
+        kex: Cow::Owned([kex, KEX_EXTENSIONS].concat()),
+        key: Cow::Borrowed(key),
+        cipher: Cow::Borrowed(cipher),
+        mac: Cow::Borrowed(mac),
+        ..Preferred::default()
+    }
+}
+
+fn parse_names<T>(
+    kind: &str,
+    names: &[String],
+    parse: impl Fn(&str) -> Option<T>,
+) -> Result<Vec<T>, ConnectionError> {
+    names
+        .iter()
+        .map(|name| {
+            parse(name.trim()).ok_or_else(|| ConnectionError::Algorithm(format!("{kind} {name}")))
+        })
+        .collect()
+}
+
+/// 按目标配置生成算法偏好, 未指定预置集时沿用 `allow_insecure_algos`
+pub(crate) fn preferred(options: &TargetSSHOptions) -> Result<Preferred, ConnectionError> {
+    let algorithms = &options.algorithms;
+    let profile = algorithms.profile.or(options
+        .allow_insecure_algos
+        .unwrap_or(false)
+        .then_some(AlgorithmProfile::Compat));
+    let mut preferred = profile.map(profile_preferred).unwrap_or_default();
+    if !algorithms.kex.is_empty() {
+        let mut names = parse_names("kex", &algorithms.kex, |s| kex::Name::try_from(s).ok())?;
+        names.extend_from_slice(KEX_EXTENSIONS);
+        preferred.kex = Cow::Owned(names);
+    }
+    if !algorithms.host_key.is_empty() {
+        preferred.key = Cow::Owned(parse_names("host key", &algorithms.host_key, |s| {
+            Algorithm::new(s).ok()
+        })?);
+    }
+    if !algorithms.cipher.is_empty() {
+        preferred.cipher = Cow::Owned(parse_names("cipher", &algorithms.cipher, |s| {
+            cipher::Name::try_from(s).ok()
+        })?);
+    }
+    if !algorithms.mac.is_empty() {
+        preferred.mac = Cow::Owned(parse_names("mac", &algorithms.mac, |s| {
+            mac::Name::try_from(s).ok()
+        })?);
+    }
+    if !algorithms.compression.is_empty() {
+        preferred.compression =
+            Cow::Owned(parse_names("compression", &algorithms.compression, |s| {
+                compression::Name::try_from(s).ok()
+            })?);
+    }
+    Ok(preferred)
+}
+
+/// 实际协商的算法, 加密及mac为客户端到服务端方向
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NegotiatedAlgorithms {
+    pub kex: String,
+    pub host_key: String,
+    pub cipher: String,
+    pub mac: String,
+    pub compression: String,
+}
+
+impl NegotiatedAlgorithms {
+    /// 返回其中的弱算法
+    pub fn weak(&self) -> Vec<&str> {
+        let mut weak = vec![];
+        if self.kex.contains("sha1") {
+            weak.push(self.kex.as_str());
+        }
+        if matches!(self.host_key.as_str(), "ssh-rsa" | "ssh-dss") {
+            weak.push(self.host_key.as_str());
+        }
+        if ["cbc", "3des", "arcfour", "none"]
+            .iter()
+            .any(|w| self.cipher.contains(w))
+        {
+            weak.push(self.cipher.as_str());
+        }
+        if self.mac.contains("sha1") || self.mac.contains("md5") {
+            weak.push(self.mac.as_str());
+        }
+        weak
+    }
+}
+
+/// KEXINIT 中的算法列表
+#[derive(Debug, Clone, Default)]
+pub(crate) struct KexInit {
+    kex: Vec<String>,
+    host_key: Vec<String>,
+    cipher: Vec<String>,
+    mac: Vec<String>,
+    compression: Vec<String>,
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<usize> {
+    let bytes = data.get(pos..pos + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+}
+
+/// 解析版本标识后的首个数据包, 数据不足时返回 `Ok(None)`
+fn parse_kexinit(data: &[u8]) -> Result<Option<KexInit>, ()> {
+    // 版本标识前允许有其它文本行
+    let mut pos = 0;
+    loop {
+        let Some(end) = data[pos..].iter().position(|b| *b == b'\n') else {
+            return Ok(None);
+        };
+        let line = &data[pos..pos + end];
+        pos += end + 1;
+        if line.starts_with(b"SSH-") {
+            break;
+        }
+    }
+    let Some(length) = read_u32(data, pos) else {
+        return Ok(None);
+    };
+    if length > MAX_KEXINIT_BUFFER {
+        return Err(());
+    }
+    let Some(packet) = data.get(pos + 4..pos + 4 + length) else {
+        return Ok(None);
+    };
+    let padding = *packet.first().ok_or(())? as usize;
+    let payload = packet
+        .get(1..length.checked_sub(padding).ok_or(())?)
+        .ok_or(())?;
+    // SSH_MSG_KEXINIT, 16字节cookie
+    if payload.first() != Some(&20) {
+        return Err(());
+    }
+    let mut pos = 17;
+    let mut lists = Vec::with_capacity(8);
+    for _ in 0..8 {
+        let len = read_u32(payload, pos).ok_or(())?;
+        let list = payload.get(pos + 4..pos + 4 + len).ok_or(())?;
+        lists.push(
+            String::from_utf8_lossy(list)
+                .split(',')
+                .filter(|s| !s.is_empty())
+                .map(String::from)
+                .collect::<Vec<_>>(),
+        );
+        pos += 4 + len;
+    }
+    Ok(Some(KexInit {
+        kex: lists[0].clone(),
+        host_key: lists[1].clone(),
+        cipher: lists[2].clone(),
+        mac: lists[4].clone(),
+        compression: lists[6].clone(),
+    }))
+}
+
+fn pick<'a>(client: impl IntoIterator<Item = &'a str>, server: &[String]) -> String {
+    client
+        .into_iter()
+        .find(|c| server.iter().any(|s| s == c))
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// 按双方实际发送的 KEXINIT 计算协商结果, 客户端优先, 与 RFC4253 7.1 及russh一致
+pub(crate) fn negotiate(client: &KexInit, server: &KexInit) -> NegotiatedAlgorithms {
+    let kex = pick(
+        client
+            .kex
+            .iter()
+            .map(String::as_str)
+            .filter(|n| !n.starts_with("ext-info-") && !n.starts_with("kex-strict-")),
+        &server.kex,
+    );
+    let host_key = pick(client.host_key.iter().map(String::as_str), &server.host_key);
+    let cipher = pick(client.cipher.iter().map(String::as_str), &server.cipher);
+    // AEAD加密算法不单独协商mac
+    let mac = if cipher.contains("gcm") || cipher.contains("poly1305") {
+        "<implicit>".to_string()
+    } else {
+        pick(client.mac.iter().map(String::as_str), &server.mac)
+    };
+    let compression = pick(
+        client.compression.iter().map(String::as_str),
+        &server.compression,
+    );
+    NegotiatedAlgorithms {
+        kex,
+        host_key,
+        cipher,
+        mac,
+        compression,
+    }
+}
+
+/// 连接双方的 KEXINIT
+#[derive(Debug, Default)]
+pub(crate) struct KexInits {
+    pub client: Option<KexInit>,
+    pub server: Option<KexInit>,
+}
+
+impl KexInits {
+    /// 双方 KEXINIT 均已解析时返回协商结果
+    pub(crate) fn negotiated(&self) -> Option<NegotiatedAlgorithms> {
+        Some(negotiate(self.client.as_ref()?, self.server.as_ref()?))
+    }
+}
+
+pub(crate) type KexInitSlot = Arc<std::sync::Mutex<KexInits>>;
+
+/// 单向数据中的 KEXINIT 解析状态
+#[derive(Default)]
+struct KexInitParser {
+    buffer: Vec<u8>,
+    done: bool,
+}
+
+impl KexInitParser {
+    fn inspect(&mut self, data: &[u8]) -> Option<KexInit> {
+        self.buffer.extend_from_slice(data);
+        let kexinit = match parse_kexinit(&self.buffer) {
+            Ok(None) if self.buffer.len() <= MAX_KEXINIT_BUFFER => return None,
+            Ok(kexinit) => kexinit,
+            Err(_) => None,
+        };
+        self.done = true;
+        self.buffer = vec![];
+        kexinit
+    }
+}
+
+/// 透传连接数据, 同时解析双方的 KEXINIT, 以记录russh实际协商的算法
+pub(crate) struct KexInitTap<S> {
+    inner: S,
+    read: KexInitParser,
+    write: KexInitParser,
+    result: KexInitSlot,
+}
+
+impl<S> KexInitTap<S> {
+    pub(crate) fn new(inner: S, result: KexInitSlot) -> Self {
+        Self {
+            inner,
+            read: Default::default(),
+            write: Default::default(),
+            result,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for KexInitTap<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if !self.read.done && matches!(poll, Poll::Ready(Ok(()))) {
+            let data = buf.filled()[filled..].to_vec();
+            if let Some(kexinit) = self.read.inspect(&data) {
+                if let Ok(mut result) = self.result.lock() {
+                    result.server = Some(kexinit);
+                }
+            }
+        }
+        poll
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for KexInitTap<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if !self.write.done {
+            if let Poll::Ready(Ok(written)) = poll {
+                if let Some(kexinit) = self.write.inspect(&buf[..written]) {
+                    if let Ok(mut result) = self.result.lock() {
+                        result.client = Some(kexinit);
+                    }
+                }
+            }
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// 按会话记录各主机协商的算法, 便于排查仍在使用弱算法的主机
+#[async_trait]
+pub trait AlgorithmRecorder: Send + Sync {
+    async fn record(
+        &self,
+        session_id: SessionId,
+        host: &str,
+        port: u16,
+        algorithms: &NegotiatedAlgorithms,
+    ) -> anyhow::Result<()>;
+}
+
+static ALGORITHM_RECORDER: LazyLock<RwLock<Option<Arc<dyn AlgorithmRecorder>>>> =
+    LazyLock::new(|| RwLock::new(None));
+
+/// 设置全局算法记录, 默认仅输出日志
+pub fn set_algorithm_recorder(recorder: Arc<dyn AlgorithmRecorder>) {
+    if let Ok(mut current) = ALGORITHM_RECORDER.write() {
+        *current = Some(recorder);
+    }
+}
+
+pub(crate) fn algorithm_recorder() -> Option<Arc<dyn AlgorithmRecorder>> {
+    match ALGORITHM_RECORDER.read() {
+        Ok(recorder) => recorder.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use genesis_common::SSHAlgorithms;
+
+    fn name_list(names: &str) -> Vec<u8> {
+        let mut data = (names.len() as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(names.as_bytes());
+        data
+    }
+
+    /// 构造版本标识及 KEXINIT 数据包
+    fn kexinit_packet(ident: &str, lists: [&str; 10]) -> Vec<u8> {
+        let mut payload = vec![20u8];
+        payload.extend_from_slice(&[0; 16]);
+        for list in lists {
+            payload.extend(name_list(list));
+        }
+        payload.extend_from_slice(&[0, 0, 0, 0, 0]);
+        let padding = 4;
+        let mut data = ident.as_bytes().to_vec();
+        data.extend(((payload.len() + 1 + padding) as u32).to_be_bytes());
+        data.push(padding as u8);
+        data.extend(payload);
+        data.extend([0; 4]);
+        data
+    }
+
+    /// 按算法偏好构造客户端 KEXINIT
+    fn client_kexinit(options: &TargetSSHOptions) -> Result<KexInit, ConnectionError> {
+        let preferred = preferred(options)?;
+        let join = |names: Vec<&str>| names.join(",");
+        let kex = join(preferred.kex.iter().map(|n| n.as_ref()).collect());
+        let key = join(preferred.key.iter().map(|k| k.as_str()).collect());
+        let cipher = join(preferred.cipher.iter().map(|n| n.as_ref()).collect());
+        let mac = join(preferred.mac.iter().map(|n| n.as_ref()).collect());
+        let compression = join(preferred.compression.iter().map(|n| n.as_ref()).collect());
+        let data = kexinit_packet(
+            "SSH-2.0-russh\r\n",
+            [
+                &kex,
+                &key,
+                &cipher,
+                &cipher,
+                &mac,
+                &mac,
+                &compression,
+                &compression,
+                "",
+                "",
+            ],
+        );
+        Ok(parse_kexinit(&data).unwrap().unwrap())
+    }
+
+    #[test]
+    fn test_negotiate_kexinit() {
+        let data = kexinit_packet(
+            "welcome\r\nSSH-2.0-OpenSSH_7.4\r\n",
+            [
+                "diffie-hellman-group14-sha1,kex-strict-s-v00@openssh.com",
+                "ssh-rsa,rsa-sha2-256",
+                "aes128-cbc,aes128-ctr",
+                "aes128-cbc,aes128-ctr",
+                "hmac-sha1,hmac-sha2-256",
+                "hmac-sha1,hmac-sha2-256",
+                "none",
+                "none",
+                "",
+                "",
+            ],
+        );
+
+        assert!(parse_kexinit(&data[..40]).unwrap().is_none());
+        let server = parse_kexinit(&data).unwrap().unwrap();
+
+        let mut options = TargetSSHOptions {
+            algorithms: SSHAlgorithms {
+                profile: Some(AlgorithmProfile::Compat),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let negotiated = negotiate(&client_kexinit(&options).unwrap(), &server);
+        assert_eq!(negotiated.kex, "diffie-hellman-group14-sha1");
+        assert_eq!(negotiated.host_key, "rsa-sha2-256");
+        assert_eq!(negotiated.cipher, "aes128-ctr");
+        assert_eq!(negotiated.mac, "hmac-sha2-256");
+        assert_eq!(negotiated.weak(), vec!["diffie-hellman-group14-sha1"]);
+
+        // 现代算法集与该主机无共同的kex
+        options.algorithms.profile = Some(AlgorithmProfile::Modern);
+        assert_eq!(
+            negotiate(&client_kexinit(&options).unwrap(), &server).kex,
+            ""
+        );
+
+        options.algorithms.cipher = vec!["rot13".into()];
+        assert!(preferred(&options).is_err());
+    }
+
+    struct MemoryRecorder(std::sync::Mutex<Vec<(u16, NegotiatedAlgorithms)>>);
+
+    #[async_trait]
+    impl AlgorithmRecorder for MemoryRecorder {
+        async fn record(
+            &self,
+            _session_id: SessionId,
+            _host: &str,
+            port: u16,
+            algorithms: &NegotiatedAlgorithms,
+        ) -> anyhow::Result<()> {
+            self.0.lock().unwrap().push((port, algorithms.clone()));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_negotiated_algorithms() {
+        let recorder = Arc::new(MemoryRecorder(Default::default()));
+        set_algorithm_recorder(recorder.clone());
+        let server = crate::testing::TestServer::start(crate::testing::FakeShell::new())
+            .await
+            .unwrap();
+        let mut options = server.ssh_options();
+        options.algorithms.cipher = vec!["aes256-ctr".into()];
+        options.algorithms.mac = vec!["hmac-sha2-512".into()];
+        let output = crate::exec_command(uuid::Uuid::new_v4(), options, "echo ok")
+            .await
+            .unwrap();
+        assert_eq!(output.stdout, "ok");
+        let negotiated = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            loop {
+                let recorded = recorder.0.lock().unwrap().clone();
+                if let Some((_, negotiated)) = recorded
+                    .into_iter()
+                    .find(|(port, _)| *port == server.addr().port())
+                {
+                    return negotiated;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(negotiated.cipher, "aes256-ctr");
+        assert_eq!(negotiated.mac, "hmac-sha2-512");
+        assert_eq!(negotiated.host_key, "ssh-ed25519");
+        assert!(!negotiated.kex.is_empty());
+    }
+}
@@ -8,6 +8,7 @@ use tracing::*;
 use uuid::Uuid;
 
 use super::error::SshClientError;
+use super::traffic::ChannelMeter;
 use crate::{ChannelOperation, RCEvent};
 
 pub struct DirectTCPIPChannel {
@@ -16,6 +17,7 @@ pub struct DirectTCPIPChannel {
     ops_rx: UnboundedReceiver<ChannelOperation>,
     events_tx: UnboundedSender<RCEvent>,
     session_id: SessionId,
+    meter: ChannelMeter,
 }
 
 impl DirectTCPIPChannel {
@@ -25,6 +27,7 @@ impl DirectTCPIPChannel {
         ops_rx: UnboundedReceiver<ChannelOperation>,
         events_tx: UnboundedSender<RCEvent>,
         session_id: SessionId,
+        meter: ChannelMeter,
     ) -> Self {
         DirectTCPIPChannel {
             client_channel,
@@ -32,6 +35,7 @@ impl DirectTCPIPChannel {
             ops_rx,
             events_tx,
             session_id,
+            meter,
         }
     }
 
@@ -41,6 +45,7 @@ impl DirectTCPIPChannel {
                 incoming_data = self.ops_rx.recv() => {
                     match incoming_data {
                         Some(ChannelOperation::Data(data)) => {
+                            self.meter.upload(data.len()).await;
                             self.client_channel.data(&*data).await?;
                         }
                         Some(ChannelOperation::Eof) => {
@@ -57,6 +62,7 @@ impl DirectTCPIPChannel {
                     match channel_event {
                         Some(russh::ChannelMsg::Data { data }) => {
                             let bytes: &[u8] = &data;
+                            self.meter.download(bytes.len()).await;
                             self.events_tx.send(RCEvent::Output(
                                 self.channel_id,
                                 Bytes::from(bytes.to_vec()),
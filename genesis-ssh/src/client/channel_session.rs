@@ -8,6 +8,8 @@ use tracing::*;
 use uuid::Uuid;
 
 use super::error::SshClientError;
+use super::traffic::ChannelMeter;
+use super::InnerEvent;
 use crate::{ChannelOperation, RCEvent};
 
 pub struct SessionChannel {
@@ -15,7 +17,9 @@ pub struct SessionChannel {
     channel_id: Uuid,
     ops_rx: UnboundedReceiver<ChannelOperation>,
     events_tx: UnboundedSender<RCEvent>,
+    inner_event_tx: UnboundedSender<InnerEvent>,
     session_id: SessionId,
+    meter: ChannelMeter,
     closed: bool,
 }
 
@@ -25,14 +29,18 @@ impl SessionChannel {
         channel_id: Uuid,
         ops_rx: UnboundedReceiver<ChannelOperation>,
         events_tx: UnboundedSender<RCEvent>,
+        inner_event_tx: UnboundedSender<InnerEvent>,
         session_id: SessionId,
+        meter: ChannelMeter,
     ) -> Self {
         SessionChannel {
             client_channel,
             channel_id,
             ops_rx,
             events_tx,
+            inner_event_tx,
             session_id,
+            meter,
             closed: false,
         }
     }
@@ -43,9 +51,11 @@ impl SessionChannel {
                 incoming_data = self.ops_rx.recv() => {
                     match incoming_data {
                         Some(ChannelOperation::Data(data)) => {
+                            self.meter.upload(data.len()).await;
                             self.client_channel.data(&*data).await?;
                         }
                         Some(ChannelOperation::ExtendedData { ext, data }) => {
+                            self.meter.upload(data.len()).await;
                             self.client_channel.extended_data(ext, &*data).await?;
                         }
                         Some(ChannelOperation::RequestPty(request)) => {
@@ -71,13 +81,13 @@ impl SessionChannel {
                             self.client_channel.request_shell(true).await?;
                         },
                         Some(ChannelOperation::RequestEnv(name, value)) => {
-                            self.client_channel.set_env(false, name, value).await?;
+                            self.client_channel.set_env(true, name, value).await?;
                         },
                         Some(ChannelOperation::RequestExec(command)) => {
-                            self.client_channel.exec(false, command).await?;
+                            self.client_channel.exec(true, command).await?;
                         },
                         Some(ChannelOperation::RequestSubsystem(name)) => {
-                            self.client_channel.request_subsystem(false, &name).await?;
+                            self.client_channel.request_subsystem(true, &name).await?;
                         },
                         Some(ChannelOperation::Eof) => {
                             self.client_channel.eof().await?;
@@ -105,12 +115,15 @@ impl SessionChannel {
                     match channel_event {
                         Some(russh::ChannelMsg::Data { data }) => {
                             let bytes: &[u8] = &data;
+                            self.meter.download(bytes.len()).await;
                             self.events_tx.send(RCEvent::Output(
                                 self.channel_id,
                                 Bytes::from(bytes.to_vec()),
                             )).map_err(|_| SshClientError::MpscError)?;
                         }
                         Some(russh::ChannelMsg::Close) => {
+                            // 服务端正常关闭, 连接断开时通道直接结束
+                            let _ = self.inner_event_tx.send(InnerEvent::ChannelClosed(self.channel_id));
                             break;
                         },
                         Some(russh::ChannelMsg::Success) => {
@@ -137,6 +150,7 @@ impl SessionChannel {
                         }
                         Some(russh::ChannelMsg::ExtendedData { data, ext }) => {
                             let data: &[u8] = &data;
+                            self.meter.download(data.len()).await;
                             self.events_tx.send(RCEvent::ExtendedData {
                                 channel: self.channel_id,
                                 data: Bytes::from(data.to_vec()),
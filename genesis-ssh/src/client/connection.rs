@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use genesis_common::{SessionId, TargetSSHOptions};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::{oneshot, Mutex};
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+use uuid::Uuid;
+
+use super::{
+    connect_remote_client, relay_auth_prompt, RCCommand, RCCommandReply, RCEvent, SessionTraffic,
+};
+use crate::{ChannelOperation, DirectTCPIPParams, ForwardedTcpIpParams};
+
+type ChannelMap = Arc<Mutex<HashMap<Uuid, UnboundedSender<RCEvent>>>>;
+/// 远程转发监听, 按服务端监听端口分发
+type ForwardMap = Arc<Mutex<HashMap<u32, UnboundedSender<ForwardedChannel>>>>;
+
+/// 服务端经远程转发打开的通道
+pub type ForwardedChannel = (RemoteChannel, ForwardedTcpIpParams);
+
+/// 远程连接, 按通道分发事件, 可在同一连接上打开多个通道
+#[derive(Clone)]
+pub struct RemoteConnection {
+    id: SessionId,
+    command_tx: UnboundedSender<(RCCommand, Option<RCCommandReply>)>,
+    channels: ChannelMap,
+    forwards: ForwardMap,
+    ctx: CancellationToken,
+    traffic: Arc<SessionTraffic>,
+}
+
+impl RemoteConnection {
+    pub async fn connect(id: SessionId, option: TargetSSHOptions) -> Result<Self> {
+        let mut handle = connect_remote_client(id, option).await?;
+        let channels: ChannelMap = Arc::new(Mutex::new(HashMap::new()));
+        let forwards: ForwardMap = Arc::new(Mutex::new(HashMap::new()));
+        let ctx = CancellationToken::new();
+        let command_tx = handle.command_tx.clone();
+        let traffic = handle.traffic.clone();
+        tokio::spawn({
+            let channels = channels.clone();
+            let forwards = forwards.clone();
+            let ctx = ctx.clone();
+            async move {
+                while let Some(event) = handle.event_rx.recv().await {
+                    // 重连时的认证提示
+                    let event = match event {
+                        RCEvent::AuthPrompt(prompt) => {
+                            relay_auth_prompt(id, prompt);
+                            continue;
+                        }
+                        event => event,
+                    };
+                    let channel_id = match &event {
+                        RCEvent::ForwardedTcpIp(channel_id, params) => {
+                            let (tx, rx) = unbounded_channel();
+                            channels.lock().await.insert(*channel_id, tx);
+                            let channel = RemoteChannel {
+                                id: *channel_id,
+                                command_tx: command_tx.clone(),
+                                events: rx,
+                            };
+                            let accepted = match forwards.lock().await.get(&params.connected_port) {
+                                Some(tx) => tx.send((channel, params.clone())).map_err(|e| e.0 .0),
+                                None => Err(channel),
+                            };
+                            if let Err(channel) = accepted {
+                                warn!(session_id=%id, ?params, "no listener for forwarded channel");
+                                channel.close();
+                            }
+                            continue;
+                        }
+                        RCEvent::Output(id, _)
+                        | RCEvent::Success(id)
+                        | RCEvent::ChannelFailure(id)
+                        | RCEvent::Eof(id)
+                        | RCEvent::Close(id)
+                        | RCEvent::ExitStatus(id, _) => *id,
+                        RCEvent::ExitSignal { channel, .. }
+                        | RCEvent::ExtendedData { channel, .. } => *channel,
+                        RCEvent::Done => break,
+                        _ => {
+                            debug!(session_id=%id, "connection event: {:?}", event);
+                            continue;
+                        }
+                    };
+                    let mut channels = channels.lock().await;
+                    let closed = matches!(event, RCEvent::Close(_));
+                    if let Some(tx) = channels.get(&channel_id) {
+                        let _ = tx.send(event);
+                    }
+                    if closed {
+                        channels.remove(&channel_id);
+                    }
+                }
+                // 连接断开, 关闭所有通道
+                channels.lock().await.clear();
+                forwards.lock().await.clear();
+                ctx.cancel();
+                debug!(session_id=%id, "remote connection closed");
+            }
+        });
+        Ok(Self {
+            id,
+            command_tx: handle.command_tx,
+            channels,
+            forwards,
+            ctx,
+            traffic,
+        })
+    }
+
+    pub fn id(&self) -> SessionId {
+        self.id
+    }
+
+    /// 连接上各通道的流量统计
+    pub fn traffic(&self) -> Arc<SessionTraffic> {
+        self.traffic.clone()
+    }
+
+    /// 连接断开时触发
+    pub fn closed(&self) -> CancellationToken {
+        self.ctx.clone()
+    }
+
+    pub async fn open_session(&self) -> Result<RemoteChannel> {
+        self.open_channel(ChannelOperation::OpenShell).await
+    }
+
+    /// 打开会话通道并请求子系统, 等待服务端确认
+    pub async fn open_subsystem(&self, name: &str) -> Result<RemoteChannel> {
+        let mut channel = self.open_session().await?;
+        channel.send(ChannelOperation::RequestSubsystem(name.to_string()))?;
+        loop {
+            match channel.recv().await {
+                Some(RCEvent::Success(_)) => return Ok(channel),
+                Some(RCEvent::ChannelFailure(_)) => {
+                    channel.close();
+                    anyhow::bail!("subsystem {} request rejected", name)
+                }
+                Some(RCEvent::Close(_)) | None => {
+                    anyhow::bail!("channel closed before subsystem {} started", name)
+                }
+                Some(e) => debug!(channel=%channel.id(), "subsystem event: {:?}", e),
+            }
+        }
+    }
+
+    pub async fn open_direct_tcpip(&self, params: DirectTCPIPParams) -> Result<RemoteChannel> {
+        self.open_channel(ChannelOperation::OpenDirectTCPIP(params))
+            .await
+    }
+
+    async fn open_channel(&self, op: ChannelOperation) -> Result<RemoteChannel> {
+        let channel_id = Uuid::new_v4();
+        let (tx, rx) = unbounded_channel();
+        // 先注册再打开, 避免丢失事件
+        self.channels.lock().await.insert(channel_id, tx);
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let result = match self
+            .command_tx
+            .send((RCCommand::Channel(channel_id, op), Some(reply_tx)))
+        {
+            Ok(_) => reply_rx.await.map_err(anyhow::Error::from),
+            Err(e) => Err(anyhow::anyhow!("send open channel command error: {}", e)),
+        };
+        if let Err(e) = result.and_then(|r| r.map_err(anyhow::Error::from)) {
+            self.channels.lock().await.remove(&channel_id);
+            return Err(e);
+        }
+        Ok(RemoteChannel {
+            id: channel_id,
+            command_tx: self.command_tx.clone(),
+            events: rx,
+        })
+    }
+
+    /// 请求服务端监听 `address:port`, 返回该端口上转发过来的通道
+    pub async fn tcpip_forward(
+        &self,
+        address: &str,
+        port: u32,
+    ) -> Result<UnboundedReceiver<ForwardedChannel>> {
+        if port == 0 {
+            anyhow::bail!("remote forward port must be specified");
+        }
+        let (tx, rx) = unbounded_channel();
+        {
+            let mut forwards = self.forwards.lock().await;
+            if forwards.contains_key(&port) {
+                anyhow::bail!("remote forward on port {} already exists", port);
+            }
+            forwards.insert(port, tx);
+        }
+        let result = self
+            .command(RCCommand::ForwardTCPIP(address.to_string(), port))
+            .await;
+        if let Err(e) = result {
+            self.forwards.lock().await.remove(&port);
+            return Err(e);
+        }
+        Ok(rx)
+    }
+
+    pub async fn cancel_tcpip_forward(&self, address: &str, port: u32) -> Result<()> {
+        self.forwards.lock().await.remove(&port);
+        self.command(RCCommand::CancelTCPIPForward(address.to_string(), port))
+            .await
+    }
+
+    async fn command(&self, command: RCCommand) -> Result<()> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.command_tx
+            .send((command, Some(reply_tx)))
+            .map_err(|e| anyhow::anyhow!("send command error: {}", e))?;
+        Ok(reply_rx.await??)
+    }
+
+    pub(crate) fn send_channel_op(&self, channel_id: Uuid, op: ChannelOperation) -> Result<()> {
+        self.command_tx
+            .send((RCCommand::Channel(channel_id, op), None))
+            .map_err(|e| anyhow::anyhow!("send channel operation error: {}", e))
+    }
+
+    pub fn disconnect(&self) {
+        let _ = self.command_tx.send((RCCommand::Disconnect, None));
+    }
+}
+
+/// 远程连接上的单个通道
+pub struct RemoteChannel {
+    id: Uuid,
+    command_tx: UnboundedSender<(RCCommand, Option<RCCommandReply>)>,
+    events: UnboundedReceiver<RCEvent>,
+}
+
+impl RemoteChannel {
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn send(&self, op: ChannelOperation) -> Result<()> {
+        self.command_tx
+            .send((RCCommand::Channel(self.id, op), None))
+            .map_err(|e| anyhow::anyhow!("send channel operation error: {}", e))
+    }
+
+    /// 接收通道事件, 通道关闭后返回None
+    pub async fn recv(&mut self) -> Option<RCEvent> {
+        self.events.recv().await
+    }
+
+    pub fn close(&self) {
+        let _ = self.send(ChannelOperation::Close);
+    }
+}
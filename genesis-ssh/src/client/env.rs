@@ -0,0 +1,151 @@
+//! shell会话的环境变量注入
+
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+use genesis_common::SSHEnvironment;
+use tracing::*;
+
+use crate::ChannelOperation;
+
+/// 跟踪pty、env及shell请求的应答, 服务端按请求顺序应答
+///
+/// 被拒绝的变量在shell请求成功后以 export 命令补发
+pub(crate) struct EnvInjector {
+    /// 待应答的请求, env请求保存变量, pty及shell为None
+    pending: VecDeque<Option<(String, String)>>,
+    inline: Vec<(String, String)>,
+    fallback: bool,
+}
+
+impl EnvInjector {
+    /// 返回跟踪器及需在pty之后、shell之前发送的env请求
+    pub(crate) fn new(env: &SSHEnvironment) -> (Self, Vec<ChannelOperation>) {
+        let mut requests = vec![];
+        let mut inline = vec![];
+        for (name, value) in &env.vars {
+            if !valid_name(name) {
+                warn!(%name, "invalid environment variable name, skipped");
+                continue;
+            }
+            if env.accepts(name) {
+                requests.push((name.clone(), value.clone()));
+            } else if env.inline_fallback {
+                inline.push((name.clone(), value.clone()));
+            } else {
+                warn!(%name, "environment variable not in accept env, skipped");
+            }
+        }
+        let ops = requests
+            .iter()
+            .map(|(name, value)| ChannelOperation::RequestEnv(name.clone(), value.clone()))
+            .collect();
+        let mut pending = VecDeque::with_capacity(requests.len() + 2);
+        pending.push_back(None);
+        pending.extend(requests.into_iter().map(Some));
+        pending.push_back(None);
+        let injector = Self {
+            pending,
+            inline,
+            fallback: env.inline_fallback,
+        };
+        (injector, ops)
+    }
+
+    /// 处理一次请求应答, shell请求成功时返回需写入shell的 export 命令
+    pub(crate) fn reply(&mut self, success: bool) -> Option<Bytes> {
+        match self.pending.pop_front()? {
+            Some((name, value)) => {
+                if !success {
+                    debug!(%name, "environment variable rejected by server");
+                    if self.fallback {
+                        self.inline.push((name, value));
+                    }
+                }
+                None
+            }
+            None if self.pending.is_empty() => {
+                let inline = std::mem::take(&mut self.inline);
+                (success && !inline.is_empty()).then(|| Bytes::from(export_command(&inline)))
+            }
+            None => None,
+        }
+    }
+}
+
+fn valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// 以空格开头, 避免写入history
+fn export_command(vars: &[(String, String)]) -> String {
+    let mut command = " export".to_string();
+    for (name, value) in vars {
+        command.push_str(&format!(" {}='{}'", name, value.replace('\'', r"'\''")));
+    }
+    command.push('\r');
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> SSHEnvironment {
+        SSHEnvironment {
+            vars: vars
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_env_injector() {
+        let env = env(&[
+            ("LANG", "en_US.UTF-8"),
+            ("LC_ALL", "C"),
+            ("PS1", "it's \\u> "),
+            ("1BAD", "x"),
+        ]);
+        let (mut injector, ops) = EnvInjector::new(&env);
+        let names = ops
+            .iter()
+            .map(|op| match op {
+                ChannelOperation::RequestEnv(name, _) => name.as_str(),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["LANG", "LC_ALL"]);
+        // pty, LANG, LC_ALL(拒绝), shell
+        assert_eq!(injector.reply(true), None);
+        assert_eq!(injector.reply(true), None);
+        assert_eq!(injector.reply(false), None);
+        assert_eq!(
+            injector.reply(true),
+            Some(Bytes::from(" export PS1='it'\\''s \\u> ' LC_ALL='C'\r"))
+        );
+        // 重连重放的应答忽略
+        assert_eq!(injector.reply(true), None);
+    }
+
+    #[test]
+    fn test_env_without_fallback() {
+        let mut env = env(&[("LANG", "C"), ("TZ", "UTC")]);
+        env.inline_fallback = false;
+        env.accept_env = vec!["*".to_string()];
+        let (mut injector, ops) = EnvInjector::new(&env);
+        assert_eq!(ops.len(), 2);
+        assert_eq!(injector.reply(true), None);
+        assert_eq!(injector.reply(false), None);
+        assert_eq!(injector.reply(false), None);
+        assert_eq!(injector.reply(true), None);
+        assert!(!SSHEnvironment::default().accepts("TZ"));
+        assert!(SSHEnvironment::default().accepts("LC_TIME"));
+    }
+}
@@ -0,0 +1,248 @@
+use anyhow::Result;
+use bytes::{Bytes, BytesMut};
+use genesis_common::{SessionId, TargetSSHOptions};
+use russh::Sig;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tokio::sync::oneshot;
+use tracing::*;
+use uuid::Uuid;
+
+use super::{RCEvent, RemoteConnection};
+use crate::ChannelOperation;
+
+/// ssh扩展数据类型, 1为标准错误输出
+const SSH_EXTENDED_DATA_STDERR: u32 = 1;
+
+/// 命令退出方式
+#[derive(Debug, Clone)]
+pub enum ExecExit {
+    /// 正常退出, 携带退出码
+    Status(u32),
+    /// 被信号终止
+    Signal {
+        signal_name: Sig,
+        core_dumped: bool,
+        error_message: String,
+    },
+    /// 通道关闭但服务端未返回退出状态
+    Unknown,
+}
+
+impl ExecExit {
+    pub fn success(&self) -> bool {
+        matches!(self, ExecExit::Status(0))
+    }
+
+    pub fn code(&self) -> Option<u32> {
+        match self {
+            ExecExit::Status(code) => Some(*code),
+            _ => None,
+        }
+    }
+}
+
+/// 命令执行结果
+#[derive(Debug, Clone)]
+pub struct ExecOutput {
+    pub stdout: Bytes,
+    pub stderr: Bytes,
+    pub exit: ExecExit,
+}
+
+/// 非交互命令执行, 标准输出与标准错误分开返回
+pub struct ExecProcess {
+    channel_id: Uuid,
+    connection: RemoteConnection,
+    pub stdout: UnboundedReceiver<Bytes>,
+    pub stderr: UnboundedReceiver<Bytes>,
+    exit: oneshot::Receiver<ExecExit>,
+}
+
+impl ExecProcess {
+    pub(crate) async fn spawn(connection: RemoteConnection, command: &str) -> Result<Self> {
+        let mut channel = connection.open_session().await?;
+        channel.send(ChannelOperation::RequestExec(command.to_string()))?;
+        loop {
+            match channel.recv().await {
+                Some(RCEvent::Success(_)) => break,
+                Some(RCEvent::ChannelFailure(_)) => {
+                    channel.close();
+                    anyhow::bail!("exec request rejected: {}", command)
+                }
+                Some(RCEvent::Close(_)) | None => {
+                    anyhow::bail!("channel closed before exec started: {}", command)
+                }
+                Some(e) => debug!(channel=%channel.id(), "exec event: {:?}", e),
+            }
+        }
+        let (stdout_tx, stdout) = unbounded_channel();
+        let (stderr_tx, stderr) = unbounded_channel();
+        let (exit_tx, exit) = oneshot::channel();
+        let channel_id = channel.id();
+        tokio::spawn(async move {
+            let mut result = ExecExit::Unknown;
+            while let Some(event) = channel.recv().await {
+                match event {
+                    RCEvent::Output(_, data) => {
+                        let _ = stdout_tx.send(data);
+                    }
+                    RCEvent::ExtendedData { data, ext, .. } if ext == SSH_EXTENDED_DATA_STDERR => {
+                        let _ = stderr_tx.send(data);
+                    }
+                    RCEvent::ExitStatus(_, code) => result = ExecExit::Status(code),
+                    RCEvent::ExitSignal {
+                        signal_name,
+                        core_dumped,
+                        error_message,
+                        ..
+                    } => {
+                        result = ExecExit::Signal {
+                            signal_name,
+                            core_dumped,
+                            error_message,
+                        }
+                    }
+                    RCEvent::Close(_) => break,
+                    _ => {}
+                }
+            }
+            debug!(channel=%channel.id(), exit=?result, "exec finished");
+            let _ = exit_tx.send(result);
+        });
+        Ok(Self {
+            channel_id,
+            connection,
+            stdout,
+            stderr,
+            exit,
+        })
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.channel_id
+    }
+
+    /// 写入标准输入
+    pub fn write_stdin(&self, data: Bytes) -> Result<()> {
+        self.send(ChannelOperation::Data(data))
+    }
+
+    /// 关闭标准输入
+    pub fn close_stdin(&self) -> Result<()> {
+        self.send(ChannelOperation::Eof)
+    }
+
+    pub fn signal(&self, signal: Sig) -> Result<()> {
+        self.send(ChannelOperation::Signal(signal))
+    }
+
+    fn send(&self, op: ChannelOperation) -> Result<()> {
+        self.connection.send_channel_op(self.channel_id, op)
+    }
+
+    /// 等待命令结束, 未读取的输出将被丢弃
+    pub async fn wait(self) -> Result<ExecExit> {
+        Ok(self.exit.await?)
+    }
+
+    /// 等待命令结束并收集全部输出
+    pub async fn wait_with_output(mut self) -> Result<ExecOutput> {
+        let mut stdout = BytesMut::new();
+        let mut stderr = BytesMut::new();
+        let mut stdout_open = true;
+        let mut stderr_open = true;
+        while stdout_open || stderr_open {
+            tokio::select! {
+                data = self.stdout.recv(), if stdout_open => match data {
+                    Some(data) => stdout.extend_from_slice(&data),
+                    None => stdout_open = false,
+                },
+                data = self.stderr.recv(), if stderr_open => match data {
+                    Some(data) => stderr.extend_from_slice(&data),
+                    None => stderr_open = false,
+                },
+            }
+        }
+        Ok(ExecOutput {
+            stdout: stdout.freeze(),
+            stderr: stderr.freeze(),
+            exit: self.exit.await?,
+        })
+    }
+}
+
+impl RemoteConnection {
+    /// 在连接上执行命令, 不分配pty
+    pub async fn exec(&self, command: &str) -> Result<ExecProcess> {
+        ExecProcess::spawn(self.clone(), command).await
+    }
+}
+
+/// 建立连接执行单条命令后断开
+pub async fn exec_command(
+    id: SessionId,
+    option: TargetSSHOptions,
+    command: &str,
+) -> Result<ExecOutput> {
+    let connection = RemoteConnection::connect(id, option).await?;
+    let result = match connection.exec(command).await {
+        Ok(process) => process.wait_with_output().await,
+        Err(e) => Err(e),
+    };
+    connection.disconnect();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FakeShell, TestServer};
+
+    #[tokio::test]
+    async fn test_exec_command() {
+        let server = TestServer::start(FakeShell::new().command_with_stderr(
+            "echo out; echo err 1>&2; exit 3",
+            "out\n",
+            "err\n",
+            3,
+        ))
+        .await
+        .unwrap();
+        let output = exec_command(
+            Uuid::new_v4(),
+            server.ssh_options(),
+            "echo out; echo err 1>&2; exit 3",
+        )
+        .await
+        .unwrap();
+        assert_eq!(output.stdout, Bytes::from_static(b"out\n"));
+        assert_eq!(output.stderr, Bytes::from_static(b"err\n"));
+        assert_eq!(output.exit.code(), Some(3));
+        assert!(!output.exit.success());
+    }
+
+    #[tokio::test]
+    async fn test_exec_on_shared_connection() {
+        let server = TestServer::start(FakeShell::new().command("uname", "Linux", 0))
+            .await
+            .unwrap();
+        let connection = RemoteConnection::connect(Uuid::new_v4(), server.ssh_options())
+            .await
+            .unwrap();
+        // 同一连接上依次打开多个exec通道
+        let uname = connection.exec("uname").await.unwrap();
+        let missing = connection.exec("missing").await.unwrap();
+        let output = uname.wait_with_output().await.unwrap();
+        assert_eq!(output.stdout, Bytes::from_static(b"Linux"));
+        assert!(output.exit.success());
+        let output = missing.wait_with_output().await.unwrap();
+        assert!(output.stdout.is_empty());
+        assert_eq!(
+            output.stderr,
+            Bytes::from_static(b"bash: missing: command not found")
+        );
+        assert_eq!(output.exit.code(), Some(127));
+        assert_eq!(server.connections(), 1);
+        connection.disconnect();
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, LazyLock};
+use std::time::SystemTime;
+
+use anyhow::Result;
+use genesis_common::{SessionId, TargetSSHOptions};
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+use uuid::Uuid;
+
+use super::{LocalTunnel, RemoteConnection, RemoteTunnel, SocksOptions, SocksProxy, TunnelStats};
+
+/// 转发方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForwardKind {
+    /// 本地监听, 经ssh连接远端地址
+    Local,
+    /// 服务端监听, 经ssh连接本地地址
+    Remote,
+    /// 本地socks5代理, 经ssh连接请求的目标地址
+    Dynamic,
+}
+
+/// 端口转发信息
+#[derive(Debug, Clone)]
+pub struct ForwardInfo {
+    pub id: Uuid,
+    pub kind: ForwardKind,
+    pub session_id: SessionId,
+    /// 本地及动态转发为本地监听地址, 远程转发为本地目标地址
+    pub local_addr: String,
+    /// 本地转发为远端目标地址, 远程转发为服务端监听地址, 动态转发为空
+    pub remote_host: String,
+    pub remote_port: u16,
+    pub created_at: SystemTime,
+    pub connections: u64,
+    pub active: u64,
+    /// 经ssh通道发出的字节数
+    pub bytes_sent: u64,
+    /// 经ssh通道收到的字节数
+    pub bytes_received: u64,
+}
+
+enum Tunnel {
+    Local(LocalTunnel),
+    Remote(RemoteTunnel),
+    Dynamic(SocksProxy),
+}
+
+impl Tunnel {
+    fn connection(&self) -> &RemoteConnection {
+        match self {
+            Tunnel::Local(t) => t.connection(),
+            Tunnel::Remote(t) => t.connection(),
+            Tunnel::Dynamic(t) => t.connection(),
+        }
+    }
+
+    fn stats(&self) -> Arc<TunnelStats> {
+        match self {
+            Tunnel::Local(t) => t.stats(),
+            Tunnel::Remote(t) => t.stats(),
+            Tunnel::Dynamic(t) => t.stats(),
+        }
+    }
+
+    fn stopped(&self) -> CancellationToken {
+        match self {
+            Tunnel::Local(t) => t.stopped(),
+            Tunnel::Remote(t) => t.stopped(),
+            Tunnel::Dynamic(t) => t.stopped(),
+        }
+    }
+
+    fn close(&self) {
+        match self {
+            Tunnel::Local(t) => t.close(),
+            Tunnel::Remote(t) => t.close(),
+            Tunnel::Dynamic(t) => t.close(),
+        }
+    }
+}
+
+struct Forward {
+    id: Uuid,
+    kind: ForwardKind,
+    local_addr: String,
+    remote_host: String,
+    remote_port: u16,
+    created_at: SystemTime,
+    tunnel: Tunnel,
+}
+
+impl Forward {
+    fn info(&self) -> ForwardInfo {
+        let stats = self.tunnel.stats();
+        ForwardInfo {
+            id: self.id,
+            kind: self.kind,
+            session_id: self.tunnel.connection().id(),
+            local_addr: self.local_addr.clone(),
+            remote_host: self.remote_host.clone(),
+            remote_port: self.remote_port,
+            created_at: self.created_at,
+            connections: stats.connections.load(Ordering::Relaxed),
+            active: stats.active.load(Ordering::Relaxed),
+            bytes_sent: stats.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: stats.bytes_received.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 本地端口转发管理, 停止的转发在 `close` 后移除
+#[derive(Default)]
+pub struct ForwardManager {
+    forwards: Mutex<HashMap<Uuid, Forward>>,
+}
+
+impl ForwardManager {
+    /// 建立ssh连接并监听 `bind`, 将连接转发至远端 `remote_host:remote_port`
+    pub async fn start(
+        &self,
+        session_id: SessionId,
+        option: TargetSSHOptions,
+        bind: &str,
+        remote_host: String,
+        remote_port: u16,
+    ) -> Result<ForwardInfo> {
+        let tunnel =
+            LocalTunnel::start(session_id, option, bind, remote_host.clone(), remote_port).await?;
+        let forward = Forward {
+            id: Uuid::new_v4(),
+            kind: ForwardKind::Local,
+            local_addr: tunnel.local_addr().to_string(),
+            remote_host,
+            remote_port,
+            created_at: SystemTime::now(),
+            tunnel: Tunnel::Local(tunnel),
+        };
+        Ok(self.insert(forward).await)
+    }
+
+    /// 建立ssh连接并请求服务端监听 `bind_address:bind_port`, 将连接转发至本地 `local_host:local_port`
+    pub async fn start_remote(
+        &self,
+        session_id: SessionId,
+        option: TargetSSHOptions,
+        bind_address: String,
+        bind_port: u16,
+        local_host: String,
+        local_port: u16,
+    ) -> Result<ForwardInfo> {
+        let local_addr = format!("{}:{}", local_host, local_port);
+        let tunnel = RemoteTunnel::start(
+            session_id,
+            option,
+            bind_address.clone(),
+            bind_port as u32,
+            local_host,
+            local_port,
+        )
+        .await?;
+        let forward = Forward {
+            id: Uuid::new_v4(),
+            kind: ForwardKind::Remote,
+            local_addr,
+            remote_host: bind_address,
+            remote_port: bind_port,
+            created_at: SystemTime::now(),
+            tunnel: Tunnel::Remote(tunnel),
+        };
+        Ok(self.insert(forward).await)
+    }
+
+    /// 建立ssh连接并在 `bind` 上启动socks5代理
+    pub async fn start_dynamic(
+        &self,
+        session_id: SessionId,
+        option: TargetSSHOptions,
+        bind: &str,
+        options: SocksOptions,
+    ) -> Result<ForwardInfo> {
+        let proxy = SocksProxy::start(session_id, option, bind, options).await?;
+        let forward = Forward {
+            id: Uuid::new_v4(),
+            kind: ForwardKind::Dynamic,
+            local_addr: proxy.local_addr().to_string(),
+            remote_host: String::new(),
+            remote_port: 0,
+            created_at: SystemTime::now(),
+            tunnel: Tunnel::Dynamic(proxy),
+        };
+        Ok(self.insert(forward).await)
+    }
+
+    async fn insert(&self, forward: Forward) -> ForwardInfo {
+        let info = forward.info();
+        info!(forward=%info.id, kind=?info.kind, session_id=%info.session_id, local_addr=%info.local_addr, "forward started");
+        self.forwards.lock().await.insert(forward.id, forward);
+        info
+    }
+
+    pub async fn list(&self) -> Vec<ForwardInfo> {
+        let forwards = self.forwards.lock().await;
+        let mut list = forwards
+            .values()
+            .filter(|f| !f.tunnel.stopped().is_cancelled())
+            .map(Forward::info)
+            .collect::<Vec<_>>();
+        list.sort_by_key(|f| f.created_at);
+        list
+    }
+
+    pub async fn get(&self, id: &Uuid) -> Option<ForwardInfo> {
+        self.forwards.lock().await.get(id).map(Forward::info)
+    }
+
+    /// 转发停止时触发
+    pub async fn stopped(&self, id: &Uuid) -> Option<CancellationToken> {
+        self.forwards
+            .lock()
+            .await
+            .get(id)
+            .map(|f| f.tunnel.stopped())
+    }
+
+    /// 关闭并移除转发, 返回关闭时的统计信息
+    pub async fn close(&self, id: &Uuid) -> Option<ForwardInfo> {
+        let forward = self.forwards.lock().await.remove(id)?;
+        forward.tunnel.close();
+        info!(forward=%id, "forward closed");
+        Some(forward.info())
+    }
+
+    pub async fn close_all(&self) {
+        for (id, forward) in self.forwards.lock().await.drain() {
+            forward.tunnel.close();
+            info!(forward=%id, "forward closed");
+        }
+    }
+}
+
+static FORWARD_MANAGER: LazyLock<ForwardManager> = LazyLock::new(ForwardManager::default);
+
+/// 全局端口转发管理
+pub fn forward_manager() -> &'static ForwardManager {
+    &FORWARD_MANAGER
+}
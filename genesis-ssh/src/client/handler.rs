@@ -1,20 +1,26 @@
-use genesis_common::{SessionId, TargetSSHOptions};
-use russh::client::{Msg, Session};
-use russh::keys::PublicKey;
+use genesis_common::{HostKeyPolicy, SessionId, TargetSSHOptions};
+use russh::client::{DisconnectReason, Msg, Session};
+use russh::keys::{PublicKey, PublicKeyBase64};
 use russh::Channel;
 use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::oneshot;
 use tracing::*;
 
+use super::agent::{agent_socket, forward_agent_channel};
+use super::known_hosts::{check_known_host, known_hosts_store, HostKey, KnownHostResult};
 use crate::{ConnectionError, ForwardedTcpIpParams};
 
 #[allow(dead_code)]
 #[derive(Debug)]
 pub enum ClientHandlerEvent {
     HostKeyReceived(PublicKey),
-    HostKeyUnknown(PublicKey, oneshot::Sender<bool>),
+    HostKeyUnknown(HostKey, oneshot::Sender<bool>),
+    /// 主机密钥与已记录的不一致
+    HostKeyChanged(HostKey, oneshot::Sender<bool>),
     ForwardedTcpIp(Channel<Msg>, ForwardedTcpIpParams),
     X11(Channel<Msg>, String, u32),
+    /// 连接异常断开, 如保活超时或读写失败
+    ConnectionLost(String),
     Disconnect,
 }
 
@@ -38,11 +44,107 @@ pub enum ClientHandlerError {
     Internal,
 }
 
+impl ClientHandler {
+    /// 交由调用方确认主机密钥, 无人应答时视为拒绝
+    async fn confirm_host_key(
+        &self,
+        key: &PublicKey,
+        event: fn(HostKey, oneshot::Sender<bool>) -> ClientHandlerEvent,
+    ) -> bool {
+        let host_key = HostKey {
+            host: self.ssh_options.host.clone(),
+            port: self.ssh_options.port,
+            key: key.clone(),
+        };
+        let (tx, rx) = oneshot::channel();
+        let _ = self.event_tx.send(event(host_key, tx));
+        rx.await.unwrap_or(false)
+    }
+
+    async fn save_host_key(&self, key: &PublicKey) -> Result<(), ClientHandlerError> {
+        known_hosts_store()
+            .save(&self.ssh_options.host, self.ssh_options.port, key)
+            .await
+            .map_err(|error| {
+                error!(?error, session=%self.session_id, "Failed to save known host");
+                ClientHandlerError::Internal
+            })
+    }
+}
+
 impl russh::client::Handler for ClientHandler {
     type Error = ClientHandlerError;
 
-    async fn check_server_key(&mut self, _: &PublicKey) -> Result<bool, Self::Error> {
-        Ok(true)
+    async fn check_server_key(
+        &mut self,
+        server_public_key: &PublicKey,
+    ) -> Result<bool, Self::Error> {
+        let host = &self.ssh_options.host;
+        let port = self.ssh_options.port;
+        let _ = self.event_tx.send(ClientHandlerEvent::HostKeyReceived(
+            server_public_key.clone(),
+        ));
+
+        let store = known_hosts_store();
+        let result = check_known_host(store.as_ref(), host, port, server_public_key)
+            .await
+            .map_err(|error| {
+                error!(?error, session=%self.session_id, "Failed to check known hosts");
+                ClientHandlerError::Internal
+            })?;
+        match result {
+            KnownHostResult::Valid => Ok(true),
+            KnownHostResult::Mismatch { known_key } => {
+                // Tofu 策略下密钥变更由调用方确认后替换
+                let replace = match self.ssh_options.host_key_policy {
+                    HostKeyPolicy::Tofu => {
+                        self.confirm_host_key(server_public_key, ClientHandlerEvent::HostKeyChanged)
+                            .await
+                    }
+                    HostKeyPolicy::Ask | HostKeyPolicy::Strict | HostKeyPolicy::AcceptNew => false,
+                };
+                if !replace {
+                    warn!(session=%self.session_id, %host, port, "Host key mismatch");
+                    return Err(ClientHandlerError::ConnectionError(
+                        ConnectionError::HostKeyMismatch {
+                            received_key_type: server_public_key.algorithm().to_string(),
+                            received_key_base64: server_public_key.public_key_base64(),
+                            known_key_type: known_key.algorithm().to_string(),
+                            known_key_base64: known_key.public_key_base64(),
+                        },
+                    ));
+                }
+                store.remove(host, port).await.map_err(|error| {
+                    error!(?error, session=%self.session_id, "Failed to remove known host");
+                    ClientHandlerError::Internal
+                })?;
+                self.save_host_key(server_public_key).await?;
+                info!(session=%self.session_id, %host, port, "Replaced changed host key");
+                Ok(true)
+            }
+            KnownHostResult::Unknown => {
+                let trusted = match self.ssh_options.host_key_policy {
+                    HostKeyPolicy::Strict => false,
+                    HostKeyPolicy::Tofu | HostKeyPolicy::AcceptNew => true,
+                    HostKeyPolicy::Ask => {
+                        self.confirm_host_key(server_public_key, ClientHandlerEvent::HostKeyUnknown)
+                            .await
+                    }
+                };
+                if !trusted {
+                    warn!(session=%self.session_id, %host, port, "Unknown host key rejected");
+                    return Err(ClientHandlerError::ConnectionError(
+                        ConnectionError::UnknownHostKey {
+                            key_type: server_public_key.algorithm().to_string(),
+                            key_base64: server_public_key.public_key_base64(),
+                        },
+                    ));
+                }
+                self.save_host_key(server_public_key).await?;
+                info!(session=%self.session_id, %host, port, "Learned new host key");
+                Ok(true)
+            }
+        }
     }
 
     async fn server_channel_open_forwarded_tcpip(
@@ -68,6 +170,39 @@ impl russh::client::Handler for ClientHandler {
         Ok(())
     }
 
+    async fn server_channel_open_agent_forward(
+        &mut self,
+        channel: Channel<Msg>,
+        _session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        let target = format!(
+            "{}@{}:{}",
+            self.ssh_options.username, self.ssh_options.host, self.ssh_options.port
+        );
+        let forward = &self.ssh_options.agent_forward;
+        if !forward.enabled {
+            warn!(session=%self.session_id, %target, "Agent forwarding is not enabled");
+            let _ = channel.close().await;
+            return Ok(());
+        }
+        let socket = match agent_socket(forward.socket.as_deref()) {
+            Ok(socket) => socket,
+            Err(error) => {
+                warn!(?error, session=%self.session_id, "Agent forwarding unavailable");
+                let _ = channel.close().await;
+                return Ok(());
+            }
+        };
+        info!(session=%self.session_id, %target, "Agent forward channel opened");
+        let session_id = self.session_id;
+        tokio::spawn(async move {
+            if let Err(error) = forward_agent_channel(channel, socket, target, session_id).await {
+                warn!(?error, session=%session_id, "Agent forwarding error");
+            }
+        });
+        Ok(())
+    }
+
     async fn server_channel_open_x11(
         &mut self,
         channel: Channel<Msg>,
@@ -83,6 +218,25 @@ impl russh::client::Handler for ClientHandler {
         ));
         Ok(())
     }
+
+    async fn disconnected(
+        &mut self,
+        reason: DisconnectReason<Self::Error>,
+    ) -> Result<(), Self::Error> {
+        match reason {
+            DisconnectReason::ReceivedDisconnect(info) => {
+                debug!(session=%self.session_id, ?info, "Server disconnected");
+                Ok(())
+            }
+            DisconnectReason::Error(error) => {
+                warn!(session=%self.session_id, ?error, "Connection lost");
+                let _ = self
+                    .event_tx
+                    .send(ClientHandlerEvent::ConnectionLost(format!("{error:?}")));
+                Err(error)
+            }
+        }
+    }
 }
 
 impl Drop for ClientHandler {
@@ -0,0 +1,221 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, RwLock};
+
+use async_trait::async_trait;
+use russh::keys::known_hosts::{known_host_keys_path, learn_known_hosts_path};
+use russh::keys::PublicKey;
+use tokio::sync::Mutex;
+
+/// 主机密钥存储
+#[async_trait]
+pub trait KnownHostsStore: Send + Sync {
+    /// 查询主机已记录的密钥
+    async fn lookup(&self, host: &str, port: u16) -> anyhow::Result<Vec<PublicKey>>;
+    /// 记录主机密钥
+    async fn save(&self, host: &str, port: u16, key: &PublicKey) -> anyhow::Result<()>;
+    /// 删除主机已记录的密钥, 用于替换变更的密钥
+    async fn remove(&self, host: &str, port: u16) -> anyhow::Result<()>;
+}
+
+/// 待确认的主机密钥
+#[derive(Debug, Clone)]
+pub struct HostKey {
+    pub host: String,
+    pub port: u16,
+    pub key: PublicKey,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum KnownHostResult {
+    Valid,
+    Unknown,
+    Mismatch { known_key: PublicKey },
+}
+
+/// 校验主机密钥, 已有记录但均不匹配时视为变更
+pub async fn check_known_host(
+    store: &dyn KnownHostsStore,
+    host: &str,
+    port: u16,
+    key: &PublicKey,
+) -> anyhow::Result<KnownHostResult> {
+    let known = store.lookup(host, port).await?;
+    if known.iter().any(|known| known.key_data() == key.key_data()) {
+        return Ok(KnownHostResult::Valid);
+    }
+    Ok(match known.into_iter().next() {
+        Some(known_key) => KnownHostResult::Mismatch { known_key },
+        None => KnownHostResult::Unknown,
+    })
+}
+
+/// 内存存储, 进程重启后丢失
+#[derive(Default)]
+pub struct MemoryKnownHosts {
+    keys: Mutex<HashMap<(String, u16), Vec<PublicKey>>>,
+}
+
+#[async_trait]
+impl KnownHostsStore for MemoryKnownHosts {
+    async fn lookup(&self, host: &str, port: u16) -> anyhow::Result<Vec<PublicKey>> {
+        let keys = self.keys.lock().await;
+        Ok(keys
+            .get(&(host.to_string(), port))
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn save(&self, host: &str, port: u16, key: &PublicKey) -> anyhow::Result<()> {
+        let mut keys = self.keys.lock().await;
+        keys.entry((host.to_string(), port))
+            .or_default()
+            .push(key.clone());
+        Ok(())
+    }
+
+    async fn remove(&self, host: &str, port: u16) -> anyhow::Result<()> {
+        let mut keys = self.keys.lock().await;
+        keys.remove(&(host.to_string(), port));
+        Ok(())
+    }
+}
+
+/// OpenSSH known_hosts 文件存储
+pub struct OpenSSHKnownHosts {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl OpenSSHKnownHosts {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+}
+
+#[async_trait]
+impl KnownHostsStore for OpenSSHKnownHosts {
+    async fn lookup(&self, host: &str, port: u16) -> anyhow::Result<Vec<PublicKey>> {
+        let _guard = self.lock.lock().await;
+        Ok(known_host_keys_path(host, port, &self.path)?
+            .into_iter()
+            .map(|(_, key)| key)
+            .collect())
+    }
+
+    async fn save(&self, host: &str, port: u16, key: &PublicKey) -> anyhow::Result<()> {
+        let _guard = self.lock.lock().await;
+        learn_known_hosts_path(host, port, key, &self.path)?;
+        Ok(())
+    }
+
+    async fn remove(&self, host: &str, port: u16) -> anyhow::Result<()> {
+        let _guard = self.lock.lock().await;
+        let matched = known_host_keys_path(host, port, &self.path)?
+            .into_iter()
+            .map(|(line, _)| line)
+            .collect::<HashSet<_>>();
+        if matched.is_empty() {
+            return Ok(());
+        }
+        // russh 返回的行号从1开始且不计注释行
+        let content = std::fs::read_to_string(&self.path)?;
+        let mut line = 0;
+        let kept = content
+            .split_inclusive('\n')
+            .filter(|l| {
+                if l.starts_with('#') {
+                    return true;
+                }
+                line += 1;
+                !matched.contains(&line)
+            })
+            .collect::<String>();
+        std::fs::write(&self.path, kept)?;
+        Ok(())
+    }
+}
+
+static KNOWN_HOSTS_STORE: LazyLock<RwLock<Arc<dyn KnownHostsStore>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(MemoryKnownHosts::default())));
+
+/// 设置全局主机密钥存储, 默认为内存存储
+pub fn set_known_hosts_store(store: Arc<dyn KnownHostsStore>) {
+    if let Ok(mut current) = KNOWN_HOSTS_STORE.write() {
+        *current = store;
+    }
+}
+
+pub fn known_hosts_store() -> Arc<dyn KnownHostsStore> {
+    match KNOWN_HOSTS_STORE.read() {
+        Ok(store) => store.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use russh::keys::parse_public_key_base64;
+
+    const ED25519_A: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIBmSaRmNUd0QOC30gXeg7HEHiTow1dAODqUIZVeXHj0C";
+    const ED25519_B: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIP896usYML4JLyVd98fEX8yZzWAWr61itDvj1Jb3vuyE";
+    const ECDSA: &str = "AAAAE2VjZHNhLXNoYTItbmlzdHAyNTYAAAAIbmlzdHAyNTYAAABBBEZLScKQRiMrskv1xNPxlXml6BNjzcUxojAQ1VJE6g+faY/HwlCWjP7GEkybCWj803Q2st6dLZxvUKuNnH8kjlo=";
+
+    #[tokio::test]
+    async fn test_check_known_host() {
+        let store = MemoryKnownHosts::default();
+        let a = parse_public_key_base64(ED25519_A).unwrap();
+        let b = parse_public_key_base64(ED25519_B).unwrap();
+        let ecdsa = parse_public_key_base64(ECDSA).unwrap();
+
+        let res = check_known_host(&store, "10.0.0.1", 22, &a).await.unwrap();
+        assert_eq!(res, KnownHostResult::Unknown);
+
+        store.save("10.0.0.1", 22, &a).await.unwrap();
+        let res = check_known_host(&store, "10.0.0.1", 22, &a).await.unwrap();
+        assert_eq!(res, KnownHostResult::Valid);
+        // 同类型不同密钥
+        let res = check_known_host(&store, "10.0.0.1", 22, &b).await.unwrap();
+        assert_eq!(
+            res,
+            KnownHostResult::Mismatch {
+                known_key: a.clone()
+            }
+        );
+        // 不同类型密钥同样视为变更
+        let res = check_known_host(&store, "10.0.0.1", 22, &ecdsa)
+            .await
+            .unwrap();
+        assert_eq!(res, KnownHostResult::Mismatch { known_key: a });
+        // 端口不同
+        let res = check_known_host(&store, "10.0.0.1", 2222, &b)
+            .await
+            .unwrap();
+        assert_eq!(res, KnownHostResult::Unknown);
+    }
+
+    #[tokio::test]
+    async fn test_openssh_known_hosts_remove() {
+        let path =
+            std::env::temp_dir().join(format!("genesis-{}.known_hosts", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "# genesis\n").unwrap();
+        let store = OpenSSHKnownHosts::new(&path);
+        let a = parse_public_key_base64(ED25519_A).unwrap();
+        let b = parse_public_key_base64(ED25519_B).unwrap();
+        store.save("10.0.0.1", 22, &a).await.unwrap();
+        store.save("10.0.0.2", 22, &b).await.unwrap();
+        store.save("10.0.0.1", 2222, &b).await.unwrap();
+
+        store.remove("10.0.0.1", 22).await.unwrap();
+        assert!(store.lookup("10.0.0.1", 22).await.unwrap().is_empty());
+        assert_eq!(store.lookup("10.0.0.2", 22).await.unwrap(), vec![b.clone()]);
+        assert_eq!(store.lookup("10.0.0.1", 2222).await.unwrap(), vec![b]);
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(content.starts_with("# genesis\n"));
+    }
+}
@@ -1,36 +1,70 @@
+mod agent;
+mod algorithms;
 mod channel_direct_tcpip;
 mod channel_session;
+mod connection;
+mod env;
 mod error;
+mod exec;
+mod forward;
 mod handler;
-use std::borrow::Cow;
+mod keys;
+mod known_hosts;
+mod pool;
+mod prompt;
+mod sftp;
+mod socks;
+mod traffic;
+mod tunnel;
 use std::collections::HashMap;
+use std::future::Future;
 use std::io;
 use std::net::ToSocketAddrs;
 use std::sync::Arc;
+use std::time::Duration;
 
+pub use agent::{agent_socket, key_fingerprint};
+pub use algorithms::{set_algorithm_recorder, AlgorithmRecorder, NegotiatedAlgorithms};
 use anyhow::Result;
 use bytes::Bytes;
 use channel_direct_tcpip::DirectTCPIPChannel;
 use channel_session::SessionChannel;
+pub use connection::{ForwardedChannel, RemoteChannel, RemoteConnection};
+use env::EnvInjector;
 pub use error::SshClientError;
+pub use exec::{exec_command, ExecExit, ExecOutput, ExecProcess};
+pub use forward::{forward_manager, ForwardInfo, ForwardKind, ForwardManager};
 use futures::pin_mut;
 use genesis_common::{EventHub, NotifyEnum};
-use genesis_common::{SSHTargetAuth, SessionId, TargetSSHOptions};
+use genesis_common::{ReconnectPolicy, SSHTargetAuth, SessionId, TargetSSHOptions};
 use handler::ClientHandler;
-use russh::client::{AuthResult, Handle};
-use russh::keys::PublicKey;
-use russh::{kex, Preferred, Sig};
+pub use keys::{load_private_key, set_certificate_issuer, CertificateIssuer};
+pub use known_hosts::*;
+pub use pool::{
+    connection_pool, start_pooled_ssh_connect, ConnectionPool, PoolKey, PoolLease, PoolOptions,
+    PoolStats, PooledChannel,
+};
+pub use prompt::{auth_prompt_relay, AuthPrompt, AuthPromptItem, AuthPromptRelay};
+use russh::client::{AuthResult, Handle, KeyboardInteractiveAuthResponse};
+use russh::keys::{PrivateKeyWithHashAlg, PublicKey};
+use russh::{MethodKind, Sig};
+pub use sftp::{SftpByteStream, SftpClient, SftpEntry};
+pub use socks::{SocksAuth, SocksDestination, SocksOptions, SocksProxy, SocksRule};
+use tokio::net::TcpStream;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 use tokio::sync::{oneshot, watch, Mutex};
 use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
 use tracing::*;
+pub use traffic::{session_traffic, SessionTraffic, TrafficStats};
+pub use tunnel::{LocalTunnel, RemoteTunnel, TunnelStats};
 use uuid::Uuid;
 
+use self::algorithms::{KexInitSlot, KexInitTap};
 use self::handler::ClientHandlerEvent;
 use super::{ChannelOperation, DirectTCPIPParams};
 use crate::client::handler::ClientHandlerError;
-use crate::ForwardedTcpIpParams;
+use crate::{ForwardedTcpIpParams, PtyRequest};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ConnectionError {
@@ -41,6 +75,11 @@ pub enum ConnectionError {
         known_key_type: String,
         known_key_base64: String,
     },
+    #[error("Unknown host key")]
+    UnknownHostKey {
+        key_type: String,
+        key_base64: String,
+    },
     #[error(transparent)]
     Io(#[from] std::io::Error),
     #[error(transparent)]
@@ -55,6 +94,14 @@ pub enum ConnectionError {
     Aborted,
     #[error("Authentication failed")]
     Authentication,
+    #[error("Connection lost: {0}")]
+    ConnectionLost(String),
+    #[error("Unsupported algorithm: {0}")]
+    Algorithm(String),
+    #[error("Agent error: {0}")]
+    Agent(String),
+    #[error("Certificate error: {0}")]
+    Certificate(String),
 }
 
 #[derive(Debug)]
@@ -83,16 +130,33 @@ pub enum RCEvent {
     // ForwardedTCPIP(Uuid, DirectTCPIPParams),
     Done,
     HostKeyReceived(PublicKey),
-    HostKeyUnknown(PublicKey, oneshot::Sender<bool>),
+    HostKeyUnknown(HostKey, oneshot::Sender<bool>),
+    /// 主机密钥变更, 确认后替换已记录的密钥
+    HostKeyChanged(HostKey, oneshot::Sender<bool>),
+    /// keyboard-interactive 认证中无法自动应答的提示
+    AuthPrompt(AuthPrompt),
     ForwardedTcpIp(Uuid, ForwardedTcpIpParams),
     X11(Uuid, String, u32),
+    /// 连接断开后开始第 `attempt` 次重连, 等待 `delay` 后发起
+    Reconnecting {
+        attempt: u32,
+        max_attempts: u32,
+        delay: Duration,
+    },
+    /// 重连成功, 已恢复shell通道
+    Reconnected {
+        attempt: u32,
+    },
 }
 
+/// 等待用户应答认证提示的时长
+const AUTH_PROMPT_TIMEOUT: Duration = Duration::from_secs(120);
+
 pub type RCCommandReply = oneshot::Sender<Result<(), SshClientError>>;
 
 #[derive(Clone, Debug)]
 pub enum RCCommand {
-    Connect(TargetSSHOptions),
+    Connect(Box<TargetSSHOptions>),
     Channel(Uuid, ChannelOperation),
     ForwardTCPIP(String, u32),
     CancelTCPIPForward(String, u32),
@@ -111,26 +175,43 @@ pub enum RCState {
 enum InnerEvent {
     RCCommand(RCCommand, Option<RCCommandReply>),
     ClientHandlerEvent(ClientHandlerEvent),
+    /// 服务端正常关闭会话通道, 不再重放
+    ChannelClosed(Uuid),
 }
 
 pub struct RemoteClient {
     id: SessionId,
     tx: UnboundedSender<RCEvent>,
     session: Option<Arc<Mutex<Handle<ClientHandler>>>>,
+    jump_sessions: Vec<Handle<ClientHandler>>,
     channel_pipes: Arc<Mutex<HashMap<Uuid, UnboundedSender<ChannelOperation>>>>,
     pending_ops: Vec<(Uuid, ChannelOperation)>,
     pending_forwards: Vec<(String, u32)>,
+    /// 已生效的远程转发, 重连后重新请求
+    tcpip_forwards: Vec<(String, u32)>,
     state: RCState,
     abort_rx: UnboundedReceiver<()>,
     inner_event_rx: UnboundedReceiver<InnerEvent>,
     inner_event_tx: UnboundedSender<InnerEvent>,
     child_tasks: Vec<JoinHandle<Result<(), SshClientError>>>,
+    options: Option<TargetSSHOptions>,
+    shell_replays: HashMap<Uuid, ShellReplay>,
+    traffic: Arc<SessionTraffic>,
+}
+
+/// 重连后需重放的shell通道请求
+#[derive(Clone, Debug, Default)]
+struct ShellReplay {
+    pty: Option<PtyRequest>,
+    env: Vec<(String, String)>,
+    shell: bool,
 }
 
 pub struct RemoteClientHandles {
     pub event_rx: UnboundedReceiver<RCEvent>,
     pub command_tx: UnboundedSender<(RCCommand, Option<RCCommandReply>)>,
     pub abort_tx: UnboundedSender<()>,
+    pub traffic: Arc<SessionTraffic>,
 }
 
 impl RemoteClient {
@@ -139,19 +220,25 @@ impl RemoteClient {
         let (command_tx, mut command_rx) = unbounded_channel();
         let (abort_tx, abort_rx) = unbounded_channel();
         let (inner_event_tx, inner_event_rx) = unbounded_channel();
+        let traffic = SessionTraffic::new(id);
 
         let this = Self {
             id,
             tx: event_tx,
             session: None,
+            jump_sessions: vec![],
             channel_pipes: Arc::new(Mutex::new(HashMap::new())),
             pending_ops: vec![],
             pending_forwards: vec![],
+            tcpip_forwards: vec![],
             state: RCState::NotInitialized,
             inner_event_rx,
             inner_event_tx: inner_event_tx.clone(),
             child_tasks: vec![],
             abort_rx,
+            options: None,
+            shell_replays: HashMap::new(),
+            traffic: traffic.clone(),
         };
 
         tokio::spawn(
@@ -173,11 +260,18 @@ impl RemoteClient {
             event_rx,
             command_tx,
             abort_tx,
+            traffic,
         })
     }
 
+    /// 会话及各通道的流量统计
+    pub fn traffic(&self) -> Arc<SessionTraffic> {
+        self.traffic.clone()
+    }
+
     fn set_disconnected(&mut self) {
         self.session = None;
+        self.jump_sessions.clear();
         for (id, op) in self.pending_ops.drain(..) {
             if let ChannelOperation::OpenShell = op {
                 let _ = self.tx.send(RCEvent::Close(id));
@@ -208,6 +302,7 @@ impl RemoteClient {
             return Ok(());
         }
 
+        self.record_replay(channel_id, &op);
         match op {
             ChannelOperation::OpenShell => {
                 self.open_shell(channel_id).await?;
@@ -230,6 +325,47 @@ impl RemoteClient {
         Ok(())
     }
 
+    fn record_replay(&mut self, channel_id: Uuid, op: &ChannelOperation) {
+        match op {
+            ChannelOperation::OpenShell => {
+                self.shell_replays
+                    .insert(channel_id, ShellReplay::default());
+            }
+            ChannelOperation::RequestPty(pty) => {
+                if let Some(replay) = self.shell_replays.get_mut(&channel_id) {
+                    replay.pty = Some(pty.clone());
+                }
+            }
+            ChannelOperation::ResizePty(pty) => {
+                if let Some(replay) = self.shell_replays.get_mut(&channel_id) {
+                    if let Some(current) = replay.pty.as_mut() {
+                        current.col_width = pty.col_width;
+                        current.row_height = pty.row_height;
+                        current.pix_width = pty.pix_width;
+                        current.pix_height = pty.pix_height;
+                    }
+                }
+            }
+            ChannelOperation::RequestEnv(name, value) => {
+                if let Some(replay) = self.shell_replays.get_mut(&channel_id) {
+                    match replay.env.iter_mut().find(|(n, _)| n == name) {
+                        Some(env) => env.1 = value.clone(),
+                        None => replay.env.push((name.clone(), value.clone())),
+                    }
+                }
+            }
+            ChannelOperation::RequestShell => {
+                if let Some(replay) = self.shell_replays.get_mut(&channel_id) {
+                    replay.shell = true;
+                }
+            }
+            ChannelOperation::Close => {
+                self.shell_replays.remove(&channel_id);
+            }
+            _ => {}
+        }
+    }
+
     pub fn start(mut self) -> io::Result<JoinHandle<anyhow::Result<()>>> {
         let name = format!("SSH {} client commands", self.id);
         tokio::task::Builder::new().name(&name).spawn(
@@ -276,23 +412,48 @@ impl RemoteClient {
                 }
                 return Ok(brk);
             }
+            InnerEvent::ChannelClosed(channel_id) => {
+                self.shell_replays.remove(&channel_id);
+            }
             InnerEvent::ClientHandlerEvent(client_event) => {
                 debug!("Client handler event: {:?}", client_event);
                 match client_event {
+                    ClientHandlerEvent::ConnectionLost(reason) => {
+                        warn!(session=%self.id, %reason, "Connection lost");
+                        match self.reconnect().await {
+                            Ok(true) => {}
+                            Ok(false) => {
+                                let _ = self.tx.send(RCEvent::ConnectionError(
+                                    ConnectionError::ConnectionLost(reason),
+                                ));
+                                self._on_disconnect().await?;
+                            }
+                            Err(error) => {
+                                let aborted = matches!(error, ConnectionError::Aborted);
+                                let _ = self.tx.send(RCEvent::ConnectionError(error));
+                                self._on_disconnect().await?;
+                                if aborted {
+                                    return Ok(true);
+                                }
+                            }
+                        }
+                    }
                     ClientHandlerEvent::Disconnect => {
                         self._on_disconnect().await?;
                     }
                     ClientHandlerEvent::ForwardedTcpIp(channel, params) => {
                         info!("New forwarded connection: {params:?}");
-                        let id = self.setup_server_initiated_channel(channel).await?;
-                        let _ = self.tx.send(RCEvent::ForwardedTcpIp(id, params));
+                        self.setup_server_initiated_channel(channel, |id| {
+                            RCEvent::ForwardedTcpIp(id, params)
+                        })
+                        .await?;
                     }
                     ClientHandlerEvent::X11(channel, originator_address, originator_port) => {
                         info!("New X11 connection from {originator_address}:{originator_port:?}");
-                        let id = self.setup_server_initiated_channel(channel).await?;
-                        let _ = self
-                            .tx
-                            .send(RCEvent::X11(id, originator_address, originator_port));
+                        self.setup_server_initiated_channel(channel, |id| {
+                            RCEvent::X11(id, originator_address, originator_port)
+                        })
+                        .await?;
                     }
                     event => {
                         error!(?event, "Unhandled client handler event");
@@ -303,16 +464,27 @@ impl RemoteClient {
         Ok(false)
     }
 
+    /// 注册服务端发起的通道, 先发送打开事件再启动通道任务, 保证事件先于通道数据
     async fn setup_server_initiated_channel(
         &mut self,
         channel: russh::Channel<russh::client::Msg>,
+        opened: impl FnOnce(Uuid) -> RCEvent,
     ) -> Result<Uuid> {
         let id = Uuid::new_v4();
 
         let (tx, rx) = unbounded_channel();
         self.channel_pipes.lock().await.insert(id, tx);
+        let _ = self.tx.send(opened(id));
 
-        let session_channel = SessionChannel::new(channel, id, rx, self.tx.clone(), self.id);
+        let session_channel = SessionChannel::new(
+            channel,
+            id,
+            rx,
+            self.tx.clone(),
+            self.inner_event_tx.clone(),
+            self.id,
+            self.traffic.meter(id),
+        );
 
         self.child_tasks.push(
             tokio::task::Builder::new()
@@ -325,8 +497,10 @@ impl RemoteClient {
 
     async fn handle_command(&mut self, cmd: RCCommand) -> Result<bool, SshClientError> {
         match cmd {
-            RCCommand::Connect(options) => match self.connect(options).await {
+            RCCommand::Connect(options) => match self.connect((*options).clone()).await {
                 Ok(_) => {
+                    self.traffic.set_rate_limit(&options.rate_limit);
+                    self.options = Some(*options);
                     self.set_state(RCState::Connected)
                         .map_err(SshClientError::other)?;
                     let ops = self.pending_ops.drain(..).collect::<Vec<_>>();
@@ -362,61 +536,212 @@ impl RemoteClient {
         Ok(false)
     }
 
-    async fn connect(&mut self, ssh_options: TargetSSHOptions) -> Result<(), ConnectionError> {
-        let address_str = format!("{}:{}", ssh_options.host, ssh_options.port);
-        let address = match address_str
-            .to_socket_addrs()
-            .map_err(ConnectionError::Io)
-            .and_then(|mut x| x.next().ok_or(ConnectionError::Resolve))
-        {
-            Ok(address) => address,
-            Err(error) => {
-                error!(?error, address=%address_str, "Cannot resolve target address");
-                self.set_disconnected();
-                return Err(error);
-            }
-        };
-
-        info!(?address, username = &ssh_options.username[..], "Connecting");
-        let algos = if ssh_options.allow_insecure_algos.unwrap_or(false) {
-            Preferred {
-                kex: Cow::Borrowed(&[
-                    kex::CURVE25519,
-                    kex::CURVE25519_PRE_RFC_8731,
-                    kex::ECDH_SHA2_NISTP256,
-                    kex::ECDH_SHA2_NISTP384,
-                    kex::ECDH_SHA2_NISTP521,
-                    kex::DH_G16_SHA512,
-                    kex::DH_G14_SHA256, // non-default
-                    kex::DH_G14_SHA256,
-                    kex::DH_G1_SHA1, // non-default
-                    kex::EXTENSION_SUPPORT_AS_CLIENT,
-                    kex::EXTENSION_SUPPORT_AS_SERVER,
-                    kex::EXTENSION_OPENSSH_STRICT_KEX_AS_CLIENT,
-                    kex::EXTENSION_OPENSSH_STRICT_KEX_AS_SERVER,
-                ]),
-                ..<_>::default()
-            }
-        } else {
-            Preferred::default()
-        };
+    fn client_config(
+        ssh_options: &TargetSSHOptions,
+    ) -> Result<Arc<russh::client::Config>, ConnectionError> {
+        let algos = algorithms::preferred(ssh_options)?;
 
+        let keepalive = &ssh_options.keepalive;
         let config = russh::client::Config {
             preferred: algos,
+            keepalive_interval: (keepalive.interval > 0)
+                .then(|| Duration::from_secs(keepalive.interval)),
+            keepalive_max: keepalive.max,
             ..Default::default()
         };
-        let config = Arc::new(config);
+        Ok(Arc::new(config))
+    }
 
-        let (event_tx, mut event_rx) = unbounded_channel();
-        let handler = ClientHandler {
-            ssh_options: ssh_options.clone(),
-            event_tx,
-            session_id: self.id,
-        };
+    async fn connect(&mut self, ssh_options: TargetSSHOptions) -> Result<(), ConnectionError> {
+        let mut jump_sessions: Vec<Handle<ClientHandler>> = vec![];
+        let lost = CancellationToken::new();
+        let result = self
+            .connect_hops(ssh_options, &mut jump_sessions, lost.clone())
+            .await;
+        if result.is_err() {
+            // 后续跳连接失败, 停止转发并断开已建立的跳板连接
+            lost.cancel();
+            for jump in jump_sessions.iter().rev() {
+                let _ = jump
+                    .disconnect(russh::Disconnect::ByApplication, "", "")
+                    .await;
+            }
+        }
+        result
+    }
 
-        let fut_connect = russh::client::connect(config, address, handler);
-        pin_mut!(fut_connect);
+    async fn connect_hops(
+        &mut self,
+        ssh_options: TargetSSHOptions,
+        jump_sessions: &mut Vec<Handle<ClientHandler>>,
+        lost: CancellationToken,
+    ) -> Result<(), ConnectionError> {
+        let hops = ssh_options.hops();
+        let last = hops.len() - 1;
+
+        for (index, hop) in hops.into_iter().enumerate() {
+            let config = Self::client_config(&hop)?;
+            let (event_tx, mut event_rx) = unbounded_channel();
+            let handler = ClientHandler {
+                ssh_options: hop.clone(),
+                event_tx,
+                session_id: self.id,
+            };
+
+            let kexinit = KexInitSlot::default();
+            let mut session = match jump_sessions.last() {
+                None => {
+                    let address_str = format!("{}:{}", hop.host, hop.port);
+                    let address = match address_str
+                        .to_socket_addrs()
+                        .map_err(ConnectionError::Io)
+                        .and_then(|mut x| x.next().ok_or(ConnectionError::Resolve))
+                    {
+                        Ok(address) => address,
+                        Err(error) => {
+                            error!(?error, address=%address_str, "Cannot resolve target address");
+                            return Err(error);
+                        }
+                    };
+                    info!(?address, username = &hop.username[..], "Connecting");
+                    let fut_connect = {
+                        let config = config.clone();
+                        let kexinit = kexinit.clone();
+                        async move {
+                            let stream = TcpStream::connect(address).await.map_err(|e| {
+                                ClientHandlerError::ConnectionError(ConnectionError::Io(e))
+                            })?;
+                            if config.nodelay {
+                                let _ = stream.set_nodelay(true);
+                            }
+                            let stream = KexInitTap::new(stream, kexinit);
+                            russh::client::connect_stream(config, stream, handler).await
+                        }
+                    };
+                    self.wait_session(fut_connect, &mut event_rx).await?
+                }
+                Some(previous) => {
+                    // 通过上一跳的 direct-tcpip 通道建立连接
+                    info!(host=%hop.host, port=hop.port, username = &hop.username[..], "Connecting via jump host");
+                    let channel = previous
+                        .channel_open_direct_tcpip(
+                            hop.host.clone(),
+                            hop.port as u32,
+                            "127.0.0.1",
+                            0,
+                        )
+                        .await?;
+                    let fut_connect = russh::client::connect_stream(
+                        config.clone(),
+                        KexInitTap::new(channel.into_stream(), kexinit.clone()),
+                        handler,
+                    );
+                    self.wait_session(fut_connect, &mut event_rx).await?
+                }
+            };
+
+            let auth_result = match Self::authenticate(&mut session, &hop, &self.tx).await {
+                Ok(auth_result) => auth_result,
+                Err(error) => {
+                    let _ = session
+                        .disconnect(russh::Disconnect::ByApplication, "", "")
+                        .await;
+                    return Err(error);
+                }
+            };
+            if !auth_result {
+                error!(host=%hop.host, "Auth rejected");
+                let _ = session
+                    .disconnect(russh::Disconnect::ByApplication, "", "")
+                    .await;
+                return Err(ConnectionError::Authentication);
+            }
+
+            let negotiated = kexinit.lock().ok().and_then(|k| k.negotiated());
+            if let Some(negotiated) = negotiated {
+                Self::record_algorithms(self.id, &hop, negotiated);
+            }
+
+            self.forward_hop_events(event_rx, lost.clone());
+            if index < last {
+                info!(host=%hop.host, port=hop.port, "Jump host connected");
+                jump_sessions.push(session);
+                continue;
+            }
+
+            self.session = Some(Arc::new(Mutex::new(session)));
+            self.jump_sessions = std::mem::take(jump_sessions);
+
+            info!(host=%hop.host, port=hop.port, "Connected");
 
+            return Ok(());
+        }
+        Err(ConnectionError::Internal)
+    }
+
+    /// 转发一跳连接的事件, 任一跳异常断开后由重连流程接管, 不再转发该连接各跳的后续事件
+    fn forward_hop_events(
+        &self,
+        mut event_rx: UnboundedReceiver<ClientHandlerEvent>,
+        lost: CancellationToken,
+    ) {
+        tokio::spawn(
+            {
+                let inner_event_tx = self.inner_event_tx.clone();
+                async move {
+                    loop {
+                        let e = tokio::select! {
+                            biased;
+                            _ = lost.cancelled() => break,
+                            e = event_rx.recv() => match e {
+                                Some(e) => e,
+                                None => break,
+                            },
+                        };
+                        info!("{:?}", e);
+                        if matches!(e, ClientHandlerEvent::ConnectionLost(_)) {
+                            // 同一连接仅转发首个断开事件
+                            if !lost.is_cancelled() {
+                                lost.cancel();
+                                inner_event_tx.send(InnerEvent::ClientHandlerEvent(e))?;
+                            }
+                            break;
+                        }
+                        inner_event_tx.send(InnerEvent::ClientHandlerEvent(e))?;
+                    }
+                    Ok::<(), anyhow::Error>(())
+                }
+            }
+            .instrument(Span::current()),
+        );
+    }
+
+    fn record_algorithms(id: SessionId, hop: &TargetSSHOptions, negotiated: NegotiatedAlgorithms) {
+        let weak = negotiated.weak();
+        if weak.is_empty() {
+            info!(host=%hop.host, port=hop.port, ?negotiated, "Negotiated algorithms");
+        } else {
+            warn!(host=%hop.host, port=hop.port, ?negotiated, ?weak, "Negotiated weak algorithms");
+        }
+        if let Some(recorder) = algorithms::algorithm_recorder() {
+            let (host, port) = (hop.host.clone(), hop.port);
+            tokio::spawn(async move {
+                if let Err(e) = recorder.record(id, &host, port, &negotiated).await {
+                    warn!(%host, port, "record negotiated algorithms error: {:?}", e);
+                }
+            });
+        }
+    }
+
+    async fn wait_session<F>(
+        &mut self,
+        fut_connect: F,
+        event_rx: &mut UnboundedReceiver<ClientHandlerEvent>,
+    ) -> Result<Handle<ClientHandler>, ConnectionError>
+    where
+        F: Future<Output = Result<Handle<ClientHandler>, ClientHandlerError>>,
+    {
+        pin_mut!(fut_connect);
         loop {
             tokio::select! {
                 Some(event) = event_rx.recv() => {
@@ -427,74 +752,186 @@ impl RemoteClient {
                         ClientHandlerEvent::HostKeyUnknown(key, reply) => {
                             self.tx.send(RCEvent::HostKeyUnknown(key, reply)).map_err(|_| ConnectionError::Internal)?;
                         }
+                        ClientHandlerEvent::HostKeyChanged(key, reply) => {
+                            self.tx.send(RCEvent::HostKeyChanged(key, reply)).map_err(|_| ConnectionError::Internal)?;
+                        }
                         _ => {}
                     }
                 }
                 Some(_) = self.abort_rx.recv() => {
                     info!("Abort requested");
-                    self.set_disconnected();
                     return Err(ConnectionError::Aborted)
                 }
                 session = &mut fut_connect => {
-                    let mut session = match session {
-                        Ok(session) => session,
-                        Err(error) => {
-                            let connection_error = match error {
-                                ClientHandlerError::ConnectionError(e) => e,
-                                ClientHandlerError::Ssh(e) => ConnectionError::Ssh(e),
-                                ClientHandlerError::Internal => ConnectionError::Internal,
-                            };
-                            error!(error=?connection_error, "Connection error");
-                            return Err(connection_error);
-                        }
-                    };
-                    let mut auth_result = false;
-                    match ssh_options.auth {
-                        SSHTargetAuth::Password(auth) => {
-                            let response = session
-                                .authenticate_password(ssh_options.username.clone(), auth.password)
-                                .await?;
-
-                            match response {
-                                AuthResult::Success => {
-                                    auth_result = true;
-                                    debug!(username=&ssh_options.username[..], "Authenticated with password");
-                                }
-                                AuthResult::Failure{ .. } => {
-                                    debug!(username=&ssh_options.username[..], "Authenticated with password error");
-                                }
-                            }
-                        }
-                        SSHTargetAuth::PublicKey(_) => {
-                            error!(username=&ssh_options.username[..], auth_result, "Authenticated with publicKey");
-                            return Err(ConnectionError::Authentication);
-                        }
-                    }
+                    return session.map_err(|error| {
+                        let connection_error = match error {
+                            ClientHandlerError::ConnectionError(e) => e,
+                            ClientHandlerError::Ssh(e) => ConnectionError::Ssh(e),
+                            ClientHandlerError::Internal => ConnectionError::Internal,
+                        };
+                        error!(error=?connection_error, "Connection error");
+                        connection_error
+                    });
+                }
+            }
+        }
+    }
 
-                    if !auth_result {
-                        error!("Auth rejected");
-                        let _ = session
-                            .disconnect(russh::Disconnect::ByApplication, "", "")
-                            .await;
-                        return Err(ConnectionError::Authentication);
+    async fn authenticate(
+        session: &mut Handle<ClientHandler>,
+        ssh_options: &TargetSSHOptions,
+        tx: &UnboundedSender<RCEvent>,
+    ) -> Result<bool, ConnectionError> {
+        let response = match &ssh_options.auth {
+            SSHTargetAuth::Password(auth) => {
+                let response = session
+                    .authenticate_password(ssh_options.username.clone(), auth.password.clone())
+                    .await?;
+                debug!(
+                    username = &ssh_options.username[..],
+                    success = response.success(),
+                    "Authenticated with password"
+                );
+                response
+            }
+            SSHTargetAuth::PublicKey(auth) => {
+                let key = match load_private_key(auth) {
+                    Ok(key) => Arc::new(key),
+                    Err(error) => {
+                        error!(
+                            ?error,
+                            username = &ssh_options.username[..],
+                            "Load private key error"
+                        );
+                        return Err(error);
                     }
+                };
+                // rsa 密钥需协商签名算法, 其余类型忽略
+                let hash_alg = if key.algorithm().is_rsa() {
+                    session.best_supported_rsa_hash().await?.flatten()
+                } else {
+                    None
+                };
+                let response = session
+                    .authenticate_publickey(
+                        ssh_options.username.clone(),
+                        PrivateKeyWithHashAlg::new(key.clone(), hash_alg),
+                    )
+                    .await?;
+                debug!(username=&ssh_options.username[..], algorithm=%key.algorithm(), success=response.success(), "Authenticated with publicKey");
+                response
+            }
+            SSHTargetAuth::Certificate(auth) => {
+                let (key, cert) = keys::load_certificate(auth).await.inspect_err(|error| {
+                    error!(
+                        ?error,
+                        username = &ssh_options.username[..],
+                        "Load certificate error"
+                    );
+                })?;
+                let key_id = cert.key_id().to_string();
+                let response = session
+                    .authenticate_openssh_cert(ssh_options.username.clone(), Arc::new(key), cert)
+                    .await?;
+                debug!(username=&ssh_options.username[..], %key_id, success=response.success(), "Authenticated with certificate");
+                response
+            }
+            SSHTargetAuth::Agent(auth) => {
+                agent::authenticate_with_agent(session, ssh_options, auth).await?
+            }
+            SSHTargetAuth::KeyboardInteractive(_) => {
+                return Self::authenticate_keyboard_interactive(session, ssh_options, tx).await;
+            }
+        };
+        match response {
+            AuthResult::Success => Ok(true),
+            // 密码被拒绝或公钥部分成功(多因素)时, 继续尝试 keyboard-interactive
+            AuthResult::Failure {
+                remaining_methods,
+                partial_success,
+            } if remaining_methods.contains(&MethodKind::KeyboardInteractive)
+                && (partial_success || matches!(ssh_options.auth, SSHTargetAuth::Password(_))) =>
+            {
+                Self::authenticate_keyboard_interactive(session, ssh_options, tx).await
+            }
+            AuthResult::Failure { .. } => Ok(false),
+        }
+    }
 
-                    self.session = Some(Arc::new(Mutex::new(session)));
-
-                    info!(?address, "Connected");
-
-                    tokio::spawn({
-                        let inner_event_tx = self.inner_event_tx.clone();
-                        async move {
-                            while let Some(e) = event_rx.recv().await {
-                                info!("{:?}", e);
-                                inner_event_tx.send(InnerEvent::ClientHandlerEvent(e))?
+    /// 提示优先以已保存凭证应答, 其余通过 `RCEvent::AuthPrompt` 交由用户应答
+    async fn authenticate_keyboard_interactive(
+        session: &mut Handle<ClientHandler>,
+        ssh_options: &TargetSSHOptions,
+        tx: &UnboundedSender<RCEvent>,
+    ) -> Result<bool, ConnectionError> {
+        let mut response = session
+            .authenticate_keyboard_interactive_start(ssh_options.username.clone(), None)
+            .await?;
+        let mut password_used = false;
+        loop {
+            match response {
+                KeyboardInteractiveAuthResponse::Success => {
+                    debug!(
+                        username = &ssh_options.username[..],
+                        "Authenticated with keyboard-interactive"
+                    );
+                    return Ok(true);
+                }
+                KeyboardInteractiveAuthResponse::Failure { .. } => {
+                    debug!(
+                        username = &ssh_options.username[..],
+                        "Authenticated with keyboard-interactive error"
+                    );
+                    return Ok(false);
+                }
+                KeyboardInteractiveAuthResponse::InfoRequest {
+                    name,
+                    instructions,
+                    prompts,
+                } => {
+                    let prompts = prompts
+                        .into_iter()
+                        .map(|p| AuthPromptItem {
+                            prompt: p.prompt,
+                            echo: p.echo,
+                        })
+                        .collect::<Vec<_>>();
+                    let mut answers =
+                        prompt::stored_answers(&ssh_options.auth, &prompts, &mut password_used);
+                    let pending = prompts
+                        .iter()
+                        .zip(answers.iter())
+                        .filter(|(_, a)| a.is_none())
+                        .map(|(p, _)| p.clone())
+                        .collect::<Vec<_>>();
+                    if !pending.is_empty() {
+                        let (prompt, rx) = AuthPrompt::new(name, instructions, pending);
+                        tx.send(RCEvent::AuthPrompt(prompt))
+                            .map_err(|_| ConnectionError::Internal)?;
+                        let mut replies = match tokio::time::timeout(AUTH_PROMPT_TIMEOUT, rx).await
+                        {
+                            Ok(Ok(replies)) => replies.into_iter(),
+                            Ok(Err(_)) => {
+                                warn!(
+                                    username = &ssh_options.username[..],
+                                    "Auth prompt not answered"
+                                );
+                                return Ok(false);
                             }
-                            Ok::<(), anyhow::Error>(())
+                            Err(_) => {
+                                warn!(username = &ssh_options.username[..], "Auth prompt timeout");
+                                return Ok(false);
+                            }
+                        };
+                        for answer in answers.iter_mut().filter(|a| a.is_none()) {
+                            *answer = Some(replies.next().unwrap_or_default());
                         }
-                    }.instrument(Span::current()));
-
-                    return Ok(())
+                    }
+                    response = session
+                        .authenticate_keyboard_interactive_respond(
+                            answers.into_iter().map(Option::unwrap_or_default).collect(),
+                        )
+                        .await?;
                 }
             }
         }
@@ -504,11 +941,27 @@ impl RemoteClient {
         if let Some(session) = &self.session {
             let session = session.lock().await;
             let channel = session.channel_open_session().await?;
+            if self
+                .options
+                .as_ref()
+                .is_some_and(|options| options.agent_forward.enabled)
+            {
+                info!(session=%self.id, channel=%channel_id, "request agent forwarding");
+                channel.agent_forward(false).await?;
+            }
 
             let (tx, rx) = unbounded_channel();
             self.channel_pipes.lock().await.insert(channel_id, tx);
 
-            let channel = SessionChannel::new(channel, channel_id, rx, self.tx.clone(), self.id);
+            let channel = SessionChannel::new(
+                channel,
+                channel_id,
+                rx,
+                self.tx.clone(),
+                self.inner_event_tx.clone(),
+                self.id,
+                self.traffic.meter(channel_id),
+            );
             self.child_tasks.push(
                 tokio::task::Builder::new()
                     .name(&format!("SSH {} {:?} ops", self.id, channel_id))
@@ -538,8 +991,14 @@ impl RemoteClient {
             let (tx, rx) = unbounded_channel();
             self.channel_pipes.lock().await.insert(channel_id, tx);
 
-            let channel =
-                DirectTCPIPChannel::new(channel, channel_id, rx, self.tx.clone(), self.id);
+            let channel = DirectTCPIPChannel::new(
+                channel,
+                channel_id,
+                rx,
+                self.tx.clone(),
+                self.id,
+                self.traffic.meter(channel_id),
+            );
             self.child_tasks.push(
                 tokio::task::Builder::new()
                     .name(&format!("SSH {} {:?} ops", self.id, channel_id))
@@ -553,7 +1012,9 @@ impl RemoteClient {
     async fn tcpip_forward(&mut self, address: String, port: u32) -> Result<(), SshClientError> {
         if let Some(session) = &self.session {
             let mut session = session.lock().await;
-            session.tcpip_forward(address, port).await?;
+            session.tcpip_forward(address.clone(), port).await?;
+            info!(session=%self.id, %address, port, "Remote forward requested");
+            self.tcpip_forwards.push((address, port));
         } else {
             self.pending_forwards.push((address, port));
         }
@@ -567,6 +1028,8 @@ impl RemoteClient {
     ) -> Result<(), SshClientError> {
         if let Some(session) = &self.session {
             let session = session.lock().await;
+            self.tcpip_forwards
+                .retain(|x| x.0 != address || x.1 != port);
             session.cancel_tcpip_forward(address, port).await?;
         } else {
             self.pending_forwards
@@ -582,16 +1045,122 @@ impl RemoteClient {
                 .await
                 .disconnect(russh::Disconnect::ByApplication, "", "")
                 .await;
+            for jump in self.jump_sessions.iter().rev() {
+                let _ = jump
+                    .disconnect(russh::Disconnect::ByApplication, "", "")
+                    .await;
+            }
             self.set_disconnected();
         }
     }
 
     async fn _on_disconnect(&mut self) -> Result<()> {
-        self.set_disconnected();
+        if self.state != RCState::Disconnected {
+            self.set_disconnected();
+        }
+        Ok(())
+    }
+
+    /// 按重连策略重连, 未开启时返回false, 重连失败返回最后一次错误
+    async fn reconnect(&mut self) -> Result<bool, ConnectionError> {
+        let Some(options) = self.options.clone() else {
+            return Ok(false);
+        };
+        let policy = options.reconnect.clone();
+        if !policy.enabled || policy.max_attempts == 0 {
+            return Ok(false);
+        }
+        self.session = None;
+        self.jump_sessions.clear();
+        let _ = self.set_state(RCState::Connecting);
+        let mut last_error = ConnectionError::Internal;
+        for attempt in 1..=policy.max_attempts {
+            let delay = reconnect_delay(&policy, attempt);
+            info!(session=%self.id, attempt, ?delay, "Reconnecting");
+            let _ = self.tx.send(RCEvent::Reconnecting {
+                attempt,
+                max_attempts: policy.max_attempts,
+                delay,
+            });
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                Some(_) = self.abort_rx.recv() => {
+                    info!("Abort requested");
+                    return Err(ConnectionError::Aborted);
+                }
+            }
+            match self.connect(options.clone()).await {
+                Ok(_) => {
+                    let _ = self.set_state(RCState::Connected);
+                    if let Err(error) = self.replay_shells().await {
+                        error!(?error, session=%self.id, "Replay shell channel error");
+                    }
+                    for (address, port) in std::mem::take(&mut self.tcpip_forwards) {
+                        if let Err(error) = self.tcpip_forward(address.clone(), port).await {
+                            error!(?error, session=%self.id, %address, port, "Replay remote forward error");
+                        }
+                    }
+                    let _ = self.tx.send(RCEvent::Reconnected { attempt });
+                    return Ok(true);
+                }
+                Err(ConnectionError::Aborted) => return Err(ConnectionError::Aborted),
+                Err(error) => {
+                    warn!(?error, session=%self.id, attempt, "Reconnect failed");
+                    last_error = error;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    /// 重新打开shell通道并重放pty、env及shell请求, 通道id保持不变
+    async fn replay_shells(&mut self) -> Result<(), SshClientError> {
+        let replays = self
+            .shell_replays
+            .iter()
+            .map(|(id, replay)| (*id, replay.clone()))
+            .collect::<Vec<_>>();
+        for (channel_id, replay) in replays {
+            self.apply_channel_op(channel_id, ChannelOperation::OpenShell)
+                .await?;
+            if let Some(pty) = replay.pty {
+                self.apply_channel_op(channel_id, ChannelOperation::RequestPty(pty))
+                    .await?;
+            }
+            for (name, value) in replay.env {
+                self.apply_channel_op(channel_id, ChannelOperation::RequestEnv(name, value))
+                    .await?;
+            }
+            if replay.shell {
+                self.apply_channel_op(channel_id, ChannelOperation::RequestShell)
+                    .await?;
+            }
+        }
         Ok(())
     }
 }
 
+/// 指数退避, 第一次重连等待 `backoff`, 不超过 `max_backoff`
+fn reconnect_delay(policy: &ReconnectPolicy, attempt: u32) -> Duration {
+    let factor = 1u64 << attempt.saturating_sub(1).min(16);
+    Duration::from_millis(
+        policy
+            .backoff
+            .saturating_mul(factor)
+            .min(policy.max_backoff.max(policy.backoff)),
+    )
+}
+
+/// 终端中显示的重连提示
+fn reconnecting_message(attempt: u32, max_attempts: u32, delay: Duration) -> Bytes {
+    Bytes::from(format!(
+        "\r\n\x1b[33mconnection lost, reconnecting ({attempt}/{max_attempts}) in {}s...\x1b[0m\r\n",
+        delay.as_secs_f32()
+    ))
+}
+
+const RECONNECTED_MESSAGE: &[u8] = b"\r\n\x1b[32mreconnected\x1b[0m\r\n";
+
 impl Drop for RemoteClient {
     fn drop(&mut self) {
         for task in self.child_tasks.drain(..) {
@@ -609,6 +1178,7 @@ impl Drop for RemoteClient {
 /// - EventHub<Bytes>为ssh返回数据,可自行订阅操作
 /// - UnboundedSender<Bytes> 为可发送到ssh服务端
 /// - UnboundedSender<ServerExtraEnum> 为自行添加ssh事件,比如断开连接等
+/// - watch::Receiver<NotifyEnum> 为连接状态, shell退出后为退出码
 pub async fn start_ssh_connect_with_state(
     uuid: Uuid,
     option: TargetSSHOptions,
@@ -617,10 +1187,11 @@ pub async fn start_ssh_connect_with_state(
     EventHub<Bytes>,
     UnboundedSender<Bytes>,
     UnboundedSender<ServerExtraEnum>,
+    watch::Receiver<NotifyEnum>,
 )> {
     let (hub, sender, notify, see) = start_ssh_connect_base(uuid, option, ctx).await?;
-    wait_ssh_state(uuid, notify).await?;
-    anyhow::Ok((hub, sender, see))
+    wait_ssh_state(uuid, notify.clone()).await?;
+    anyhow::Ok((hub, sender, see, notify))
 }
 
 async fn wait_ssh_state(
@@ -657,6 +1228,96 @@ pub async fn start_ssh_connect(
     Ok((hub, tx, rx))
 }
 
+pub(crate) fn relay_auth_prompt(uuid: Uuid, prompt: AuthPrompt) {
+    if let Err(prompt) = auth_prompt_relay().relay(&uuid, prompt) {
+        warn!(session_id=%uuid, name=%prompt.name, "no responder for auth prompt");
+    }
+}
+
+/// 经认证提示转交用户确认未知或变更的主机密钥, 无人应答则拒绝
+fn confirm_host_key(uuid: Uuid, host_key: HostKey, changed: bool, reply: oneshot::Sender<bool>) {
+    let HostKey { host, port, key } = host_key;
+    let fingerprint = key.fingerprint(Default::default());
+    let instructions = if changed {
+        format!(
+            "The host key for '{}:{}' has changed.\n{} key fingerprint is {}.",
+            host,
+            port,
+            key.algorithm(),
+            fingerprint
+        )
+    } else {
+        format!(
+            "The authenticity of host '{}:{}' can't be established.\n{} key fingerprint is {}.",
+            host,
+            port,
+            key.algorithm(),
+            fingerprint
+        )
+    };
+    let (prompt, rx) = AuthPrompt::new(
+        "host key verification".to_string(),
+        instructions,
+        vec![AuthPromptItem {
+            prompt: "Are you sure you want to continue connecting (yes/no)? ".to_string(),
+            echo: true,
+        }],
+    );
+    if auth_prompt_relay().relay(&uuid, prompt).is_err() {
+        warn!(session_id=%uuid, %host, %fingerprint, changed, "no responder to confirm host key");
+        let _ = reply.send(false);
+        return;
+    }
+    tokio::spawn(async move {
+        let trusted = match tokio::time::timeout(AUTH_PROMPT_TIMEOUT, rx).await {
+            Ok(Ok(answers)) => answers
+                .first()
+                .is_some_and(|answer| answer.trim().eq_ignore_ascii_case("yes")),
+            _ => false,
+        };
+        info!(session_id=%uuid, %fingerprint, changed, trusted, "host key confirmed");
+        let _ = reply.send(trusted);
+    });
+}
+
+/// 创建远程连接并等待连接完成, 连接失败时直接返回具体错误
+pub(crate) async fn connect_remote_client(
+    uuid: Uuid,
+    option: TargetSSHOptions,
+) -> Result<RemoteClientHandles> {
+    let mut handle = RemoteClient::create(uuid)?;
+    let (tx, mut rx) = oneshot::channel();
+    handle
+        .command_tx
+        .send((RCCommand::Connect(Box::new(option.clone())), Some(tx)))?;
+    // 连接过程中处理主机密钥确认
+    loop {
+        tokio::select! {
+            biased;
+            Some(e) = handle.event_rx.recv() => match e {
+                RCEvent::HostKeyUnknown(key, reply) => confirm_host_key(uuid, key, false, reply),
+                RCEvent::HostKeyChanged(key, reply) => confirm_host_key(uuid, key, true, reply),
+                RCEvent::AuthPrompt(prompt) => relay_auth_prompt(uuid, prompt),
+                RCEvent::ConnectionError(e) => {
+                    error!(session_id=%uuid, "connection error:{:?}", e);
+                    return Err(e.into());
+                }
+                RCEvent::State(RCState::Connected) => {
+                    info!("state Connected");
+                }
+                _ => {
+                    debug!("receive event : {:?}", e);
+                }
+            },
+            reply = &mut rx => {
+                reply??;
+                break;
+            }
+        }
+    }
+    Ok(handle)
+}
+
 pub async fn start_ssh_connect_base(
     uuid: Uuid,
     option: TargetSSHOptions,
@@ -668,12 +1329,15 @@ pub async fn start_ssh_connect_base(
     UnboundedSender<ServerExtraEnum>,
 )> {
     // step1. start connect
-    let mut handle = RemoteClient::create(uuid)?;
-    let (tx, rx) = oneshot::channel();
-    handle
-        .command_tx
-        .send((RCCommand::Connect(option.clone()), Some(tx)))?;
-    let _ = rx.await?;
+    let (notify_sender, notify_receiver) = watch::channel(NotifyEnum::INIT);
+    let mut handle = match connect_remote_client(uuid, option.clone()).await {
+        Ok(handle) => handle,
+        Err(e) => {
+            let _ = notify_sender.send(NotifyEnum::ERROR(e.to_string()));
+            return Err(e);
+        }
+    };
+    let _ = notify_sender.send(NotifyEnum::SUCCESS);
     // step2. start open channel
     let channel_id = Uuid::new_v4();
     let message = (
@@ -699,7 +1363,14 @@ pub async fn start_ssh_connect_base(
     );
     info!(channel_id=%channel_id,"request pty");
     handle.command_tx.send(message)?;
-    // step4. start shell
+    // step4. request env, 服务端拒绝的变量在shell就绪后补发
+    let (mut env, env_ops) = EnvInjector::new(&option.env);
+    for op in env_ops {
+        handle
+            .command_tx
+            .send((RCCommand::Channel(channel_id, op), None))?;
+    }
+    // step5. start shell
     let message = (
         RCCommand::Channel(channel_id, ChannelOperation::RequestShell),
         None,
@@ -707,17 +1378,38 @@ pub async fn start_ssh_connect_base(
     info!(channel_id=%channel_id,"request shell");
     handle.command_tx.send(message)?;
 
-    // step5 create event hub
+    // step6 create event hub
     let (hub, sender) = EventHub::setup();
-    let (notify_sender, notify_receiver) = watch::channel(NotifyEnum::INIT);
-    // step6. start ssh channel
+    // 开启重连时连接中断导致的通道关闭不代表会话结束, 以连接结束为准
+    let reconnect = option.reconnect.enabled;
+    let env_option = option.env;
+    let command_tx = handle.command_tx.clone();
+    // step7. start ssh channel
     tokio::spawn(async move {
+        // shell已正常退出, 通道关闭后不再重连
+        let mut exited = false;
         while let Some(e) = handle.event_rx.recv().await {
             match e {
+                RCEvent::ExitStatus(_, code) => {
+                    exited = true;
+                    let _ = notify_sender.send(NotifyEnum::EXIT(code));
+                }
+                RCEvent::ExitSignal { .. } | RCEvent::Eof(_) => {
+                    exited = true;
+                }
                 RCEvent::Output(_, bytes) => {
                     //let _ = sender.send_once(bytes).await;
                     let _ = sender.send_all(bytes).await;
                 }
+                RCEvent::Success(_) | RCEvent::ChannelFailure(_) => {
+                    let success = matches!(e, RCEvent::Success(_));
+                    if let Some(data) = env.reply(success) {
+                        let _ = command_tx.send((
+                            RCCommand::Channel(channel_id, ChannelOperation::Data(data)),
+                            None,
+                        ));
+                    }
+                }
                 RCEvent::ConnectionError(e) => {
                     error!("connection error:{:?}", e);
                     let _ = notify_sender.send(NotifyEnum::ERROR(e.to_string()));
@@ -726,7 +1418,32 @@ pub async fn start_ssh_connect_base(
                     info!("state Connected");
                     let _ = notify_sender.send(NotifyEnum::SUCCESS);
                 }
+                RCEvent::Reconnecting {
+                    attempt,
+                    max_attempts,
+                    delay,
+                } => {
+                    // 重连后重放pty、env及shell请求, 被拒绝的变量需再次补发
+                    env = EnvInjector::new(&env_option).0;
+                    let _ = sender
+                        .send_all(reconnecting_message(attempt, max_attempts, delay))
+                        .await;
+                }
+                RCEvent::AuthPrompt(prompt) => relay_auth_prompt(uuid, prompt),
+                RCEvent::Reconnected { attempt } => {
+                    exited = false;
+                    info!(session_id=%uuid, attempt, "reconnected");
+                    let _ = sender
+                        .send_all(Bytes::from_static(RECONNECTED_MESSAGE))
+                        .await;
+                }
+                RCEvent::Close(uuid) if reconnect && !exited => {
+                    debug!(sessionId=%uuid, "channel closed, wait for reconnect");
+                }
                 RCEvent::Close(uuid) => {
+                    if reconnect {
+                        let _ = command_tx.send((RCCommand::Disconnect, None));
+                    }
                     let id = uuid.to_string();
                     if let Some(ref ctx) = ctx {
                         if !ctx.is_cancelled() {
@@ -735,6 +1452,14 @@ pub async fn start_ssh_connect_base(
                         }
                     }
                 }
+                RCEvent::Done if reconnect => {
+                    if let Some(ref ctx) = ctx {
+                        if !ctx.is_cancelled() {
+                            debug!(sessionId=%uuid, "server connect break");
+                            ctx.cancelled().await;
+                        }
+                    }
+                }
                 _ => {
                     info!("receive event : {:?}", e);
                 }
@@ -782,109 +1507,357 @@ pub enum ServerExtraEnum {
 mod tests {
     use super::RemoteClient;
     use super::*;
-    use genesis_common::SshTargetPasswordAuth;
-    use tokio::io::AsyncWriteExt;
+    use genesis_common::{HostKeyPolicy, SshTargetPasswordAuth, SshTargetPublicKeyAuth};
     use uuid::Uuid;
     #[tokio::test]
-    #[ignore]
     async fn test_remote_client() {
-        tracing_subscriber::fmt().init();
-        let uuid = Uuid::new_v4();
-        info!("test remote client uuid:{}", uuid.to_string());
-        let rc = RemoteClient::create(uuid);
-        match rc {
-            Ok(mut handle) => {
-                // step1. create connect
-                let (tx, rx) = oneshot::channel();
-                info!("start connect");
-                let message = (
-                    RCCommand::Connect(TargetSSHOptions {
-                        host: "10.0.1.52".into(),
-                        port: 22,
-                        username: "root".into(),
-                        allow_insecure_algos: Some(true),
-                        auth: SSHTargetAuth::Password(SshTargetPasswordAuth {
-                            password: "1qaz2wsx".into(),
-                        }),
-                        pty_request: Default::default(),
-                    }),
-                    Some(tx),
-                );
-                handle.command_tx.send(message).unwrap();
-                info!("wait connct command");
-                match rx.await {
-                    Ok(r) => match r {
-                        Ok(_) => {}
-                        Err(e) => {
-                            error!("send command error: {}", e);
-                            return;
-                        }
-                    },
-                    Err(e) => {
-                        error!("await send command error:{}", e);
-                        return;
-                    }
-                };
-                info!("connect success");
+        use crate::testing::{FakeShell, TestServer};
+        let server = TestServer::start(FakeShell::new().command("hostname", "genesis", 0))
+            .await
+            .unwrap();
+        let mut handle = RemoteClient::create(Uuid::new_v4()).unwrap();
+        // step1. create connect
+        let (tx, rx) = oneshot::channel();
+        handle
+            .command_tx
+            .send((RCCommand::Connect(Box::new(server.ssh_options())), Some(tx)))
+            .unwrap();
+        rx.await.unwrap().unwrap();
 
-                // step2. start shell
-                let channel_id = Uuid::new_v4();
-                let message = (
-                    RCCommand::Channel(channel_id, ChannelOperation::OpenShell),
-                    None,
-                );
-                info!(channel_id=%channel_id,"open channel");
-                handle.command_tx.send(message).unwrap();
-
-                // step3. request pty
-                let message = (
-                    RCCommand::Channel(
-                        channel_id,
-                        ChannelOperation::RequestPty(crate::PtyRequest {
-                            term: "xterm".into(),
-                            col_width: 100,
-                            row_height: 10,
-                            pix_width: 0,
-                            pix_height: 0,
-                            modes: vec![],
-                        }),
-                    ),
-                    None,
-                );
-                info!(channel_id=%channel_id,"request pty");
-                handle.command_tx.send(message).unwrap();
-                // step4. start shell
-                let message = (
-                    RCCommand::Channel(channel_id, ChannelOperation::RequestShell),
-                    None,
-                );
-                info!(channel_id=%channel_id,"request shell");
-                handle.command_tx.send(message).unwrap();
-
-                let handle = tokio::spawn(async move {
-                    // io_std feature
-                    let mut out = tokio::io::stdout();
-                    while let Some(e) = handle.event_rx.recv().await {
-                        match e {
-                            crate::RCEvent::Output(_, bytes) => {
-                                let _ = out.write_all(&bytes).await;
-                                let _ = out.flush().await;
-                                let character = '#';
-                                let byte: u8 = character as u8;
-                                if bytes.contains(&byte) {
-                                    return;
-                                }
-                            }
-                            _ => {
-                                info!("receive envent : {:?}", e);
+        // step2. open channel, request pty and shell
+        let channel_id = Uuid::new_v4();
+        for operation in [
+            ChannelOperation::OpenShell,
+            ChannelOperation::RequestPty(crate::PtyRequest {
+                term: "xterm".into(),
+                col_width: 100,
+                row_height: 10,
+                pix_width: 0,
+                pix_height: 0,
+                modes: vec![],
+            }),
+            ChannelOperation::RequestShell,
+        ] {
+            handle
+                .command_tx
+                .send((RCCommand::Channel(channel_id, operation), None))
+                .unwrap();
+        }
+
+        // step3. run command and exit
+        let mut output = String::new();
+        let mut sent = false;
+        let exit_code = tokio::time::timeout(Duration::from_secs(10), async {
+            while let Some(e) = handle.event_rx.recv().await {
+                match e {
+                    RCEvent::Output(_, bytes) => {
+                        output.push_str(&String::from_utf8_lossy(&bytes));
+                        if !sent && output.ends_with("# ") {
+                            sent = true;
+                            for line in ["hostname\r", "exit\r"] {
+                                handle
+                                    .command_tx
+                                    .send((
+                                        RCCommand::Channel(
+                                            channel_id,
+                                            ChannelOperation::Data(Bytes::from(line)),
+                                        ),
+                                        None,
+                                    ))
+                                    .unwrap();
                             }
                         }
                     }
-                });
-                let _ = tokio::join!(handle);
-                info!("success test");
+                    RCEvent::ExitStatus(id, code) => {
+                        assert_eq!(id, channel_id);
+                        return Some(code);
+                    }
+                    _ => {}
+                }
             }
-            Err(e) => error!("error create remote client:{:?}", e),
-        }
+            None
+        })
+        .await
+        .unwrap();
+        assert_eq!(exit_code, Some(0));
+        assert!(output.contains("genesis\r\n"), "output: {output:?}");
+        let _ = handle.command_tx.send((RCCommand::Disconnect, None));
+    }
+
+    #[tokio::test]
+    async fn test_public_key_auth_with_test_server() {
+        use crate::testing::{test_client_key, TestServer};
+        use russh::keys::ssh_key::{private::Ed25519Keypair, LineEnding};
+        use russh::keys::PrivateKey;
+        let server = TestServer::start(Default::default()).await.unwrap();
+        let connect = |key: PrivateKey| {
+            let mut option = server.ssh_options();
+            option.auth = SSHTargetAuth::PublicKey(SshTargetPublicKeyAuth {
+                private_key: Some(key.to_openssh(LineEnding::LF).unwrap().to_string()),
+                private_key_path: None,
+                passphrase: None,
+            });
+            async move {
+                let handle = connect_remote_client(Uuid::new_v4(), option).await?;
+                let _ = handle.command_tx.send((RCCommand::Disconnect, None));
+                anyhow::Ok(())
+            }
+        };
+        connect(test_client_key()).await.unwrap();
+        // 服务端未授权的密钥认证失败
+        let other = PrivateKey::from(Ed25519Keypair::from_seed(&[9; 32]));
+        assert!(connect(other).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_host_key_confirmation_with_test_server() {
+        let server = crate::testing::TestServer::start(Default::default())
+            .await
+            .unwrap();
+        let mut option = server.ssh_options();
+        option.host_key_policy = HostKeyPolicy::Ask;
+        // 无人确认时拒绝未知密钥
+        let result = connect_remote_client(Uuid::new_v4(), option.clone()).await;
+        assert!(result.is_err());
+
+        let uuid = Uuid::new_v4();
+        let mut prompts = auth_prompt_relay().register(uuid);
+        let responder = tokio::spawn(async move {
+            let prompt = prompts.recv().await.unwrap();
+            assert_eq!(prompt.name, "host key verification");
+            assert!(prompt.instructions.contains("SHA256:"));
+            prompt.answer(vec!["yes".to_string()]);
+        });
+        let handle = connect_remote_client(uuid, option.clone()).await.unwrap();
+        responder.await.unwrap();
+        auth_prompt_relay().remove(&uuid);
+        let _ = handle.command_tx.send((RCCommand::Disconnect, None));
+        // 已记录的密钥无需再次确认
+        let handle = connect_remote_client(Uuid::new_v4(), option).await.unwrap();
+        let _ = handle.command_tx.send((RCCommand::Disconnect, None));
+    }
+
+    #[tokio::test]
+    async fn test_tofu_pins_unknown_host_key_with_test_server() {
+        let server = crate::testing::TestServer::start(Default::default())
+            .await
+            .unwrap();
+        let mut option = server.ssh_options();
+        option.host_key_policy = HostKeyPolicy::Tofu;
+        let store = known_hosts_store();
+        assert!(store
+            .lookup(&option.host, option.port)
+            .await
+            .unwrap()
+            .is_empty());
+        // 无交互调用方时直接信任并记录
+        let handle = connect_remote_client(Uuid::new_v4(), option.clone())
+            .await
+            .unwrap();
+        let _ = handle.command_tx.send((RCCommand::Disconnect, None));
+        let known = store.lookup(&option.host, option.port).await.unwrap();
+        assert_eq!(known.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_changed_host_key_with_test_server() {
+        use russh::keys::ssh_key::private::Ed25519Keypair;
+        use russh::keys::PrivateKey;
+        let server = crate::testing::TestServer::start(Default::default())
+            .await
+            .unwrap();
+        let mut option = server.ssh_options();
+        // 预先记录其他密钥, 模拟主机密钥变更
+        let store = known_hosts_store();
+        let other = PrivateKey::from(Ed25519Keypair::from_seed(&[9; 32]));
+        store
+            .save(&option.host, option.port, other.public_key())
+            .await
+            .unwrap();
+
+        // accept-new 直接拒绝变更的密钥
+        option.host_key_policy = HostKeyPolicy::AcceptNew;
+        let error = connect_remote_client(Uuid::new_v4(), option.clone())
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(
+            error.downcast_ref::<ConnectionError>(),
+            Some(ConnectionError::HostKeyMismatch { .. })
+        ));
+        // tofu 无人确认时同样拒绝
+        option.host_key_policy = HostKeyPolicy::Tofu;
+        assert!(connect_remote_client(Uuid::new_v4(), option.clone())
+            .await
+            .is_err());
+
+        // tofu 确认后替换已记录的密钥
+        let uuid = Uuid::new_v4();
+        let mut prompts = auth_prompt_relay().register(uuid);
+        let responder = tokio::spawn(async move {
+            let prompt = prompts.recv().await.unwrap();
+            assert!(prompt.instructions.contains("has changed"));
+            prompt.answer(vec!["yes".to_string()]);
+        });
+        let handle = connect_remote_client(uuid, option.clone()).await.unwrap();
+        responder.await.unwrap();
+        auth_prompt_relay().remove(&uuid);
+        let _ = handle.command_tx.send((RCCommand::Disconnect, None));
+        let known = store.lookup(&option.host, option.port).await.unwrap();
+        assert_eq!(known.len(), 1);
+        assert_ne!(known[0].key_data(), other.public_key().key_data());
+    }
+
+    #[tokio::test]
+    async fn test_jump_hosts_with_test_server() {
+        use crate::testing::{FakeShell, TestServer};
+        let jump = TestServer::start(FakeShell::new()).await.unwrap();
+        let target = TestServer::start(FakeShell::new().command("hostname", "target", 0))
+            .await
+            .unwrap();
+        let mut option = target.ssh_options();
+        // 两跳均经同一跳板
+        option.jump_hosts = vec![jump.jump_host(), jump.jump_host()];
+        let output = exec_command(Uuid::new_v4(), option.clone(), "hostname")
+            .await
+            .unwrap();
+        assert_eq!(output.stdout, Bytes::from_static(b"target"));
+
+        // 目标认证失败时断开已建立的跳板连接
+        option.auth = SSHTargetAuth::Password(SshTargetPasswordAuth {
+            password: "invalid".into(),
+        });
+        let result = connect_remote_client(Uuid::new_v4(), option).await;
+        assert!(result.is_err());
+        let closed = tokio::time::timeout(Duration::from_secs(5), async {
+            while jump.connections() > 0 || target.connections() > 0 {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await;
+        assert!(closed.is_ok(), "jump connections: {}", jump.connections());
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_with_test_server() {
+        use crate::testing::{FakeShell, TestServer};
+        let server = TestServer::start(FakeShell::new().command("pwd", "/root", 0))
+            .await
+            .unwrap();
+        let mut option = server.ssh_options();
+        option.reconnect = ReconnectPolicy {
+            enabled: true,
+            max_attempts: 3,
+            backoff: 50,
+            max_backoff: 100,
+        };
+        // 服务端拒绝env请求, 重连后需再次以 export 补发
+        option.env.vars = [("TZ".to_string(), "Asia/Shanghai".to_string())].into();
+        let (hub, sender, _) = start_ssh_connect(Uuid::new_v4(), option).await.unwrap();
+        let mut receiver = hub.subscribe(|_| true).await.unbox();
+        let mut output = String::new();
+        let mut wait_for = async |expected: &str| {
+            output.clear();
+            tokio::time::timeout(Duration::from_secs(10), async {
+                while let Some(bytes) = receiver.recv().await {
+                    output.push_str(&String::from_utf8_lossy(&bytes));
+                    if output.contains(expected) {
+                        return true;
+                    }
+                }
+                false
+            })
+            .await
+            .unwrap_or(false)
+        };
+        assert!(wait_for("# ").await);
+
+        // 传输中断后重连并重开shell
+        server.drop_connections();
+        assert!(wait_for("reconnected").await);
+        assert!(wait_for(" export TZ='Asia/Shanghai'\r\n").await);
+        sender.send(Bytes::from_static(b"echo $TZ\r")).unwrap();
+        assert!(wait_for("\r\nAsia/Shanghai\r\n").await);
+        sender.send(Bytes::from_static(b"pwd\r")).unwrap();
+        assert!(wait_for("/root\r\n").await);
+        assert_eq!(server.connections(), 1);
+
+        // shell正常退出后不再重连
+        sender.send(Bytes::from_static(b"exit\r")).unwrap();
+        let closed = tokio::time::timeout(Duration::from_secs(5), async {
+            while server.connections() > 0 {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await;
+        assert!(closed.is_ok());
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(server.connections(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_via_jump_host_with_test_server() {
+        use crate::testing::{FakeShell, TestServer};
+        let jump = TestServer::start(FakeShell::new()).await.unwrap();
+        let target = TestServer::start(FakeShell::new().command("pwd", "/root", 0))
+            .await
+            .unwrap();
+        let mut option = target.ssh_options();
+        option.jump_hosts = vec![jump.jump_host()];
+        option.reconnect = ReconnectPolicy {
+            enabled: true,
+            max_attempts: 3,
+            backoff: 50,
+            max_backoff: 100,
+        };
+        let (hub, sender, _) = start_ssh_connect(Uuid::new_v4(), option).await.unwrap();
+        let mut receiver = hub.subscribe(|_| true).await.unbox();
+        let mut output = String::new();
+        let mut wait_for = async |expected: &str| {
+            output.clear();
+            tokio::time::timeout(Duration::from_secs(10), async {
+                while let Some(bytes) = receiver.recv().await {
+                    output.push_str(&String::from_utf8_lossy(&bytes));
+                    if output.contains(expected) {
+                        return true;
+                    }
+                }
+                false
+            })
+            .await
+            .unwrap_or(false)
+        };
+        assert!(wait_for("# ").await);
+
+        // 跳板连接中断后经新跳板重连
+        jump.drop_connections();
+        assert!(wait_for("reconnected").await);
+        sender.send(Bytes::from_static(b"pwd\r")).unwrap();
+        assert!(wait_for("/root\r\n").await);
+
+        // 旧跳板连接的后续事件不影响重连后的会话
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        sender.send(Bytes::from_static(b"pwd\r")).unwrap();
+        assert!(wait_for("/root\r\n").await);
+        assert_eq!(jump.connections(), 1);
+        assert_eq!(target.connections(), 1);
+    }
+
+    #[test]
+    fn test_reconnect_delay() {
+        let policy = ReconnectPolicy {
+            enabled: true,
+            max_attempts: 10,
+            backoff: 500,
+            max_backoff: 3000,
+        };
+        let delays = (1..=5)
+            .map(|attempt| reconnect_delay(&policy, attempt).as_millis())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, vec![500, 1000, 2000, 3000, 3000]);
+        assert_eq!(
+            reconnect_delay(&policy, u32::MAX),
+            Duration::from_millis(3000)
+        );
     }
 }
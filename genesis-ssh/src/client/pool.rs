@@ -0,0 +1,679 @@
+//! 按目标及账户复用已认证的连接, 在同一连接上打开多个通道
+
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Weak};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use bytes::Bytes;
+use genesis_common::{
+    EventHub, EventSender, NotifyEnum, SSHTargetAuth, SessionId, TargetSSHOptions,
+};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::{watch, Mutex, OwnedSemaphorePermit, Semaphore};
+use tracing::*;
+use uuid::Uuid;
+
+use super::{
+    reconnect_delay, reconnecting_message, EnvInjector, ExecOutput, RCEvent, RemoteChannel,
+    RemoteConnection, ServerExtraEnum, RECONNECTED_MESSAGE,
+};
+use crate::{ChannelOperation, PtyRequest};
+
+/// 空闲连接检查间隔
+const EVICT_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+pub struct PoolOptions {
+    /// 每个目标最多建立的连接数
+    pub max_connections: usize,
+    /// 每个连接最多同时打开的通道数, 不应超过服务端 MaxSessions(默认10)
+    pub max_channels: usize,
+    /// 无通道的连接保留时长, 超时后断开
+    pub idle_timeout: Duration,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self {
+            max_connections: 4,
+            max_channels: 8,
+            idle_timeout: Duration::from_secs(300),
+        }
+    }
+}
+
+/// 连接复用的键, 目标地址、登录账户及其余连接参数的摘要
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PoolKey {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    /// 认证、跳板机、算法、主机密钥策略、代理转发、限速、环境变量及提权等参数的摘要
+    pub options: u64,
+}
+
+impl TryFrom<&TargetSSHOptions> for PoolKey {
+    type Error = serde_json::Error;
+
+    fn try_from(option: &TargetSSHOptions) -> Result<Self, Self::Error> {
+        // pty按通道请求, 重连由通道使用方处理, 均不影响连接复用
+        let mut option = option.clone();
+        option.pty_request = Default::default();
+        option.reconnect = Default::default();
+        // 可重新签发的证书每次签发均不同, 按签发凭证区分
+        if let SSHTargetAuth::Certificate(auth) = &mut option.auth {
+            if auth.credential_id.is_some() {
+                auth.private_key.clear();
+                auth.certificate.clear();
+            }
+        }
+        let mut hasher = DefaultHasher::new();
+        serde_json::to_vec(&option)?.hash(&mut hasher);
+        Ok(Self {
+            host: option.host,
+            port: option.port,
+            username: option.username,
+            options: hasher.finish(),
+        })
+    }
+}
+
+/// 单个目标的连接情况
+#[derive(Debug, Clone)]
+pub struct PoolStats {
+    pub key: PoolKey,
+    pub connections: usize,
+    pub channels: usize,
+}
+
+struct PooledConnection {
+    connection: RemoteConnection,
+    channels: AtomicUsize,
+    last_used: std::sync::Mutex<Instant>,
+}
+
+impl PooledConnection {
+    fn touch(&self) {
+        if let Ok(mut last_used) = self.last_used.lock() {
+            *last_used = Instant::now();
+        }
+    }
+
+    fn idle_for(&self) -> Duration {
+        self.last_used
+            .lock()
+            .map(|last_used| last_used.elapsed())
+            .unwrap_or_default()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.connection.closed().is_cancelled()
+    }
+}
+
+struct TargetPool {
+    /// 限制目标上的通道总数, 即 `max_connections * max_channels`
+    permits: Arc<Semaphore>,
+    capacity: usize,
+    connections: Mutex<Vec<Arc<PooledConnection>>>,
+    /// 串行新建连接, 建立连接期间不阻塞复用已有连接
+    connecting: Mutex<()>,
+}
+
+/// 连接上的通道占用, 释放后连接可被复用
+pub struct PoolLease {
+    connection: Arc<PooledConnection>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl PoolLease {
+    pub fn connection(&self) -> &RemoteConnection {
+        &self.connection.connection
+    }
+}
+
+impl Drop for PoolLease {
+    fn drop(&mut self) {
+        self.connection.channels.fetch_sub(1, Ordering::SeqCst);
+        self.connection.touch();
+    }
+}
+
+/// 连接池中打开的通道, 释放时关闭通道
+pub struct PooledChannel {
+    channel: RemoteChannel,
+    lease: PoolLease,
+}
+
+impl PooledChannel {
+    pub fn connection(&self) -> &RemoteConnection {
+        self.lease.connection()
+    }
+}
+
+impl Deref for PooledChannel {
+    type Target = RemoteChannel;
+
+    fn deref(&self) -> &Self::Target {
+        &self.channel
+    }
+}
+
+impl DerefMut for PooledChannel {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.channel
+    }
+}
+
+impl Drop for PooledChannel {
+    fn drop(&mut self) {
+        self.channel.close();
+    }
+}
+
+pub struct ConnectionPool {
+    options: PoolOptions,
+    targets: Mutex<HashMap<PoolKey, Arc<TargetPool>>>,
+}
+
+impl ConnectionPool {
+    /// 创建连接池并定期断开空闲连接, 需在tokio运行时内调用
+    pub fn new(options: PoolOptions) -> Arc<Self> {
+        let pool = Arc::new(Self {
+            options,
+            targets: Mutex::new(HashMap::new()),
+        });
+        let weak = Arc::downgrade(&pool);
+        tokio::spawn(Self::evict_loop(weak));
+        pool
+    }
+
+    async fn evict_loop(pool: Weak<Self>) {
+        loop {
+            tokio::time::sleep(EVICT_INTERVAL).await;
+            let Some(pool) = pool.upgrade() else {
+                break;
+            };
+            pool.evict_idle().await;
+        }
+    }
+
+    pub fn options(&self) -> &PoolOptions {
+        &self.options
+    }
+
+    /// 占用目标上的一个通道名额, 无可用连接时新建连接, 达到上限时等待
+    pub async fn acquire(&self, id: SessionId, option: &TargetSSHOptions) -> Result<PoolLease> {
+        let key = PoolKey::try_from(option)?;
+        let target = {
+            let mut targets = self.targets.lock().await;
+            targets
+                .entry(key.clone())
+                .or_insert_with(|| {
+                    let permits =
+                        self.options.max_connections.max(1) * self.options.max_channels.max(1);
+                    Arc::new(TargetPool {
+                        permits: Arc::new(Semaphore::new(permits)),
+                        capacity: permits,
+                        connections: Mutex::new(vec![]),
+                        connecting: Mutex::new(()),
+                    })
+                })
+                .clone()
+        };
+        let permit = target.permits.clone().acquire_owned().await?;
+        let connection = match self.reuse(&target).await {
+            Some(connection) => connection,
+            None => {
+                let _connecting = target.connecting.lock().await;
+                // 等待期间其他请求可能已新建连接
+                match self.reuse(&target).await {
+                    Some(connection) => connection,
+                    None => {
+                        // 建立连接时不持有连接列表的锁, 断开后不自动重连, 由使用方重新获取
+                        let mut option = option.clone();
+                        option.reconnect.enabled = false;
+                        let connection = RemoteConnection::connect(id, option).await?;
+                        let connection = Arc::new(PooledConnection {
+                            connection,
+                            channels: AtomicUsize::new(1),
+                            last_used: std::sync::Mutex::new(Instant::now()),
+                        });
+                        let mut connections = target.connections.lock().await;
+                        connections.push(connection.clone());
+                        info!(session_id=%id, ?key, total=connections.len(), "new pooled connection");
+                        connection
+                    }
+                }
+            }
+        };
+        Ok(PoolLease {
+            connection,
+            _permit: permit,
+        })
+    }
+
+    /// 选取通道最少且未满的连接并占用一个通道
+    async fn reuse(&self, target: &TargetPool) -> Option<Arc<PooledConnection>> {
+        let mut connections = target.connections.lock().await;
+        connections.retain(|c| !c.is_closed());
+        let connection = connections
+            .iter()
+            .filter(|c| c.channels.load(Ordering::SeqCst) < self.options.max_channels.max(1))
+            .min_by_key(|c| c.channels.load(Ordering::SeqCst))
+            .cloned()?;
+        connection.channels.fetch_add(1, Ordering::SeqCst);
+        connection.touch();
+        debug!(connection=%connection.connection.id(), "reuse pooled connection");
+        Some(connection)
+    }
+
+    pub async fn open_session(
+        &self,
+        id: SessionId,
+        option: &TargetSSHOptions,
+    ) -> Result<PooledChannel> {
+        let lease = self.acquire(id, option).await?;
+        let channel = lease.connection().open_session().await?;
+        Ok(PooledChannel { channel, lease })
+    }
+
+    /// 在复用的连接上执行单条命令
+    pub async fn exec_command(
+        &self,
+        id: SessionId,
+        option: &TargetSSHOptions,
+        command: &str,
+    ) -> Result<ExecOutput> {
+        let lease = self.acquire(id, option).await?;
+        lease
+            .connection()
+            .exec(command)
+            .await?
+            .wait_with_output()
+            .await
+    }
+
+    /// 断开超过空闲时长且无通道的连接, 返回断开的连接数
+    pub async fn evict_idle(&self) -> usize {
+        let targets: Vec<_> = self.targets.lock().await.values().cloned().collect();
+        let mut evicted = 0;
+        for target in targets {
+            let mut connections = target.connections.lock().await;
+            connections.retain(|c| {
+                let idle = c.channels.load(Ordering::SeqCst) == 0
+                    && c.idle_for() >= self.options.idle_timeout;
+                if idle {
+                    debug!(connection=%c.connection.id(), "evict idle pooled connection");
+                    c.connection.disconnect();
+                }
+                if idle || c.is_closed() {
+                    evicted += 1;
+                    return false;
+                }
+                true
+            });
+        }
+        // 保留仍有通道占用或正在建立连接的目标
+        self.targets.lock().await.retain(|_, t| {
+            t.permits.available_permits() < t.capacity
+                || t.connections
+                    .try_lock()
+                    .map(|c| !c.is_empty())
+                    .unwrap_or(true)
+        });
+        evicted
+    }
+
+    pub async fn stats(&self) -> Vec<PoolStats> {
+        let targets: Vec<_> = self
+            .targets
+            .lock()
+            .await
+            .iter()
+            .map(|(k, t)| (k.clone(), t.clone()))
+            .collect();
+        let mut stats = Vec::with_capacity(targets.len());
+        for (key, target) in targets {
+            let connections = target.connections.lock().await;
+            stats.push(PoolStats {
+                key,
+                connections: connections.len(),
+                channels: connections
+                    .iter()
+                    .map(|c| c.channels.load(Ordering::SeqCst))
+                    .sum(),
+            });
+        }
+        stats
+    }
+
+    /// 断开所有连接
+    pub async fn close_all(&self) {
+        let targets: Vec<_> = self.targets.lock().await.drain().map(|(_, t)| t).collect();
+        for target in targets {
+            for connection in target.connections.lock().await.drain(..) {
+                connection.connection.disconnect();
+            }
+        }
+    }
+}
+
+static CONNECTION_POOL: LazyLock<Arc<ConnectionPool>> =
+    LazyLock::new(|| ConnectionPool::new(PoolOptions::default()));
+
+/// 全局连接池
+pub fn connection_pool() -> Arc<ConnectionPool> {
+    CONNECTION_POOL.clone()
+}
+
+/// 同 `start_ssh_connect_with_state`, 但复用连接池中的连接, 仅打开新的shell通道,
+/// 连接中断时按重连策略在池中重新打开shell
+pub async fn start_pooled_ssh_connect(
+    uuid: Uuid,
+    mut option: TargetSSHOptions,
+) -> Result<(
+    EventHub<Bytes>,
+    UnboundedSender<Bytes>,
+    UnboundedSender<ServerExtraEnum>,
+    watch::Receiver<NotifyEnum>,
+)> {
+    let (notify_sender, notify_receiver) = watch::channel(NotifyEnum::INIT);
+    let (mut channel, mut env) = match open_pooled_shell(uuid, &option).await {
+        Ok(shell) => shell,
+        Err(e) => {
+            let _ = notify_sender.send(NotifyEnum::ERROR(e.to_string()));
+            return Err(e);
+        }
+    };
+    let _ = notify_sender.send(NotifyEnum::SUCCESS);
+
+    let (hub, sender) = EventHub::setup();
+    let (tx, mut rx) = unbounded_channel::<Bytes>();
+    let (extra_tx, mut extra_rx) = unbounded_channel::<ServerExtraEnum>();
+    tokio::spawn(async move {
+        // shell已正常退出, 通道关闭后不再重连
+        let mut exited = false;
+        let mut extra_closed = false;
+        loop {
+            tokio::select! {
+                event = channel.recv() => match event {
+                    Some(RCEvent::Output(_, bytes)) => {
+                        let _ = sender.send_all(bytes).await;
+                    }
+                    Some(RCEvent::ExitStatus(_, code)) => {
+                        exited = true;
+                        let _ = notify_sender.send(NotifyEnum::EXIT(code));
+                    }
+                    Some(RCEvent::ExitSignal { .. }) | Some(RCEvent::Eof(_)) => {
+                        exited = true;
+                    }
+                    Some(RCEvent::Success(_)) => {
+                        if let Some(data) = env.reply(true) {
+                            let _ = channel.send(ChannelOperation::Data(data));
+                        }
+                    }
+                    Some(RCEvent::ChannelFailure(_)) => {
+                        env.reply(false);
+                    }
+                    Some(RCEvent::Close(_)) | None if exited || !option.reconnect.enabled => {
+                        debug!(session_id=%uuid, "pooled shell closed");
+                        break;
+                    }
+                    Some(RCEvent::Close(_)) | None => {
+                        match reopen_pooled_shell(uuid, &mut option, &sender, &mut extra_rx).await {
+                            Ok(Some(shell)) => (channel, env) = shell,
+                            Ok(None) => break,
+                            Err(e) => {
+                                let _ = notify_sender.send(NotifyEnum::ERROR(e.to_string()));
+                                break;
+                            }
+                        }
+                    }
+                    Some(e) => debug!(session_id=%uuid, "receive event : {:?}", e),
+                },
+                data = rx.recv() => match data {
+                    Some(data) => {
+                        let _ = channel.send(ChannelOperation::Data(data));
+                    }
+                    None => break,
+                },
+                extra = extra_rx.recv(), if !extra_closed => match extra {
+                    Some(ServerExtraEnum::Disconnect) => break,
+                    Some(ServerExtraEnum::ChannelOperation(op)) => {
+                        if let ChannelOperation::ResizePty(pty) = &op {
+                            resize_pty_request(&mut option, pty);
+                        }
+                        let _ = channel.send(op);
+                    }
+                    None => extra_closed = true,
+                },
+            }
+        }
+        drop(notify_sender);
+    });
+    Ok((hub, tx, extra_tx, notify_receiver))
+}
+
+/// 从连接池打开shell通道并请求pty、env及shell
+async fn open_pooled_shell(
+    uuid: Uuid,
+    option: &TargetSSHOptions,
+) -> Result<(PooledChannel, EnvInjector)> {
+    let channel = connection_pool().open_session(uuid, option).await?;
+    channel.send(ChannelOperation::RequestPty(PtyRequest {
+        term: option.pty_request.term.clone(),
+        col_width: option.pty_request.width,
+        row_height: option.pty_request.height,
+        pix_width: 0,
+        pix_height: 0,
+        modes: vec![],
+    }))?;
+    let (env, env_ops) = EnvInjector::new(&option.env);
+    for op in env_ops {
+        channel.send(op)?;
+    }
+    channel.send(ChannelOperation::RequestShell)?;
+    info!(session_id=%uuid, channel_id=%channel.id(), connection=%channel.connection().id(), "pooled shell opened");
+    Ok((channel, env))
+}
+
+/// 按重连策略重新打开shell, 等待期间收到断开请求时返回 `None`
+async fn reopen_pooled_shell(
+    uuid: Uuid,
+    option: &mut TargetSSHOptions,
+    sender: &EventSender<Bytes>,
+    extra_rx: &mut UnboundedReceiver<ServerExtraEnum>,
+) -> Result<Option<(PooledChannel, EnvInjector)>> {
+    let policy = option.reconnect.clone();
+    let mut last_error = anyhow::anyhow!("connection lost");
+    for attempt in 1..=policy.max_attempts {
+        let delay = reconnect_delay(&policy, attempt);
+        info!(session_id=%uuid, attempt, ?delay, "reopen pooled shell");
+        let _ = sender
+            .send_all(reconnecting_message(attempt, policy.max_attempts, delay))
+            .await;
+        let sleep = tokio::time::sleep(delay);
+        tokio::pin!(sleep);
+        loop {
+            tokio::select! {
+                _ = &mut sleep => break,
+                extra = extra_rx.recv() => match extra {
+                    Some(ServerExtraEnum::ChannelOperation(ChannelOperation::ResizePty(pty))) => {
+                        resize_pty_request(option, &pty);
+                    }
+                    Some(ServerExtraEnum::ChannelOperation(_)) => {}
+                    Some(ServerExtraEnum::Disconnect) => return Ok(None),
+                    None => {
+                        (&mut sleep).await;
+                        break;
+                    }
+                },
+            }
+        }
+        match open_pooled_shell(uuid, option).await {
+            Ok(shell) => {
+                info!(session_id=%uuid, attempt, "pooled shell reconnected");
+                let _ = sender
+                    .send_all(Bytes::from_static(RECONNECTED_MESSAGE))
+                    .await;
+                return Ok(Some(shell));
+            }
+            Err(e) => {
+                warn!(session_id=%uuid, attempt, error=?e, "reopen pooled shell failed");
+                last_error = e;
+            }
+        }
+    }
+    Err(last_error)
+}
+
+/// 记录终端大小, 重新打开shell时按最新大小请求pty
+fn resize_pty_request(option: &mut TargetSSHOptions, pty: &PtyRequest) {
+    option.pty_request.width = pty.col_width;
+    option.pty_request.height = pty.row_height;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FakeShell, TestServer};
+    use genesis_common::ReconnectPolicy;
+
+    #[test]
+    fn test_pool_key() {
+        let option = TargetSSHOptions {
+            host: "127.0.0.1".into(),
+            ..Default::default()
+        };
+        let mut other = option.clone();
+        other.pty_request.width = 200;
+        other.reconnect.enabled = true;
+        let key = PoolKey::try_from(&option).unwrap();
+        assert_eq!(key, PoolKey::try_from(&other).unwrap());
+        other.jump_hosts.push(Default::default());
+        assert_ne!(key, PoolKey::try_from(&other).unwrap());
+        let mut other = option.clone();
+        other.allow_insecure_algos = Some(true);
+        assert_ne!(key, PoolKey::try_from(&other).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_pool_reuse() {
+        let server = TestServer::start(FakeShell::new()).await.unwrap();
+        let option = server.ssh_options();
+        let pool = ConnectionPool::new(PoolOptions {
+            max_connections: 2,
+            max_channels: 2,
+            idle_timeout: Duration::ZERO,
+        });
+        let first = pool.open_session(Uuid::new_v4(), &option).await.unwrap();
+        let second = pool.open_session(Uuid::new_v4(), &option).await.unwrap();
+        assert_eq!(first.connection().id(), second.connection().id());
+        // 单连接通道数已满, 新建连接
+        let third = pool.open_session(Uuid::new_v4(), &option).await.unwrap();
+        assert_ne!(first.connection().id(), third.connection().id());
+        let output = pool
+            .exec_command(Uuid::new_v4(), &option, "echo ok")
+            .await
+            .unwrap();
+        assert_eq!(output.stdout, Bytes::from_static(b"ok"));
+        let stats = pool.stats().await;
+        assert_eq!(stats[0].connections, 2);
+        assert_eq!(stats[0].channels, 3);
+        assert_eq!(server.connections(), 2);
+
+        // 连接参数不同时不复用
+        let mut env_option = option.clone();
+        env_option.env.vars.insert("LANG".into(), "C".into());
+        let fourth = pool
+            .open_session(Uuid::new_v4(), &env_option)
+            .await
+            .unwrap();
+        assert_ne!(first.connection().id(), fourth.connection().id());
+        assert_ne!(third.connection().id(), fourth.connection().id());
+        assert_eq!(pool.stats().await.len(), 2);
+
+        drop((first, second, third, fourth));
+        assert_eq!(pool.evict_idle().await, 3);
+        assert!(pool.stats().await.is_empty());
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while server.connections() > 0 {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pooled_shell_reconnect() {
+        let server = TestServer::start(FakeShell::new().command("pwd", "/root", 0))
+            .await
+            .unwrap();
+        let mut option = server.ssh_options();
+        option.reconnect = ReconnectPolicy {
+            enabled: true,
+            max_attempts: 3,
+            backoff: 50,
+            max_backoff: 100,
+        };
+        let (hub, sender, extra, mut notify) = start_pooled_ssh_connect(Uuid::new_v4(), option)
+            .await
+            .unwrap();
+        let mut receiver = hub.subscribe(|_| true).await.unbox();
+        let mut output = String::new();
+        let mut wait_for = async |expected: &str| {
+            output.clear();
+            tokio::time::timeout(Duration::from_secs(10), async {
+                while let Some(bytes) = receiver.recv().await {
+                    output.push_str(&String::from_utf8_lossy(&bytes));
+                    if output.contains(expected) {
+                        return true;
+                    }
+                }
+                false
+            })
+            .await
+            .unwrap_or(false)
+        };
+        assert!(wait_for("# ").await);
+
+        // 连接中断后在池中重新打开shell
+        server.drop_connections();
+        assert!(wait_for("reconnected").await);
+        let resize = PtyRequest {
+            term: "xterm".into(),
+            col_width: 120,
+            row_height: 40,
+            pix_width: 0,
+            pix_height: 0,
+            modes: vec![],
+        };
+        extra
+            .send(ServerExtraEnum::ChannelOperation(
+                ChannelOperation::ResizePty(resize),
+            ))
+            .unwrap();
+        sender.send(Bytes::from_static(b"pwd\r")).unwrap();
+        assert!(wait_for("/root\r\n").await);
+        assert_eq!(server.connections(), 1);
+
+        // shell正常退出后通知退出码, 不再重连
+        sender.send(Bytes::from_static(b"unknown\r")).unwrap();
+        sender.send(Bytes::from_static(b"exit\r")).unwrap();
+        let exited = tokio::time::timeout(
+            Duration::from_secs(5),
+            notify.wait_for(|n| matches!(n, NotifyEnum::EXIT(_))),
+        )
+        .await
+        .unwrap()
+        .map(|n| matches!(*n, NotifyEnum::EXIT(127)))
+        .unwrap();
+        assert!(exited);
+    }
+}
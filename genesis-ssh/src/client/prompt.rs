@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+use genesis_common::{SSHTargetAuth, SessionId};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+
+/// keyboard-interactive 单条提示
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthPromptItem {
+    pub prompt: String,
+    /// 输入是否可回显, 为false时应按密码处理
+    pub echo: bool,
+}
+
+/// 需由用户应答的认证提示, 应答顺序与 `prompts` 一致
+#[derive(Debug)]
+pub struct AuthPrompt {
+    pub name: String,
+    pub instructions: String,
+    pub prompts: Vec<AuthPromptItem>,
+    reply: oneshot::Sender<Vec<String>>,
+}
+
+impl AuthPrompt {
+    pub(crate) fn new(
+        name: String,
+        instructions: String,
+        prompts: Vec<AuthPromptItem>,
+    ) -> (Self, oneshot::Receiver<Vec<String>>) {
+        let (reply, rx) = oneshot::channel();
+        (
+            Self {
+                name,
+                instructions,
+                prompts,
+                reply,
+            },
+            rx,
+        )
+    }
+
+    pub fn answer(self, responses: Vec<String>) {
+        let _ = self.reply.send(responses);
+    }
+}
+
+/// 按会话转交认证提示, 未注册的会话无法完成需用户应答的认证
+#[derive(Default)]
+pub struct AuthPromptRelay {
+    sessions: Mutex<HashMap<SessionId, UnboundedSender<AuthPrompt>>>,
+}
+
+impl AuthPromptRelay {
+    /// 连接前注册, 返回该会话的认证提示
+    pub fn register(&self, id: SessionId) -> UnboundedReceiver<AuthPrompt> {
+        let (tx, rx) = unbounded_channel();
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.insert(id, tx);
+        }
+        rx
+    }
+
+    pub fn remove(&self, id: &SessionId) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.remove(id);
+        }
+    }
+
+    /// 转交失败时返回原提示
+    pub(crate) fn relay(&self, id: &SessionId, prompt: AuthPrompt) -> Result<(), AuthPrompt> {
+        let sessions = match self.sessions.lock() {
+            Ok(sessions) => sessions,
+            Err(_) => return Err(prompt),
+        };
+        match sessions.get(id) {
+            Some(tx) => tx.send(prompt).map_err(|e| e.0),
+            None => Err(prompt),
+        }
+    }
+}
+
+static AUTH_PROMPT_RELAY: LazyLock<AuthPromptRelay> = LazyLock::new(AuthPromptRelay::default);
+
+/// 全局认证提示转交
+pub fn auth_prompt_relay() -> &'static AuthPromptRelay {
+    &AUTH_PROMPT_RELAY
+}
+
+/// 以已保存的凭证应答提示, 密码仅使用一次, 避免密码错误时反复重试
+pub(crate) fn stored_answers(
+    auth: &SSHTargetAuth,
+    prompts: &[AuthPromptItem],
+    password_used: &mut bool,
+) -> Vec<Option<String>> {
+    let (password, responses) = match auth {
+        SSHTargetAuth::Password(auth) => (Some(&auth.password), &[][..]),
+        SSHTargetAuth::KeyboardInteractive(auth) => {
+            (auth.password.as_ref(), auth.responses.as_slice())
+        }
+        SSHTargetAuth::PublicKey(_) | SSHTargetAuth::Certificate(_) | SSHTargetAuth::Agent(_) => {
+            (None, &[][..])
+        }
+    };
+    prompts
+        .iter()
+        .map(|item| {
+            let prompt = item.prompt.to_lowercase();
+            if let Some(r) = responses
+                .iter()
+                .find(|r| !r.prompt.is_empty() && prompt.contains(&r.prompt.to_lowercase()))
+            {
+                return Some(r.response.clone());
+            }
+            match password {
+                Some(password) if !*password_used && prompt.contains("password") => {
+                    *password_used = true;
+                    Some(password.clone())
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use genesis_common::{
+        SshPromptResponse, SshTargetKeyboardInteractiveAuth, SshTargetPasswordAuth,
+    };
+
+    fn item(prompt: &str) -> AuthPromptItem {
+        AuthPromptItem {
+            prompt: prompt.into(),
+            echo: false,
+        }
+    }
+
+    #[test]
+    fn test_stored_answers() {
+        let auth = SSHTargetAuth::Password(SshTargetPasswordAuth {
+            password: "1qaz2wsx".into(),
+        });
+        let mut used = false;
+        let answers = stored_answers(
+            &auth,
+            &[item("Password: "), item("Verification code: ")],
+            &mut used,
+        );
+        assert_eq!(answers, vec![Some("1qaz2wsx".to_string()), None]);
+        // 密码错误后再次提示时交由用户应答
+        let answers = stored_answers(&auth, &[item("Password: ")], &mut used);
+        assert_eq!(answers, vec![None]);
+
+        let auth = SSHTargetAuth::KeyboardInteractive(SshTargetKeyboardInteractiveAuth {
+            password: None,
+            responses: vec![SshPromptResponse {
+                prompt: "token".into(),
+                response: "123456".into(),
+            }],
+        });
+        let mut used = false;
+        let answers = stored_answers(&auth, &[item("Enter TOKEN:"), item("Password:")], &mut used);
+        assert_eq!(answers, vec![Some("123456".to_string()), None]);
+    }
+}
@@ -0,0 +1,371 @@
+use std::pin::Pin;
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use genesis_common::{SessionId, TargetSSHOptions};
+use russh_sftp::client::fs::Metadata;
+use russh_sftp::client::SftpSession;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+
+use super::tunnel::bridge;
+use super::RemoteConnection;
+
+const SFTP_CHUNK_SIZE: usize = 32 * 1024;
+
+/// sftp文件信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SftpEntry {
+    pub name: String,
+    pub path: String,
+    pub size: u64,
+    /// 权限位, 如 0o755
+    pub permissions: u32,
+    pub is_dir: bool,
+    pub is_symlink: bool,
+    /// 修改时间, unix时间戳(秒)
+    pub modified: Option<u32>,
+}
+
+impl SftpEntry {
+    fn new(name: String, path: String, metadata: &Metadata) -> Self {
+        Self {
+            name,
+            path,
+            size: metadata.size.unwrap_or_default(),
+            permissions: metadata.permissions.unwrap_or_default() & 0o7777,
+            is_dir: metadata.is_dir(),
+            is_symlink: metadata.is_symlink(),
+            modified: metadata.mtime,
+        }
+    }
+}
+
+pub type SftpByteStream = Pin<Box<dyn Stream<Item = Result<Bytes>> + Send>>;
+
+/// 基于 RemoteConnection 的sftp客户端
+pub struct SftpClient {
+    connection: RemoteConnection,
+    session: SftpSession,
+    ctx: CancellationToken,
+    /// 连接由客户端创建时, 关闭客户端同时断开连接
+    owned: bool,
+}
+
+impl SftpClient {
+    pub async fn connect(id: SessionId, option: TargetSSHOptions) -> Result<Self> {
+        let connection = RemoteConnection::connect(id, option).await?;
+        let mut client = Self::open(connection.clone()).await.inspect_err(|_| {
+            connection.disconnect();
+        })?;
+        client.owned = true;
+        Ok(client)
+    }
+
+    /// 在已有连接上打开sftp子系统
+    pub async fn open(connection: RemoteConnection) -> Result<Self> {
+        let channel = connection.open_subsystem("sftp").await?;
+        let ctx = CancellationToken::new();
+        let (local, remote) = tokio::io::duplex(SFTP_CHUNK_SIZE * 4);
+        tokio::spawn(bridge(remote, channel, ctx.clone(), None));
+        let session = SftpSession::new(local)
+            .await
+            .inspect_err(|_| ctx.cancel())?;
+        debug!(session_id=%connection.id(), "sftp session opened");
+        Ok(Self {
+            connection,
+            session,
+            ctx,
+            owned: false,
+        })
+    }
+
+    pub async fn canonicalize(&self, path: &str) -> Result<String> {
+        Ok(self.session.canonicalize(path).await?)
+    }
+
+    pub async fn list(&self, path: &str) -> Result<Vec<SftpEntry>> {
+        let mut entries = self
+            .session
+            .read_dir(path)
+            .await?
+            .filter(|e| e.file_name() != "." && e.file_name() != "..")
+            .map(|e| {
+                let name = e.file_name();
+                SftpEntry::new(name.clone(), join_path(path, &name), &e.metadata())
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then(a.name.cmp(&b.name)));
+        Ok(entries)
+    }
+
+    pub async fn stat(&self, path: &str) -> Result<SftpEntry> {
+        let metadata = self.session.metadata(path).await?;
+        Ok(SftpEntry::new(
+            file_name(path).to_string(),
+            path.to_string(),
+            &metadata,
+        ))
+    }
+
+    pub async fn exists(&self, path: &str) -> Result<bool> {
+        Ok(self.session.try_exists(path).await?)
+    }
+
+    pub async fn mkdir(&self, path: &str) -> Result<()> {
+        Ok(self.session.create_dir(path).await?)
+    }
+
+    pub async fn rename(&self, from: &str, to: &str) -> Result<()> {
+        Ok(self.session.rename(from, to).await?)
+    }
+
+    /// 删除文件或空目录
+    pub async fn remove(&self, path: &str) -> Result<()> {
+        let metadata = self.session.symlink_metadata(path).await?;
+        if metadata.is_dir() {
+            self.session.remove_dir(path).await?;
+        } else {
+            self.session.remove_file(path).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn chmod(&self, path: &str, mode: u32) -> Result<()> {
+        let mut metadata = self.session.metadata(path).await?;
+        // 保留文件类型位
+        let file_type = metadata.permissions.unwrap_or_default() & !0o7777;
+        metadata.permissions = Some(file_type | (mode & 0o7777));
+        Ok(self.session.set_metadata(path, metadata).await?)
+    }
+
+    /// 分块读取文件, `ctx` 取消后流返回错误并结束
+    pub async fn read(&self, path: &str, ctx: CancellationToken) -> Result<SftpByteStream> {
+        let file = self.session.open(path).await?;
+        let path = path.to_string();
+        let stream = futures::stream::unfold(Some(file), move |file| {
+            let ctx = ctx.clone();
+            let path = path.clone();
+            async move {
+                let mut file = file?;
+                let mut buf = vec![0u8; SFTP_CHUNK_SIZE];
+                tokio::select! {
+                    read = file.read(&mut buf) => match read {
+                        Ok(0) => None,
+                        Ok(n) => {
+                            buf.truncate(n);
+                            Some((Ok(Bytes::from(buf)), Some(file)))
+                        }
+                        Err(e) => Some((Err(anyhow::anyhow!("sftp read {} error: {}", path, e)), None)),
+                    },
+                    _ = ctx.cancelled() => Some((Err(anyhow::anyhow!("sftp read {} cancelled", path)), None)),
+                }
+            }
+        });
+        Ok(Box::pin(stream))
+    }
+
+    /// 下载文件至 `writer`, 返回写入字节数
+    pub async fn download<W>(
+        &self,
+        path: &str,
+        mut writer: W,
+        ctx: CancellationToken,
+    ) -> Result<u64>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut stream = self.read(path, ctx).await?;
+        let mut total = 0u64;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            writer.write_all(&chunk).await?;
+            total += chunk.len() as u64;
+        }
+        writer.flush().await?;
+        Ok(total)
+    }
+
+    /// 将数据流写入文件, 文件存在时覆盖, 返回写入字节数
+    pub async fn write<S>(&self, path: &str, mut stream: S, ctx: CancellationToken) -> Result<u64>
+    where
+        S: Stream<Item = Result<Bytes>> + Unpin,
+    {
+        let mut file = self.session.create(path).await?;
+        let mut total = 0u64;
+        loop {
+            let chunk = tokio::select! {
+                chunk = stream.next() => chunk,
+                _ = ctx.cancelled() => anyhow::bail!("sftp write {} cancelled", path),
+            };
+            let Some(chunk) = chunk else {
+                break;
+            };
+            let chunk = chunk?;
+            tokio::select! {
+                res = file.write_all(&chunk) => res?,
+                _ = ctx.cancelled() => anyhow::bail!("sftp write {} cancelled", path),
+            }
+            total += chunk.len() as u64;
+        }
+        file.shutdown().await?;
+        Ok(total)
+    }
+
+    /// 从 `reader` 上传文件, 返回写入字节数
+    pub async fn upload<R>(&self, path: &str, reader: R, ctx: CancellationToken) -> Result<u64>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+    {
+        let stream = futures::stream::unfold(reader, |mut reader| async move {
+            let mut buf = vec![0u8; SFTP_CHUNK_SIZE];
+            match reader.read(&mut buf).await {
+                Ok(0) => None,
+                Ok(n) => {
+                    buf.truncate(n);
+                    Some((Ok(Bytes::from(buf)), reader))
+                }
+                Err(e) => Some((Err(e.into()), reader)),
+            }
+        });
+        self.write(path, Box::pin(stream), ctx).await
+    }
+
+    pub async fn close(&self) -> Result<()> {
+        let res = self.session.close().await;
+        self.ctx.cancel();
+        if self.owned {
+            self.connection.disconnect();
+        }
+        Ok(res?)
+    }
+}
+
+impl Drop for SftpClient {
+    fn drop(&mut self) {
+        self.ctx.cancel();
+        if self.owned {
+            self.connection.disconnect();
+        }
+    }
+}
+
+fn join_path(dir: &str, name: &str) -> String {
+    if dir.is_empty() || dir == "." {
+        return name.to_string();
+    }
+    format!("{}/{}", dir.trim_end_matches('/'), name)
+}
+
+fn file_name(path: &str) -> &str {
+    let trimmed = path.trim_end_matches('/');
+    match trimmed.rsplit_once('/') {
+        Some((_, name)) => name,
+        None if trimmed.is_empty() => path,
+        None => trimmed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FakeShell, TestServer};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_path() {
+        assert_eq!(join_path("/root/", "a.txt"), "/root/a.txt");
+        assert_eq!(join_path("/", "etc"), "/etc");
+        assert_eq!(join_path(".", "a"), "a");
+        assert_eq!(file_name("/var/log/"), "log");
+        assert_eq!(file_name("/"), "/");
+        assert_eq!(file_name("a.txt"), "a.txt");
+    }
+
+    #[tokio::test]
+    async fn test_sftp_client() {
+        let server = TestServer::start(FakeShell::new()).await.unwrap();
+        let client = SftpClient::connect(Uuid::new_v4(), server.ssh_options())
+            .await
+            .unwrap();
+        let ctx = CancellationToken::new();
+        let data = Bytes::from_static(b"genesis sftp");
+        assert_eq!(client.canonicalize(".").await.unwrap(), "/root");
+        client.mkdir("/tmp/genesis-sftp").await.unwrap();
+        let written = client
+            .write(
+                "/tmp/genesis-sftp/a.txt",
+                futures::stream::iter(vec![Ok(data.clone())]),
+                ctx.clone(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(written, data.len() as u64);
+        client
+            .chmod("/tmp/genesis-sftp/a.txt", 0o600)
+            .await
+            .unwrap();
+        let entry = client.stat("/tmp/genesis-sftp/a.txt").await.unwrap();
+        assert_eq!(entry.permissions, 0o600);
+        assert_eq!(entry.size, data.len() as u64);
+        let mut out = vec![];
+        client
+            .download("/tmp/genesis-sftp/a.txt", &mut out, ctx.clone())
+            .await
+            .unwrap();
+        assert_eq!(out, data);
+        client
+            .rename("/tmp/genesis-sftp/a.txt", "/tmp/genesis-sftp/b.txt")
+            .await
+            .unwrap();
+        client.mkdir("/tmp/genesis-sftp/sub").await.unwrap();
+        let list = client.list("/tmp/genesis-sftp").await.unwrap();
+        let names = list.iter().map(|e| e.name.as_str()).collect::<Vec<_>>();
+        // 目录在前
+        assert_eq!(names, vec!["sub", "b.txt"]);
+        assert_eq!(list[1].path, "/tmp/genesis-sftp/b.txt");
+        assert!(client.remove("/tmp/genesis-sftp").await.is_err());
+        client.remove("/tmp/genesis-sftp/b.txt").await.unwrap();
+        client.remove("/tmp/genesis-sftp/sub").await.unwrap();
+        client.remove("/tmp/genesis-sftp").await.unwrap();
+        assert!(!client.exists("/tmp/genesis-sftp").await.unwrap());
+        client.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sftp_upload_large_file() {
+        let server = TestServer::start(FakeShell::new()).await.unwrap();
+        let connection = RemoteConnection::connect(Uuid::new_v4(), server.ssh_options())
+            .await
+            .unwrap();
+        let client = SftpClient::open(connection.clone()).await.unwrap();
+        // 多个分块
+        let data = (0..SFTP_CHUNK_SIZE * 3 + 17)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        let ctx = CancellationToken::new();
+        let written = client
+            .upload("big.bin", std::io::Cursor::new(data.clone()), ctx.clone())
+            .await
+            .unwrap();
+        assert_eq!(written, data.len() as u64);
+        let mut out = vec![];
+        client
+            .download("/root/big.bin", &mut out, ctx.clone())
+            .await
+            .unwrap();
+        assert_eq!(out, data);
+        // 已取消时读取失败
+        ctx.cancel();
+        assert!(client
+            .download("/root/big.bin", &mut vec![], ctx)
+            .await
+            .is_err());
+        // 非自有连接关闭客户端不断开连接
+        client.close().await.unwrap();
+        assert!(!connection.closed().is_cancelled());
+        connection.disconnect();
+    }
+}
@@ -0,0 +1,519 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+
+use anyhow::Result;
+use genesis_common::{SessionId, TargetSSHOptions};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+
+use super::tunnel::bridge;
+use super::{RemoteConnection, TunnelStats};
+use crate::DirectTCPIPParams;
+
+const SOCKS_VERSION: u8 = 0x05;
+const AUTH_VERSION: u8 = 0x01;
+const METHOD_NO_AUTH: u8 = 0x00;
+const METHOD_PASSWORD: u8 = 0x02;
+const METHOD_UNACCEPTABLE: u8 = 0xff;
+const CMD_CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+const REPLY_SUCCEEDED: u8 = 0x00;
+const REPLY_GENERAL_FAILURE: u8 = 0x01;
+const REPLY_NOT_ALLOWED: u8 = 0x02;
+const REPLY_HOST_UNREACHABLE: u8 = 0x04;
+const REPLY_COMMAND_NOT_SUPPORTED: u8 = 0x07;
+const REPLY_ADDRESS_NOT_SUPPORTED: u8 = 0x08;
+
+/// socks5 用户名密码认证
+#[derive(Debug, Clone)]
+pub struct SocksAuth {
+    pub username: String,
+    pub password: String,
+}
+
+/// 目标地址访问规则
+#[derive(Debug, Clone)]
+pub struct SocksRule {
+    pub allow: bool,
+    /// 目标主机, 支持精确匹配、`*` 、`*.example.com` 及 `10.0.0.0/8`;
+    /// 存在ip规则时目标必须为ip, 域名在远端解析, 无法可靠匹配
+    pub host: String,
+    /// 目标端口, 为空时匹配全部端口
+    pub port: Option<u16>,
+}
+
+impl SocksRule {
+    pub fn allow(host: impl Into<String>, port: Option<u16>) -> Self {
+        Self {
+            allow: true,
+            host: host.into(),
+            port,
+        }
+    }
+
+    pub fn deny(host: impl Into<String>, port: Option<u16>) -> Self {
+        Self {
+            allow: false,
+            host: host.into(),
+            port,
+        }
+    }
+
+    /// 规则为ip或网段
+    fn is_ip_rule(&self) -> bool {
+        self.host.contains('/') || self.host.parse::<IpAddr>().is_ok()
+    }
+
+    fn matches(&self, host: &str, port: u16) -> bool {
+        if self.port.is_some_and(|p| p != port) {
+            return false;
+        }
+        let pattern = self.host.as_str();
+        if pattern == "*" {
+            return true;
+        }
+        if let Some(suffix) = pattern.strip_prefix("*.") {
+            let host = host.to_ascii_lowercase();
+            let suffix = suffix.to_ascii_lowercase();
+            return host.ends_with(&format!(".{}", suffix));
+        }
+        if let Some((net, prefix)) = pattern.split_once('/') {
+            return match (
+                net.parse::<IpAddr>(),
+                prefix.parse::<u32>(),
+                host.parse::<IpAddr>(),
+            ) {
+                (Ok(net), Ok(prefix), Ok(ip)) => cidr_contains(net, prefix, ip.to_canonical()),
+                _ => false,
+            };
+        }
+        if let (Ok(pattern), Ok(ip)) = (pattern.parse::<IpAddr>(), host.parse::<IpAddr>()) {
+            return pattern == ip.to_canonical();
+        }
+        pattern.eq_ignore_ascii_case(host)
+    }
+}
+
+fn cidr_contains(net: IpAddr, prefix: u32, ip: IpAddr) -> bool {
+    match (net, ip) {
+        (IpAddr::V4(net), IpAddr::V4(ip)) if prefix <= 32 => {
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(net) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(net), IpAddr::V6(ip)) if prefix <= 128 => {
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(net) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+/// 代理目标访问记录, 放行及拒绝的请求均会上报
+#[derive(Debug, Clone)]
+pub struct SocksDestination {
+    pub peer: SocketAddr,
+    pub host: String,
+    pub port: u16,
+    pub allowed: bool,
+}
+
+/// socks5 代理配置, 规则按顺序匹配, 均未匹配时由 `default_allow` 决定, 默认拒绝
+#[derive(Debug, Clone, Default)]
+pub struct SocksOptions {
+    pub auth: Option<SocksAuth>,
+    pub rules: Vec<SocksRule>,
+    pub default_allow: bool,
+    /// 每个目标地址的访问记录
+    pub audit: Option<UnboundedSender<SocksDestination>>,
+}
+
+impl SocksOptions {
+    pub fn is_allowed(&self, host: &str, port: u16) -> bool {
+        if host.parse::<IpAddr>().is_err() && self.rules.iter().any(SocksRule::is_ip_rule) {
+            return false;
+        }
+        self.rules
+            .iter()
+            .find(|r| r.matches(host, port))
+            .map(|r| r.allow)
+            .unwrap_or(self.default_allow)
+    }
+}
+
+/// 动态转发, 本地socks5代理的每个CONNECT请求经ssh direct-tcpip通道连接目标
+pub struct SocksProxy {
+    local_addr: SocketAddr,
+    connection: RemoteConnection,
+    ctx: CancellationToken,
+    stats: Arc<TunnelStats>,
+}
+
+impl SocksProxy {
+    /// 建立ssh连接并监听本地地址, `bind` 端口为0时随机分配
+    pub async fn start(
+        id: SessionId,
+        option: TargetSSHOptions,
+        bind: &str,
+        options: SocksOptions,
+    ) -> Result<Self> {
+        let connection = RemoteConnection::connect(id, option).await?;
+        Self::start_with_connection(connection.clone(), bind, options)
+            .await
+            .inspect_err(|_| connection.disconnect())
+    }
+
+    pub async fn start_with_connection(
+        connection: RemoteConnection,
+        bind: &str,
+        options: SocksOptions,
+    ) -> Result<Self> {
+        let listener = TcpListener::bind(bind).await?;
+        let local_addr = listener.local_addr()?;
+        let ctx = CancellationToken::new();
+        let stats = Arc::new(TunnelStats::default());
+        let options = Arc::new(options);
+        info!(session_id=%connection.id(), %local_addr, "socks proxy started");
+        tokio::spawn({
+            let connection = connection.clone();
+            let ctx = ctx.clone();
+            let stats = stats.clone();
+            let closed = connection.closed();
+            async move {
+                loop {
+                    tokio::select! {
+                        accepted = listener.accept() => match accepted {
+                            Ok((stream, peer)) => {
+                                let connection = connection.clone();
+                                let ctx = ctx.clone();
+                                let stats = stats.clone();
+                                let options = options.clone();
+                                tokio::spawn(async move {
+                                    if let Err(e) = serve(stream, peer, connection.clone(), options, ctx, stats).await {
+                                        debug!(session_id=%connection.id(), %peer, "socks request error: {}", e);
+                                    }
+                                });
+                            }
+                            Err(e) => {
+                                error!("socks proxy accept error: {}", e);
+                                break;
+                            }
+                        },
+                        _ = ctx.cancelled() => break,
+                        _ = closed.cancelled() => break,
+                    }
+                }
+                ctx.cancel();
+                info!(%local_addr, "socks proxy stopped");
+            }
+        });
+        Ok(Self {
+            local_addr,
+            connection,
+            ctx,
+            stats,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn stats(&self) -> Arc<TunnelStats> {
+        self.stats.clone()
+    }
+
+    /// 代理停止时触发, 包括主动关闭及ssh连接断开
+    pub fn stopped(&self) -> CancellationToken {
+        self.ctx.clone()
+    }
+
+    pub fn connection(&self) -> &RemoteConnection {
+        &self.connection
+    }
+
+    /// 关闭代理时同时断开ssh连接
+    pub fn close(&self) {
+        self.ctx.cancel();
+        self.connection.disconnect();
+    }
+}
+
+impl Drop for SocksProxy {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+async fn serve<S>(
+    mut stream: S,
+    peer: SocketAddr,
+    connection: RemoteConnection,
+    options: Arc<SocksOptions>,
+    ctx: CancellationToken,
+    stats: Arc<TunnelStats>,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let session_id = connection.id();
+    let (host, port) = match handshake(&mut stream, &options).await? {
+        Ok(target) => target,
+        Err(reply) => {
+            send_reply(&mut stream, reply).await?;
+            anyhow::bail!("unsupported socks request");
+        }
+    };
+    let allowed = options.is_allowed(&host, port);
+    if let Some(audit) = &options.audit {
+        let _ = audit.send(SocksDestination {
+            peer,
+            host: host.clone(),
+            port,
+            allowed,
+        });
+    }
+    if !allowed {
+        warn!(%session_id, %peer, %host, port, "socks destination denied");
+        send_reply(&mut stream, REPLY_NOT_ALLOWED).await?;
+        return Ok(());
+    }
+    info!(%session_id, %peer, %host, port, "socks destination");
+    let params = DirectTCPIPParams {
+        host_to_connect: host.clone(),
+        port_to_connect: port as u32,
+        originator_address: peer.ip().to_string(),
+        originator_port: peer.port() as u32,
+    };
+    match connection.open_direct_tcpip(params).await {
+        Ok(channel) => {
+            send_reply(&mut stream, REPLY_SUCCEEDED).await?;
+            bridge(stream, channel, ctx, Some(stats)).await;
+            Ok(())
+        }
+        Err(e) => {
+            send_reply(&mut stream, REPLY_HOST_UNREACHABLE).await?;
+            anyhow::bail!("open direct-tcpip to {}:{} error: {}", host, port, e)
+        }
+    }
+}
+
+/// 完成方法协商、认证并读取请求, 返回目标地址; 不支持的请求返回应答码
+async fn handshake<S>(stream: &mut S, options: &SocksOptions) -> Result<Result<(String, u16), u8>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header).await?;
+    if header[0] != SOCKS_VERSION {
+        anyhow::bail!("unsupported socks version {}", header[0]);
+    }
+    let mut methods = vec![0u8; header[1] as usize];
+    stream.read_exact(&mut methods).await?;
+    let method = if options.auth.is_some() {
+        METHOD_PASSWORD
+    } else {
+        METHOD_NO_AUTH
+    };
+    if !methods.contains(&method) {
+        stream
+            .write_all(&[SOCKS_VERSION, METHOD_UNACCEPTABLE])
+            .await?;
+        anyhow::bail!("no acceptable socks auth method");
+    }
+    stream.write_all(&[SOCKS_VERSION, method]).await?;
+    if let Some(auth) = &options.auth {
+        let version = stream.read_u8().await?;
+        if version != AUTH_VERSION {
+            anyhow::bail!("unsupported socks auth version {}", version);
+        }
+        let username = read_string(stream).await?;
+        let password = read_string(stream).await?;
+        if username != auth.username || password != auth.password {
+            stream.write_all(&[AUTH_VERSION, 0x01]).await?;
+            anyhow::bail!("socks auth failed for user {}", username);
+        }
+        stream.write_all(&[AUTH_VERSION, 0x00]).await?;
+    }
+
+    let mut request = [0u8; 4];
+    stream.read_exact(&mut request).await?;
+    if request[0] != SOCKS_VERSION {
+        anyhow::bail!("unsupported socks version {}", request[0]);
+    }
+    let host = match request[3] {
+        ATYP_IPV4 => {
+            let mut addr = [0u8; 4];
+            stream.read_exact(&mut addr).await?;
+            Ipv4Addr::from(addr).to_string()
+        }
+        ATYP_IPV6 => {
+            let mut addr = [0u8; 16];
+            stream.read_exact(&mut addr).await?;
+            Ipv6Addr::from(addr).to_string()
+        }
+        ATYP_DOMAIN => read_string(stream).await?,
+        _ => return Ok(Err(REPLY_ADDRESS_NOT_SUPPORTED)),
+    };
+    let port = stream.read_u16().await?;
+    if request[1] != CMD_CONNECT {
+        return Ok(Err(REPLY_COMMAND_NOT_SUPPORTED));
+    }
+    if host.is_empty() {
+        return Ok(Err(REPLY_GENERAL_FAILURE));
+    }
+    Ok(Ok((host, port)))
+}
+
+async fn read_string<S: AsyncRead + Unpin>(stream: &mut S) -> Result<String> {
+    let len = stream.read_u8().await?;
+    let mut buf = vec![0u8; len as usize];
+    stream.read_exact(&mut buf).await?;
+    Ok(String::from_utf8(buf)?)
+}
+
+/// 应答中绑定地址固定为 0.0.0.0:0
+async fn send_reply<S: AsyncWrite + Unpin>(stream: &mut S, reply: u8) -> Result<()> {
+    stream
+        .write_all(&[SOCKS_VERSION, reply, 0x00, ATYP_IPV4, 0, 0, 0, 0, 0, 0])
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FakeShell, TestServer};
+    use tokio::net::TcpStream;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_socks_rules() {
+        let options = SocksOptions {
+            auth: None,
+            rules: vec![
+                SocksRule::deny("10.0.1.52", Some(22)),
+                SocksRule::allow("10.0.0.0/8", None),
+                SocksRule::allow("*.example.com", Some(443)),
+            ],
+            ..Default::default()
+        };
+        assert!(!options.is_allowed("10.0.1.52", 22));
+        assert!(options.is_allowed("10.0.1.52", 80));
+        assert!(!options.is_allowed("192.168.1.1", 80));
+        assert!(!options.is_allowed("::ffff:10.0.1.52", 22));
+        assert!(options.is_allowed("::ffff:10.0.0.1", 80));
+        // 存在ip规则时域名及非标准ip写法一律拒绝
+        for host in ["010.0.1.52", "167837236", "api.example.com"] {
+            assert!(!options.is_allowed(host, 443));
+        }
+
+        let options = SocksOptions {
+            rules: vec![SocksRule::allow("*.example.com", Some(443))],
+            ..Default::default()
+        };
+        assert!(options.is_allowed("api.Example.com", 443));
+        assert!(!options.is_allowed("example.com", 443));
+        assert!(!options.is_allowed("api.example.com", 80));
+        assert!(!options.is_allowed("10.0.0.1", 443));
+        assert!(!SocksOptions::default().is_allowed("example.com", 80));
+    }
+
+    #[tokio::test]
+    async fn test_socks_handshake() {
+        let options = SocksOptions {
+            auth: Some(SocksAuth {
+                username: "genesis".into(),
+                password: "secret".into(),
+            }),
+            ..Default::default()
+        };
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        let task = tokio::spawn(async move { handshake(&mut server, &options).await });
+        client.write_all(&[5, 1, METHOD_PASSWORD]).await.unwrap();
+        let mut reply = [0u8; 2];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply, [5, METHOD_PASSWORD]);
+        client
+            .write_all(b"\x01\x07genesis\x06secret")
+            .await
+            .unwrap();
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply, [AUTH_VERSION, 0]);
+        client
+            .write_all(b"\x05\x01\x00\x03\x0bexample.com\x01\xbb")
+            .await
+            .unwrap();
+        let target = task.await.unwrap().unwrap();
+        assert_eq!(target, Ok(("example.com".to_string(), 443)));
+    }
+
+    /// 经代理发起CONNECT请求, 返回应答码及连接
+    async fn socks_connect(proxy: SocketAddr, ip: Ipv4Addr, port: u16) -> (u8, TcpStream) {
+        let mut stream = TcpStream::connect(proxy).await.unwrap();
+        stream.write_all(&[5, 1, METHOD_NO_AUTH]).await.unwrap();
+        let mut method = [0u8; 2];
+        stream.read_exact(&mut method).await.unwrap();
+        assert_eq!(method, [5, METHOD_NO_AUTH]);
+        let mut request = vec![5, CMD_CONNECT, 0, ATYP_IPV4];
+        request.extend_from_slice(&ip.octets());
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request).await.unwrap();
+        let mut reply = [0u8; 10];
+        stream.read_exact(&mut reply).await.unwrap();
+        (reply[1], stream)
+    }
+
+    #[tokio::test]
+    async fn test_socks_proxy_with_test_server() {
+        let server = TestServer::start(FakeShell::new()).await.unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let echo = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let (mut reader, mut writer) = stream.split();
+                    let _ = tokio::io::copy(&mut reader, &mut writer).await;
+                });
+            }
+        });
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let options = SocksOptions {
+            rules: vec![SocksRule::allow("127.0.0.1", Some(echo.port()))],
+            audit: Some(tx),
+            ..Default::default()
+        };
+        let proxy = SocksProxy::start(Uuid::new_v4(), server.ssh_options(), "127.0.0.1:0", options)
+            .await
+            .unwrap();
+
+        let (reply, mut stream) =
+            socks_connect(proxy.local_addr(), Ipv4Addr::LOCALHOST, echo.port()).await;
+        assert_eq!(reply, REPLY_SUCCEEDED);
+        stream.write_all(b"hello").await.unwrap();
+        let mut buf = [0u8; 5];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+
+        // 规则均未匹配时默认拒绝
+        let (reply, _) = socks_connect(proxy.local_addr(), Ipv4Addr::LOCALHOST, 1).await;
+        assert_eq!(reply, REPLY_NOT_ALLOWED);
+
+        let allowed = rx.recv().await.unwrap();
+        assert_eq!(
+            (allowed.host.as_str(), allowed.port, allowed.allowed),
+            ("127.0.0.1", echo.port(), true)
+        );
+        let denied = rx.recv().await.unwrap();
+        assert_eq!(
+            (denied.host.as_str(), denied.port, denied.allowed),
+            ("127.0.0.1", 1, false)
+        );
+        proxy.close();
+    }
+}
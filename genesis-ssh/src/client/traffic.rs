@@ -0,0 +1,264 @@
+//! 会话及通道的流量统计, 按方向令牌桶限速
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use genesis_common::{SSHRateLimit, SessionId};
+use uuid::Uuid;
+
+/// 流量快照
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TrafficStats {
+    /// 发往目标字节数
+    pub bytes_sent: u64,
+    /// 目标返回字节数
+    pub bytes_received: u64,
+    pub packets_sent: u64,
+    pub packets_received: u64,
+}
+
+#[derive(Debug, Default)]
+struct TrafficCounter {
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    packets_sent: AtomicU64,
+    packets_received: AtomicU64,
+}
+
+impl TrafficCounter {
+    fn sent(&self, len: usize) {
+        self.bytes_sent.fetch_add(len as u64, Ordering::Relaxed);
+        self.packets_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn received(&self, len: usize) {
+        self.bytes_received.fetch_add(len as u64, Ordering::Relaxed);
+        self.packets_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> TrafficStats {
+        TrafficStats {
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            packets_sent: self.packets_sent.load(Ordering::Relaxed),
+            packets_received: self.packets_received.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 令牌桶, 令牌不足时记为欠额, 由调用方等待偿还
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    /// 当前令牌数及上次补充时间
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    fn new(rate: u64, burst: u64) -> Self {
+        let burst = if burst == 0 { rate } else { burst } as f64;
+        Self {
+            rate: rate as f64,
+            burst,
+            state: Mutex::new((burst, Instant::now())),
+        }
+    }
+
+    /// 扣除 `amount` 个令牌, 返回需等待的时长
+    fn reserve(&self, amount: usize, now: Instant) -> Duration {
+        let Ok(mut state) = self.state.lock() else {
+            return Duration::ZERO;
+        };
+        let elapsed = now.saturating_duration_since(state.1).as_secs_f64();
+        state.0 = (state.0 + elapsed * self.rate).min(self.burst) - amount as f64;
+        state.1 = now;
+        if state.0 >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.0 / self.rate)
+        }
+    }
+
+    async fn acquire(&self, amount: usize) {
+        let wait = self.reserve(amount, Instant::now());
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Limiter {
+    upload: Option<Arc<TokenBucket>>,
+    download: Option<Arc<TokenBucket>>,
+}
+
+/// 会话流量, 汇总该会话下所有通道
+#[derive(Debug)]
+pub struct SessionTraffic {
+    id: SessionId,
+    total: TrafficCounter,
+    channels: Mutex<HashMap<Uuid, Arc<TrafficCounter>>>,
+    limiter: Mutex<Limiter>,
+}
+
+impl SessionTraffic {
+    pub(crate) fn new(id: SessionId) -> Arc<Self> {
+        let traffic = Arc::new(Self {
+            id,
+            total: TrafficCounter::default(),
+            channels: Mutex::new(HashMap::new()),
+            limiter: Mutex::new(Limiter::default()),
+        });
+        if let Ok(mut registry) = REGISTRY.lock() {
+            registry.retain(|_, v| v.strong_count() > 0);
+            registry.insert(id, Arc::downgrade(&traffic));
+        }
+        traffic
+    }
+
+    pub fn id(&self) -> SessionId {
+        self.id
+    }
+
+    /// 会话累计流量, 包含已关闭的通道
+    pub fn total(&self) -> TrafficStats {
+        self.total.snapshot()
+    }
+
+    /// 打开中的通道流量
+    pub fn channel(&self, channel_id: &Uuid) -> Option<TrafficStats> {
+        let channels = self.channels.lock().ok()?;
+        channels.get(channel_id).map(|c| c.snapshot())
+    }
+
+    pub fn channels(&self) -> Vec<(Uuid, TrafficStats)> {
+        self.channels
+            .lock()
+            .map(|channels| channels.iter().map(|(k, v)| (*k, v.snapshot())).collect())
+            .unwrap_or_default()
+    }
+
+    /// 更新限速, 仅对之后打开的通道生效
+    pub(crate) fn set_rate_limit(&self, limit: &SSHRateLimit) {
+        let bucket = |rate: u64| (rate > 0).then(|| Arc::new(TokenBucket::new(rate, limit.burst)));
+        if let Ok(mut limiter) = self.limiter.lock() {
+            *limiter = Limiter {
+                upload: bucket(limit.upload),
+                download: bucket(limit.download),
+            };
+        }
+    }
+
+    /// 为通道创建计量, 通道关闭(计量释放)时移除通道统计
+    pub(crate) fn meter(self: &Arc<Self>, channel_id: Uuid) -> ChannelMeter {
+        let channel = Arc::new(TrafficCounter::default());
+        if let Ok(mut channels) = self.channels.lock() {
+            channels.insert(channel_id, channel.clone());
+        }
+        let (upload, download) = self
+            .limiter
+            .lock()
+            .map(|l| (l.upload.clone(), l.download.clone()))
+            .unwrap_or_default();
+        ChannelMeter {
+            channel_id,
+            channel,
+            session: self.clone(),
+            upload,
+            download,
+        }
+    }
+}
+
+/// 单个通道的计量及限速
+pub(crate) struct ChannelMeter {
+    channel_id: Uuid,
+    channel: Arc<TrafficCounter>,
+    session: Arc<SessionTraffic>,
+    upload: Option<Arc<TokenBucket>>,
+    download: Option<Arc<TokenBucket>>,
+}
+
+impl ChannelMeter {
+    /// 记录发往目标的数据, 超出限速时等待
+    pub(crate) async fn upload(&self, len: usize) {
+        self.channel.sent(len);
+        self.session.total.sent(len);
+        if let Some(bucket) = &self.upload {
+            bucket.acquire(len).await;
+        }
+    }
+
+    /// 记录目标返回的数据, 超出限速时等待, 等待期间不再读取通道数据
+    pub(crate) async fn download(&self, len: usize) {
+        self.channel.received(len);
+        self.session.total.received(len);
+        if let Some(bucket) = &self.download {
+            bucket.acquire(len).await;
+        }
+    }
+}
+
+impl Drop for ChannelMeter {
+    fn drop(&mut self) {
+        if let Ok(mut channels) = self.session.channels.lock() {
+            channels.remove(&self.channel_id);
+        }
+    }
+}
+
+static REGISTRY: LazyLock<Mutex<HashMap<SessionId, Weak<SessionTraffic>>>> =
+    LazyLock::new(Default::default);
+
+/// 按会话id查询流量, 会话结束后返回None
+pub fn session_traffic(id: &SessionId) -> Option<Arc<SessionTraffic>> {
+    REGISTRY.lock().ok()?.get(id).and_then(Weak::upgrade)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let bucket = TokenBucket::new(1000, 0);
+        let start = Instant::now();
+        // 初始突发容量等同一秒速率
+        assert_eq!(bucket.reserve(1000, start), Duration::ZERO);
+        assert_eq!(bucket.reserve(500, start), Duration::from_millis(500));
+        // 欠额偿还后按速率补充
+        let later = start + Duration::from_millis(1000);
+        assert_eq!(bucket.reserve(0, later), Duration::ZERO);
+        let much_later = start + Duration::from_secs(10);
+        assert_eq!(bucket.reserve(1500, much_later), Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn test_session_traffic() {
+        let id = Uuid::new_v4();
+        let traffic = SessionTraffic::new(id);
+        traffic.set_rate_limit(&SSHRateLimit::default());
+        let channel_id = Uuid::new_v4();
+        let meter = traffic.meter(channel_id);
+        meter.upload(10).await;
+        meter.download(100).await;
+        meter.download(20).await;
+        let expected = TrafficStats {
+            bytes_sent: 10,
+            bytes_received: 120,
+            packets_sent: 1,
+            packets_received: 2,
+        };
+        assert_eq!(traffic.channel(&channel_id), Some(expected));
+        assert!(session_traffic(&id).is_some());
+        drop(meter);
+        assert_eq!(traffic.channel(&channel_id), None);
+        assert_eq!(traffic.total(), expected);
+        drop(traffic);
+        assert!(session_traffic(&id).is_none());
+    }
+}
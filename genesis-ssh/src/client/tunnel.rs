@@ -0,0 +1,426 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use anyhow::Result;
+use bytes::Bytes;
+use genesis_common::{SessionId, TargetSSHOptions};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+
+use super::{RCEvent, RemoteChannel, RemoteConnection};
+use crate::{ChannelOperation, DirectTCPIPParams};
+
+/// 隧道流量统计
+#[derive(Debug, Default)]
+pub struct TunnelStats {
+    /// 累计连接数
+    pub connections: AtomicU64,
+    /// 当前活跃连接数
+    pub active: AtomicU64,
+    /// 本地发往远端字节数
+    pub bytes_sent: AtomicU64,
+    /// 远端返回字节数
+    pub bytes_received: AtomicU64,
+}
+
+/// 本地隧道, 将本地端口的连接经ssh转发至远端地址
+pub struct LocalTunnel {
+    local_addr: SocketAddr,
+    connection: RemoteConnection,
+    ctx: CancellationToken,
+    stats: Arc<TunnelStats>,
+}
+
+impl LocalTunnel {
+    /// 建立ssh连接并监听本地地址, `bind` 端口为0时随机分配
+    pub async fn start(
+        id: SessionId,
+        option: TargetSSHOptions,
+        bind: &str,
+        remote_host: String,
+        remote_port: u16,
+    ) -> Result<Self> {
+        let connection = RemoteConnection::connect(id, option).await?;
+        Self::start_with_connection(connection, bind, remote_host, remote_port).await
+    }
+
+    pub async fn start_with_connection(
+        connection: RemoteConnection,
+        bind: &str,
+        remote_host: String,
+        remote_port: u16,
+    ) -> Result<Self> {
+        let listener = TcpListener::bind(bind).await?;
+        let local_addr = listener.local_addr()?;
+        let ctx = CancellationToken::new();
+        let stats = Arc::new(TunnelStats::default());
+        info!(session_id=%connection.id(), %local_addr, %remote_host, remote_port, "local tunnel started");
+        tokio::spawn({
+            let connection = connection.clone();
+            let ctx = ctx.clone();
+            let stats = stats.clone();
+            let closed = connection.closed();
+            async move {
+                loop {
+                    tokio::select! {
+                        accepted = listener.accept() => match accepted {
+                            Ok((stream, peer)) => {
+                                let params = DirectTCPIPParams {
+                                    host_to_connect: remote_host.clone(),
+                                    port_to_connect: remote_port as u32,
+                                    originator_address: peer.ip().to_string(),
+                                    originator_port: peer.port() as u32,
+                                };
+                                let connection = connection.clone();
+                                let ctx = ctx.clone();
+                                let stats = stats.clone();
+                                tokio::spawn(async move {
+                                    match connection.open_direct_tcpip(params).await {
+                                        Ok(channel) => {
+                                            debug!(session_id=%connection.id(), %peer, channel=%channel.id(), "tunnel connection opened");
+                                            bridge(stream, channel, ctx, Some(stats)).await
+                                        }
+                                        Err(e) => error!(session_id=%connection.id(), %peer, "open direct-tcpip error: {}", e),
+                                    }
+                                });
+                            }
+                            Err(e) => {
+                                error!("local tunnel accept error: {}", e);
+                                break;
+                            }
+                        },
+                        _ = ctx.cancelled() => break,
+                        _ = closed.cancelled() => break,
+                    }
+                }
+                ctx.cancel();
+                info!(%local_addr, "local tunnel stopped");
+            }
+        });
+        Ok(Self {
+            local_addr,
+            connection,
+            ctx,
+            stats,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn stats(&self) -> Arc<TunnelStats> {
+        self.stats.clone()
+    }
+
+    /// 隧道停止时触发, 包括主动关闭及ssh连接断开
+    pub fn stopped(&self) -> CancellationToken {
+        self.ctx.clone()
+    }
+
+    pub fn connection(&self) -> &RemoteConnection {
+        &self.connection
+    }
+
+    /// 关闭隧道时同时断开ssh连接
+    pub fn close(&self) {
+        self.ctx.cancel();
+        self.connection.disconnect();
+    }
+}
+
+impl Drop for LocalTunnel {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// 远程隧道, 服务端监听端口的连接经ssh转发至本地目标地址
+pub struct RemoteTunnel {
+    bind_address: String,
+    bind_port: u32,
+    connection: RemoteConnection,
+    ctx: CancellationToken,
+    stats: Arc<TunnelStats>,
+}
+
+impl RemoteTunnel {
+    /// 建立ssh连接并请求服务端监听 `bind_address:bind_port`, 连接转发至 `local_host:local_port`
+    pub async fn start(
+        id: SessionId,
+        option: TargetSSHOptions,
+        bind_address: String,
+        bind_port: u32,
+        local_host: String,
+        local_port: u16,
+    ) -> Result<Self> {
+        let connection = RemoteConnection::connect(id, option).await?;
+        Self::start_with_connection(
+            connection.clone(),
+            bind_address,
+            bind_port,
+            local_host,
+            local_port,
+        )
+        .await
+        .inspect_err(|_| connection.disconnect())
+    }
+
+    pub async fn start_with_connection(
+        connection: RemoteConnection,
+        bind_address: String,
+        bind_port: u32,
+        local_host: String,
+        local_port: u16,
+    ) -> Result<Self> {
+        let mut forwarded = connection.tcpip_forward(&bind_address, bind_port).await?;
+        let address = bind_address.clone();
+        let ctx = CancellationToken::new();
+        let stats = Arc::new(TunnelStats::default());
+        info!(session_id=%connection.id(), %bind_address, bind_port, %local_host, local_port, "remote tunnel started");
+        tokio::spawn({
+            let connection = connection.clone();
+            let ctx = ctx.clone();
+            let stats = stats.clone();
+            let closed = connection.closed();
+            async move {
+                loop {
+                    tokio::select! {
+                        accepted = forwarded.recv() => match accepted {
+                            Some((channel, params)) => {
+                                let local_host = local_host.clone();
+                                let session_id = connection.id();
+                                let ctx = ctx.clone();
+                                let stats = stats.clone();
+                                tokio::spawn(async move {
+                                    match TcpStream::connect((local_host.as_str(), local_port)).await {
+                                        Ok(stream) => {
+                                            debug!(%session_id, originator=%params.originator_address, channel=%channel.id(), "remote tunnel connection opened");
+                                            bridge(stream, channel, ctx, Some(stats)).await
+                                        }
+                                        Err(e) => {
+                                            error!(%session_id, %local_host, local_port, "connect local target error: {}", e);
+                                            channel.close();
+                                        }
+                                    }
+                                });
+                            }
+                            None => break,
+                        },
+                        _ = ctx.cancelled() => break,
+                        _ = closed.cancelled() => break,
+                    }
+                }
+                ctx.cancel();
+                info!(%bind_address, bind_port, "remote tunnel stopped");
+            }
+        });
+        Ok(Self {
+            bind_address: address,
+            bind_port,
+            connection,
+            ctx,
+            stats,
+        })
+    }
+
+    pub fn bind_address(&self) -> &str {
+        &self.bind_address
+    }
+
+    pub fn bind_port(&self) -> u32 {
+        self.bind_port
+    }
+
+    pub fn stats(&self) -> Arc<TunnelStats> {
+        self.stats.clone()
+    }
+
+    /// 隧道停止时触发, 包括主动关闭及ssh连接断开
+    pub fn stopped(&self) -> CancellationToken {
+        self.ctx.clone()
+    }
+
+    pub fn connection(&self) -> &RemoteConnection {
+        &self.connection
+    }
+
+    /// 关闭隧道时同时断开ssh连接, 服务端监听随连接释放
+    pub fn close(&self) {
+        self.ctx.cancel();
+        self.connection.disconnect();
+    }
+}
+
+impl Drop for RemoteTunnel {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// 本地流与ssh通道双向拷贝
+pub(crate) async fn bridge<S>(
+    stream: S,
+    mut channel: RemoteChannel,
+    ctx: CancellationToken,
+    stats: Option<Arc<TunnelStats>>,
+) where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    if let Some(stats) = &stats {
+        stats.connections.fetch_add(1, Ordering::Relaxed);
+        stats.active.fetch_add(1, Ordering::Relaxed);
+    }
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let mut buf = vec![0u8; 32 * 1024];
+    let mut local_eof = false;
+    loop {
+        tokio::select! {
+            read = reader.read(&mut buf), if !local_eof => match read {
+                Ok(0) => {
+                    local_eof = true;
+                    let _ = channel.send(ChannelOperation::Eof);
+                }
+                Ok(n) => {
+                    if let Some(stats) = &stats {
+                        stats.bytes_sent.fetch_add(n as u64, Ordering::Relaxed);
+                    }
+                    if channel.send(ChannelOperation::Data(Bytes::copy_from_slice(&buf[..n]))).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    debug!(channel=%channel.id(), "local read error: {}", e);
+                    break;
+                }
+            },
+            event = channel.recv() => match event {
+                Some(RCEvent::Output(_, data)) => {
+                    if let Some(stats) = &stats {
+                        stats.bytes_received.fetch_add(data.len() as u64, Ordering::Relaxed);
+                    }
+                    if writer.write_all(&data).await.is_err() {
+                        break;
+                    }
+                }
+                Some(RCEvent::Eof(_)) => {
+                    let _ = writer.shutdown().await;
+                }
+                Some(RCEvent::Close(_)) | None => break,
+                Some(_) => {}
+            },
+            _ = ctx.cancelled() => break,
+        }
+    }
+    if let Some(stats) = &stats {
+        stats.active.fetch_sub(1, Ordering::Relaxed);
+    }
+    channel.close();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FakeShell, TestServer};
+    use uuid::Uuid;
+
+    /// 回显服务, 返回监听地址
+    async fn echo_server() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let (mut reader, mut writer) = stream.split();
+                    let _ = tokio::io::copy(&mut reader, &mut writer).await;
+                });
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_local_tunnel_with_test_server() {
+        let server = TestServer::start(FakeShell::new()).await.unwrap();
+        let echo = echo_server().await;
+        let tunnel = LocalTunnel::start(
+            Uuid::new_v4(),
+            server.ssh_options(),
+            "127.0.0.1:0",
+            echo.ip().to_string(),
+            echo.port(),
+        )
+        .await
+        .unwrap();
+        assert!(tunnel.local_addr().ip().is_loopback());
+        for message in [&b"hello"[..], &b"genesis"[..]] {
+            let mut stream = TcpStream::connect(tunnel.local_addr()).await.unwrap();
+            stream.write_all(message).await.unwrap();
+            let mut buf = vec![0u8; message.len()];
+            stream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(buf, message);
+        }
+        let stats = tunnel.stats();
+        assert_eq!(stats.connections.load(Ordering::Relaxed), 2);
+        assert_eq!(stats.bytes_sent.load(Ordering::Relaxed), 12);
+        assert_eq!(stats.bytes_received.load(Ordering::Relaxed), 12);
+
+        // ssh连接断开后隧道停止
+        let stopped = tunnel.stopped();
+        server.drop_connections();
+        tokio::time::timeout(std::time::Duration::from_secs(5), stopped.cancelled())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_remote_tunnel_with_test_server() {
+        let server = TestServer::start(FakeShell::new()).await.unwrap();
+        let echo = echo_server().await;
+        // 服务端监听端口需显式指定
+        let port = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let tunnel = RemoteTunnel::start(
+            Uuid::new_v4(),
+            server.ssh_options(),
+            "127.0.0.1".to_string(),
+            port as u32,
+            echo.ip().to_string(),
+            echo.port(),
+        )
+        .await
+        .unwrap();
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        stream.write_all(b"reverse").await.unwrap();
+        let mut buf = [0u8; 7];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"reverse");
+        assert_eq!(tunnel.stats().connections.load(Ordering::Relaxed), 1);
+
+        // 同一连接上重复监听同一端口被拒绝
+        let connection = tunnel.connection().clone();
+        assert!(connection
+            .tcpip_forward("127.0.0.1", port as u32)
+            .await
+            .is_err());
+        // 取消后服务端不再监听
+        connection
+            .cancel_tcpip_forward("127.0.0.1", port as u32)
+            .await
+            .unwrap();
+        let closed = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        })
+        .await;
+        assert!(closed.is_ok());
+    }
+}
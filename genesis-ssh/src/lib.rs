@@ -1,5 +1,12 @@
 mod client;
 mod common;
+mod server;
+mod telnet;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
 pub use client::*;
 pub use common::*;
+pub use russh::keys;
+pub use server::*;
+pub use telnet::*;
@@ -0,0 +1,162 @@
+//! 堡垒机模式, 接受 `ssh user%target@bastion` 登录并代理至目标
+
+mod session;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use genesis_common::SessionId;
+use russh::keys::{PrivateKey, PublicKey};
+use russh::server::{Config, Server};
+use russh::{MethodKind, MethodSet};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+use tracing::{debug, info};
+
+use crate::{PtyRequest, ServerExtraEnum};
+pub use session::BastionSession;
+
+/// 登录名中用户与目标的分隔符
+pub const TARGET_SEPARATOR: char = '%';
+
+/// 解析登录名 `user%target`, 返回用户及目标
+pub fn parse_login(login: &str) -> Option<(&str, &str)> {
+    let (user, target) = login.split_once(TARGET_SEPARATOR)?;
+    if user.is_empty() || target.is_empty() {
+        return None;
+    }
+    Some((user, target))
+}
+
+/// 已认证的堡垒机用户
+#[derive(Debug, Clone)]
+pub struct BastionUser {
+    pub id: String,
+    pub username: String,
+}
+
+/// 目标会话, 录像及命令审计由实现方负责
+pub struct BastionShell {
+    /// 发往目标的输入
+    pub input: UnboundedSender<Bytes>,
+    /// 目标输出, 关闭后结束会话
+    pub output: UnboundedReceiver<Bytes>,
+    /// 窗口调整及断开
+    pub control: UnboundedSender<ServerExtraEnum>,
+    /// 目标shell的退出码, 未收到时不向客户端发送退出状态
+    pub exit_status: oneshot::Receiver<u32>,
+}
+
+/// 堡垒机的用户认证及目标连接
+#[async_trait]
+pub trait BastionHandler: Send + Sync {
+    /// 密码认证, 认证失败返回None
+    async fn auth_password(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> anyhow::Result<Option<BastionUser>>;
+
+    /// 公钥认证, 认证失败返回None
+    async fn auth_publickey(
+        &self,
+        username: &str,
+        key: &PublicKey,
+    ) -> anyhow::Result<Option<BastionUser>>;
+
+    /// 连接目标并打开交互式shell
+    async fn open_shell(
+        &self,
+        session_id: SessionId,
+        user: &BastionUser,
+        target: &str,
+        peer: Option<SocketAddr>,
+        pty: PtyRequest,
+    ) -> anyhow::Result<BastionShell>;
+}
+
+#[derive(Debug, Clone)]
+pub struct BastionOptions {
+    /// 主机密钥
+    pub keys: Vec<PrivateKey>,
+    /// 认证失败后的等待时间
+    pub auth_rejection_time: Duration,
+    /// 空闲超时
+    pub inactivity_timeout: Option<Duration>,
+}
+
+impl Default for BastionOptions {
+    fn default() -> Self {
+        Self {
+            keys: vec![],
+            auth_rejection_time: Duration::from_secs(1),
+            inactivity_timeout: Some(Duration::from_secs(3600)),
+        }
+    }
+}
+
+pub struct BastionServer {
+    handler: Arc<dyn BastionHandler>,
+}
+
+impl BastionServer {
+    pub fn new(handler: Arc<dyn BastionHandler>) -> Self {
+        Self { handler }
+    }
+
+    /// 在已绑定的端口上提供服务, 直至监听结束
+    pub async fn run(
+        mut self,
+        listener: TcpListener,
+        options: BastionOptions,
+    ) -> anyhow::Result<()> {
+        if options.keys.is_empty() {
+            anyhow::bail!("bastion host key is empty");
+        }
+        let config = Config {
+            keys: options.keys,
+            methods: MethodSet::from(&[MethodKind::PublicKey, MethodKind::Password][..]),
+            auth_rejection_time: options.auth_rejection_time,
+            // OpenSSH 首先以 none 探测可用的认证方式, 无需等待
+            auth_rejection_time_initial: Some(Duration::ZERO),
+            inactivity_timeout: options.inactivity_timeout,
+            ..Default::default()
+        };
+        info!(addr = ?listener.local_addr(), "bastion listening");
+        self.run_on_socket(Arc::new(config), &listener).await?;
+        Ok(())
+    }
+}
+
+impl Server for BastionServer {
+    type Handler = BastionSession;
+
+    fn new_client(&mut self, peer_addr: Option<SocketAddr>) -> BastionSession {
+        BastionSession::new(self.handler.clone(), peer_addr)
+    }
+
+    fn handle_session_error(&mut self, error: anyhow::Error) {
+        debug!("bastion session error: {:?}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_login() {
+        assert_eq!(parse_login("alice%web-01"), Some(("alice", "web-01")));
+        assert_eq!(
+            parse_login("alice%root%web-01"),
+            Some(("alice", "root%web-01"))
+        );
+        assert_eq!(parse_login("alice"), None);
+        assert_eq!(parse_login("%web-01"), None);
+        assert_eq!(parse_login("alice%"), None);
+    }
+}
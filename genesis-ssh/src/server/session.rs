@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use genesis_common::SessionId;
+use russh::keys::PublicKey;
+use russh::server::{Auth, Handle, Handler, Msg, Session};
+use russh::{Channel, ChannelId, CryptoVec, Pty};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use super::{parse_login, BastionHandler, BastionUser};
+use crate::{ChannelOperation, PtyRequest, ServerExtraEnum};
+
+struct ShellState {
+    id: SessionId,
+    input: UnboundedSender<Bytes>,
+    control: UnboundedSender<ServerExtraEnum>,
+}
+
+/// 单个客户端连接, 每个shell通道对应一个目标会话
+pub struct BastionSession {
+    id: Uuid,
+    handler: Arc<dyn BastionHandler>,
+    peer: Option<SocketAddr>,
+    user: Option<BastionUser>,
+    target: String,
+    ptys: HashMap<ChannelId, PtyRequest>,
+    shells: HashMap<ChannelId, ShellState>,
+}
+
+impl BastionSession {
+    pub(crate) fn new(handler: Arc<dyn BastionHandler>, peer: Option<SocketAddr>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            handler,
+            peer,
+            user: None,
+            target: String::new(),
+            ptys: HashMap::new(),
+            shells: HashMap::new(),
+        }
+    }
+
+    fn accept(&mut self, result: anyhow::Result<Option<BastionUser>>, target: &str) -> Auth {
+        match result {
+            Ok(Some(user)) => {
+                info!(conn_id=%self.id, peer=?self.peer, user=%user.username, target, "bastion login");
+                self.user = Some(user);
+                self.target = target.to_string();
+                Auth::Accept
+            }
+            Ok(None) => {
+                warn!(conn_id=%self.id, peer=?self.peer, target, "bastion login rejected");
+                Auth::reject()
+            }
+            Err(e) => {
+                error!(conn_id=%self.id, peer=?self.peer, "bastion auth error: {:?}", e);
+                Auth::reject()
+            }
+        }
+    }
+
+    fn close_shell(&mut self, channel: ChannelId) {
+        if let Some(shell) = self.shells.remove(&channel) {
+            debug!(conn_id=%self.id, session_id=%shell.id, "close bastion shell");
+            let _ = shell.control.send(ServerExtraEnum::Disconnect);
+        }
+    }
+}
+
+impl Drop for BastionSession {
+    fn drop(&mut self) {
+        for (_, shell) in self.shells.drain() {
+            let _ = shell.control.send(ServerExtraEnum::Disconnect);
+        }
+    }
+}
+
+fn default_pty() -> PtyRequest {
+    PtyRequest {
+        term: "xterm".to_string(),
+        col_width: 80,
+        row_height: 24,
+        pix_width: 0,
+        pix_height: 0,
+        modes: vec![],
+    }
+}
+
+/// 转发目标输出, 结束后发送目标的退出码并关闭通道
+async fn forward_output(
+    handle: Handle,
+    channel: ChannelId,
+    mut output: UnboundedReceiver<Bytes>,
+    exit_status: oneshot::Receiver<u32>,
+) {
+    while let Some(bytes) = output.recv().await {
+        if handle
+            .data(channel, CryptoVec::from_slice(&bytes))
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+    if let Ok(code) = exit_status.await {
+        let _ = handle.exit_status_request(channel, code).await;
+    }
+    let _ = handle.eof(channel).await;
+    let _ = handle.close(channel).await;
+}
+
+impl Handler for BastionSession {
+    type Error = anyhow::Error;
+
+    async fn auth_password(&mut self, user: &str, password: &str) -> Result<Auth, Self::Error> {
+        let Some((username, target)) = parse_login(user) else {
+            warn!(conn_id=%self.id, login=user, "login must be user%target");
+            return Ok(Auth::reject());
+        };
+        let result = self.handler.auth_password(username, password).await;
+        Ok(self.accept(result, target))
+    }
+
+    async fn auth_publickey_offered(
+        &mut self,
+        user: &str,
+        _public_key: &PublicKey,
+    ) -> Result<Auth, Self::Error> {
+        match parse_login(user) {
+            Some(_) => Ok(Auth::Accept),
+            None => Ok(Auth::reject()),
+        }
+    }
+
+    async fn auth_publickey(
+        &mut self,
+        user: &str,
+        public_key: &PublicKey,
+    ) -> Result<Auth, Self::Error> {
+        let Some((username, target)) = parse_login(user) else {
+            return Ok(Auth::reject());
+        };
+        let result = self.handler.auth_publickey(username, public_key).await;
+        Ok(self.accept(result, target))
+    }
+
+    async fn channel_open_session(
+        &mut self,
+        _channel: Channel<Msg>,
+        _session: &mut Session,
+    ) -> Result<bool, Self::Error> {
+        Ok(self.user.is_some())
+    }
+
+    async fn pty_request(
+        &mut self,
+        channel: ChannelId,
+        term: &str,
+        col_width: u32,
+        row_height: u32,
+        pix_width: u32,
+        pix_height: u32,
+        modes: &[(Pty, u32)],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.ptys.insert(
+            channel,
+            PtyRequest {
+                term: term.to_string(),
+                col_width,
+                row_height,
+                pix_width,
+                pix_height,
+                modes: modes.to_vec(),
+            },
+        );
+        session.channel_success(channel)?;
+        Ok(())
+    }
+
+    async fn window_change_request(
+        &mut self,
+        channel: ChannelId,
+        col_width: u32,
+        row_height: u32,
+        pix_width: u32,
+        pix_height: u32,
+        _session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        let pty = self.ptys.entry(channel).or_insert_with(default_pty);
+        pty.col_width = col_width;
+        pty.row_height = row_height;
+        pty.pix_width = pix_width;
+        pty.pix_height = pix_height;
+        if let Some(shell) = self.shells.get(&channel) {
+            let _ = shell.control.send(ServerExtraEnum::ChannelOperation(
+                ChannelOperation::ResizePty(pty.clone()),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn shell_request(
+        &mut self,
+        channel: ChannelId,
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        let Some(user) = self.user.clone() else {
+            session.channel_failure(channel)?;
+            return Ok(());
+        };
+        let pty = self.ptys.get(&channel).cloned().unwrap_or_else(default_pty);
+        let session_id = Uuid::new_v4();
+        match self
+            .handler
+            .open_shell(session_id, &user, &self.target, self.peer, pty)
+            .await
+        {
+            Ok(shell) => {
+                info!(conn_id=%self.id, session_id=%session_id, target=%self.target, "bastion shell opened");
+                session.channel_success(channel)?;
+                self.shells.insert(
+                    channel,
+                    ShellState {
+                        id: session_id,
+                        input: shell.input,
+                        control: shell.control,
+                    },
+                );
+                tokio::spawn(forward_output(
+                    session.handle(),
+                    channel,
+                    shell.output,
+                    shell.exit_status,
+                ));
+            }
+            Err(e) => {
+                error!(conn_id=%self.id, target=%self.target, "bastion open shell error: {:?}", e);
+                session.channel_success(channel)?;
+                let message = format!("genesis: connect to {} failed: {}\r\n", self.target, e);
+                session.extended_data(channel, 1, CryptoVec::from_slice(message.as_bytes()))?;
+                session.exit_status_request(channel, 1)?;
+                session.eof(channel)?;
+                session.close(channel)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn exec_request(
+        &mut self,
+        channel: ChannelId,
+        data: &[u8],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        warn!(conn_id=%self.id, command=%String::from_utf8_lossy(data), "bastion exec is not supported");
+        session.channel_failure(channel)?;
+        Ok(())
+    }
+
+    async fn data(
+        &mut self,
+        channel: ChannelId,
+        data: &[u8],
+        _session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        if let Some(shell) = self.shells.get(&channel) {
+            if shell.input.send(Bytes::copy_from_slice(data)).is_err() {
+                self.close_shell(channel);
+            }
+        }
+        Ok(())
+    }
+
+    async fn channel_close(
+        &mut self,
+        channel: ChannelId,
+        _session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.ptys.remove(&channel);
+        self.close_shell(channel);
+        Ok(())
+    }
+}
@@ -0,0 +1,264 @@
+//! telnet 客户端, 提供与ssh一致的字节流接口, 用于仅支持telnet的老旧网络设备
+
+mod protocol;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Context;
+use bytes::Bytes;
+use genesis_common::{EventHub, EventSender, NotifyEnum, TargetTelnetOptions};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info};
+use uuid::Uuid;
+
+use crate::{ChannelOperation, ServerExtraEnum};
+pub use protocol::TelnetNegotiator;
+
+/// 建立telnet连接
+/// ## 参数
+/// - uuid: 会话id
+/// - option: 连接参数
+/// - ctx: 连接断开时,调用其cancel方法
+/// ## 返回参数
+/// - EventHub<Bytes>为telnet返回数据, 已去除协商命令
+/// - UnboundedSender<Bytes> 为可发送到telnet服务端
+/// - UnboundedSender<ServerExtraEnum> 为调整窗口大小及断开连接等
+/// - watch::Receiver<NotifyEnum> 为连接状态, 与ssh一致, 连接断开后发送端释放
+pub async fn start_telnet_connect_with_state(
+    uuid: Uuid,
+    option: TargetTelnetOptions,
+    ctx: Option<CancellationToken>,
+) -> anyhow::Result<(
+    EventHub<Bytes>,
+    UnboundedSender<Bytes>,
+    UnboundedSender<ServerExtraEnum>,
+    watch::Receiver<NotifyEnum>,
+)> {
+    // step1. start connect
+    let addr = (option.host.as_str(), option.port);
+    let stream = if option.connect_timeout == 0 {
+        TcpStream::connect(addr).await
+    } else {
+        tokio::time::timeout(
+            Duration::from_secs(option.connect_timeout),
+            TcpStream::connect(addr),
+        )
+        .await
+        .with_context(|| format!("connect to {}:{} timeout", option.host, option.port))?
+    }
+    .with_context(|| format!("connect to {}:{} failed", option.host, option.port))?;
+    let _ = stream.set_nodelay(true);
+    info!(session_id=%uuid, host=%option.host, port=option.port, "telnet connected");
+    let (notify_sender, notify_receiver) = watch::channel(NotifyEnum::SUCCESS);
+
+    let negotiator = Arc::new(Mutex::new(TelnetNegotiator::new(
+        &option.pty_request.term,
+        option.pty_request.width,
+        option.pty_request.height,
+    )));
+    let shutdown = ctx.as_ref().map(|c| c.child_token()).unwrap_or_default();
+    let (read_half, write_half) = stream.into_split();
+    let (write_sc, write_rc) = unbounded_channel::<Bytes>();
+    // step2. create event hub
+    let (hub, sender) = EventHub::setup();
+    tokio::spawn(write_loop(uuid, write_half, write_rc, shutdown.clone()));
+    tokio::spawn({
+        let read = read_loop(
+            uuid,
+            read_half,
+            sender,
+            negotiator.clone(),
+            write_sc.clone(),
+            shutdown.clone(),
+            ctx,
+        );
+        async move {
+            if let Err(e) = read.await {
+                let _ = notify_sender.send(NotifyEnum::ERROR(e.to_string()));
+            }
+        }
+    });
+
+    // step3. user input
+    let (tx, mut rx) = unbounded_channel::<Bytes>();
+    tokio::spawn({
+        let negotiator = negotiator.clone();
+        let write_sc = write_sc.clone();
+        async move {
+            while let Some(data) = rx.recv().await {
+                let data = negotiator.lock().unwrap().encode(&data);
+                if write_sc.send(data).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    // step4. extra operation
+    let (ses, mut ser) = unbounded_channel();
+    tokio::spawn(async move {
+        while let Some(e) = ser.recv().await {
+            match e {
+                ServerExtraEnum::Disconnect => {
+                    shutdown.cancel();
+                    break;
+                }
+                ServerExtraEnum::ChannelOperation(ChannelOperation::ResizePty(pty)) => {
+                    let naws = negotiator
+                        .lock()
+                        .unwrap()
+                        .resize(pty.col_width, pty.row_height);
+                    if let Some(naws) = naws {
+                        let _ = write_sc.send(naws);
+                    }
+                }
+                ServerExtraEnum::ChannelOperation(ChannelOperation::Data(data)) => {
+                    let data = negotiator.lock().unwrap().encode(&data);
+                    let _ = write_sc.send(data);
+                }
+                ServerExtraEnum::ChannelOperation(
+                    ChannelOperation::Close | ChannelOperation::Eof,
+                ) => {
+                    shutdown.cancel();
+                    break;
+                }
+                ServerExtraEnum::ChannelOperation(co) => {
+                    debug!(session_id=%uuid, "telnet ignore channel operation: {:?}", co);
+                }
+            }
+        }
+    });
+    anyhow::Ok((hub, tx, ses, notify_receiver))
+}
+
+async fn read_loop(
+    uuid: Uuid,
+    mut reader: OwnedReadHalf,
+    sender: EventSender<Bytes>,
+    negotiator: Arc<Mutex<TelnetNegotiator>>,
+    write_sc: UnboundedSender<Bytes>,
+    shutdown: CancellationToken,
+    ctx: Option<CancellationToken>,
+) -> std::io::Result<()> {
+    let mut result = Ok(());
+    let mut buf = vec![0u8; 8192];
+    loop {
+        let n = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            n = reader.read(&mut buf) => match n {
+                Ok(0) => {
+                    debug!(session_id=%uuid, "telnet server closed");
+                    break;
+                }
+                Ok(n) => n,
+                Err(e) => {
+                    error!(session_id=%uuid, "telnet read error: {:?}", e);
+                    result = Err(e);
+                    break;
+                }
+            },
+        };
+        let decoded = negotiator.lock().unwrap().decode(&buf[..n]);
+        if !decoded.reply.is_empty() {
+            let _ = write_sc.send(decoded.reply);
+        }
+        if !decoded.data.is_empty() {
+            let _ = sender.send_all(decoded.data).await;
+        }
+    }
+    shutdown.cancel();
+    if let Some(ctx) = ctx {
+        ctx.cancel();
+    }
+    info!(session_id=%uuid, "telnet disconnected");
+    result
+}
+
+async fn write_loop(
+    uuid: Uuid,
+    mut writer: OwnedWriteHalf,
+    mut write_rc: UnboundedReceiver<Bytes>,
+    shutdown: CancellationToken,
+) {
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            data = write_rc.recv() => match data {
+                Some(data) => {
+                    if let Err(e) = writer.write_all(&data).await {
+                        error!(session_id=%uuid, "telnet write error: {:?}", e);
+                        break;
+                    }
+                }
+                None => break,
+            },
+        }
+    }
+    let _ = writer.shutdown().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::protocol::*;
+    use super::*;
+    use genesis_common::PtyRequest;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_telnet_connect() -> anyhow::Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await?;
+            stream
+                .write_all(&[IAC, WILL, OPT_ECHO, IAC, DO, OPT_NAWS, IAC, DO, OPT_TTYPE])
+                .await?;
+            stream.write_all(&[IAC, SB, OPT_TTYPE, 1, IAC, SE]).await?;
+            stream.write_all(b"Username: ").await?;
+            let mut received = vec![];
+            let mut buf = [0u8; 256];
+            while !received.ends_with(b"admin\r\0") {
+                let n = stream.read(&mut buf).await?;
+                anyhow::ensure!(n > 0, "client closed");
+                received.extend_from_slice(&buf[..n]);
+            }
+            anyhow::Ok(received)
+        });
+
+        let option = TargetTelnetOptions {
+            host: addr.ip().to_string(),
+            port: addr.port(),
+            pty_request: PtyRequest {
+                term: "vt100".to_string(),
+                width: 132,
+                height: 40,
+            },
+            connect_timeout: 5,
+        };
+        let (hub, sender, see, notify) =
+            start_telnet_connect_with_state(Uuid::new_v4(), option, None).await?;
+        assert!(matches!(*notify.borrow(), NotifyEnum::SUCCESS));
+        let mut receiver = hub.subscribe(|_| true).await.unbox();
+        let data = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await?
+            .unwrap();
+        assert_eq!(data.as_ref(), b"Username: ");
+        sender.send(Bytes::from_static(b"admin\r"))?;
+
+        let received = tokio::time::timeout(Duration::from_secs(5), server).await???;
+        let contains = |needle: &[u8]| received.windows(needle.len()).any(|w| w == needle);
+        assert!(contains(&[IAC, DO, OPT_ECHO]));
+        assert!(contains(&[
+            IAC, WILL, OPT_NAWS, IAC, SB, OPT_NAWS, 0, 132, 0, 40, IAC, SE
+        ]));
+        assert!(contains(b"\xff\xfa\x18\x00vt100\xff\xf0"));
+        let _ = see.send(ServerExtraEnum::Disconnect);
+        Ok(())
+    }
+}
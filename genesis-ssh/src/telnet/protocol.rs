@@ -0,0 +1,260 @@
+//! telnet 选项协商, 参考 RFC 854/855/857/858/1073/1091
+
+use std::collections::HashSet;
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+pub const IAC: u8 = 255;
+pub const DONT: u8 = 254;
+pub const DO: u8 = 253;
+pub const WONT: u8 = 252;
+pub const WILL: u8 = 251;
+pub const SB: u8 = 250;
+pub const SE: u8 = 240;
+
+pub const OPT_ECHO: u8 = 1;
+pub const OPT_SGA: u8 = 3;
+pub const OPT_TTYPE: u8 = 24;
+pub const OPT_NAWS: u8 = 31;
+
+const TTYPE_IS: u8 = 0;
+const TTYPE_SEND: u8 = 1;
+/// 子协商数据长度上限, 超出部分丢弃
+const MAX_SUB_NEGOTIATION: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Data,
+    Iac,
+    Command(u8),
+    Sub,
+    SubIac,
+}
+
+/// 解析结果, `data` 为终端数据, `reply` 为需回复服务端的协商数据
+#[derive(Debug, Default)]
+pub struct Decoded {
+    pub data: Bytes,
+    pub reply: Bytes,
+}
+
+/// 客户端协商状态
+///
+/// 本端支持 NAWS、TTYPE、SGA, 远端支持 ECHO、SGA, 其余选项一律拒绝;
+/// 仅在选项状态变化时应答, 避免协商循环
+#[derive(Debug)]
+pub struct TelnetNegotiator {
+    term: String,
+    width: u16,
+    height: u16,
+    state: State,
+    sub: Vec<u8>,
+    local: HashSet<u8>,
+    remote: HashSet<u8>,
+}
+
+impl TelnetNegotiator {
+    pub fn new(term: &str, width: u32, height: u32) -> Self {
+        Self {
+            term: term.to_string(),
+            width: clamp_size(width),
+            height: clamp_size(height),
+            state: State::Data,
+            sub: vec![],
+            local: HashSet::new(),
+            remote: HashSet::new(),
+        }
+    }
+
+    /// 本端已启用的选项
+    pub fn local_enabled(&self, option: u8) -> bool {
+        self.local.contains(&option)
+    }
+
+    /// 远端已启用的选项
+    pub fn remote_enabled(&self, option: u8) -> bool {
+        self.remote.contains(&option)
+    }
+
+    /// 解析服务端数据, 协商命令可跨多次读取
+    pub fn decode(&mut self, input: &[u8]) -> Decoded {
+        let mut data = BytesMut::with_capacity(input.len());
+        let mut reply = BytesMut::new();
+        for &byte in input {
+            self.state = match (self.state, byte) {
+                (State::Data, IAC) => State::Iac,
+                (State::Data, _) => {
+                    data.put_u8(byte);
+                    State::Data
+                }
+                (State::Iac, IAC) => {
+                    data.put_u8(IAC);
+                    State::Data
+                }
+                (State::Iac, WILL | WONT | DO | DONT) => State::Command(byte),
+                (State::Iac, SB) => {
+                    self.sub.clear();
+                    State::Sub
+                }
+                // NOP、GA 等单字节命令无需处理
+                (State::Iac, _) => State::Data,
+                (State::Command(command), option) => {
+                    self.negotiate(command, option, &mut reply);
+                    State::Data
+                }
+                (State::Sub, IAC) => State::SubIac,
+                (State::Sub, _) => {
+                    if self.sub.len() < MAX_SUB_NEGOTIATION {
+                        self.sub.push(byte);
+                    }
+                    State::Sub
+                }
+                (State::SubIac, SE) => {
+                    self.sub_negotiate(&mut reply);
+                    State::Data
+                }
+                (State::SubIac, _) => {
+                    if self.sub.len() < MAX_SUB_NEGOTIATION {
+                        self.sub.push(byte);
+                    }
+                    State::Sub
+                }
+            };
+        }
+        Decoded {
+            data: data.freeze(),
+            reply: reply.freeze(),
+        }
+    }
+
+    /// 转义用户输入, 单独的CR按 NVT 规范补NUL
+    pub fn encode(&self, input: &[u8]) -> Bytes {
+        let mut buf = BytesMut::with_capacity(input.len() + 1);
+        for (i, &byte) in input.iter().enumerate() {
+            match byte {
+                IAC => buf.put_slice(&[IAC, IAC]),
+                b'\r' if input.get(i + 1) != Some(&b'\n') => buf.put_slice(b"\r\0"),
+                _ => buf.put_u8(byte),
+            }
+        }
+        buf.freeze()
+    }
+
+    /// 调整窗口大小, NAWS 未启用时仅记录
+    pub fn resize(&mut self, width: u32, height: u32) -> Option<Bytes> {
+        self.width = clamp_size(width);
+        self.height = clamp_size(height);
+        self.local_enabled(OPT_NAWS).then(|| self.naws())
+    }
+
+    fn negotiate(&mut self, command: u8, option: u8, reply: &mut BytesMut) {
+        match command {
+            WILL => {
+                if matches!(option, OPT_ECHO | OPT_SGA) {
+                    if self.remote.insert(option) {
+                        reply.put_slice(&[IAC, DO, option]);
+                    }
+                } else {
+                    reply.put_slice(&[IAC, DONT, option]);
+                }
+            }
+            WONT if self.remote.remove(&option) => reply.put_slice(&[IAC, DONT, option]),
+            DO => {
+                if matches!(option, OPT_NAWS | OPT_TTYPE | OPT_SGA) {
+                    if self.local.insert(option) {
+                        reply.put_slice(&[IAC, WILL, option]);
+                        if option == OPT_NAWS {
+                            reply.put_slice(&self.naws());
+                        }
+                    }
+                } else {
+                    reply.put_slice(&[IAC, WONT, option]);
+                }
+            }
+            DONT if self.local.remove(&option) => reply.put_slice(&[IAC, WONT, option]),
+            _ => {}
+        }
+    }
+
+    fn sub_negotiate(&mut self, reply: &mut BytesMut) {
+        if self.sub.as_slice() == [OPT_TTYPE, TTYPE_SEND] && self.local_enabled(OPT_TTYPE) {
+            reply.put_slice(&[IAC, SB, OPT_TTYPE, TTYPE_IS]);
+            reply.put_slice(self.term.as_bytes());
+            reply.put_slice(&[IAC, SE]);
+        }
+        self.sub.clear();
+    }
+
+    fn naws(&self) -> Bytes {
+        let mut buf = BytesMut::from(&[IAC, SB, OPT_NAWS][..]);
+        for byte in self
+            .width
+            .to_be_bytes()
+            .into_iter()
+            .chain(self.height.to_be_bytes())
+        {
+            // 窗口大小中的255同样需要转义
+            if byte == IAC {
+                buf.put_u8(IAC);
+            }
+            buf.put_u8(byte);
+        }
+        buf.put_slice(&[IAC, SE]);
+        buf.freeze()
+    }
+}
+
+fn clamp_size(size: u32) -> u16 {
+    size.min(u16::MAX as u32) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate() {
+        let mut negotiator = TelnetNegotiator::new("xterm", 80, 24);
+        let decoded = negotiator.decode(&[
+            IAC, WILL, OPT_ECHO, IAC, WILL, OPT_SGA, IAC, DO, OPT_NAWS, IAC, DO, 39, b'o', b'k',
+        ]);
+        assert_eq!(decoded.data.as_ref(), b"ok");
+        assert_eq!(
+            decoded.reply.as_ref(),
+            &[
+                IAC, DO, OPT_ECHO, IAC, DO, OPT_SGA, IAC, WILL, OPT_NAWS, IAC, SB, OPT_NAWS, 0, 80,
+                0, 24, IAC, SE, IAC, WONT, 39
+            ]
+        );
+        // 已启用的选项不再应答
+        assert!(negotiator.decode(&[IAC, WILL, OPT_ECHO]).reply.is_empty());
+        assert_eq!(
+            negotiator.resize(255, 30).unwrap().as_ref(),
+            &[IAC, SB, OPT_NAWS, 0, IAC, IAC, 0, 30, IAC, SE]
+        );
+    }
+
+    #[test]
+    fn test_decode_split() {
+        let mut negotiator = TelnetNegotiator::new("vt100", 80, 24);
+        assert!(negotiator.decode(&[IAC, DO]).reply.is_empty());
+        assert_eq!(
+            negotiator.decode(&[OPT_TTYPE, IAC, SB]).reply.as_ref(),
+            &[IAC, WILL, OPT_TTYPE]
+        );
+        let decoded = negotiator.decode(&[OPT_TTYPE, TTYPE_SEND, IAC, SE, IAC, IAC]);
+        assert_eq!(decoded.data.as_ref(), &[IAC]);
+        assert_eq!(
+            decoded.reply.as_ref(),
+            &[IAC, SB, OPT_TTYPE, TTYPE_IS, b'v', b't', b'1', b'0', b'0', IAC, SE]
+        );
+    }
+
+    #[test]
+    fn test_encode() {
+        let negotiator = TelnetNegotiator::new("xterm", 80, 24);
+        assert_eq!(negotiator.encode(b"ls\r").as_ref(), b"ls\r\0");
+        assert_eq!(negotiator.encode(b"ls\r\n").as_ref(), b"ls\r\n");
+        assert_eq!(negotiator.encode(&[IAC]).as_ref(), &[IAC, IAC]);
+    }
+}
@@ -0,0 +1,887 @@
+//! 进程内ssh测试服务, 以可编排的伪shell代替真实主机
+
+mod sftp;
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use genesis_common::{
+    HostKeyPolicy, SSHJumpHost, SSHTargetAuth, SshTargetPasswordAuth, TargetSSHOptions,
+};
+use russh::keys::ssh_key::private::Ed25519Keypair;
+use russh::keys::{PrivateKey, PublicKey};
+use russh::server::{Auth, Config, Handler, Msg, Server, Session};
+use russh::{Channel, ChannelId, CryptoVec, MethodKind, MethodSet};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::{AbortHandle, JoinHandle};
+
+use self::sftp::{MemoryFs, SftpSession};
+
+pub const TEST_USERNAME: &str = "root";
+pub const TEST_PASSWORD: &str = "genesis";
+
+/// 测试服务接受公钥认证的客户端密钥
+pub fn test_client_key() -> PrivateKey {
+    PrivateKey::from(Ed25519Keypair::from_seed(&[8; 32]))
+}
+
+/// 预置命令的输出及退出码
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FakeCommand {
+    pub output: String,
+    /// exec通道中以扩展数据输出, shell中随输出回显
+    pub stderr: String,
+    pub exit_code: u32,
+}
+
+/// 伪shell, 回显输入, 执行预置命令后重新输出PS1
+///
+/// 内置 `echo`、`echo $?` 及 `exit`, 预置命令优先; 未知命令按 bash 返回 127
+///
+/// 会话内另支持 `export NAME='value'`、`echo $NAME`、`printf` 及以 `;` 分隔的多条命令,
+/// 设置sudo密码后支持 `sudo cmd`
+#[derive(Debug, Clone)]
+pub struct FakeShell {
+    ps1: String,
+    banner: String,
+    commands: HashMap<String, FakeCommand>,
+    accept_env: Vec<String>,
+    sudo_password: Option<String>,
+}
+
+pub const SUDO_PROMPT: &str = "[sudo] password for root: ";
+
+impl Default for FakeShell {
+    fn default() -> Self {
+        Self {
+            // 与 bash 一致, 以 OSC 设置标题, 便于 PipeManger 识别提示符
+            ps1: "\x1b]0;root@genesis:~\x07[root@genesis ~]# ".to_string(),
+            banner: String::new(),
+            commands: HashMap::new(),
+            accept_env: vec![],
+            sudo_password: None,
+        }
+    }
+}
+
+impl FakeShell {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_ps1(mut self, ps1: impl Into<String>) -> Self {
+        self.ps1 = ps1.into();
+        self
+    }
+
+    /// 登录后、首个PS1之前输出
+    pub fn with_banner(mut self, banner: impl Into<String>) -> Self {
+        self.banner = banner.into();
+        self
+    }
+
+    /// 接受的env请求变量名, 其余env请求回复失败
+    pub fn with_accept_env(mut self, names: &[&str]) -> Self {
+        self.accept_env = names.iter().map(|name| name.to_string()).collect();
+        self
+    }
+
+    /// `sudo` 提示输入密码, 输入不回显, 连续三次错误后失败
+    pub fn with_sudo_password(mut self, password: impl Into<String>) -> Self {
+        self.sudo_password = Some(password.into());
+        self
+    }
+
+    pub fn command(
+        mut self,
+        command: impl Into<String>,
+        output: impl Into<String>,
+        exit_code: u32,
+    ) -> Self {
+        self.commands.insert(
+            command.into(),
+            FakeCommand {
+                output: output.into(),
+                exit_code,
+                ..Default::default()
+            },
+        );
+        self
+    }
+
+    /// 同时输出标准错误的预置命令
+    pub fn command_with_stderr(
+        mut self,
+        command: impl Into<String>,
+        output: impl Into<String>,
+        stderr: impl Into<String>,
+        exit_code: u32,
+    ) -> Self {
+        self.commands.insert(
+            command.into(),
+            FakeCommand {
+                output: output.into(),
+                stderr: stderr.into(),
+                exit_code,
+            },
+        );
+        self
+    }
+
+    /// 执行单行命令, `exit` 返回None
+    pub fn execute(&self, line: &str, last_exit: u32) -> Option<FakeCommand> {
+        let line = line.trim();
+        if let Some(command) = self.commands.get(line) {
+            return Some(command.clone());
+        }
+        let command = match line.split_once(' ').unwrap_or((line, "")) {
+            ("exit", _) => return None,
+            ("", _) => FakeCommand {
+                exit_code: last_exit,
+                ..Default::default()
+            },
+            ("echo", "$?") => FakeCommand {
+                output: last_exit.to_string(),
+                ..Default::default()
+            },
+            ("echo", args) => FakeCommand {
+                output: args.to_string(),
+                ..Default::default()
+            },
+            (name, _) => FakeCommand {
+                stderr: format!("bash: {name}: command not found"),
+                exit_code: 127,
+                ..Default::default()
+            },
+        };
+        Some(command)
+    }
+}
+
+/// 换行转为终端的 `\r\n`
+fn terminal_output(output: &str) -> String {
+    let mut data = output.replace("\r\n", "\n").replace('\n', "\r\n");
+    if !data.is_empty() && !data.ends_with("\r\n") {
+        data.push_str("\r\n");
+    }
+    data
+}
+
+#[derive(Default)]
+struct ShellState {
+    line: Vec<u8>,
+    last_exit: u32,
+    last_cr: bool,
+    env: HashMap<String, String>,
+    /// 等待密码的sudo命令及已失败次数
+    sudo: Option<(String, u32)>,
+}
+
+enum SudoStep {
+    /// 非sudo命令或密码正确, 执行命令
+    Run(String),
+    /// 提示输入密码
+    Prompt(&'static str),
+    /// 密码错误次数过多
+    Fail(&'static str),
+}
+
+impl ShellState {
+    fn sudo(&mut self, password: Option<&str>, line: String) -> SudoStep {
+        if let Some((command, attempts)) = self.sudo.take() {
+            if password == Some(line.as_str()) {
+                return SudoStep::Run(command);
+            }
+            if attempts >= 2 {
+                return SudoStep::Fail("sudo: 3 incorrect password attempts");
+            }
+            self.sudo = Some((command, attempts + 1));
+            return SudoStep::Prompt("Sorry, try again.\r\n[sudo] password for root: ");
+        }
+        let Some(mut rest) = line
+            .trim()
+            .strip_prefix("sudo ")
+            .filter(|_| password.is_some())
+        else {
+            return SudoStep::Run(line);
+        };
+        // 跳过 `-i`、`-u user` 等选项
+        while rest.starts_with('-') {
+            let skip = if rest.starts_with("-u ") { 2 } else { 1 };
+            rest = rest.splitn(skip + 1, ' ').nth(skip).unwrap_or_default();
+        }
+        self.sudo = Some((rest.to_string(), 0));
+        SudoStep::Prompt(SUDO_PROMPT)
+    }
+
+    /// `printf 'format' args`, 支持八进制转义及 `%s`, 输出不追加换行
+    fn printf(&mut self, line: &str) -> Option<String> {
+        let rest = line.trim().strip_prefix("printf ")?;
+        let rest = rest.trim_start().strip_prefix('\'')?;
+        let (format, args) = rest.split_once('\'')?;
+        let mut args = args.split_whitespace().map(|arg| match arg {
+            "$?" => self.last_exit.to_string(),
+            arg => arg.to_string(),
+        });
+        let mut output = String::new();
+        let mut chars = format.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('\\', Some('0'..='7')) => {
+                    let mut code = 0;
+                    for _ in 0..3 {
+                        let Some(digit) = chars.peek().and_then(|c| c.to_digit(8)) else {
+                            break;
+                        };
+                        code = code * 8 + digit;
+                        chars.next();
+                    }
+                    output.extend(char::from_u32(code));
+                }
+                ('\\', Some('n')) => {
+                    chars.next();
+                    output.push_str("\r\n");
+                }
+                ('%', Some('s')) => {
+                    chars.next();
+                    output.push_str(&args.next().unwrap_or_default());
+                }
+                (c, _) => output.push(c),
+            }
+        }
+        self.last_exit = 0;
+        Some(output)
+    }
+
+    /// 会话相关的内置命令
+    fn builtin(&mut self, line: &str) -> Option<FakeCommand> {
+        let line = line.trim();
+        if let Some(assignments) = line.strip_prefix("export ") {
+            self.env.extend(parse_assignments(assignments));
+            return Some(FakeCommand::default());
+        }
+        let name = line.strip_prefix("echo $").filter(|name| *name != "?")?;
+        Some(FakeCommand {
+            output: self.env.get(name).cloned().unwrap_or_default(),
+            ..Default::default()
+        })
+    }
+}
+
+/// 以单引号外的 `;` 拆分命令
+fn split_commands(line: &str) -> Vec<&str> {
+    let mut commands = vec![];
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in line.char_indices() {
+        match c {
+            '\'' => quoted = !quoted,
+            ';' if !quoted => {
+                commands.push(&line[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    commands.push(&line[start..]);
+    commands
+}
+
+/// 解析 `A='x' B='it'\''s'` 形式的赋值
+fn parse_assignments(input: &str) -> Vec<(String, String)> {
+    let mut vars = vec![];
+    let mut chars = input.trim().chars().peekable();
+    while chars.peek().is_some() {
+        let name = chars.by_ref().take_while(|c| *c != '=').collect::<String>();
+        let mut value = String::new();
+        let mut quoted = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '\'' => quoted = !quoted,
+                '\\' if !quoted => value.extend(chars.next()),
+                ' ' if !quoted => break,
+                c => value.push(c),
+            }
+        }
+        vars.push((name.trim().to_string(), value));
+    }
+    vars
+}
+
+struct FakeShellSession {
+    shell: Arc<FakeShell>,
+    channels: HashMap<ChannelId, ShellState>,
+    /// 尚未请求shell或exec的通道, 可用于子系统
+    pending: HashMap<ChannelId, Channel<Msg>>,
+    /// 远程转发的监听任务
+    forwards: HashMap<(String, u32), AbortHandle>,
+    fs: Arc<Mutex<MemoryFs>>,
+    connections: Arc<AtomicUsize>,
+}
+
+impl Drop for FakeShellSession {
+    fn drop(&mut self) {
+        for forward in self.forwards.values() {
+            forward.abort();
+        }
+        self.connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn send(session: &mut Session, channel: ChannelId, data: &str) -> Result<(), russh::Error> {
+    session.data(channel, CryptoVec::from_slice(data.as_bytes()))
+}
+
+impl Handler for FakeShellSession {
+    type Error = russh::Error;
+
+    async fn auth_password(&mut self, user: &str, password: &str) -> Result<Auth, Self::Error> {
+        if user == TEST_USERNAME && password == TEST_PASSWORD {
+            Ok(Auth::Accept)
+        } else {
+            Ok(Auth::reject())
+        }
+    }
+
+    async fn auth_publickey(&mut self, user: &str, key: &PublicKey) -> Result<Auth, Self::Error> {
+        if user == TEST_USERNAME && key.key_data() == test_client_key().public_key().key_data() {
+            Ok(Auth::Accept)
+        } else {
+            Ok(Auth::reject())
+        }
+    }
+
+    async fn channel_open_session(
+        &mut self,
+        channel: Channel<Msg>,
+        _session: &mut Session,
+    ) -> Result<bool, Self::Error> {
+        self.channels.insert(channel.id(), ShellState::default());
+        self.pending.insert(channel.id(), channel);
+        Ok(true)
+    }
+
+    async fn subsystem_request(
+        &mut self,
+        channel: ChannelId,
+        name: &str,
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        match self.pending.remove(&channel) {
+            Some(pending) if name == "sftp" => {
+                self.channels.remove(&channel);
+                session.channel_success(channel)?;
+                russh_sftp::server::run(pending.into_stream(), SftpSession::new(self.fs.clone()))
+                    .await;
+                Ok(())
+            }
+            _ => session.channel_failure(channel),
+        }
+    }
+
+    /// 转发至本地端口, 供跳板及端口转发测试
+    async fn channel_open_direct_tcpip(
+        &mut self,
+        channel: Channel<Msg>,
+        host_to_connect: &str,
+        port_to_connect: u32,
+        _originator_address: &str,
+        _originator_port: u32,
+        _session: &mut Session,
+    ) -> Result<bool, Self::Error> {
+        let Ok(mut stream) = TcpStream::connect((host_to_connect, port_to_connect as u16)).await
+        else {
+            return Ok(false);
+        };
+        tokio::spawn(async move {
+            let mut channel = channel.into_stream();
+            let _ = tokio::io::copy_bidirectional(&mut channel, &mut stream).await;
+        });
+        Ok(true)
+    }
+
+    /// 在本地监听, 连接经 forwarded-tcpip 通道转回客户端
+    async fn tcpip_forward(
+        &mut self,
+        address: &str,
+        port: &mut u32,
+        session: &mut Session,
+    ) -> Result<bool, Self::Error> {
+        let Ok(listener) = TcpListener::bind((address, *port as u16)).await else {
+            return Ok(false);
+        };
+        if let Ok(addr) = listener.local_addr() {
+            *port = addr.port() as u32;
+        }
+        let handle = session.handle();
+        let (address, port) = (address.to_string(), *port);
+        let task = tokio::spawn({
+            let address = address.clone();
+            async move {
+                while let Ok((mut stream, peer)) = listener.accept().await {
+                    let channel = handle
+                        .channel_open_forwarded_tcpip(
+                            address.clone(),
+                            port,
+                            peer.ip().to_string(),
+                            peer.port() as u32,
+                        )
+                        .await;
+                    let Ok(channel) = channel else {
+                        continue;
+                    };
+                    tokio::spawn(async move {
+                        let mut channel = channel.into_stream();
+                        let _ = tokio::io::copy_bidirectional(&mut channel, &mut stream).await;
+                    });
+                }
+            }
+        });
+        self.forwards.insert((address, port), task.abort_handle());
+        Ok(true)
+    }
+
+    async fn cancel_tcpip_forward(
+        &mut self,
+        address: &str,
+        port: u32,
+        _session: &mut Session,
+    ) -> Result<bool, Self::Error> {
+        match self.forwards.remove(&(address.to_string(), port)) {
+            Some(forward) => {
+                forward.abort();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn pty_request(
+        &mut self,
+        channel: ChannelId,
+        _term: &str,
+        _col_width: u32,
+        _row_height: u32,
+        _pix_width: u32,
+        _pix_height: u32,
+        _modes: &[(russh::Pty, u32)],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        session.channel_success(channel)
+    }
+
+    async fn env_request(
+        &mut self,
+        channel: ChannelId,
+        variable_name: &str,
+        variable_value: &str,
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        if !self
+            .shell
+            .accept_env
+            .iter()
+            .any(|name| name == variable_name)
+        {
+            return session.channel_failure(channel);
+        }
+        if let Some(state) = self.channels.get_mut(&channel) {
+            state
+                .env
+                .insert(variable_name.to_string(), variable_value.to_string());
+        }
+        session.channel_success(channel)
+    }
+
+    async fn shell_request(
+        &mut self,
+        channel: ChannelId,
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.pending.remove(&channel);
+        session.channel_success(channel)?;
+        if !self.shell.banner.is_empty() {
+            send(session, channel, &terminal_output(&self.shell.banner))?;
+        }
+        send(session, channel, &self.shell.ps1)
+    }
+
+    async fn exec_request(
+        &mut self,
+        channel: ChannelId,
+        data: &[u8],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.pending.remove(&channel);
+        session.channel_success(channel)?;
+        let line = String::from_utf8_lossy(data);
+        let command = self.shell.execute(&line, 0).unwrap_or_default();
+        send(session, channel, &command.output)?;
+        if !command.stderr.is_empty() {
+            session.extended_data(channel, 1, CryptoVec::from_slice(command.stderr.as_bytes()))?;
+        }
+        session.exit_status_request(channel, command.exit_code)?;
+        session.eof(channel)?;
+        session.close(channel)
+    }
+
+    async fn data(
+        &mut self,
+        channel: ChannelId,
+        data: &[u8],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        let Some(state) = self.channels.get_mut(&channel) else {
+            return Ok(());
+        };
+        for &byte in data {
+            let last_cr = std::mem::replace(&mut state.last_cr, byte == b'\r');
+            match byte {
+                b'\n' if last_cr => {}
+                b'\r' | b'\n' => {
+                    send(session, channel, "\r\n")?;
+                    let line = String::from_utf8_lossy(&state.line).to_string();
+                    state.line.clear();
+                    let line = match state.sudo(self.shell.sudo_password.as_deref(), line) {
+                        SudoStep::Run(line) => line,
+                        SudoStep::Prompt(prompt) => {
+                            send(session, channel, prompt)?;
+                            continue;
+                        }
+                        SudoStep::Fail(output) => {
+                            state.last_exit = 1;
+                            send(session, channel, &terminal_output(output))?;
+                            send(session, channel, &self.shell.ps1)?;
+                            continue;
+                        }
+                    };
+                    // 预置命令按整行匹配
+                    let commands = match self.shell.commands.contains_key(line.trim()) {
+                        true => vec![line.as_str()],
+                        false => split_commands(&line),
+                    };
+                    let mut exited = false;
+                    for line in commands {
+                        if let Some(output) = state.printf(line) {
+                            send(session, channel, &output)?;
+                            continue;
+                        }
+                        let command = state
+                            .builtin(line)
+                            .or_else(|| self.shell.execute(line, state.last_exit));
+                        match command {
+                            Some(command) => {
+                                state.last_exit = command.exit_code;
+                                for output in [&command.output, &command.stderr] {
+                                    if !output.is_empty() {
+                                        send(session, channel, &terminal_output(output))?;
+                                    }
+                                }
+                            }
+                            None => {
+                                exited = true;
+                                break;
+                            }
+                        }
+                    }
+                    if exited {
+                        session.exit_status_request(channel, state.last_exit)?;
+                        session.eof(channel)?;
+                        session.close(channel)?;
+                        self.channels.remove(&channel);
+                        return Ok(());
+                    }
+                    send(session, channel, &self.shell.ps1)?;
+                }
+                // ctrl-c
+                0x03 => {
+                    state.line.clear();
+                    state.sudo = None;
+                    state.last_exit = 130;
+                    send(session, channel, "^C\r\n")?;
+                    send(session, channel, &self.shell.ps1)?;
+                }
+                // backspace
+                0x7f | 0x08 => {
+                    if state.line.pop().is_some() {
+                        send(session, channel, "\x08 \x08")?;
+                    }
+                }
+                _ => {
+                    state.line.push(byte);
+                    // 输入密码时不回显
+                    if state.sudo.is_none() {
+                        session.data(channel, CryptoVec::from_slice(&[byte]))?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn channel_close(
+        &mut self,
+        channel: ChannelId,
+        _session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.channels.remove(&channel);
+        self.pending.remove(&channel);
+        Ok(())
+    }
+}
+
+struct FakeShellServer {
+    shell: Arc<FakeShell>,
+    fs: Arc<Mutex<MemoryFs>>,
+    connections: Arc<AtomicUsize>,
+}
+
+impl Server for FakeShellServer {
+    type Handler = FakeShellSession;
+
+    fn new_client(&mut self, _peer_addr: Option<SocketAddr>) -> FakeShellSession {
+        self.connections.fetch_add(1, Ordering::SeqCst);
+        FakeShellSession {
+            shell: self.shell.clone(),
+            channels: HashMap::new(),
+            pending: HashMap::new(),
+            forwards: HashMap::new(),
+            fs: self.fs.clone(),
+            connections: self.connections.clone(),
+        }
+    }
+}
+
+/// 监听本地随机端口的ssh服务, drop 时停止
+///
+/// 支持 `sftp` 子系统, 文件保存在内存中并由该服务的所有连接共享
+pub struct TestServer {
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+    connections: Arc<AtomicUsize>,
+    relays: Arc<Mutex<Vec<AbortHandle>>>,
+}
+
+impl TestServer {
+    pub async fn start(shell: FakeShell) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let config = Arc::new(Config {
+            keys: vec![PrivateKey::from(Ed25519Keypair::from_seed(&[7; 32]))],
+            methods: MethodSet::from(&[MethodKind::Password, MethodKind::PublicKey][..]),
+            auth_rejection_time: std::time::Duration::ZERO,
+            ..Default::default()
+        });
+        let connections = Arc::new(AtomicUsize::new(0));
+        let relays = Arc::new(Mutex::new(vec![]));
+        let mut server = FakeShellServer {
+            shell: Arc::new(shell),
+            fs: Default::default(),
+            connections: connections.clone(),
+        };
+        // 经内存管道中转, 以便模拟网络中断
+        let task = tokio::spawn({
+            let relays = relays.clone();
+            async move {
+                while let Ok((mut socket, peer_addr)) = listener.accept().await {
+                    let (mut local, remote) = tokio::io::duplex(64 * 1024);
+                    let relay = tokio::spawn(async move {
+                        let _ = tokio::io::copy_bidirectional(&mut socket, &mut local).await;
+                    });
+                    if let Ok(mut relays) = relays.lock() {
+                        relays.push(relay.abort_handle());
+                    }
+                    let handler = server.new_client(Some(peer_addr));
+                    let config = config.clone();
+                    tokio::spawn(async move {
+                        if let Ok(session) =
+                            russh::server::run_stream(config, remote, handler).await
+                        {
+                            let _ = session.await;
+                        }
+                    });
+                }
+            }
+        });
+        Ok(Self {
+            addr,
+            task,
+            connections,
+            relays,
+        })
+    }
+
+    /// 断开所有连接的底层传输, 模拟网络中断
+    pub fn drop_connections(&self) {
+        if let Ok(mut relays) = self.relays.lock() {
+            for relay in relays.drain(..) {
+                relay.abort();
+            }
+        }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// 当前保持的连接数
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+
+    /// 以本服务为跳板的参数
+    pub fn jump_host(&self) -> SSHJumpHost {
+        SSHJumpHost {
+            host: self.addr.ip().to_string(),
+            port: self.addr.port(),
+            username: TEST_USERNAME.to_string(),
+            auth: SSHTargetAuth::Password(SshTargetPasswordAuth {
+                password: TEST_PASSWORD.to_string(),
+            }),
+            host_key_policy: HostKeyPolicy::AcceptNew,
+        }
+    }
+
+    /// 连接参数, 自动信任测试服务的主机密钥
+    pub fn ssh_options(&self) -> TargetSSHOptions {
+        TargetSSHOptions {
+            host: self.addr.ip().to_string(),
+            port: self.addr.port(),
+            username: TEST_USERNAME.to_string(),
+            auth: SSHTargetAuth::Password(SshTargetPasswordAuth {
+                password: TEST_PASSWORD.to_string(),
+            }),
+            host_key_policy: HostKeyPolicy::AcceptNew,
+            ..Default::default()
+        }
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.task.abort();
+        self.drop_connections();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{exec_command, start_ssh_connect, ExecExit};
+    use bytes::Bytes;
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_fake_shell_execute() {
+        let shell = FakeShell::new().command("false", "", 1);
+        assert_eq!(shell.execute("false", 0).unwrap().exit_code, 1);
+        assert_eq!(shell.execute("echo $?", 1).unwrap().output, "1");
+        assert_eq!(shell.execute("echo hello", 0).unwrap().output, "hello");
+        assert_eq!(shell.execute("vim", 0).unwrap().exit_code, 127);
+        assert!(shell.execute("exit", 0).is_none());
+        assert_eq!(
+            parse_assignments("TZ='UTC' PS1='it'\\''s $ '"),
+            vec![
+                ("TZ".to_string(), "UTC".to_string()),
+                ("PS1".to_string(), "it's $ ".to_string())
+            ]
+        );
+        assert_eq!(
+            split_commands("export A='x;y'; false; echo $?"),
+            vec!["export A='x;y'", " false", " echo $?"]
+        );
+        let mut state = ShellState {
+            last_exit: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            state.printf(r"printf '\033]133;D;%s\007' $?").unwrap(),
+            "\x1b]133;D;2\x07"
+        );
+        assert_eq!(state.last_exit, 0);
+    }
+
+    #[tokio::test]
+    async fn test_exec_with_test_server() {
+        let server = TestServer::start(FakeShell::new().command("uname", "Linux", 0))
+            .await
+            .unwrap();
+        let output = exec_command(Uuid::new_v4(), server.ssh_options(), "uname")
+            .await
+            .unwrap();
+        assert_eq!(output.stdout, Bytes::from_static(b"Linux"));
+        assert!(matches!(output.exit, ExecExit::Status(0)));
+
+        let output = exec_command(Uuid::new_v4(), server.ssh_options(), "missing")
+            .await
+            .unwrap();
+        assert!(matches!(output.exit, ExecExit::Status(127)));
+    }
+
+    #[tokio::test]
+    async fn test_shell_with_test_server() {
+        let server = TestServer::start(FakeShell::new().command("pwd", "/root", 0))
+            .await
+            .unwrap();
+        let (hub, sender, _) = start_ssh_connect(Uuid::new_v4(), server.ssh_options())
+            .await
+            .unwrap();
+        let mut receiver = hub.subscribe(|_| true).await.unbox();
+        sender.send(Bytes::from_static(b"pwd\r")).unwrap();
+        let mut output = String::new();
+        let result = tokio::time::timeout(Duration::from_secs(10), async {
+            while let Some(bytes) = receiver.recv().await {
+                output.push_str(&String::from_utf8_lossy(&bytes));
+                if output.contains("/root\r\n") {
+                    break;
+                }
+            }
+        })
+        .await;
+        assert!(result.is_ok(), "unexpected output: {output:?}");
+        assert!(output.contains("[root@genesis ~]# pwd\r\n/root\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_shell_env_with_test_server() {
+        let server = TestServer::start(FakeShell::new().with_accept_env(&["LANG"]))
+            .await
+            .unwrap();
+        let mut option = server.ssh_options();
+        // LC_TIME 被服务端拒绝, HISTFILE 不在 accept_env 中, 均以 export 补发
+        option.env.vars = [
+            ("LANG", "C.UTF-8"),
+            ("LC_TIME", "C"),
+            ("HISTFILE", "/dev/null"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let (hub, sender, _) = start_ssh_connect(Uuid::new_v4(), option).await.unwrap();
+        let mut receiver = hub.subscribe(|_| true).await.unbox();
+        let mut output = String::new();
+        let result = tokio::time::timeout(Duration::from_secs(10), async {
+            for (command, expected) in [
+                ("", " export HISTFILE='/dev/null' LC_TIME='C'\r\n"),
+                ("echo $LANG\r", "\r\nC.UTF-8\r\n"),
+                ("echo $LC_TIME\r", "\r\nC\r\n"),
+            ] {
+                output.clear();
+                if !command.is_empty() {
+                    sender.send(Bytes::from(command)).unwrap();
+                }
+                while let Some(bytes) = receiver.recv().await {
+                    output.push_str(&String::from_utf8_lossy(&bytes));
+                    if output.contains(expected) {
+                        break;
+                    }
+                }
+            }
+        })
+        .await;
+        assert!(result.is_ok(), "unexpected output: {output:?}");
+    }
+}
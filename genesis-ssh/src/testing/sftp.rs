@@ -0,0 +1,442 @@
+//! 内存文件系统上的sftp子系统
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use russh_sftp::protocol::{
+    Attrs, Data, File, FileAttributes, FileMode, Handle, Name, OpenFlags, Status, StatusCode,
+    Version,
+};
+
+const HOME: &str = "/root";
+
+#[derive(Debug, Clone)]
+struct Node {
+    data: Vec<u8>,
+    dir: bool,
+    permissions: u32,
+}
+
+impl Node {
+    fn dir() -> Self {
+        Self {
+            data: vec![],
+            dir: true,
+            permissions: 0o755,
+        }
+    }
+
+    fn file() -> Self {
+        Self {
+            data: vec![],
+            dir: false,
+            permissions: 0o644,
+        }
+    }
+
+    fn attrs(&self) -> FileAttributes {
+        let mode = match self.dir {
+            true => FileMode::DIR,
+            false => FileMode::REG,
+        };
+        FileAttributes {
+            size: Some(self.data.len() as u64),
+            uid: Some(0),
+            gid: Some(0),
+            permissions: Some(mode.bits() | self.permissions),
+            atime: Some(0),
+            mtime: Some(0),
+            ..FileAttributes::empty()
+        }
+    }
+}
+
+/// 以绝对路径为键的文件树, 同一测试服务的连接共享
+#[derive(Debug)]
+pub(crate) struct MemoryFs {
+    nodes: BTreeMap<String, Node>,
+}
+
+impl Default for MemoryFs {
+    fn default() -> Self {
+        let nodes = ["/", HOME, "/tmp"]
+            .into_iter()
+            .map(|path| (path.to_string(), Node::dir()))
+            .collect();
+        Self { nodes }
+    }
+}
+
+impl MemoryFs {
+    fn node(&mut self, path: &str) -> Result<&mut Node, StatusCode> {
+        self.nodes.get_mut(path).ok_or(StatusCode::NoSuchFile)
+    }
+
+    fn check_parent(&self, path: &str) -> Result<(), StatusCode> {
+        match self.nodes.get(parent(path)) {
+            Some(node) if node.dir => Ok(()),
+            _ => Err(StatusCode::NoSuchFile),
+        }
+    }
+
+    fn children(&self, dir: &str) -> Vec<(String, &Node)> {
+        let prefix = match dir {
+            "/" => "/".to_string(),
+            dir => format!("{dir}/"),
+        };
+        self.nodes
+            .range(prefix.clone()..)
+            .take_while(|(path, _)| path.starts_with(&prefix))
+            .filter(|(path, _)| {
+                !path[prefix.len()..].is_empty() && !path[prefix.len()..].contains('/')
+            })
+            .map(|(path, node)| (path[prefix.len()..].to_string(), node))
+            .collect()
+    }
+}
+
+/// 将相对路径及 `.`、`..` 解析为绝对路径
+fn normalize(path: &str) -> String {
+    let path = match path.starts_with('/') {
+        true => path.to_string(),
+        false => format!("{HOME}/{path}"),
+    };
+    let mut parts: Vec<&str> = vec![];
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    format!("/{}", parts.join("/"))
+}
+
+fn parent(path: &str) -> &str {
+    match path.rsplit_once('/') {
+        Some(("", _)) | None => "/",
+        Some((parent, _)) => parent,
+    }
+}
+
+fn ok(id: u32) -> Status {
+    Status {
+        id,
+        status_code: StatusCode::Ok,
+        error_message: "Ok".to_string(),
+        language_tag: "en-US".to_string(),
+    }
+}
+
+enum OpenHandle {
+    File(String),
+    Dir { path: String, listed: bool },
+}
+
+pub(crate) struct SftpSession {
+    fs: Arc<Mutex<MemoryFs>>,
+    handles: HashMap<String, OpenHandle>,
+    next_handle: u64,
+}
+
+impl SftpSession {
+    pub(crate) fn new(fs: Arc<Mutex<MemoryFs>>) -> Self {
+        Self {
+            fs,
+            handles: HashMap::new(),
+            next_handle: 0,
+        }
+    }
+
+    fn with_fs<T>(
+        &self,
+        f: impl FnOnce(&mut MemoryFs) -> Result<T, StatusCode>,
+    ) -> Result<T, StatusCode> {
+        let mut fs = self.fs.lock().map_err(|_| StatusCode::Failure)?;
+        f(&mut fs)
+    }
+
+    fn open_handle(&mut self, id: u32, handle: OpenHandle) -> Handle {
+        self.next_handle += 1;
+        let name = self.next_handle.to_string();
+        self.handles.insert(name.clone(), handle);
+        Handle { id, handle: name }
+    }
+
+    fn file_path(&self, handle: &str) -> Result<String, StatusCode> {
+        match self.handles.get(handle) {
+            Some(OpenHandle::File(path)) => Ok(path.clone()),
+            _ => Err(StatusCode::Failure),
+        }
+    }
+
+    fn stat_path(&self, id: u32, path: &str) -> Result<Attrs, StatusCode> {
+        let path = normalize(path);
+        self.with_fs(|fs| {
+            let attrs = fs.node(&path)?.attrs();
+            Ok(Attrs { id, attrs })
+        })
+    }
+
+    fn set_attrs(&self, id: u32, path: &str, attrs: FileAttributes) -> Result<Status, StatusCode> {
+        self.with_fs(|fs| {
+            let node = fs.node(path)?;
+            if let Some(permissions) = attrs.permissions {
+                node.permissions = permissions & 0o7777;
+            }
+            if let Some(size) = attrs.size.filter(|_| !node.dir) {
+                node.data.resize(size as usize, 0);
+            }
+            Ok(ok(id))
+        })
+    }
+}
+
+impl russh_sftp::server::Handler for SftpSession {
+    type Error = StatusCode;
+
+    fn unimplemented(&self) -> Self::Error {
+        StatusCode::OpUnsupported
+    }
+
+    async fn init(
+        &mut self,
+        _version: u32,
+        _extensions: HashMap<String, String>,
+    ) -> Result<Version, Self::Error> {
+        Ok(Version::new())
+    }
+
+    async fn realpath(&mut self, id: u32, path: String) -> Result<Name, Self::Error> {
+        Ok(Name {
+            id,
+            files: vec![File::dummy(normalize(&path))],
+        })
+    }
+
+    async fn open(
+        &mut self,
+        id: u32,
+        filename: String,
+        pflags: OpenFlags,
+        _attrs: FileAttributes,
+    ) -> Result<Handle, Self::Error> {
+        let path = normalize(&filename);
+        self.with_fs(|fs| {
+            match fs.nodes.get_mut(&path) {
+                Some(node) if node.dir => return Err(StatusCode::Failure),
+                Some(_) if pflags.contains(OpenFlags::EXCLUDE) => return Err(StatusCode::Failure),
+                Some(node) => {
+                    if pflags.contains(OpenFlags::TRUNCATE) {
+                        node.data.clear();
+                    }
+                }
+                None if pflags.contains(OpenFlags::CREATE) => {
+                    fs.check_parent(&path)?;
+                    fs.nodes.insert(path.clone(), Node::file());
+                }
+                None => return Err(StatusCode::NoSuchFile),
+            }
+            Ok(())
+        })?;
+        Ok(self.open_handle(id, OpenHandle::File(path)))
+    }
+
+    async fn close(&mut self, id: u32, handle: String) -> Result<Status, Self::Error> {
+        self.handles.remove(&handle).ok_or(StatusCode::Failure)?;
+        Ok(ok(id))
+    }
+
+    async fn read(
+        &mut self,
+        id: u32,
+        handle: String,
+        offset: u64,
+        len: u32,
+    ) -> Result<Data, Self::Error> {
+        let path = self.file_path(&handle)?;
+        self.with_fs(|fs| {
+            let data = &fs.node(&path)?.data;
+            let start = offset as usize;
+            if start >= data.len() {
+                return Err(StatusCode::Eof);
+            }
+            let end = data.len().min(start + len as usize);
+            Ok(Data {
+                id,
+                data: data[start..end].to_vec(),
+            })
+        })
+    }
+
+    async fn write(
+        &mut self,
+        id: u32,
+        handle: String,
+        offset: u64,
+        data: Vec<u8>,
+    ) -> Result<Status, Self::Error> {
+        let path = self.file_path(&handle)?;
+        self.with_fs(|fs| {
+            let file = &mut fs.node(&path)?.data;
+            let start = offset as usize;
+            if file.len() < start + data.len() {
+                file.resize(start + data.len(), 0);
+            }
+            file[start..start + data.len()].copy_from_slice(&data);
+            Ok(ok(id))
+        })
+    }
+
+    async fn stat(&mut self, id: u32, path: String) -> Result<Attrs, Self::Error> {
+        self.stat_path(id, &path)
+    }
+
+    async fn lstat(&mut self, id: u32, path: String) -> Result<Attrs, Self::Error> {
+        self.stat_path(id, &path)
+    }
+
+    async fn fstat(&mut self, id: u32, handle: String) -> Result<Attrs, Self::Error> {
+        let path = self.file_path(&handle)?;
+        self.stat_path(id, &path)
+    }
+
+    async fn setstat(
+        &mut self,
+        id: u32,
+        path: String,
+        attrs: FileAttributes,
+    ) -> Result<Status, Self::Error> {
+        self.set_attrs(id, &normalize(&path), attrs)
+    }
+
+    async fn fsetstat(
+        &mut self,
+        id: u32,
+        handle: String,
+        attrs: FileAttributes,
+    ) -> Result<Status, Self::Error> {
+        let path = self.file_path(&handle)?;
+        self.set_attrs(id, &path, attrs)
+    }
+
+    async fn opendir(&mut self, id: u32, path: String) -> Result<Handle, Self::Error> {
+        let path = normalize(&path);
+        self.with_fs(|fs| match fs.node(&path)?.dir {
+            true => Ok(()),
+            false => Err(StatusCode::Failure),
+        })?;
+        Ok(self.open_handle(
+            id,
+            OpenHandle::Dir {
+                path,
+                listed: false,
+            },
+        ))
+    }
+
+    async fn readdir(&mut self, id: u32, handle: String) -> Result<Name, Self::Error> {
+        let Some(OpenHandle::Dir { path, listed }) = self.handles.get_mut(&handle) else {
+            return Err(StatusCode::Failure);
+        };
+        // 一次返回全部条目, 再次读取时结束
+        if std::mem::replace(listed, true) {
+            return Err(StatusCode::Eof);
+        }
+        let path = path.clone();
+        self.with_fs(|fs| {
+            let files = fs
+                .children(&path)
+                .into_iter()
+                .map(|(name, node)| File::new(name, node.attrs()))
+                .collect();
+            Ok(Name { id, files })
+        })
+    }
+
+    async fn remove(&mut self, id: u32, filename: String) -> Result<Status, Self::Error> {
+        let path = normalize(&filename);
+        self.with_fs(|fs| {
+            if fs.node(&path)?.dir {
+                return Err(StatusCode::Failure);
+            }
+            fs.nodes.remove(&path);
+            Ok(ok(id))
+        })
+    }
+
+    async fn mkdir(
+        &mut self,
+        id: u32,
+        path: String,
+        _attrs: FileAttributes,
+    ) -> Result<Status, Self::Error> {
+        let path = normalize(&path);
+        self.with_fs(|fs| {
+            if fs.nodes.contains_key(&path) {
+                return Err(StatusCode::Failure);
+            }
+            fs.check_parent(&path)?;
+            fs.nodes.insert(path, Node::dir());
+            Ok(ok(id))
+        })
+    }
+
+    async fn rmdir(&mut self, id: u32, path: String) -> Result<Status, Self::Error> {
+        let path = normalize(&path);
+        self.with_fs(|fs| {
+            if !fs.node(&path)?.dir || !fs.children(&path).is_empty() {
+                return Err(StatusCode::Failure);
+            }
+            fs.nodes.remove(&path);
+            Ok(ok(id))
+        })
+    }
+
+    async fn rename(
+        &mut self,
+        id: u32,
+        oldpath: String,
+        newpath: String,
+    ) -> Result<Status, Self::Error> {
+        let (from, to) = (normalize(&oldpath), normalize(&newpath));
+        self.with_fs(|fs| {
+            fs.node(&from)?;
+            if fs.nodes.contains_key(&to) {
+                return Err(StatusCode::Failure);
+            }
+            fs.check_parent(&to)?;
+            // 目录连同其下条目一并移动
+            let prefix = format!("{from}/");
+            let moved = fs
+                .nodes
+                .keys()
+                .filter(|path| **path == from || path.starts_with(&prefix))
+                .cloned()
+                .collect::<Vec<_>>();
+            for path in moved {
+                if let Some(node) = fs.nodes.remove(&path) {
+                    fs.nodes
+                        .insert(format!("{to}{}", &path[from.len()..]), node);
+                }
+            }
+            Ok(ok(id))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("a.txt"), "/root/a.txt");
+        assert_eq!(normalize("/tmp/./a/../b/"), "/tmp/b");
+        assert_eq!(normalize("/.."), "/");
+        assert_eq!(parent("/tmp/b"), "/tmp");
+        assert_eq!(parent("/tmp"), "/");
+    }
+}
@@ -1,4 +1,4 @@
-use crate::common::{AssetAddressType, AssetType};
+use crate::common::{AssetAddressType, AssetType, ProtocolOptions};
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
@@ -13,6 +13,7 @@ pub struct AssetSaveCmd {
     pub org_id: Option<String>,
     pub location: Option<String>,
     pub alias_name: Option<String>,
+    #[validate(nested)]
     pub protocol_list: Option<Vec<ProtocolSaveItem>>,
 }
 #[derive(Debug, Clone, Serialize, Deserialize, Validate)]
@@ -21,4 +22,6 @@ pub struct ProtocolSaveItem {
     pub protocol: String,
     #[validate(range(min = 1, max = 65535, message = "port must in [1~65535]"))]
     pub port: i32,
+    #[validate(nested)]
+    pub options: Option<ProtocolOptions>,
 }
@@ -8,6 +8,8 @@ pub struct CredentialSaveCmd {
     pub id: Option<String>,
     pub principal: String,
     pub credential: String,
+    #[serde(default)]
+    pub passphrase: String,
     pub asset_id: String,
     pub protocol_id: String,
     pub auth_type: AuthType,
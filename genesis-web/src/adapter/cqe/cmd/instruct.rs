@@ -1,8 +1,10 @@
 //! instruct
 
 use crate::adapter::ExecuteReplaceItem;
+use crate::service::batch::BatchPolicy;
 use genesis_process::InData;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use validator::Validate;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Validate)]
@@ -22,3 +24,27 @@ pub struct InstructExecuteCmd {
     pub node: String,
     pub replaces: Vec<ExecuteReplaceItem>,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+#[serde(rename_all = "camelCase")] // 使用驼峰命名格式
+pub struct InstructBatchExecuteCmd {
+    pub id: String,
+    pub name: String,
+    /// 目标节点id
+    #[serde(default)]
+    pub nodes: Vec<String>,
+    /// 资产组(组织)id, 组内资产的ssh账号均作为目标
+    pub asset_group: Option<String>,
+    pub replaces: Vec<ExecuteReplaceItem>,
+    #[serde(default)]
+    pub policy: BatchPolicy,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+#[serde(rename_all = "camelCase")] // 使用驼峰命名格式
+pub struct InstructDryRunCmd {
+    pub data: InData,
+    /// 各节点的模拟输出, 以节点id为键
+    #[serde(default)]
+    pub outputs: HashMap<String, String>,
+}
@@ -4,5 +4,6 @@ pub mod credential;
 pub mod guacamole;
 pub mod instruct;
 pub mod node;
+pub mod port_forward;
 pub mod ssh;
 pub mod user;
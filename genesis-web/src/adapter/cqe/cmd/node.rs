@@ -1,3 +1,4 @@
+use crate::common::ProtocolOptions;
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
@@ -10,6 +11,8 @@ pub struct NodeSaveCmd {
     pub port: i32,
     pub account: String,
     pub password: String,
+    #[validate(nested)]
+    pub options: Option<ProtocolOptions>,
     #[serde(default)]
     pub remark: String,
 }
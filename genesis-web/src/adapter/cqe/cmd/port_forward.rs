@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct PortForwardSaveCmd {
+    /// 建立ssh连接所用凭证
+    #[validate(length(min = 1, message = "credential is empty"))]
+    pub credential_id: String,
+    /// 本地监听地址, 默认 127.0.0.1
+    pub bind_host: Option<String>,
+    /// 是否允许监听全部地址, 如 0.0.0.0
+    #[serde(default)]
+    pub allow_wildcard_bind: bool,
+    /// 本地监听端口, 0为随机分配
+    #[serde(default)]
+    pub bind_port: u16,
+    #[validate(length(min = 1, message = "remote host is empty"))]
+    pub remote_host: String,
+    #[validate(range(min = 1, message = "remote port is error"))]
+    pub remote_port: u16,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct RemotePortForwardSaveCmd {
+    /// 建立ssh连接所用凭证
+    #[validate(length(min = 1, message = "credential is empty"))]
+    pub credential_id: String,
+    /// 服务端监听地址, 默认 127.0.0.1
+    pub bind_host: Option<String>,
+    /// 是否允许监听全部地址, 如 0.0.0.0
+    #[serde(default)]
+    pub allow_wildcard_bind: bool,
+    /// 服务端监听端口
+    #[validate(range(min = 1, message = "bind port is error"))]
+    pub bind_port: u16,
+    /// 转发目标地址, 默认 127.0.0.1
+    pub local_host: Option<String>,
+    #[validate(range(min = 1, message = "local port is error"))]
+    pub local_port: u16,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct DynamicPortForwardSaveCmd {
+    /// 建立ssh连接所用凭证
+    #[validate(length(min = 1, message = "credential is empty"))]
+    pub credential_id: String,
+    /// 本地监听地址, 默认 127.0.0.1
+    pub bind_host: Option<String>,
+    /// 是否允许监听全部地址, 如 0.0.0.0
+    #[serde(default)]
+    pub allow_wildcard_bind: bool,
+    /// 本地监听端口, 0为随机分配
+    #[serde(default)]
+    pub bind_port: u16,
+    /// socks5认证用户名, 为空时不认证
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// 目标地址规则, 按顺序匹配
+    #[serde(default)]
+    #[validate(nested)]
+    pub rules: Vec<SocksRuleCmd>,
+    /// 规则均未匹配时是否放行, 默认拒绝
+    pub default_allow: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct SocksRuleCmd {
+    pub allow: bool,
+    /// 支持精确匹配、`*` 、`*.example.com` 及 `10.0.0.0/8`, 存在ip规则时仅允许ip目标
+    #[validate(length(min = 1, message = "rule host is empty"))]
+    pub host: String,
+    pub port: Option<u16>,
+}
@@ -23,4 +23,14 @@ pub struct UserRegisterCmd {
     pub remark: String,
     #[serde(default)]
     pub name: String,
+    /// ssh公钥, authorized_keys 格式
+    #[serde(default, rename = "publicKeys")]
+    pub public_keys: String,
+}
+
+/// 更新当前用户的ssh公钥, 用于堡垒机登录
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct UserPublicKeysCmd {
+    pub public_keys: String,
 }
@@ -6,4 +6,6 @@ use serde::{Deserialize, Serialize};
 pub struct ExecuteListQuery {
     pub page_query: PageQuery,
     pub name: Option<String>,
+    /// 查询批量执行的子任务
+    pub parent_id: Option<String>,
 }
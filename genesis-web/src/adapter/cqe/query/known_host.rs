@@ -0,0 +1,20 @@
+use crate::common::PageQuery;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KnownHostListQuery {
+    pub page_query: PageQuery,
+    pub host: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SshAlgorithmListQuery {
+    pub page_query: PageQuery,
+    pub host: Option<String>,
+    pub session_id: Option<String>,
+    /// 仅查询使用弱算法的主机
+    #[serde(default)]
+    pub weak_only: bool,
+}
@@ -2,4 +2,6 @@ pub mod asset;
 pub mod credential;
 pub mod execute;
 pub mod instruct;
+pub mod known_host;
 pub mod node;
+pub mod port_forward;
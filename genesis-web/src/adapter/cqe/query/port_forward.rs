@@ -0,0 +1,11 @@
+use crate::common::PageQuery;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PortForwardListQuery {
+    pub page_query: PageQuery,
+    pub remote_host: Option<String>,
+    /// 0-本地转发, 1-远程转发, 2-动态转发
+    pub kind: Option<i8>,
+}
@@ -5,6 +5,7 @@ use serde::{Deserialize, Serialize};
 #[serde(rename_all = "camelCase")]
 pub struct ExecuteVO {
     pub id: String,
+    pub parent_id: String,
     pub state: i32,
     pub name: String,
     pub node_id: String,
@@ -19,6 +20,7 @@ pub struct ExecuteVO {
 #[serde(rename_all = "camelCase")]
 pub struct ExecuteListItemVO {
     pub id: String,
+    pub parent_id: String,
     pub state: i32,
     pub name: String,
     pub node_id: String,
@@ -0,0 +1,30 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KnownHostListItemVO {
+    pub id: String,
+    pub host: String,
+    pub port: i32,
+    pub key_type: String,
+    pub key_base64: String,
+    pub fingerprint: String,
+    pub created_at: chrono::DateTime<Local>,
+}
+
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SshAlgorithmListItemVO {
+    pub id: String,
+    pub session_id: String,
+    pub host: String,
+    pub port: i32,
+    pub kex: String,
+    pub host_key: String,
+    pub cipher: String,
+    pub mac: String,
+    pub compression: String,
+    pub weak: String,
+    pub updated_at: chrono::DateTime<Local>,
+}
@@ -4,7 +4,9 @@ pub mod asset;
 pub mod credential;
 pub mod execute;
 pub mod instruct;
+pub mod known_host;
 pub mod node;
+pub mod port_forward;
 pub mod user;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -1,3 +1,4 @@
+use crate::common::ProtocolOptions;
 use chrono::Local;
 use serde::{Deserialize, Serialize};
 
@@ -9,6 +10,7 @@ pub struct NodeVO {
     pub host: String,
     pub account: String,
     pub port: i32,
+    pub options: ProtocolOptions,
     pub remark: String,
 }
 
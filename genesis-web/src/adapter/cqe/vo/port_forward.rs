@@ -0,0 +1,40 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+/// 运行中的端口转发
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PortForwardVO {
+    pub id: String,
+    /// 0-本地转发, 1-远程转发, 2-动态转发
+    pub kind: i8,
+    pub session_id: String,
+    pub local_addr: String,
+    pub remote_host: String,
+    pub remote_port: u16,
+    pub connections: u64,
+    pub active: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub created_at: chrono::DateTime<Local>,
+}
+
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PortForwardListItemVO {
+    pub id: String,
+    pub credential_id: String,
+    pub address: String,
+    pub principal: String,
+    pub kind: i8,
+    pub local_addr: String,
+    pub remote_host: String,
+    pub remote_port: i32,
+    pub connections: i64,
+    pub bytes_sent: i64,
+    pub bytes_received: i64,
+    pub state: i8,
+    pub created_by: String,
+    pub created_at: chrono::DateTime<Local>,
+    pub updated_at: chrono::DateTime<Local>,
+}
@@ -31,9 +31,10 @@ pub async fn save_asset(
                     model.asset_id = id.clone();
                     model.port = d.port;
                     model.protocol = d.protocol.clone();
-                    model
+                    model.options = serde_json::to_string(&d.options.clone().unwrap_or_default())?;
+                    Ok(model)
                 })
-                .collect(),
+                .collect::<Result<Vec<_>, serde_json::Error>>()?,
         )
         .await?;
     }
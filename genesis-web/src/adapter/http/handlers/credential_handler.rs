@@ -7,6 +7,7 @@ use crate::error::{AppError, AppJson};
 use crate::repo::model::credential;
 use crate::repo::sea;
 use crate::repo::sea::{CredentialRepo, SeaRepo};
+use crate::service::ssh_ca;
 use axum::extract::{Path, State};
 use axum::Json;
 
@@ -42,6 +43,11 @@ pub async fn delete_credential_by_id(
         .map(|_| Ok(ResponseSuccess::default()))?
 }
 
+/// 内置CA公钥, 目标主机需将其加入 `TrustedUserCAKeys`
+pub async fn get_ssh_ca_public_key() -> Result<Response<String>, AppError> {
+    Ok(Response::success(ssh_ca::ca_public_key()?))
+}
+
 pub async fn list_asset_credential(
     State(state): State<AppState>,
     Json(query): Json<CredentialListQuery>,
@@ -21,6 +21,7 @@ pub async fn get_execute_by_id(
         .map(|d| {
             Ok(Json(ExecuteVO {
                 id,
+                parent_id: d.parent_id,
                 replaces: d.replaces,
                 name: d.name,
                 state: d.state,
@@ -44,6 +45,11 @@ pub async fn list_execute(
             ))
         }
     }
+    if let Some(parent_id) = query.parent_id {
+        search_option.push(ConditionExpression::Condition(
+            Condition::all().add(execute::Column::ParentId.eq(parent_id)),
+        ))
+    }
     ExecuteRepo::find_execute_by(&state.conn, query.page_query.init(), Some(search_option))
         .await
         .map(|list| {
@@ -53,6 +59,7 @@ pub async fn list_execute(
                     .into_iter()
                     .map(|d| ExecuteListItemVO {
                         id: d.id,
+                        parent_id: d.parent_id,
                         name: d.name,
                         state: d.state,
                         remark: d.remark,
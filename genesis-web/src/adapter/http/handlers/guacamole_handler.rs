@@ -3,6 +3,7 @@ use crate::config::AppState;
 use crate::error::AppError;
 use crate::repo::sea::CredentialRepo;
 use crate::service::guacamole::process_double_axum;
+use crate::service::ssh::start_jump_tunnel;
 use axum::extract::{Query, State};
 use axum::{extract::ws::WebSocketUpgrade, response::Response};
 use genesis_process::guacamole::constants::*;
@@ -22,9 +23,18 @@ pub async fn handler_guacamole(
     let credential =
         CredentialRepo::get_credential_by_id(&state.conn, &params.permission_id).await?;
 
+    let uuid = uuid::Uuid::new_v4();
+    // 配置了跳板机时经本地隧道连接目标
+    let jump_tunnel = start_jump_tunnel(&state.conn, uuid, &credential)
+        .await
+        .map_err(AppError::from_ssh)?;
+    let (host, port) = match &jump_tunnel {
+        Some(t) => (t.local_addr().ip().to_string(), t.local_addr().port()),
+        None => (credential.address.clone(), credential.port as u16),
+    };
     let mut config = process::Configuration::new(&credential.protocol)
-        .with(GUA_HOSTNAME, &credential.address)
-        .with(GUA_HOST_PORT, &credential.port.to_string())
+        .with(GUA_HOSTNAME, &host)
+        .with(GUA_HOST_PORT, &port.to_string())
         .with(GUA_USERNAME, &credential.principal)
         .with(GUA_PASSWORD, &credential.credential)
         .with(GUA_WIDTH, &params.w.to_string())
@@ -33,7 +43,6 @@ pub async fn handler_guacamole(
     for (key, value) in default_rdp_properties() {
         config = config.with(key, value);
     }
-    let uuid = uuid::Uuid::new_v4();
     // TODO
     let tunnel = process::Tunnel::connect("127.0.0.1:14822", config)
         .await
@@ -53,6 +62,7 @@ pub async fn handler_guacamole(
                 }),
             )
             .await;
+            drop(jump_tunnel);
         });
     Ok(res)
 }
@@ -2,30 +2,40 @@ use axum::{
     extract::{Path, State},
     Json,
 };
-use genesis_common::{SshTargetPasswordAuth, TargetSSHOptions, TaskStatusEnum};
+use genesis_common::{TargetSSHOptions, TaskStatusEnum};
 use sea_orm::sea_query::ConditionExpression;
-use sea_orm::{ColumnTrait, Condition};
+use sea_orm::{ColumnTrait, Condition, DbConn};
+use std::sync::Arc;
+use tokio::sync::watch;
 use tracing::error;
 use uuid::Uuid;
 
-use crate::adapter::cmd::instruct::{InstructExecuteCmd, InstructSaveCmd};
+use crate::adapter::cmd::instruct::{
+    InstructBatchExecuteCmd, InstructDryRunCmd, InstructExecuteCmd, InstructSaveCmd,
+};
 use crate::adapter::query::instruct::InstructListQuery;
 use crate::adapter::vo::instruct::InstructVO;
 use crate::adapter::{ExecuteReplaceItem, ResList, Response, ResponseSuccess};
 use crate::config::{EXECUTE_MAP_MANAGER, SHARED_APP_CONFIG};
 use crate::repo::model;
-use crate::repo::model::instruct;
-use crate::repo::sea::{ExecuteRepo, InstructRepo, NodeRepo, SeaRepo};
+use crate::repo::model::{credential, instruct};
+use crate::repo::sea::{AssetRepo, CredentialRepo, ExecuteRepo, InstructRepo, NodeRepo, SeaRepo};
+use crate::service::batch::run_batches;
+use crate::service::ssh::{build_node_ssh_options, build_target_ssh_options};
 use crate::{
     config::AppState,
     error::{AppError, AppJson},
 };
-use genesis_process::{Graph, InData, ProcessManger};
+use genesis_process::{DryRun, Graph, InData, ProcessManger};
 
 pub async fn save_instruct(
     State(state): State<AppState>,
     AppJson(data): AppJson<InstructSaveCmd>,
 ) -> Result<Response<String>, AppError> {
+    let diagnostics = data.data.validate_graph();
+    if diagnostics.iter().any(|d| d.is_error()) {
+        return Err(AppError::InvalidGraph(diagnostics));
+    }
     let str = serde_json::to_string(&data.data)?;
     let mut model = instruct::Model::new();
     model.data = str;
@@ -40,6 +50,13 @@ pub async fn save_instruct(
         .await
         .map(|id| Ok(Response::success(id)))?
 }
+/// 以模拟输出执行指令图, 不连接资产
+pub async fn dry_run_instruct(
+    AppJson(data): AppJson<InstructDryRunCmd>,
+) -> Result<Response<DryRun>, AppError> {
+    Ok(Response::success(data.data.dry_run(&data.outputs)))
+}
+
 pub async fn get_instruct_by_id(
     State(state): State<AppState>,
     Path(id): Path<String>,
@@ -102,76 +119,152 @@ pub async fn replace_execute_param(
     anyhow::Ok(old_str)
 }
 
-pub async fn execute_instruct(
-    State(state): State<AppState>,
-    AppJson(data): AppJson<InstructExecuteCmd>,
-) -> Result<ResponseSuccess, AppError> {
-    // step1. fetch instruct data
-    let ins = InstructRepo::get_instruct_by_id(&state.conn, &data.id).await?;
-    let replaces = serde_json::to_string(&data.replaces)?;
-    // replace param
-    let new_str = replace_execute_param(ins.data, data.replaces).await?;
-    let in_data: InData = serde_json::from_str(&new_str)?;
-    // step2. build graph
-    let mut graph = Graph::new();
-    graph.build_from_edges(in_data).await;
-    let execute = graph.start_node().await?;
-    // step3. set ssh options
-    let node = NodeRepo::get_node_by_id(&state.conn, &data.node).await?;
-    let option = TargetSSHOptions {
-        host: node.host,
-        port: node.port as u16,
-        username: node.account,
-        allow_insecure_algos: Some(true),
-        auth: genesis_common::SSHTargetAuth::Password(SshTargetPasswordAuth {
-            password: node.password,
-        }),
-        // TODO pty param
-        pty_request: Default::default(),
-    };
-    // step4. insert execute data
-    let execute_uniq_id = Uuid::new_v4().to_string();
-    let mut model = model::execute::Model::new();
-    model.id = execute_uniq_id.clone();
-    model.name = data.name.clone();
-    model.state = genesis_common::TaskStatusEnum::Init as i32;
-    model.instruct_id = data.id;
-    model.instruct_name = ins.name;
-    model.node_id = data.node;
-    model.node_name = node.name;
-    model.replaces = replaces;
-    let uuid = ExecuteRepo::insert_execute_one(&state.conn, model).await?;
-    // step4. execute
-    let mut pm = ProcessManger::new(execute_uniq_id.clone(), execute)?.with_recorder_param(
-        &SHARED_APP_CONFIG.read().await.server.recording_path,
-        &option.pty_request.term,
-        option.pty_request.height,
-        option.pty_request.width,
-    )?;
-    let abort_sc = pm.get_abort_sc();
-    tokio::spawn(async move {
-        // = TaskStatusEnum::Success;
+/// 待执行的指令
+struct ExecuteInstruct {
+    id: String,
+    name: String,
+    instruct_name: String,
+    replaces: String,
+    in_data: InData,
+}
+
+impl ExecuteInstruct {
+    /// 加载并替换参数, 替换后的指令图需重新校验
+    async fn load(
+        db: &DbConn,
+        id: String,
+        name: String,
+        replaces: Vec<ExecuteReplaceItem>,
+    ) -> Result<Self, AppError> {
+        let ins = InstructRepo::get_instruct_by_id(db, &id).await?;
+        let replaces_str = serde_json::to_string(&replaces)?;
+        // replace param
+        let new_str = replace_execute_param(ins.data, replaces).await?;
+        let in_data: InData = serde_json::from_str(&new_str)?;
+        let diagnostics = in_data.validate_graph();
+        if diagnostics.iter().any(|d| d.is_error()) {
+            return Err(AppError::InvalidGraph(diagnostics));
+        }
+        Ok(Self {
+            id,
+            name,
+            instruct_name: ins.name,
+            replaces: replaces_str,
+            in_data,
+        })
+    }
+}
+
+/// 执行目标
+enum ExecuteTarget {
+    Node(String),
+    /// 资产账号
+    Account(Box<credential::Model>),
+}
+
+/// 已创建执行记录的任务
+struct PreparedExecute {
+    /// 执行记录id
+    record_id: String,
+    uniq_id: String,
+    pm: ProcessManger,
+    option: TargetSSHOptions,
+}
+
+impl PreparedExecute {
+    async fn new(
+        db: &DbConn,
+        ins: &ExecuteInstruct,
+        target: ExecuteTarget,
+        parent_id: &str,
+    ) -> anyhow::Result<Self> {
+        let env = ins.in_data.env.clone();
+        // step1. build graph
+        let mut graph = Graph::new();
+        graph.build_from_edges(ins.in_data.clone()).await;
+        let execute = graph.start_node().await.map_err(anyhow::Error::msg)?;
+        // step2. set ssh options
+        let (node_id, node_name, mut option) = match target {
+            ExecuteTarget::Node(id) => {
+                let node = NodeRepo::get_node_by_id(db, &id).await?;
+                let option = build_node_ssh_options(db, &node).await?;
+                (node.id, node.name, option)
+            }
+            ExecuteTarget::Account(credential) => {
+                let name = format!("{}@{}", credential.principal, credential.address);
+                let id = credential.id.clone();
+                let option = build_target_ssh_options(db, *credential, Default::default()).await?;
+                (id, name, option)
+            }
+        };
+        option.env.merge(&env);
+        // step3. insert execute data
+        let uniq_id = Uuid::new_v4().to_string();
+        let mut model = model::execute::Model::new();
+        model.id = uniq_id.clone();
+        model.name = ins.name.clone();
+        model.state = TaskStatusEnum::Init as i32;
+        model.instruct_id = ins.id.clone();
+        model.instruct_name = ins.instruct_name.clone();
+        model.node_id = node_id;
+        model.node_name = node_name;
+        model.replaces = ins.replaces.clone();
+        model.parent_id = parent_id.to_string();
+        let record_id = ExecuteRepo::insert_execute_one(db, model).await?;
+        let mut pm = ProcessManger::new(uniq_id.clone(), execute)?.with_recorder_param(
+            &SHARED_APP_CONFIG.read().await.server.recording_path,
+            &option.pty_request.term,
+            option.pty_request.height,
+            option.pty_request.width,
+        )?;
+        pm.with_shell_integration(ins.in_data.shell_integration);
+        Ok(Self {
+            record_id,
+            uniq_id,
+            pm,
+            option,
+        })
+    }
+
+    /// 执行并更新执行记录状态, 返回是否执行成功
+    async fn run(mut self, db: &DbConn) -> bool {
         let mut remark = String::new();
-        let status = match pm.run(Uuid::new_v4(), option).await {
+        let status = match self.pm.run(Uuid::new_v4(), self.option).await {
             Ok(em) => em,
             Err(e) => {
                 remark = e.to_string();
                 TaskStatusEnum::Error
             }
         };
-        let mut update_model = model::execute::Model::new();
-        update_model.id = uuid;
-        update_model.state = status as i32;
-        update_model.remark = remark;
-        let res = ExecuteRepo::update_execute_state(&state.conn, update_model).await;
-        match res {
-            Ok(_) => {}
-            Err(e) => {
-                error!("update state error: {:?}", e)
-            }
-        }
+        let success = matches!(status, TaskStatusEnum::Success);
+        update_execute_state(db, self.record_id, status, remark).await;
+        success
+    }
+}
+
+async fn update_execute_state(db: &DbConn, id: String, status: TaskStatusEnum, remark: String) {
+    let mut update_model = model::execute::Model::new();
+    update_model.id = id;
+    update_model.state = status as i32;
+    update_model.remark = remark;
+    if let Err(e) = ExecuteRepo::update_execute_state(db, update_model).await {
+        error!("update state error: {:?}", e)
+    }
+}
+
+pub async fn execute_instruct(
+    State(state): State<AppState>,
+    AppJson(data): AppJson<InstructExecuteCmd>,
+) -> Result<ResponseSuccess, AppError> {
+    let ins = ExecuteInstruct::load(&state.conn, data.id, data.name, data.replaces).await?;
+    let prepared =
+        PreparedExecute::new(&state.conn, &ins, ExecuteTarget::Node(data.node), "").await?;
+    let execute_uniq_id = prepared.uniq_id.clone();
+    let abort_sc = prepared.pm.get_abort_sc();
+    tokio::spawn(async move {
+        prepared.run(&state.conn).await;
     });
-    // step5. register global manager
+    // register global manager
     EXECUTE_MAP_MANAGER
         .write()
         .await
@@ -179,6 +272,103 @@ pub async fn execute_instruct(
     Ok(ResponseSuccess::default())
 }
 
+/// 在多个节点或资产组上分批执行指令, 返回父执行记录id, 停止父记录即停止全部目标
+pub async fn batch_execute_instruct(
+    State(state): State<AppState>,
+    AppJson(data): AppJson<InstructBatchExecuteCmd>,
+) -> Result<Response<String>, AppError> {
+    let ins = ExecuteInstruct::load(&state.conn, data.id, data.name, data.replaces).await?;
+    // step1. resolve targets
+    let mut targets = data
+        .nodes
+        .into_iter()
+        .map(ExecuteTarget::Node)
+        .collect::<Vec<_>>();
+    if let Some(group) = data.asset_group.filter(|g| !g.is_empty()) {
+        let assets = AssetRepo::find_assets_by_org(&state.conn, &group).await?;
+        let ids = assets.into_iter().map(|a| a.id).collect();
+        let accounts = CredentialRepo::find_credentials_by_assets(&state.conn, ids, "ssh").await?;
+        targets.extend(
+            accounts
+                .into_iter()
+                .map(|a| ExecuteTarget::Account(Box::new(a))),
+        );
+    }
+    if targets.is_empty() {
+        return Err(AppError::MsgError("no execute target".to_string()));
+    }
+    // step2. insert parent execute data
+    let mut model = model::execute::Model::new();
+    model.id = Uuid::new_v4().to_string();
+    model.name = ins.name.clone();
+    model.state = TaskStatusEnum::Init as i32;
+    model.instruct_id = ins.id.clone();
+    model.instruct_name = ins.instruct_name.clone();
+    model.node_name = format!("{} targets", targets.len());
+    model.replaces = ins.replaces.clone();
+    let parent_id = ExecuteRepo::insert_execute_one(&state.conn, model).await?;
+    let (abort_sc, abort_rc) = watch::channel(false);
+    EXECUTE_MAP_MANAGER
+        .write()
+        .await
+        .insert(parent_id.clone(), abort_sc);
+    // step3. run batches
+    let ins = Arc::new(ins);
+    let id = parent_id.clone();
+    tokio::spawn(async move {
+        let report = run_batches(targets, &data.policy, abort_rc.clone(), |target| {
+            let conn = state.conn.clone();
+            let ins = ins.clone();
+            let parent_id = id.clone();
+            let abort_rc = abort_rc.clone();
+            async move { run_batch_target(&conn, &ins, target, &parent_id, abort_rc).await }
+        })
+        .await;
+        let status = if report.aborted {
+            TaskStatusEnum::ManualStop
+        } else if report.failed > 0 || report.skipped > 0 {
+            TaskStatusEnum::Error
+        } else {
+            TaskStatusEnum::Success
+        };
+        update_execute_state(&state.conn, id.clone(), status, report.summary()).await;
+        EXECUTE_MAP_MANAGER.write().await.remove(&id);
+    });
+    Ok(Response::success(parent_id))
+}
+
+/// 执行单个目标, 父任务停止时一并停止
+async fn run_batch_target(
+    db: &DbConn,
+    ins: &ExecuteInstruct,
+    target: ExecuteTarget,
+    parent_id: &str,
+    mut parent_abort: watch::Receiver<bool>,
+) -> bool {
+    let prepared = match PreparedExecute::new(db, ins, target, parent_id).await {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            error!(parent_id, "prepare batch execute error: {:?}", e);
+            return false;
+        }
+    };
+    let uniq_id = prepared.uniq_id.clone();
+    let abort_sc = prepared.pm.get_abort_sc();
+    EXECUTE_MAP_MANAGER
+        .write()
+        .await
+        .insert(uniq_id.clone(), abort_sc.clone());
+    let forward = tokio::spawn(async move {
+        if parent_abort.wait_for(|abort| *abort).await.is_ok() {
+            let _ = abort_sc.send(true);
+        }
+    });
+    let success = prepared.run(db).await;
+    forward.abort();
+    EXECUTE_MAP_MANAGER.write().await.remove(&uniq_id);
+    success
+}
+
 /// stop execute task
 pub async fn stop_execute_by_id(
     State(state): State<AppState>,
@@ -0,0 +1,108 @@
+use crate::adapter::query::known_host::{KnownHostListQuery, SshAlgorithmListQuery};
+use crate::adapter::vo::known_host::{KnownHostListItemVO, SshAlgorithmListItemVO};
+use crate::adapter::{ResList, ResponseSuccess};
+use crate::config::AppState;
+use crate::error::AppError;
+use crate::repo::model::{known_host, ssh_algorithm};
+use crate::repo::sea::{KnownHostRepo, SeaRepo, SshAlgorithmRepo};
+use axum::extract::{Path, State};
+use axum::Json;
+use sea_orm::sea_query::ConditionExpression;
+use sea_orm::{ColumnTrait, Condition};
+
+pub async fn list_known_host(
+    State(state): State<AppState>,
+    Json(query): Json<KnownHostListQuery>,
+) -> Result<ResList<KnownHostListItemVO>, AppError> {
+    let mut search_option = Vec::new();
+    if let Some(host) = query.host {
+        if !host.is_empty() {
+            search_option.push(ConditionExpression::Condition(
+                Condition::all().add(known_host::Column::Host.contains(host)),
+            ))
+        }
+    }
+    KnownHostRepo::find_known_host_by(&state.conn, query.page_query.init(), Some(search_option))
+        .await
+        .map(|list| {
+            Ok(ResList::new(
+                list.0,
+                list.1
+                    .into_iter()
+                    .map(|d| KnownHostListItemVO {
+                        id: d.id,
+                        host: d.host,
+                        port: d.port,
+                        key_type: d.key_type,
+                        key_base64: d.key_base64,
+                        fingerprint: d.fingerprint,
+                        created_at: d.created_at,
+                    })
+                    .collect(),
+            ))
+        })?
+}
+
+/// 删除主机密钥, 主机密钥合法变更后需删除旧记录
+pub async fn delete_known_host_by_id(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<ResponseSuccess, AppError> {
+    SeaRepo::delete_by_id::<known_host::Entity>(&state.conn, &id)
+        .await
+        .map(|_| Ok(ResponseSuccess::default()))?
+}
+
+/// 各会话协商的ssh算法, 用于排查仍在使用弱算法的主机
+pub async fn list_ssh_algorithm(
+    State(state): State<AppState>,
+    Json(query): Json<SshAlgorithmListQuery>,
+) -> Result<ResList<SshAlgorithmListItemVO>, AppError> {
+    let mut search_option = Vec::new();
+    if let Some(host) = query.host {
+        if !host.is_empty() {
+            search_option.push(ConditionExpression::Condition(
+                Condition::all().add(ssh_algorithm::Column::Host.contains(host)),
+            ))
+        }
+    }
+    if let Some(session_id) = query.session_id {
+        if !session_id.is_empty() {
+            search_option.push(ConditionExpression::Condition(
+                Condition::all().add(ssh_algorithm::Column::SessionId.eq(session_id)),
+            ))
+        }
+    }
+    if query.weak_only {
+        search_option.push(ConditionExpression::Condition(
+            Condition::all().add(ssh_algorithm::Column::Weak.ne("")),
+        ))
+    }
+    SshAlgorithmRepo::find_ssh_algorithm_by(
+        &state.conn,
+        query.page_query.init(),
+        Some(search_option),
+    )
+    .await
+    .map(|list| {
+        Ok(ResList::new(
+            list.0,
+            list.1
+                .into_iter()
+                .map(|d| SshAlgorithmListItemVO {
+                    id: d.id,
+                    session_id: d.session_id,
+                    host: d.host,
+                    port: d.port,
+                    kex: d.kex,
+                    host_key: d.host_key,
+                    cipher: d.cipher,
+                    mac: d.mac,
+                    compression: d.compression,
+                    weak: d.weak,
+                    updated_at: d.updated_at,
+                })
+                .collect(),
+        ))
+    })?
+}
@@ -4,7 +4,9 @@ mod execute_handler;
 mod file_handler;
 mod guacamole_handler;
 mod instruct_handler;
+mod known_host_handler;
 mod node_handler;
+mod port_forward_handler;
 mod ssh_handler;
 mod user_handler;
 
@@ -14,6 +16,8 @@ pub use execute_handler::*;
 pub use file_handler::*;
 pub use guacamole_handler::*;
 pub use instruct_handler::*;
+pub use known_host_handler::*;
 pub use node_handler::*;
+pub use port_forward_handler::*;
 pub use ssh_handler::*;
 pub use user_handler::*;
@@ -3,6 +3,7 @@ use crate::adapter::query::node::NodeListQuery;
 use crate::adapter::vo::node::{NodeListItemVO, NodeVO};
 use crate::adapter::vo::BaseKV;
 use crate::adapter::{ResList, Response, ResponseSuccess};
+use crate::common::ProtocolOptions;
 use crate::config::AppState;
 use crate::error::{AppError, AppJson};
 use crate::repo::model::node;
@@ -22,6 +23,7 @@ pub async fn save_node(
     model.port = param.port;
     model.account = param.account;
     model.password = param.password;
+    model.options = serde_json::to_string(&param.options.unwrap_or_default())?;
     model.remark = param.remark;
     if let Some(id) = param.id {
         model.id = id;
@@ -35,16 +37,16 @@ pub async fn get_node_by_id(
     State(state): State<AppState>,
     Path(id): Path<String>,
 ) -> Result<Json<NodeVO>, AppError> {
-    NodeRepo::get_node_by_id(&state.conn, &id).await.map(|d| {
-        Ok(Json(NodeVO {
-            id,
-            name: d.name,
-            host: d.host,
-            account: d.account,
-            port: d.port,
-            remark: d.remark,
-        }))
-    })?
+    let d = NodeRepo::get_node_by_id(&state.conn, &id).await?;
+    Ok(Json(NodeVO {
+        id,
+        options: ProtocolOptions::parse(&d.options)?,
+        name: d.name,
+        host: d.host,
+        account: d.account,
+        port: d.port,
+        remark: d.remark,
+    }))
 }
 pub async fn list_node(
     State(state): State<AppState>,
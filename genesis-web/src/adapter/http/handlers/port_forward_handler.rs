@@ -0,0 +1,181 @@
+use crate::adapter::cmd::port_forward::{
+    DynamicPortForwardSaveCmd, PortForwardSaveCmd, RemotePortForwardSaveCmd,
+};
+use crate::adapter::http::middleware::auth::Context;
+use crate::adapter::query::port_forward::PortForwardListQuery;
+use crate::adapter::vo::port_forward::{PortForwardListItemVO, PortForwardVO};
+use crate::adapter::{ResList, Response, ResponseSuccess};
+use crate::config::AppState;
+use crate::error::{AppError, AppJson};
+use crate::repo::model::port_forward;
+use crate::repo::sea::PortForwardRepo;
+use crate::service::port_forward::{
+    resolve_bind_host, start_dynamic_port_forward, start_port_forward, start_remote_port_forward,
+    stop_port_forward,
+};
+use axum::extract::{Path, State};
+use axum::{Extension, Json};
+use genesis_ssh::{forward_manager, ForwardInfo, ForwardKind, SocksAuth, SocksOptions, SocksRule};
+use sea_orm::sea_query::ConditionExpression;
+use sea_orm::{ColumnTrait, Condition};
+
+impl From<ForwardInfo> for PortForwardVO {
+    fn from(f: ForwardInfo) -> Self {
+        PortForwardVO {
+            id: f.id.to_string(),
+            kind: match f.kind {
+                ForwardKind::Local => 0,
+                ForwardKind::Remote => 1,
+                ForwardKind::Dynamic => 2,
+            },
+            session_id: f.session_id.to_string(),
+            local_addr: f.local_addr,
+            remote_host: f.remote_host,
+            remote_port: f.remote_port,
+            connections: f.connections,
+            active: f.active,
+            bytes_sent: f.bytes_sent,
+            bytes_received: f.bytes_received,
+            created_at: chrono::DateTime::<chrono::Utc>::from(f.created_at).into(),
+        }
+    }
+}
+
+pub async fn save_port_forward(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<Context>,
+    AppJson(param): AppJson<PortForwardSaveCmd>,
+) -> Result<Json<Response<PortForwardVO>>, AppError> {
+    let bind_host = resolve_bind_host(param.bind_host.as_deref(), param.allow_wildcard_bind)?;
+    let bind = format!("{}:{}", bind_host, param.bind_port);
+    let forward = start_port_forward(
+        &state.conn,
+        &param.credential_id,
+        &bind,
+        param.remote_host,
+        param.remote_port,
+        &ctx.claims.username,
+    )
+    .await
+    .map_err(AppError::from_ssh)?;
+    Ok(Json(Response::success(forward.into())))
+}
+
+pub async fn save_remote_port_forward(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<Context>,
+    AppJson(param): AppJson<RemotePortForwardSaveCmd>,
+) -> Result<Json<Response<PortForwardVO>>, AppError> {
+    let forward = start_remote_port_forward(
+        &state.conn,
+        &param.credential_id,
+        resolve_bind_host(param.bind_host.as_deref(), param.allow_wildcard_bind)?,
+        param.bind_port,
+        param.local_host.unwrap_or_else(|| "127.0.0.1".to_string()),
+        param.local_port,
+        &ctx.claims.username,
+    )
+    .await
+    .map_err(AppError::from_ssh)?;
+    Ok(Json(Response::success(forward.into())))
+}
+
+pub async fn save_dynamic_port_forward(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<Context>,
+    AppJson(param): AppJson<DynamicPortForwardSaveCmd>,
+) -> Result<Json<Response<PortForwardVO>>, AppError> {
+    let bind_host = resolve_bind_host(param.bind_host.as_deref(), param.allow_wildcard_bind)?;
+    let bind = format!("{}:{}", bind_host, param.bind_port);
+    let auth = match param.username {
+        Some(username) if !username.is_empty() => Some(SocksAuth {
+            username,
+            password: param.password.unwrap_or_default(),
+        }),
+        _ => None,
+    };
+    let options = SocksOptions {
+        auth,
+        rules: param
+            .rules
+            .into_iter()
+            .map(|r| SocksRule {
+                allow: r.allow,
+                host: r.host,
+                port: r.port,
+            })
+            .collect(),
+        default_allow: param.default_allow.unwrap_or(false),
+        ..Default::default()
+    };
+    let forward = start_dynamic_port_forward(
+        &state.conn,
+        &param.credential_id,
+        &bind,
+        options,
+        &ctx.claims.username,
+    )
+    .await
+    .map_err(AppError::from_ssh)?;
+    Ok(Json(Response::success(forward.into())))
+}
+
+/// 运行中的端口转发
+pub async fn list_active_port_forward() -> Result<Json<Response<Vec<PortForwardVO>>>, AppError> {
+    let list = forward_manager().list().await;
+    Ok(Json(Response::success(
+        list.into_iter().map(PortForwardVO::from).collect(),
+    )))
+}
+
+/// 端口转发审计记录
+pub async fn list_port_forward(
+    State(state): State<AppState>,
+    Json(query): Json<PortForwardListQuery>,
+) -> Result<ResList<PortForwardListItemVO>, AppError> {
+    let mut search_option = Vec::new();
+    if let Some(host) = query.remote_host {
+        if !host.is_empty() {
+            search_option.push(ConditionExpression::Condition(
+                Condition::all().add(port_forward::Column::RemoteHost.contains(host)),
+            ))
+        }
+    }
+    if let Some(kind) = query.kind {
+        search_option.push(ConditionExpression::Condition(
+            Condition::all().add(port_forward::Column::Kind.eq(kind)),
+        ))
+    }
+    PortForwardRepo::find_port_forward_by(&state.conn, query.page_query.init(), Some(search_option))
+        .await
+        .map(|list| {
+            Ok(ResList::new(
+                list.0,
+                list.1
+                    .into_iter()
+                    .map(|d| PortForwardListItemVO {
+                        id: d.id,
+                        credential_id: d.credential_id,
+                        address: d.address,
+                        principal: d.principal,
+                        kind: d.kind,
+                        local_addr: d.local_addr,
+                        remote_host: d.remote_host,
+                        remote_port: d.remote_port,
+                        connections: d.connections,
+                        bytes_sent: d.bytes_sent,
+                        bytes_received: d.bytes_received,
+                        state: d.state,
+                        created_by: d.created_by,
+                        created_at: d.created_at,
+                        updated_at: d.updated_at,
+                    })
+                    .collect(),
+            ))
+        })?
+}
+
+pub async fn stop_port_forward_by_id(Path(id): Path<String>) -> Result<ResponseSuccess, AppError> {
+    stop_port_forward(&id).await?;
+    Ok(ResponseSuccess::default())
+}
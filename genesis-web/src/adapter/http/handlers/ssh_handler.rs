@@ -7,10 +7,13 @@ use axum::{
     response::Response,
 };
 use core::str;
+use std::collections::VecDeque;
+use std::future::Future;
 use std::sync::Arc;
 
-use crate::common::EnvelopeType;
+use crate::common::{AssetProtocolType, AuthPromptItemPayload, AuthPromptPayload, EnvelopeType};
 use crate::repo::sea::CredentialRepo;
+use crate::service::ssh::{build_target_telnet_options, build_terminal_ssh_options};
 use crate::{
     adapter::cmd::ssh::{ConnParams, SSHConnParams},
     common::{Envelope, SSHSessionCtx},
@@ -18,13 +21,18 @@ use crate::{
     error::AppError,
 };
 use futures_util::{
+    future::{BoxFuture, FutureExt},
     sink::SinkExt,
     stream::{SplitSink, SplitStream, StreamExt},
 };
-use genesis_common::{PtyRequest, SshTargetPasswordAuth, TargetSSHOptions};
+use genesis_common::PtyRequest;
 use genesis_process::{ExecuteState, SSHProcessManager};
-use genesis_ssh::{ChannelOperation, ServerExtraEnum};
-use tokio::sync::{broadcast, mpsc::UnboundedSender, watch, Mutex};
+use genesis_ssh::{
+    auth_prompt_relay, start_pooled_ssh_connect, start_telnet_connect_with_state, AuthPrompt,
+    ChannelOperation, ServerExtraEnum,
+};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::{broadcast, watch, Mutex};
 use tracing::{debug, error, info};
 use uuid::Uuid;
 
@@ -39,21 +47,27 @@ pub async fn handler_ssh(
         CredentialRepo::get_credential_by_id(&state.conn, &query.permission_id).await?;
 
     let uuid = Uuid::new_v4();
-    let option = TargetSSHOptions {
-        host: credential.address,
-        port: credential.port as u16,
-        username: credential.principal,
-        allow_insecure_algos: Some(true),
-        auth: genesis_common::SSHTargetAuth::Password(SshTargetPasswordAuth {
-            password: credential.credential,
-        }),
-        pty_request: PtyRequest {
-            term: query.term.clone(),
-            width: query.w,
-            height: query.h,
-        },
+    let pty_request = PtyRequest {
+        term: query.term.clone(),
+        width: query.w,
+        height: query.h,
+    };
+    let protocol = serde_json::from_value::<AssetProtocolType>(serde_json::Value::String(
+        credential.protocol.clone(),
+    ));
+    // telnet 与 ssh 共用录像及命令解析
+    let connect: BoxFuture<'static, anyhow::Result<_>> = match protocol {
+        Ok(AssetProtocolType::TELNET) => {
+            let option = build_target_telnet_options(&credential, pty_request)?;
+            start_telnet_connect_with_state(uuid, option, None).boxed()
+        }
+        _ => {
+            let option = build_terminal_ssh_options(&state.conn, credential, pty_request).await?;
+            // 同一目标及账户的终端复用连接池中已认证的连接
+            start_pooled_ssh_connect(uuid, option).boxed()
+        }
     };
-    // step2. connect
+    // step2. connect, 建立websocket后连接, 以便转交认证提示
     let mut ssh_manager = SSHProcessManager::new(uuid).with_recorder_param(
         &SHARED_APP_CONFIG.read().await.server.recording_path,
         &query.term,
@@ -62,11 +76,32 @@ pub async fn handler_ssh(
     )?;
     let abort_sc = ssh_manager.get_abort_sc();
     let abort_rc = ssh_manager.get_abort_rc();
-    let (server_sender, xs, see) = ssh_manager.run(option).await?;
     let res = ws.on_upgrade(move |socket| {
         let session_id = uuid;
         async move {
-            let (sender, receiver) = socket.split();
+            let (mut sender, mut receiver) = socket.split();
+            let mut prompts = auth_prompt_relay().register(session_id);
+            let connected = connect_with_prompt(
+                session_id,
+                async { anyhow::Ok(ssh_manager.attach(connect.await?).await) },
+                &mut sender,
+                &mut receiver,
+                &mut prompts,
+            )
+            .await;
+            auth_prompt_relay().remove(&session_id);
+            let (server_sender, xs, see) = match connected {
+                Ok(connected) => connected,
+                Err(e) => {
+                    error!(session_id=%session_id, "ssh connect error: {}", e);
+                    let response = AppError::from_ssh(e).to_error_response();
+                    if let Ok(payload) = serde_json::to_string(&response) {
+                        let _ = send_envelope(&mut sender, EnvelopeType::Error, payload).await;
+                    }
+                    let _ = sender.close().await;
+                    return;
+                }
+            };
             let s_c = SSHSessionCtx::new(session_id).with_on_close(Some(Box::new(move || {
                 let _ = abort_sc.send(true);
                 debug!(session_id=%session_id,"send close session channel")
@@ -85,6 +120,101 @@ pub async fn handler_ssh(
     Ok(res)
 }
 
+/// 等待连接完成, 期间将认证提示依次下发至客户端并按序转交应答
+///
+/// 仅在建立连接期间转交, 连接完成后注销; 断线重连时的认证提示无人应答, 按认证失败处理
+async fn connect_with_prompt<T>(
+    uuid: Uuid,
+    connect: impl Future<Output = anyhow::Result<T>>,
+    sender: &mut SplitSink<WebSocket, Message>,
+    receiver: &mut SplitStream<WebSocket>,
+    prompts: &mut UnboundedReceiver<AuthPrompt>,
+) -> anyhow::Result<T> {
+    tokio::pin!(connect);
+    // 待应答的提示及其下发内容, 队首为当前下发的提示, 应答格式错误时重新下发
+    let mut pending: VecDeque<(AuthPrompt, String)> = VecDeque::new();
+    loop {
+        tokio::select! {
+            connected = &mut connect => return connected,
+            Some(prompt) = prompts.recv() => {
+                let payload = serde_json::to_string(&AuthPromptPayload {
+                    name: prompt.name.clone(),
+                    instructions: prompt.instructions.clone(),
+                    prompts: prompt
+                        .prompts
+                        .iter()
+                        .map(|p| AuthPromptItemPayload {
+                            prompt: p.prompt.clone(),
+                            echo: p.echo,
+                        })
+                        .collect(),
+                })?;
+                if pending.is_empty() {
+                    send_envelope(sender, EnvelopeType::AuthPrompt, payload.clone()).await?;
+                    debug!(session_id=%uuid, "send auth prompt");
+                }
+                pending.push_back((prompt, payload));
+            }
+            ws_msg = receiver.next() => match ws_msg {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str::<Envelope>(&text) {
+                    Ok(Envelope { r#type: EnvelopeType::AuthPrompt, payload, .. }) => {
+                        let Some((prompt, prompt_payload)) = pending.pop_front() else {
+                            debug!(session_id=%uuid, "no pending auth prompt");
+                            continue;
+                        };
+                        match serde_json::from_str::<Vec<String>>(&payload) {
+                            Ok(answers) if answers.len() == prompt.prompts.len() => {
+                                prompt.answer(answers);
+                                if let Some((_, next)) = pending.front() {
+                                    send_envelope(sender, EnvelopeType::AuthPrompt, next.clone()).await?;
+                                    debug!(session_id=%uuid, "send auth prompt");
+                                }
+                            }
+                            answers => {
+                                let msg = match answers {
+                                    Ok(answers) => format!(
+                                        "expected {} auth prompt answers, got {}",
+                                        prompt.prompts.len(),
+                                        answers.len()
+                                    ),
+                                    Err(e) => format!("invalid auth prompt answer: {}", e),
+                                };
+                                debug!(session_id=%uuid, "{}", msg);
+                                let response = AppError::MsgError(msg).to_error_response();
+                                send_envelope(sender, EnvelopeType::Error, serde_json::to_string(&response)?).await?;
+                                send_envelope(sender, EnvelopeType::AuthPrompt, prompt_payload.clone()).await?;
+                                pending.push_front((prompt, prompt_payload));
+                            }
+                        }
+                    }
+                    Ok(env) => debug!(session_id=%uuid, "ignore message before connected: {:?}", env.r#type),
+                    Err(err) => debug!(session_id=%uuid, "serde deserialize error:{}", err),
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                    anyhow::bail!("websocket closed before ssh connected")
+                }
+                _ => {}
+            },
+        }
+    }
+}
+
+async fn send_envelope(
+    sender: &mut SplitSink<WebSocket, Message>,
+    r#type: EnvelopeType,
+    payload: String,
+) -> anyhow::Result<()> {
+    let envelope = Envelope {
+        version: "1.0".into(),
+        r#type,
+        payload,
+    };
+    sender
+        .send(Message::Text(serde_json::to_string(&envelope)?))
+        .await?;
+    Ok(())
+}
+
 async fn read_to_server(
     mut abort_rc: watch::Receiver<bool>,
     uuid: Uuid,
@@ -125,6 +255,9 @@ async fn read_to_server(
                                             })));
                                     debug!(session_id=%uuid,"received WindowSize message:{}",env.payload);
                                 }
+                                _ => {
+                                    debug!(session_id=%uuid,"ignore envelope type:{:?}",env.r#type);
+                                }
                             }
                             Err(err) => {
                                 debug!(session_id=%uuid,"serde deserialize error:{}",err);
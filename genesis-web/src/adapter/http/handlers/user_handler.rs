@@ -1,4 +1,4 @@
-use crate::adapter::cmd::user::{UserLoginCmd, UserRegisterCmd};
+use crate::adapter::cmd::user::{UserLoginCmd, UserPublicKeysCmd, UserRegisterCmd};
 use crate::adapter::http::middleware::auth::Context;
 use crate::adapter::vo::user::{LoginRes, UserVO};
 use crate::adapter::{Response, ResponseSuccess};
@@ -6,6 +6,7 @@ use crate::config::{AppState, SHARED_APP_CONFIG};
 use crate::error::{AppError, AppJson};
 use crate::repo::model::user;
 use crate::repo::sea::{SeaRepo, UserRepo};
+use crate::service::bastion::parse_authorized_keys;
 use crate::util::jwt;
 use axum::extract::{Path, State};
 use axum::{Extension, Json};
@@ -54,6 +55,7 @@ pub async fn user_register(
     State(state): State<AppState>,
     AppJson(data): AppJson<UserRegisterCmd>,
 ) -> Result<Json<ResponseSuccess>, AppError> {
+    parse_authorized_keys(&data.public_keys)?;
     let user = user::Model {
         id: Uuid::new_v4().to_string(),
         name: data.name,
@@ -62,12 +64,24 @@ pub async fn user_register(
         email: data.email,
         phone: data.phone,
         remark: data.remark,
+        public_keys: data.public_keys,
         ..Default::default()
     };
     UserRepo::insert_user_one(&state.conn, user).await?;
     Ok(Json(ResponseSuccess::default()))
 }
 
+/// 更新当前用户的ssh公钥
+pub async fn update_user_public_keys(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<Context>,
+    AppJson(data): AppJson<UserPublicKeysCmd>,
+) -> Result<Json<ResponseSuccess>, AppError> {
+    parse_authorized_keys(&data.public_keys)?;
+    UserRepo::update_user_public_keys(&state.conn, &ctx.claims.user_id, data.public_keys).await?;
+    Ok(Json(ResponseSuccess::default()))
+}
+
 pub async fn delete_user_by_id(
     State(state): State<AppState>,
     Path(id): Path<String>,
@@ -35,6 +35,8 @@ pub async fn routes(state: AppState) -> Router {
                 .route("/", post(save_instruct))
                 .route("/list", post(list_instruct))
                 .route("/execute", post(execute_instruct))
+                .route("/batch-execute", post(batch_execute_instruct))
+                .route("/dry-run", post(dry_run_instruct))
                 .route(
                     "/:id",
                     get(get_instruct_by_id).delete(delete_instruct_by_id),
@@ -61,12 +63,30 @@ pub async fn routes(state: AppState) -> Router {
             "/credential",
             Router::new()
                 .route("/", post(save_credential))
+                .route("/ca/public-key", get(get_ssh_ca_public_key))
                 .route(
                     "/:id",
                     get(get_credential_by_id).delete(delete_credential_by_id),
                 )
                 .route("/list", post(list_asset_credential)),
         )
+        .nest(
+            "/port-forward",
+            Router::new()
+                .route("/", post(save_port_forward))
+                .route("/remote", post(save_remote_port_forward))
+                .route("/dynamic", post(save_dynamic_port_forward))
+                .route("/active", get(list_active_port_forward))
+                .route("/:id", delete(stop_port_forward_by_id))
+                .route("/list", post(list_port_forward)),
+        )
+        .nest(
+            "/known-host",
+            Router::new()
+                .route("/:id", delete(delete_known_host_by_id))
+                .route("/list", post(list_known_host))
+                .route("/algorithm/list", post(list_ssh_algorithm)),
+        )
         .nest(
             "/execute",
             Router::new()
@@ -82,6 +102,7 @@ pub async fn routes(state: AppState) -> Router {
             "/user",
             Router::new()
                 .route("/info", get(user_info))
+                .route("/public-keys", post(update_user_public_keys))
                 .route("/:id", delete(delete_user_by_id)),
         )
         .layer(middleware::from_fn(jwt_auth_middle))
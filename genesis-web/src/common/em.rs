@@ -10,6 +10,12 @@ pub enum EnvelopeType {
     Raw,
     #[serde(rename = "w")]
     WindowSize,
+    /// 认证提示, 服务端下发提示, 客户端以json字符串数组应答
+    #[serde(rename = "a")]
+    AuthPrompt,
+    /// 连接错误, 负载为错误响应json
+    #[serde(rename = "e")]
+    Error,
 }
 
 #[derive(Serialize, Clone, Deserialize, Debug, Default, FromRepr, AsRefStr)]
@@ -49,6 +55,12 @@ pub enum AuthType {
     CertificateStr,
     #[serde(rename = "certificatePath")]
     CertificatePath,
+    /// 由内置CA按会话签发短期证书, 无需保存目标凭证
+    #[serde(rename = "ca")]
+    Ca,
+    /// 使用服务端 ssh-agent 中的密钥, 凭证内容为代理socket, 为空时使用 `SSH_AUTH_SOCK`
+    #[serde(rename = "agent")]
+    Agent,
 }
 
 #[derive(Serialize, Clone, Deserialize, Debug, Default, FromRepr, AsRefStr)]
@@ -1,8 +1,10 @@
 mod em;
+mod options;
 mod param;
 mod session;
 mod types;
 pub use em::*;
+pub use options::*;
 pub use param::*;
 pub use session::*;
 pub use types::*;
@@ -0,0 +1,122 @@
+//! protocol options
+
+use genesis_common::{
+    ElevationMode, HostKeyPolicy, ReconnectPolicy, SSHAgentForward, SSHAlgorithms, SSHElevation,
+    SSHEnvironment, SSHKeepalive, SSHRateLimit,
+};
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError};
+
+/// 资产协议扩展配置, 以json格式保存在 asset_protocol.options
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ProtocolOptions {
+    /// ssh主机密钥校验策略, 未配置时网页终端由用户确认未知密钥, 其余连接按 Tofu 处理
+    pub host_key_policy: Option<HostKeyPolicy>,
+    /// 跳板机凭证id, 按连接顺序
+    pub jump_hosts: Vec<String>,
+    /// ssh保活配置
+    pub keepalive: SSHKeepalive,
+    /// ssh断线重连策略
+    pub reconnect: ReconnectPolicy,
+    /// 未指定算法预置集时是否允许弱算法, 默认允许以兼容未配置的已有资产
+    pub allow_insecure_algos: bool,
+    /// ssh算法预置集及覆盖
+    pub algorithms: SSHAlgorithms,
+    /// CA签发证书配置
+    pub certificate: CertificateOptions,
+    /// ssh-agent 转发, 默认关闭
+    pub agent_forward: SSHAgentForward,
+    /// agent认证时仅尝试的密钥指纹(SHA256)
+    pub agent_fingerprints: Vec<String>,
+    /// 会话带宽限制, 默认不限制
+    pub rate_limit: SSHRateLimit,
+    /// shell会话环境变量及服务端 AcceptEnv
+    pub env: SSHEnvironment,
+    /// 命令提权配置
+    #[validate(nested)]
+    pub elevation: ElevationOptions,
+}
+
+impl Default for ProtocolOptions {
+    fn default() -> Self {
+        Self {
+            host_key_policy: Default::default(),
+            jump_hosts: Default::default(),
+            keepalive: Default::default(),
+            reconnect: Default::default(),
+            allow_insecure_algos: true,
+            algorithms: Default::default(),
+            certificate: Default::default(),
+            agent_forward: Default::default(),
+            agent_fingerprints: Default::default(),
+            rate_limit: Default::default(),
+            env: Default::default(),
+            elevation: Default::default(),
+        }
+    }
+}
+
+/// 命令提权配置, 提权密码与登录凭证分开保存
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ElevationOptions {
+    pub mode: ElevationMode,
+    /// 目标用户, 为空时为root
+    #[validate(custom(function = "validate_elevation_user"))]
+    pub user: Option<String>,
+    /// 提权密码所在的凭证id
+    pub credential_id: String,
+}
+
+fn validate_elevation_user(user: &str) -> Result<(), ValidationError> {
+    if user.is_empty() || SSHElevation::is_valid_user(user) {
+        return Ok(());
+    }
+    Err(ValidationError::new("elevation_user")
+        .with_message("elevation user must match [A-Za-z0-9._-]+".into()))
+}
+
+/// CA签发的会话证书参数
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CertificateOptions {
+    /// 有效期(秒), 为空时使用全局配置
+    pub validity: Option<u64>,
+    /// 证书授权账户, 为空时使用凭证账户
+    pub principals: Vec<String>,
+    /// 允许的客户端地址, CIDR格式
+    pub source_address: Vec<String>,
+    /// 强制执行的命令
+    pub force_command: Option<String>,
+}
+
+impl ProtocolOptions {
+    pub fn parse(options: &str) -> anyhow::Result<Self> {
+        if options.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_str(options)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_elevation_user() {
+        let parse = |user: &str| {
+            ProtocolOptions::parse(&format!(
+                r#"{{"elevation":{{"mode":"sudo","user":"{user}"}}}}"#
+            ))
+            .unwrap()
+        };
+        assert!(parse("app.svc_1-a").validate().is_ok());
+        assert!(parse("").validate().is_ok());
+        assert!(ProtocolOptions::default().validate().is_ok());
+        for user in ["app; reboot", "app user", "$(id)", "-c", "app'"] {
+            assert!(parse(user).validate().is_err(), "{user}");
+        }
+    }
+}
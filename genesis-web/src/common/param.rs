@@ -9,6 +9,22 @@ pub struct Envelope {
     pub payload: String,
 }
 
+/// 认证提示负载
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthPromptPayload {
+    pub name: String,
+    pub instructions: String,
+    pub prompts: Vec<AuthPromptItemPayload>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthPromptItemPayload {
+    pub prompt: String,
+    pub echo: bool,
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PageQuery {
@@ -1,6 +1,8 @@
 //! runtime pram
 
 use crate::common::{MemorySessionManager, SessionManagerTrait};
+use crate::service::known_hosts::{DbAlgorithmRecorder, DbKnownHostsStore};
+use crate::service::ssh_ca::{init_ssh_ca, DbCertificateIssuer};
 
 use super::{AppConfig, Db};
 use lazy_static::lazy_static;
@@ -32,15 +34,22 @@ pub static GLOBAL_MANAGER: Lazy<Arc<GlobalManager>> = Lazy::new(|| {
 pub struct AppState {
     pub conn: DatabaseConnection,
 }
-pub async fn init_shared_app_state(config: &AppConfig) -> Result<AppState, ()> {
+pub async fn init_shared_app_state(config: &AppConfig) -> anyhow::Result<AppState> {
     let mut state = AppState {
         conn: DatabaseConnection::Disconnected,
     };
     // step1. db connect
     state.conn = db_init(config)
         .await
-        .map_err(|e| format!("init db connect error: {e:?}"))
-        .unwrap();
+        .map_err(|e| anyhow::anyhow!("init db connect error: {e:?}"))?;
+    // step2. ssh known hosts
+    genesis_ssh::set_known_hosts_store(Arc::new(DbKnownHostsStore::new(state.conn.clone())));
+    genesis_ssh::set_algorithm_recorder(Arc::new(DbAlgorithmRecorder::new(state.conn.clone())));
+    // step3. ssh ca
+    if let Some(ca) = &config.ssh_ca {
+        init_ssh_ca(ca).map_err(|e| anyhow::anyhow!("init ssh ca error: {e:?}"))?;
+        genesis_ssh::set_certificate_issuer(Arc::new(DbCertificateIssuer::new(state.conn.clone())));
+    }
     let mut sas = SHARED_APP_STATE.write().await;
     *sas = state.clone();
     tracing::debug!("app state initialized");
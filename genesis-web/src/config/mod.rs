@@ -21,6 +21,10 @@ pub struct AppConfig {
     pub jwt_config: JwtConfig,
     #[serde(rename = "tracing")]
     pub tracing: Option<TracingConfig>,
+    #[serde(rename = "ssh_ca")]
+    pub ssh_ca: Option<SshCaConfig>,
+    #[serde(rename = "bastion")]
+    pub bastion: Option<BastionConfig>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -52,6 +56,38 @@ pub struct ServerConfig {
 pub struct TracingConfig {
     pub filter: String,
 }
+/// ssh证书颁发机构配置
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SshCaConfig {
+    /// CA私钥路径, OpenSSH 格式的 ed25519 密钥
+    pub private_key_path: String,
+    /// 证书默认有效期(秒)
+    #[serde(default = "default_cert_validity")]
+    pub validity: u64,
+}
+
+fn default_cert_validity() -> u64 {
+    300
+}
+
+/// 堡垒机配置, 客户端以 `ssh user%target@bastion` 登录
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BastionConfig {
+    /// 监听地址, 如 `0.0.0.0:2222`
+    pub addr: String,
+    /// 主机私钥路径, OpenSSH 格式
+    pub host_key_path: String,
+    /// 空闲超时(秒)
+    #[serde(default = "default_bastion_inactivity_timeout")]
+    pub inactivity_timeout: u64,
+}
+
+fn default_bastion_inactivity_timeout() -> u64 {
+    3600
+}
+
 impl ServerConfig {
     pub fn url(&self) -> String {
         format!("{}:{}", self.addr, self.port)
@@ -8,8 +8,11 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
+use genesis_process::Diagnostic;
+use genesis_ssh::ConnectionError;
 use sea_orm::DbErr;
 use serde::{de::DeserializeOwned, Serialize};
+use serde_json::json;
 use thiserror::Error;
 use validator::Validate;
 
@@ -30,6 +33,10 @@ pub enum AppError {
     AuthError(#[from] AuthError),
     #[error(transparent)]
     SerdeJsonError(#[from] serde_json::Error),
+    #[error(transparent)]
+    SshConnectionError(#[from] ConnectionError),
+    #[error("invalid instruct graph: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    InvalidGraph(Vec<Diagnostic>),
 }
 
 impl From<String> for AppError {
@@ -38,17 +45,68 @@ impl From<String> for AppError {
     }
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
+impl AppError {
+    pub fn to_error_response(&self) -> ErrorResponse {
         // 格式化时间为字符串，格式为：2022-02-02T18:12:23.443
-        AppJson(ErrorResponse {
-            code: StatusCode::BAD_REQUEST.as_u16(),
+        ErrorResponse {
+            code: self.code().as_u16(),
             msg: self.to_string(),
+            data: self.data(),
             timestamp: chrono::Local::now()
                 .format("%Y-%m-%dT%H:%M:%S%.3f")
                 .to_string(),
-        })
-        .into_response()
+        }
+    }
+
+    /// 还原ssh连接的具体错误
+    pub fn from_ssh(err: anyhow::Error) -> Self {
+        match err.downcast::<ConnectionError>() {
+            Ok(e) => AppError::SshConnectionError(e),
+            Err(e) => AppError::AnyHowError(e),
+        }
+    }
+
+    fn code(&self) -> StatusCode {
+        match self {
+            AppError::SshConnectionError(ConnectionError::HostKeyMismatch { .. }) => {
+                StatusCode::CONFLICT
+            }
+            AppError::SshConnectionError(ConnectionError::UnknownHostKey { .. }) => {
+                StatusCode::FORBIDDEN
+            }
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn data(&self) -> Option<serde_json::Value> {
+        match self {
+            AppError::SshConnectionError(ConnectionError::HostKeyMismatch {
+                received_key_type,
+                received_key_base64,
+                known_key_type,
+                known_key_base64,
+            }) => Some(json!({
+                "receivedKeyType": received_key_type,
+                "receivedKeyBase64": received_key_base64,
+                "knownKeyType": known_key_type,
+                "knownKeyBase64": known_key_base64,
+            })),
+            AppError::SshConnectionError(ConnectionError::UnknownHostKey {
+                key_type,
+                key_base64,
+            }) => Some(json!({
+                "keyType": key_type,
+                "keyBase64": key_base64,
+            })),
+            AppError::InvalidGraph(diagnostics) => serde_json::to_value(diagnostics).ok(),
+            _ => None,
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        AppJson(self.to_error_response()).into_response()
     }
 }
 
@@ -78,6 +136,8 @@ pub enum AuthError {
 pub struct ErrorResponse {
     code: u16,
     msg: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<serde_json::Value>,
     timestamp: String,
 }
 
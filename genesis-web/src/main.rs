@@ -26,7 +26,13 @@ async fn main() {
             tracing_initial(&config);
             // init state
             let state = init_shared_app_state(&config).await.unwrap();
-            // step2. start web
+            // step2. start ssh bastion
+            if let Some(bastion) = &config.bastion {
+                genesis_web::service::bastion::start_bastion(bastion, state.clone())
+                    .await
+                    .unwrap();
+            }
+            // step3. start web
             adapter::http::server::start_http_server(&config, state)
                 .await
                 .unwrap();
@@ -10,6 +10,7 @@ pub struct Model {
     pub id: String,
     pub principal: String,
     pub credential: String,
+    pub passphrase: String,
     pub asset_id: String,
     pub auth_type: String,
     pub protocol_id: String,
@@ -6,6 +6,8 @@ use serde::{Deserialize, Serialize};
 pub struct Model {
     #[sea_orm(primary_key)]
     pub id: String,
+    /// 批量执行时的父任务id
+    pub parent_id: String,
     pub name: String,
     pub node_id: String,
     pub node_name: String,
@@ -0,0 +1,32 @@
+use chrono::Local;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "known_host")]
+#[serde(default)]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: String,
+    pub host: String,
+    pub port: i32,
+    pub key_type: String,
+    pub key_base64: String,
+    pub fingerprint: String,
+    pub created_by: String,
+    pub updated_by: String,
+    pub created_at: chrono::DateTime<Local>,
+    pub updated_at: chrono::DateTime<Local>,
+    pub deleted: i8,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    pub fn new() -> Model {
+        Model::default()
+    }
+}
@@ -4,6 +4,10 @@ pub mod asset;
 pub mod credential;
 pub mod execute;
 pub mod instruct;
+pub mod known_host;
 pub mod node;
+pub mod port_forward;
+pub mod port_forward_destination;
 pub mod protocol;
+pub mod ssh_algorithm;
 pub mod user;
@@ -11,6 +11,7 @@ pub struct Model {
     pub port: i32,
     pub account: String,
     pub password: String,
+    pub options: String,
     pub created_by: String,
     pub updated_by: String,
     pub created_at: chrono::DateTime<Local>,
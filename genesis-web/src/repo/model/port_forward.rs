@@ -0,0 +1,43 @@
+use chrono::Local;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// 端口转发审计记录
+#[derive(Clone, Debug, Default, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "port_forward")]
+#[serde(default)]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: String,
+    pub credential_id: String,
+    pub address: String,
+    pub principal: String,
+    /// 0-本地转发, 1-远程转发, 2-动态转发
+    pub kind: i8,
+    /// 本地及动态转发为本地监听地址, 远程转发为本地目标地址
+    pub local_addr: String,
+    /// 本地转发为远端目标地址, 远程转发为服务端监听地址
+    pub remote_host: String,
+    pub remote_port: i32,
+    pub connections: i64,
+    pub bytes_sent: i64,
+    pub bytes_received: i64,
+    /// 0-转发中, 1-已关闭
+    pub state: i8,
+    pub created_by: String,
+    pub updated_by: String,
+    pub created_at: chrono::DateTime<Local>,
+    pub updated_at: chrono::DateTime<Local>,
+    pub deleted: i8,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    pub fn new() -> Model {
+        Model::default()
+    }
+}
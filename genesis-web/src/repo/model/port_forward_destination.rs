@@ -0,0 +1,35 @@
+use chrono::Local;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// 动态转发目标地址审计记录
+#[derive(Clone, Debug, Default, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "port_forward_destination")]
+#[serde(default)]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: String,
+    pub forward_id: String,
+    /// socks客户端地址
+    pub peer: String,
+    pub host: String,
+    pub port: i32,
+    /// 0-拒绝, 1-放行
+    pub allowed: i8,
+    pub created_by: String,
+    pub updated_by: String,
+    pub created_at: chrono::DateTime<Local>,
+    pub updated_at: chrono::DateTime<Local>,
+    pub deleted: i8,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    pub fn new() -> Model {
+        Model::default()
+    }
+}
@@ -11,6 +11,7 @@ pub struct Model {
     pub asset_id: String,
     pub protocol: String,
     pub port: i32,
+    pub options: String,
     pub status: i32,
     pub remark: String,
     pub created_by: String,
@@ -0,0 +1,38 @@
+use chrono::Local;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// 会话连接各主机时协商的ssh算法, 经跳板机时每一跳一条
+#[derive(Clone, Debug, Default, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "ssh_algorithm")]
+#[serde(default)]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: String,
+    pub session_id: String,
+    pub host: String,
+    pub port: i32,
+    pub kex: String,
+    pub host_key: String,
+    pub cipher: String,
+    pub mac: String,
+    pub compression: String,
+    /// 弱算法, 逗号分隔
+    pub weak: String,
+    pub created_by: String,
+    pub updated_by: String,
+    pub created_at: chrono::DateTime<Local>,
+    pub updated_at: chrono::DateTime<Local>,
+    pub deleted: i8,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    pub fn new() -> Model {
+        Model::default()
+    }
+}
@@ -13,6 +13,8 @@ pub struct Model {
     pub email: String,
     pub phone: String,
     pub remark: String,
+    /// ssh公钥, authorized_keys 格式, 每行一个
+    pub public_keys: String,
     pub created_by: String,
     pub updated_by: String,
     pub created_at: chrono::DateTime<Local>,
@@ -58,4 +58,14 @@ impl AssetRepo {
             .all(db)
             .await
     }
+
+    /// 查询组织下的全部资产
+    pub async fn find_assets_by_org(db: &DbConn, org_id: &str) -> Result<Vec<asset::Model>, DbErr> {
+        asset::Entity::find()
+            .filter(asset::Column::Deleted.eq(0))
+            .filter(asset::Column::OrgId.eq(org_id))
+            .order_by(asset::Column::CreatedAt, Order::Asc)
+            .all(db)
+            .await
+    }
 }
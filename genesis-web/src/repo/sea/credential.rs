@@ -41,6 +41,12 @@ impl CredentialRepo {
         if !model.credential.is_empty() {
             active_model.credential = Set(model.credential);
         }
+        if !model.passphrase.is_empty() {
+            active_model.passphrase = Set(model.passphrase);
+        }
+        if !model.auth_type.is_empty() {
+            active_model.auth_type = Set(model.auth_type);
+        }
         if !model.protocol_id.is_empty() {
             active_model.protocol_id = Set(model.protocol_id);
         }
@@ -75,4 +81,19 @@ impl CredentialRepo {
             .all(db)
             .await
     }
+
+    /// 查询资产下指定协议的账号
+    pub async fn find_credentials_by_assets(
+        db: &DbConn,
+        asset_ids: Vec<String>,
+        protocol: &str,
+    ) -> Result<Vec<credential::Model>, DbErr> {
+        credential::Entity::find()
+            .filter(credential::Column::Deleted.eq(0))
+            .filter(credential::Column::AssetId.is_in(asset_ids))
+            .filter(credential::Column::Protocol.eq(protocol))
+            .order_by(credential::Column::CreatedAt, Order::Asc)
+            .all(db)
+            .await
+    }
 }
@@ -0,0 +1,50 @@
+//! known host repo
+
+use crate::repo::model::known_host;
+use crate::repo::sea::SeaRepo;
+use sea_orm::sea_query::ConditionExpression;
+use sea_orm::{ColumnTrait, DbConn, DbErr, EntityTrait, QueryFilter};
+
+pub struct KnownHostRepo;
+
+impl KnownHostRepo {
+    pub async fn insert_known_host_one(
+        db: &DbConn,
+        data: known_host::Model,
+    ) -> anyhow::Result<String> {
+        SeaRepo::insert_with_default::<known_host::Entity, _>(db, data).await
+    }
+
+    pub async fn find_known_host_by_address(
+        db: &DbConn,
+        host: &str,
+        port: i32,
+    ) -> Result<Vec<known_host::Model>, DbErr> {
+        known_host::Entity::find()
+            .filter(known_host::Column::Host.eq(host))
+            .filter(known_host::Column::Port.eq(port))
+            .filter(known_host::Column::Deleted.eq(0))
+            .all(db)
+            .await
+    }
+
+    /// 删除主机已记录的密钥
+    pub async fn delete_known_host_by_address(
+        db: &DbConn,
+        host: &str,
+        port: i32,
+    ) -> anyhow::Result<()> {
+        for known in Self::find_known_host_by_address(db, host, port).await? {
+            SeaRepo::delete_by_id::<known_host::Entity>(db, &known.id).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn find_known_host_by(
+        db: &DbConn,
+        pg: (u64, u64),
+        search: Option<Vec<ConditionExpression>>,
+    ) -> anyhow::Result<(u64, Vec<known_host::Model>)> {
+        SeaRepo::page_with_default::<known_host::Entity>(db, pg, search).await
+    }
+}
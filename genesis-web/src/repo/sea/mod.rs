@@ -19,16 +19,22 @@ mod asset;
 mod builder;
 mod credential;
 mod execute;
+mod known_host;
 mod node;
+mod port_forward;
 mod protocol;
+mod ssh_algorithm;
 mod user;
 
 pub use asset::*;
 pub use builder::*;
 pub use credential::*;
 pub use execute::*;
+pub use known_host::*;
 pub use node::*;
+pub use port_forward::*;
 pub use protocol::*;
+pub use ssh_algorithm::*;
 pub use user::*;
 
 pub(crate) struct SeaRepo;
@@ -536,6 +542,8 @@ mod tests {
             db_config: Default::default(),
             jwt_config: Default::default(),
             tracing: Default::default(),
+            ssh_ca: Default::default(),
+            bastion: Default::default(),
         };
         let mysql_config = MysqlConfig {
             host: "127.0.0.1:13306".to_string(),
@@ -25,6 +25,7 @@ impl NodeRepo {
             password: Set(model.password),
             port: Set(model.port),
             account: Set(model.account),
+            options: Set(model.options),
             remark: Set(model.remark),
             ..Default::default()
         };
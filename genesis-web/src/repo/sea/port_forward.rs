@@ -0,0 +1,52 @@
+//! port forward repo
+
+use crate::repo::model::{port_forward, port_forward_destination};
+use crate::repo::sea::SeaRepo;
+use sea_orm::sea_query::ConditionExpression;
+use sea_orm::ActiveValue::Set;
+use sea_orm::DbConn;
+
+pub struct PortForwardRepo;
+
+impl PortForwardRepo {
+    pub async fn insert_port_forward_one(
+        db: &DbConn,
+        data: port_forward::Model,
+    ) -> anyhow::Result<String> {
+        SeaRepo::insert_with_default::<port_forward::Entity, _>(db, data).await
+    }
+
+    pub async fn insert_port_forward_destination_one(
+        db: &DbConn,
+        data: port_forward_destination::Model,
+    ) -> anyhow::Result<String> {
+        SeaRepo::insert_with_default::<port_forward_destination::Entity, _>(db, data).await
+    }
+
+    /// 转发关闭后记录最终流量
+    pub async fn update_port_forward_closed(
+        db: &DbConn,
+        id: &str,
+        connections: i64,
+        bytes_sent: i64,
+        bytes_received: i64,
+    ) -> anyhow::Result<port_forward::Model> {
+        let active_model = port_forward::ActiveModel {
+            id: Set(id.to_string()),
+            connections: Set(connections),
+            bytes_sent: Set(bytes_sent),
+            bytes_received: Set(bytes_received),
+            state: Set(1),
+            ..Default::default()
+        };
+        SeaRepo::update_with_default::<port_forward::Entity>(db, active_model).await
+    }
+
+    pub async fn find_port_forward_by(
+        db: &DbConn,
+        pg: (u64, u64),
+        search: Option<Vec<ConditionExpression>>,
+    ) -> anyhow::Result<(u64, Vec<port_forward::Model>)> {
+        SeaRepo::page_with_default::<port_forward::Entity>(db, pg, search).await
+    }
+}
@@ -0,0 +1,25 @@
+//! ssh algorithm repo
+
+use crate::repo::model::ssh_algorithm;
+use crate::repo::sea::SeaRepo;
+use sea_orm::sea_query::ConditionExpression;
+use sea_orm::DbConn;
+
+pub struct SshAlgorithmRepo;
+
+impl SshAlgorithmRepo {
+    pub async fn insert_ssh_algorithm_one(
+        db: &DbConn,
+        data: ssh_algorithm::Model,
+    ) -> anyhow::Result<String> {
+        SeaRepo::insert_with_default::<ssh_algorithm::Entity, _>(db, data).await
+    }
+
+    pub async fn find_ssh_algorithm_by(
+        db: &DbConn,
+        pg: (u64, u64),
+        search: Option<Vec<ConditionExpression>>,
+    ) -> anyhow::Result<(u64, Vec<ssh_algorithm::Model>)> {
+        SeaRepo::page_with_default::<ssh_algorithm::Entity>(db, pg, search).await
+    }
+}
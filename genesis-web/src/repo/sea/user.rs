@@ -27,6 +27,19 @@ impl UserRepo {
         SeaRepo::update_with_default::<user::Entity>(db, active_model).await
     }
 
+    pub async fn update_user_public_keys(
+        db: &DbConn,
+        id: &str,
+        public_keys: String,
+    ) -> anyhow::Result<user::Model> {
+        let active_model = user::ActiveModel {
+            id: Set(id.to_string()),
+            public_keys: Set(public_keys),
+            ..Default::default()
+        };
+        SeaRepo::update_with_default::<user::Entity>(db, active_model).await
+    }
+
     pub async fn insert_user_one(db: &DbConn, data: user::Model) -> anyhow::Result<String> {
         SeaRepo::insert_with_default::<user::Entity, _>(db, data).await
     }
@@ -0,0 +1,232 @@
+//! ssh bastion, 以 `ssh user%credentialId@bastion` 登录目标
+
+use crate::common::SSHSessionCtx;
+use crate::config::{AppState, BastionConfig, GLOBAL_MANAGER, SHARED_APP_CONFIG};
+use crate::repo::model::user;
+use crate::repo::sea::{CredentialRepo, UserRepo};
+use crate::service::ssh::build_target_ssh_options;
+use axum::async_trait;
+use genesis_common::NotifyEnum;
+use genesis_process::{ExecuteState, SSHProcessManager};
+use genesis_ssh::keys::{decode_secret_key, PublicKey};
+use genesis_ssh::{
+    BastionHandler, BastionOptions, BastionServer, BastionShell, BastionUser, PtyRequest,
+    ServerExtraEnum,
+};
+use sea_orm::{DbConn, DbErr};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc::unbounded_channel;
+use tokio::sync::{oneshot, Mutex};
+use tracing::{debug, error, info};
+use uuid::Uuid;
+
+/// 解析 authorized_keys 格式的公钥, 忽略空行及注释
+pub fn parse_authorized_keys(data: &str) -> anyhow::Result<Vec<PublicKey>> {
+    data.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            PublicKey::from_openssh(line).map_err(|e| anyhow::anyhow!("invalid public key: {e}"))
+        })
+        .collect()
+}
+
+pub struct WebBastionHandler {
+    conn: DbConn,
+}
+
+impl WebBastionHandler {
+    pub fn new(conn: DbConn) -> Self {
+        Self { conn }
+    }
+
+    async fn find_user(&self, username: &str) -> anyhow::Result<Option<user::Model>> {
+        match UserRepo::find_user_by_username(&self.conn, username).await {
+            Ok(user) if user.deleted == 0 => Ok(Some(user)),
+            Ok(_) | Err(DbErr::RecordNotFound(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+fn bastion_user(user: user::Model) -> BastionUser {
+    BastionUser {
+        id: user.id,
+        username: user.username,
+    }
+}
+
+#[async_trait]
+impl BastionHandler for WebBastionHandler {
+    async fn auth_password(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> anyhow::Result<Option<BastionUser>> {
+        Ok(self
+            .find_user(username)
+            .await?
+            .filter(|user| !user.password.is_empty() && user.password == password)
+            .map(bastion_user))
+    }
+
+    async fn auth_publickey(
+        &self,
+        username: &str,
+        key: &PublicKey,
+    ) -> anyhow::Result<Option<BastionUser>> {
+        let Some(user) = self.find_user(username).await? else {
+            return Ok(None);
+        };
+        // 单个公钥格式错误不影响其他公钥
+        let authorized = user
+            .public_keys
+            .lines()
+            .filter_map(|line| PublicKey::from_openssh(line.trim()).ok())
+            .any(|k| k.key_data() == key.key_data());
+        Ok(authorized.then(|| bastion_user(user)))
+    }
+
+    async fn open_shell(
+        &self,
+        session_id: Uuid,
+        user: &BastionUser,
+        target: &str,
+        peer: Option<SocketAddr>,
+        pty: PtyRequest,
+    ) -> anyhow::Result<BastionShell> {
+        let credential = CredentialRepo::get_credential_by_id(&self.conn, target).await?;
+        let option = build_target_ssh_options(
+            &self.conn,
+            credential,
+            genesis_common::PtyRequest {
+                term: pty.term.clone(),
+                width: pty.col_width,
+                height: pty.row_height,
+            },
+        )
+        .await?;
+        // 与web终端一致的录像及命令审计
+        let mut ssh_manager = SSHProcessManager::new(session_id).with_recorder_param(
+            &SHARED_APP_CONFIG.read().await.server.recording_path,
+            &pty.term,
+            pty.row_height,
+            pty.col_width,
+        )?;
+        let abort_sc = ssh_manager.get_abort_sc();
+        let mut abort_rc = ssh_manager.get_abort_rc();
+        let (input, mut states, see) = ssh_manager.run(option).await?;
+        let notify = ssh_manager.get_notify_rc();
+        info!(session_id=%session_id, user=%user.username, target, peer=?peer, "bastion session start");
+
+        let s_c = SSHSessionCtx::new(session_id).with_on_close(Some(Box::new({
+            let abort_sc = abort_sc.clone();
+            move || {
+                let _ = abort_sc.send(true);
+                debug!(session_id=%session_id, "send close session channel")
+            }
+        })));
+        let _ = GLOBAL_MANAGER
+            .session_manager
+            .register(session_id, Arc::new(Mutex::new(s_c)))
+            .await;
+
+        let (output_sc, output) = unbounded_channel();
+        let (exit_sc, exit_status) = oneshot::channel();
+        let username = user.username.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    state = states.recv() => match state {
+                        Ok(ExecuteState::ExecutedBytes(bytes)) => {
+                            if output_sc.send(bytes).is_err() {
+                                break;
+                            }
+                        }
+                        Ok(ExecuteState::ExecutedCmd(cmd)) => {
+                            info!(session_id=%session_id, user=%username, "bastion executed cmd:{:?}", cmd);
+                        }
+                        Ok(_) => {}
+                        Err(RecvError::Lagged(n)) => {
+                            debug!(session_id=%session_id, "bastion output lagged: {}", n);
+                        }
+                        Err(RecvError::Closed) => break,
+                    },
+                    _ = abort_rc.changed() => {
+                        if *abort_rc.borrow() {
+                            break;
+                        }
+                    }
+                }
+            }
+            // 先于输出结束发送退出码
+            let code = notify.and_then(|notify| match *notify.borrow() {
+                NotifyEnum::EXIT(code) => Some(code),
+                _ => None,
+            });
+            if let Some(code) = code {
+                let _ = exit_sc.send(code);
+            }
+            drop(output_sc);
+            let _ = GLOBAL_MANAGER.session_manager.remove(session_id).await;
+            info!(session_id=%session_id, ?code, "bastion session end");
+        });
+
+        let (control, mut control_rc) = unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(op) = control_rc.recv().await {
+                match op {
+                    ServerExtraEnum::Disconnect => break,
+                    op => {
+                        let _ = see.send(op);
+                    }
+                }
+            }
+            let _ = see.send(ServerExtraEnum::Disconnect);
+            let _ = abort_sc.send(true);
+        });
+        Ok(BastionShell {
+            input,
+            output,
+            control,
+            exit_status,
+        })
+    }
+}
+
+/// 启动堡垒机, 监听失败时返回错误, 之后在后台运行
+pub async fn start_bastion(config: &BastionConfig, state: AppState) -> anyhow::Result<()> {
+    let pem = std::fs::read_to_string(&config.host_key_path)?;
+    let key = decode_secret_key(&pem, None)?;
+    let listener = TcpListener::bind(&config.addr).await?;
+    let options = BastionOptions {
+        keys: vec![key],
+        inactivity_timeout: Some(Duration::from_secs(config.inactivity_timeout)),
+        ..Default::default()
+    };
+    let server = BastionServer::new(Arc::new(WebBastionHandler::new(state.conn)));
+    tokio::spawn(async move {
+        if let Err(e) = server.run(listener, options).await {
+            error!("bastion server error: {:?}", e);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_authorized_keys() {
+        let data = "# genesis\n\nssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAILh0U0W4gkUM0JohvQyrAKed92ofcE2s+Wba+mp0D+gU alice@laptop\n";
+        let keys = parse_authorized_keys(data).unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].comment(), "alice@laptop");
+        assert!(parse_authorized_keys("ssh-ed25519 invalid").is_err());
+    }
+}
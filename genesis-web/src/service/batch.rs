@@ -0,0 +1,209 @@
+//! 批量执行调度, 按批次滚动执行, 批次内限制并发
+
+use std::future::Future;
+
+use futures_util::stream::FuturesUnordered;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+/// 目标执行失败后的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FailurePolicy {
+    /// 不再启动新的目标, 已启动的目标执行完毕
+    #[default]
+    Stop,
+    /// 继续执行其余目标
+    Continue,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BatchPolicy {
+    /// 批次内最大并发数
+    pub max_concurrency: usize,
+    /// 每批目标数, 为0时不限
+    pub batch_size: usize,
+    /// 每批目标占比, 1-100, 优先于batch_size
+    pub batch_percent: u8,
+    pub on_failure: FailurePolicy,
+}
+
+impl Default for BatchPolicy {
+    fn default() -> Self {
+        Self {
+            max_concurrency: 10,
+            batch_size: 0,
+            batch_percent: 0,
+            on_failure: FailurePolicy::default(),
+        }
+    }
+}
+
+impl BatchPolicy {
+    /// 每批目标数, 至少为1
+    pub fn batch_len(&self, total: usize) -> usize {
+        let len = match (self.batch_percent.min(100), self.batch_size) {
+            (0, 0) => total,
+            (0, size) => size,
+            (percent, _) => (total * percent as usize).div_ceil(100),
+        };
+        len.max(1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchReport {
+    pub success: usize,
+    pub failed: usize,
+    /// 因失败或中止未执行的目标数
+    pub skipped: usize,
+    pub aborted: bool,
+}
+
+impl BatchReport {
+    pub fn summary(&self) -> String {
+        format!(
+            "success: {}, failed: {}, skipped: {}",
+            self.success, self.failed, self.skipped
+        )
+    }
+}
+
+/// 按批次执行目标, 上一批全部结束后开始下一批, `run` 返回目标是否执行成功
+pub async fn run_batches<T, F, Fut>(
+    targets: Vec<T>,
+    policy: &BatchPolicy,
+    abort: watch::Receiver<bool>,
+    run: F,
+) -> BatchReport
+where
+    F: Fn(T) -> Fut,
+    Fut: Future<Output = bool>,
+{
+    let mut report = BatchReport::default();
+    let total = targets.len();
+    let batch_len = policy.batch_len(total);
+    let max_concurrency = policy.max_concurrency.max(1);
+    let mut pending = targets.into_iter();
+    let mut stopped = false;
+    while !stopped && pending.len() > 0 {
+        let mut batch = pending
+            .by_ref()
+            .take(batch_len)
+            .collect::<Vec<_>>()
+            .into_iter();
+        let mut running = FuturesUnordered::new();
+        loop {
+            if *abort.borrow() {
+                report.aborted = true;
+                stopped = true;
+            }
+            while !stopped && running.len() < max_concurrency {
+                match batch.next() {
+                    Some(target) => running.push(run(target)),
+                    None => break,
+                }
+            }
+            let Some(success) = running.next().await else {
+                break;
+            };
+            if success {
+                report.success += 1;
+            } else {
+                report.failed += 1;
+                stopped |= policy.on_failure == FailurePolicy::Stop;
+            }
+        }
+        report.skipped += batch.len();
+    }
+    report.skipped += pending.len();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_batch_len() {
+        let mut policy = BatchPolicy::default();
+        assert_eq!(policy.batch_len(300), 300);
+        policy.batch_size = 20;
+        assert_eq!(policy.batch_len(300), 20);
+        policy.batch_percent = 10;
+        assert_eq!(policy.batch_len(300), 30);
+        assert_eq!(policy.batch_len(5), 1);
+        assert_eq!(policy.batch_len(0), 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_batches() {
+        let (_abort_sc, abort_rc) = watch::channel(false);
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let policy = BatchPolicy {
+            max_concurrency: 3,
+            batch_size: 5,
+            on_failure: FailurePolicy::Continue,
+            ..Default::default()
+        };
+        let report = run_batches((0..12).collect(), &policy, abort_rc.clone(), |i: usize| {
+            let running = running.clone();
+            let peak = peak.clone();
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                !i.is_multiple_of(4)
+            }
+        })
+        .await;
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+        assert_eq!((report.success, report.failed, report.skipped), (9, 3, 0));
+
+        // 第一批中的失败使后续批次跳过
+        let policy = BatchPolicy {
+            max_concurrency: 1,
+            batch_percent: 50,
+            ..Default::default()
+        };
+        let report = run_batches(
+            (0..10).collect(),
+            &policy,
+            abort_rc,
+            |i: usize| async move { i != 2 },
+        )
+        .await;
+        assert_eq!((report.success, report.failed, report.skipped), (2, 1, 7));
+    }
+
+    #[tokio::test]
+    async fn test_run_batches_abort() {
+        let (abort_sc, abort_rc) = watch::channel(false);
+        let policy = BatchPolicy {
+            max_concurrency: 2,
+            ..Default::default()
+        };
+        let report = run_batches((0..6).collect(), &policy, abort_rc, |i: usize| {
+            let abort_sc = abort_sc.clone();
+            async move {
+                if i == 0 {
+                    let _ = abort_sc.send(true);
+                } else {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                true
+            }
+        })
+        .await;
+        assert!(report.aborted);
+        assert_eq!((report.success, report.skipped), (2, 4));
+    }
+}
@@ -0,0 +1,88 @@
+//! ssh known hosts store
+
+use crate::repo::model::{known_host, ssh_algorithm};
+use crate::repo::sea::{KnownHostRepo, SshAlgorithmRepo};
+use async_trait::async_trait;
+use genesis_common::SessionId;
+use genesis_ssh::keys::{parse_public_key_base64, HashAlg, PublicKey, PublicKeyBase64};
+use genesis_ssh::{AlgorithmRecorder, KnownHostsStore, NegotiatedAlgorithms};
+use sea_orm::DatabaseConnection;
+use tracing::warn;
+
+/// 基于数据库的主机密钥存储
+pub struct DbKnownHostsStore {
+    conn: DatabaseConnection,
+}
+
+impl DbKnownHostsStore {
+    pub fn new(conn: DatabaseConnection) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait]
+impl KnownHostsStore for DbKnownHostsStore {
+    async fn lookup(&self, host: &str, port: u16) -> anyhow::Result<Vec<PublicKey>> {
+        let list = KnownHostRepo::find_known_host_by_address(&self.conn, host, port as i32).await?;
+        Ok(list
+            .into_iter()
+            .filter_map(|d| match parse_public_key_base64(&d.key_base64) {
+                Ok(key) => Some(key),
+                Err(e) => {
+                    warn!(id = d.id, "parse known host key error: {}", e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    async fn save(&self, host: &str, port: u16, key: &PublicKey) -> anyhow::Result<()> {
+        let mut model = known_host::Model::new();
+        model.host = host.to_string();
+        model.port = port as i32;
+        model.key_type = key.algorithm().to_string();
+        model.key_base64 = key.public_key_base64();
+        model.fingerprint = key.fingerprint(HashAlg::Sha256).to_string();
+        KnownHostRepo::insert_known_host_one(&self.conn, model).await?;
+        Ok(())
+    }
+
+    async fn remove(&self, host: &str, port: u16) -> anyhow::Result<()> {
+        KnownHostRepo::delete_known_host_by_address(&self.conn, host, port as i32).await
+    }
+}
+
+/// 基于数据库的算法协商记录, 每个会话记录一条
+pub struct DbAlgorithmRecorder {
+    conn: DatabaseConnection,
+}
+
+impl DbAlgorithmRecorder {
+    pub fn new(conn: DatabaseConnection) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait]
+impl AlgorithmRecorder for DbAlgorithmRecorder {
+    async fn record(
+        &self,
+        session_id: SessionId,
+        host: &str,
+        port: u16,
+        algorithms: &NegotiatedAlgorithms,
+    ) -> anyhow::Result<()> {
+        let mut model = ssh_algorithm::Model::new();
+        model.session_id = session_id.to_string();
+        model.host = host.to_string();
+        model.port = port as i32;
+        model.kex = algorithms.kex.clone();
+        model.host_key = algorithms.host_key.clone();
+        model.cipher = algorithms.cipher.clone();
+        model.mac = algorithms.mac.clone();
+        model.compression = algorithms.compression.clone();
+        model.weak = algorithms.weak().join(",");
+        SshAlgorithmRepo::insert_ssh_algorithm_one(&self.conn, model).await?;
+        Ok(())
+    }
+}
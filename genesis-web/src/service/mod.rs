@@ -1 +1,7 @@
+pub mod bastion;
+pub mod batch;
 pub mod guacamole;
+pub mod known_hosts;
+pub mod port_forward;
+pub mod ssh;
+pub mod ssh_ca;
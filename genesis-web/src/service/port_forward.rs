@@ -0,0 +1,206 @@
+//! port forward service
+
+use std::net::IpAddr;
+
+use crate::repo::model::{port_forward, port_forward_destination};
+use crate::repo::sea::{CredentialRepo, PortForwardRepo};
+use crate::service::ssh::build_target_ssh_options;
+use genesis_common::TargetSSHOptions;
+use genesis_ssh::{forward_manager, ForwardInfo, ForwardKind, SocksOptions};
+use sea_orm::DbConn;
+use tokio::sync::mpsc;
+use tracing::{error, info};
+use uuid::Uuid;
+
+const DEFAULT_BIND_HOST: &str = "127.0.0.1";
+
+/// 未指定时监听本机回环地址, 监听全部地址需显式开启
+pub fn resolve_bind_host(host: Option<&str>, allow_wildcard: bool) -> anyhow::Result<String> {
+    let host = match host.map(str::trim) {
+        None | Some("") => return Ok(DEFAULT_BIND_HOST.to_string()),
+        Some(host) => host,
+    };
+    let wildcard = host == "*"
+        || host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .is_ok_and(|ip| ip.is_unspecified());
+    if wildcard && !allow_wildcard {
+        anyhow::bail!("binding {} requires allowWildcardBind", host);
+    }
+    Ok(host.to_string())
+}
+
+/// 经凭证对应的ssh连接建立本地端口转发, 并记录审计信息
+pub async fn start_port_forward(
+    db: &DbConn,
+    credential_id: &str,
+    bind: &str,
+    remote_host: String,
+    remote_port: u16,
+    operator: &str,
+) -> anyhow::Result<ForwardInfo> {
+    let (model, option) = prepare_port_forward(db, credential_id).await?;
+    let forward = forward_manager()
+        .start(Uuid::new_v4(), option, bind, remote_host, remote_port)
+        .await?;
+    record_port_forward(db, model, &forward, operator).await?;
+    Ok(forward)
+}
+
+/// 经凭证对应的ssh连接建立远程端口转发, 服务端 `bind_host:bind_port` 的连接转发至本地目标
+pub async fn start_remote_port_forward(
+    db: &DbConn,
+    credential_id: &str,
+    bind_host: String,
+    bind_port: u16,
+    local_host: String,
+    local_port: u16,
+    operator: &str,
+) -> anyhow::Result<ForwardInfo> {
+    let (model, option) = prepare_port_forward(db, credential_id).await?;
+    let forward = forward_manager()
+        .start_remote(
+            Uuid::new_v4(),
+            option,
+            bind_host,
+            bind_port,
+            local_host,
+            local_port,
+        )
+        .await?;
+    record_port_forward(db, model, &forward, operator).await?;
+    Ok(forward)
+}
+
+/// 经凭证对应的ssh连接启动本地socks5代理, 每个目标地址记录一条审计
+pub async fn start_dynamic_port_forward(
+    db: &DbConn,
+    credential_id: &str,
+    bind: &str,
+    mut options: SocksOptions,
+    operator: &str,
+) -> anyhow::Result<ForwardInfo> {
+    let (model, option) = prepare_port_forward(db, credential_id).await?;
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    options.audit = Some(tx);
+    let forward = forward_manager()
+        .start_dynamic(Uuid::new_v4(), option, bind, options)
+        .await?;
+    record_port_forward(db, model, &forward, operator).await?;
+    let db = db.clone();
+    let forward_id = forward.id.to_string();
+    let operator = operator.to_string();
+    // 代理停止后发送端全部释放, 任务随之结束
+    tokio::spawn(async move {
+        while let Some(destination) = rx.recv().await {
+            let mut model = port_forward_destination::Model::new();
+            model.forward_id = forward_id.clone();
+            model.peer = destination.peer.to_string();
+            model.host = destination.host;
+            model.port = destination.port as i32;
+            model.allowed = destination.allowed as i8;
+            model.created_by = operator.clone();
+            model.updated_by = operator.clone();
+            if let Err(e) = PortForwardRepo::insert_port_forward_destination_one(&db, model).await {
+                error!(forward=%forward_id, "insert socks destination record error: {}", e);
+            }
+        }
+    });
+    Ok(forward)
+}
+
+async fn prepare_port_forward(
+    db: &DbConn,
+    credential_id: &str,
+) -> anyhow::Result<(port_forward::Model, TargetSSHOptions)> {
+    let credential = CredentialRepo::get_credential_by_id(db, credential_id).await?;
+    let mut model = port_forward::Model::new();
+    model.credential_id = credential.id.clone();
+    model.address = format!("{}:{}", credential.address, credential.port);
+    model.principal = credential.principal.clone();
+    let option = build_target_ssh_options(db, credential, Default::default()).await?;
+    Ok((model, option))
+}
+
+/// 记录审计信息, 转发停止后回写流量统计
+async fn record_port_forward(
+    db: &DbConn,
+    mut model: port_forward::Model,
+    forward: &ForwardInfo,
+    operator: &str,
+) -> anyhow::Result<()> {
+    let manager = forward_manager();
+    model.id = forward.id.to_string();
+    model.kind = match forward.kind {
+        ForwardKind::Local => 0,
+        ForwardKind::Remote => 1,
+        ForwardKind::Dynamic => 2,
+    };
+    model.local_addr = forward.local_addr.clone();
+    model.remote_host = forward.remote_host.clone();
+    model.remote_port = forward.remote_port as i32;
+    model.created_by = operator.to_string();
+    model.updated_by = operator.to_string();
+    if let Err(e) = PortForwardRepo::insert_port_forward_one(db, model).await {
+        // 未记录审计的转发不允许继续使用
+        manager.close(&forward.id).await;
+        return Err(e);
+    }
+    if let Some(stopped) = manager.stopped(&forward.id).await {
+        let db = db.clone();
+        let id = forward.id;
+        tokio::spawn(async move {
+            stopped.cancelled().await;
+            let Some(info) = forward_manager().close(&id).await else {
+                return;
+            };
+            info!(forward=%id, bytes_sent=info.bytes_sent, bytes_received=info.bytes_received, "port forward stopped");
+            if let Err(e) = PortForwardRepo::update_port_forward_closed(
+                &db,
+                &id.to_string(),
+                info.connections as i64,
+                info.bytes_sent as i64,
+                info.bytes_received as i64,
+            )
+            .await
+            {
+                error!(forward=%id, "update port forward record error: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// 停止转发, 统计信息由转发启动时的任务回写
+pub async fn stop_port_forward(id: &str) -> anyhow::Result<()> {
+    let id = Uuid::parse_str(id)?;
+    match forward_manager().stopped(&id).await {
+        Some(stopped) => {
+            stopped.cancel();
+            Ok(())
+        }
+        None => anyhow::bail!("port forward {} not found", id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_bind_host() {
+        assert_eq!(resolve_bind_host(None, false).unwrap(), "127.0.0.1");
+        assert_eq!(resolve_bind_host(Some(" "), false).unwrap(), "127.0.0.1");
+        assert_eq!(resolve_bind_host(Some("::1"), false).unwrap(), "::1");
+        assert_eq!(
+            resolve_bind_host(Some("10.0.0.1"), false).unwrap(),
+            "10.0.0.1"
+        );
+        for host in ["0.0.0.0", "::", "[::]", "*"] {
+            assert!(resolve_bind_host(Some(host), false).is_err());
+            assert_eq!(resolve_bind_host(Some(host), true).unwrap(), host);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! ssh connection options
+
+use crate::common::{AuthType, ElevationOptions, ProtocolOptions};
+use crate::repo::model::{credential, node};
+use crate::repo::sea::{CredentialRepo, ProtocolRepo};
+use crate::service::ssh_ca;
+use genesis_common::{
+    HostKeyPolicy, PtyRequest, SSHElevation, SSHJumpHost, SSHTargetAuth, SshTargetAgentAuth,
+    SshTargetPasswordAuth, SshTargetPublicKeyAuth, TargetSSHOptions, TargetTelnetOptions,
+    TargetTelnetOptionsBuilder,
+};
+use genesis_ssh::LocalTunnel;
+use sea_orm::{DbConn, DbErr};
+use uuid::Uuid;
+
+/// 根据凭证类型构造ssh认证方式
+pub fn credential_auth(
+    credential: &credential::Model,
+    options: &ProtocolOptions,
+) -> anyhow::Result<SSHTargetAuth> {
+    let auth_type = match credential.auth_type.as_str() {
+        "" => AuthType::Password,
+        at => serde_json::from_value::<AuthType>(serde_json::Value::String(at.to_string()))
+            .map_err(|_| anyhow::anyhow!("unsupported auth type: {}", at))?,
+    };
+    let passphrase = Some(credential.passphrase.clone()).filter(|p| !p.is_empty());
+    let auth = match auth_type {
+        AuthType::Password => SSHTargetAuth::Password(SshTargetPasswordAuth {
+            password: credential.credential.clone(),
+        }),
+        AuthType::CertificateStr => SSHTargetAuth::PublicKey(SshTargetPublicKeyAuth {
+            private_key: Some(credential.credential.clone()),
+            private_key_path: None,
+            passphrase,
+        }),
+        AuthType::CertificatePath => SSHTargetAuth::PublicKey(SshTargetPublicKeyAuth {
+            private_key: None,
+            private_key_path: Some(credential.credential.clone()),
+            passphrase,
+        }),
+        AuthType::Ca => ssh_ca::issue_certificate(credential, &options.certificate)?,
+        AuthType::Agent => SSHTargetAuth::Agent(SshTargetAgentAuth {
+            agent_socket: credential.credential.clone(),
+            fingerprints: options.agent_fingerprints.clone(),
+        }),
+    };
+    Ok(auth)
+}
+
+/// 查询凭证关联的资产协议配置
+pub async fn protocol_options(db: &DbConn, protocol_id: &str) -> anyhow::Result<ProtocolOptions> {
+    if protocol_id.is_empty() {
+        return Ok(ProtocolOptions::default());
+    }
+    match ProtocolRepo::get_protocol_by_id(db, protocol_id).await {
+        Ok(protocol) => ProtocolOptions::parse(&protocol.options),
+        Err(DbErr::RecordNotFound(_)) => Ok(ProtocolOptions::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// 根据跳板机凭证id构造跳板机列表, 跳板机自身的跳板配置不生效
+pub async fn resolve_jump_hosts(
+    db: &DbConn,
+    credential_ids: &[String],
+) -> anyhow::Result<Vec<SSHJumpHost>> {
+    let mut jump_hosts = Vec::with_capacity(credential_ids.len());
+    for id in credential_ids {
+        let credential = CredentialRepo::get_credential_by_id(db, id)
+            .await
+            .map_err(|e| anyhow::anyhow!("jump host credential {} error: {}", id, e))?;
+        let options = protocol_options(db, &credential.protocol_id).await?;
+        jump_hosts.push(SSHJumpHost {
+            auth: credential_auth(&credential, &options)?,
+            host: credential.address,
+            port: credential.port as u16,
+            username: credential.principal,
+            host_key_policy: options.host_key_policy.unwrap_or_default(),
+        });
+    }
+    Ok(jump_hosts)
+}
+
+/// 根据提权配置读取提权凭证, 未配置凭证时遇到密码提示将执行失败
+pub async fn resolve_elevation(
+    db: &DbConn,
+    options: &ElevationOptions,
+) -> anyhow::Result<SSHElevation> {
+    let password = match options.credential_id.as_str() {
+        "" => None,
+        id => {
+            let credential = CredentialRepo::get_credential_by_id(db, id)
+                .await
+                .map_err(|e| anyhow::anyhow!("elevation credential {} error: {}", id, e))?;
+            Some(credential.credential)
+        }
+    };
+    Ok(SSHElevation {
+        mode: options.mode,
+        user: options.user.clone(),
+        password,
+    })
+}
+
+/// 根据凭证及资产协议配置构造ssh连接参数
+pub async fn build_target_ssh_options(
+    db: &DbConn,
+    credential: credential::Model,
+    pty_request: PtyRequest,
+) -> anyhow::Result<TargetSSHOptions> {
+    target_ssh_options(db, credential, pty_request, HostKeyPolicy::default()).await
+}
+
+/// 构造网页终端的ssh连接参数, 资产未配置主机密钥策略时由用户确认未知密钥
+pub async fn build_terminal_ssh_options(
+    db: &DbConn,
+    credential: credential::Model,
+    pty_request: PtyRequest,
+) -> anyhow::Result<TargetSSHOptions> {
+    target_ssh_options(db, credential, pty_request, HostKeyPolicy::Ask).await
+}
+
+async fn target_ssh_options(
+    db: &DbConn,
+    credential: credential::Model,
+    pty_request: PtyRequest,
+    default_host_key_policy: HostKeyPolicy,
+) -> anyhow::Result<TargetSSHOptions> {
+    let options = protocol_options(db, &credential.protocol_id).await?;
+    let auth = credential_auth(&credential, &options)?;
+    Ok(TargetSSHOptions {
+        host: credential.address,
+        port: credential.port as u16,
+        username: credential.principal,
+        allow_insecure_algos: Some(options.allow_insecure_algos),
+        algorithms: options.algorithms,
+        auth,
+        pty_request,
+        host_key_policy: options.host_key_policy.unwrap_or(default_host_key_policy),
+        jump_hosts: resolve_jump_hosts(db, &options.jump_hosts).await?,
+        keepalive: options.keepalive,
+        reconnect: options.reconnect,
+        agent_forward: options.agent_forward,
+        rate_limit: options.rate_limit,
+        env: options.env,
+        elevation: resolve_elevation(db, &options.elevation).await?,
+    })
+}
+
+/// 根据凭证构造telnet连接参数, 登录由用户在终端中完成
+pub fn build_target_telnet_options(
+    credential: &credential::Model,
+    pty_request: PtyRequest,
+) -> anyhow::Result<TargetTelnetOptions> {
+    Ok(TargetTelnetOptionsBuilder::default()
+        .host(credential.address.clone())
+        .port(credential.port as u16)
+        .pty_request(pty_request)
+        .build()?)
+}
+
+/// 根据节点配置构造ssh连接参数
+pub async fn build_node_ssh_options(
+    db: &DbConn,
+    node: &node::Model,
+) -> anyhow::Result<TargetSSHOptions> {
+    let options = ProtocolOptions::parse(&node.options)?;
+    Ok(TargetSSHOptions {
+        host: node.host.clone(),
+        port: node.port as u16,
+        username: node.account.clone(),
+        allow_insecure_algos: Some(options.allow_insecure_algos),
+        algorithms: options.algorithms,
+        auth: SSHTargetAuth::Password(SshTargetPasswordAuth {
+            password: node.password.clone(),
+        }),
+        // TODO pty param
+        pty_request: Default::default(),
+        host_key_policy: options.host_key_policy.unwrap_or_default(),
+        jump_hosts: resolve_jump_hosts(db, &options.jump_hosts).await?,
+        keepalive: options.keepalive,
+        reconnect: options.reconnect,
+        agent_forward: options.agent_forward,
+        rate_limit: options.rate_limit,
+        env: options.env,
+        elevation: resolve_elevation(db, &options.elevation).await?,
+    })
+}
+
+/// 资产配置了跳板机时, 经跳板机建立本地隧道, 供guacd等非ssh协议访问目标
+pub async fn start_jump_tunnel(
+    db: &DbConn,
+    id: Uuid,
+    credential: &credential::Model,
+) -> anyhow::Result<Option<LocalTunnel>> {
+    let options = protocol_options(db, &credential.protocol_id).await?;
+    let mut jump_hosts = resolve_jump_hosts(db, &options.jump_hosts).await?;
+    let Some(last) = jump_hosts.pop() else {
+        return Ok(None);
+    };
+    let option = TargetSSHOptions {
+        host: last.host,
+        port: last.port,
+        username: last.username,
+        allow_insecure_algos: Some(options.allow_insecure_algos),
+        algorithms: options.algorithms,
+        auth: last.auth,
+        host_key_policy: last.host_key_policy,
+        jump_hosts,
+        keepalive: options.keepalive,
+        ..Default::default()
+    };
+    let tunnel = LocalTunnel::start(
+        id,
+        option,
+        "127.0.0.1:0",
+        credential.address.clone(),
+        credential.port as u16,
+    )
+    .await?;
+    Ok(Some(tunnel))
+}
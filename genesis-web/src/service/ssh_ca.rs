@@ -0,0 +1,100 @@
+//! ssh certificate authority
+
+use crate::common::CertificateOptions;
+use crate::config::SshCaConfig;
+use crate::repo::model::credential;
+use crate::repo::sea::CredentialRepo;
+use crate::service::ssh::protocol_options;
+use async_trait::async_trait;
+use genesis_common::{SSHTargetAuth, SshTargetCertificateAuth};
+use genesis_crypto::{SshCa, UserCertRequest};
+use genesis_ssh::CertificateIssuer;
+use sea_orm::DatabaseConnection;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::info;
+use uuid::Uuid;
+
+struct CaState {
+    ca: SshCa,
+    validity: Duration,
+}
+
+static SSH_CA: OnceLock<CaState> = OnceLock::new();
+
+/// 加载CA私钥, 仅首次调用生效
+pub fn init_ssh_ca(config: &SshCaConfig) -> anyhow::Result<()> {
+    let pem = std::fs::read_to_string(&config.private_key_path)?;
+    let ca = SshCa::from_openssh(pem)?;
+    info!(public_key = ca.public_key()?, "ssh ca loaded");
+    let _ = SSH_CA.set(CaState {
+        ca,
+        validity: Duration::from_secs(config.validity),
+    });
+    Ok(())
+}
+
+/// CA公钥, 需配置到目标主机的 `TrustedUserCAKeys`
+pub fn ca_public_key() -> anyhow::Result<String> {
+    SSH_CA
+        .get()
+        .ok_or_else(|| anyhow::anyhow!("ssh ca is not configured"))?
+        .ca
+        .public_key()
+}
+
+/// 为凭证签发会话证书, 每次调用生成独立的密钥
+pub fn issue_certificate(
+    credential: &credential::Model,
+    options: &CertificateOptions,
+) -> anyhow::Result<SSHTargetAuth> {
+    let state = SSH_CA
+        .get()
+        .ok_or_else(|| anyhow::anyhow!("ssh ca is not configured"))?;
+    let validity = options
+        .validity
+        .map(Duration::from_secs)
+        .unwrap_or(state.validity);
+    let key_id = format!("genesis-{}-{}", credential.id, Uuid::new_v4());
+    let mut request = UserCertRequest::new(key_id.clone(), credential.principal.clone(), validity);
+    if !options.principals.is_empty() {
+        request.principals = options.principals.clone();
+    }
+    request.source_address = options.source_address.clone();
+    request.force_command = options.force_command.clone();
+    let session = state.ca.issue_session(&request)?;
+    info!(
+        key_id,
+        principals = ?request.principals,
+        valid_before = session.valid_before,
+        "ssh certificate issued"
+    );
+    Ok(SSHTargetAuth::Certificate(SshTargetCertificateAuth {
+        private_key: session.private_key,
+        certificate: session.certificate,
+        credential_id: Some(credential.id.clone()),
+    }))
+}
+
+/// 连接及重连时按凭证重新签发即将失效的证书
+pub struct DbCertificateIssuer {
+    conn: DatabaseConnection,
+}
+
+impl DbCertificateIssuer {
+    pub fn new(conn: DatabaseConnection) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait]
+impl CertificateIssuer for DbCertificateIssuer {
+    async fn issue(&self, credential_id: &str) -> anyhow::Result<SshTargetCertificateAuth> {
+        let credential = CredentialRepo::get_credential_by_id(&self.conn, credential_id).await?;
+        let options = protocol_options(&self.conn, &credential.protocol_id).await?;
+        match issue_certificate(&credential, &options.certificate)? {
+            SSHTargetAuth::Certificate(auth) => Ok(auth),
+            _ => anyhow::bail!("unexpected auth issued for credential {}", credential_id),
+        }
+    }
+}
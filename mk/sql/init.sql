@@ -26,6 +26,7 @@ CREATE TABLE `user`
     `phone`     varchar(64)  CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '电话',
     `password`     varchar(1024) CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '密码',
     `remark`       varchar(1024) CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '描述',
+    `public_keys`  text CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL COMMENT 'ssh公钥, authorized_keys 格式',
     `created_by`        varchar(128) CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci   NOT NULL DEFAULT '' COMMENT '创建人',
     `updated_by`        varchar(128) CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci   NOT NULL DEFAULT '' COMMENT '更新人',
     `created_at`        datetime                                                        NOT NULL DEFAULT CURRENT_TIMESTAMP COMMENT 'create time',
@@ -44,6 +45,7 @@ CREATE TABLE `node`
     `port`      int  NOT NULL DEFAULT '22' COMMENT '端口',
     `account`   varchar(128)  CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '账户',
     `password`     varchar(128) CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '密码',
+    `options`      varchar(4096) CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '连接扩展配置(json)',
     `created_by`        varchar(128) CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci   NOT NULL DEFAULT '' COMMENT '创建人',
     `updated_by`        varchar(128) CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci   NOT NULL DEFAULT '' COMMENT '更新人',
     `remark`            varchar(1024) CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '描述',
@@ -59,6 +61,7 @@ DROP TABLE IF EXISTS `execute_task`;
 CREATE TABLE `execute_task`
 (
     `id`             varchar(128)        NOT NULL COMMENT '主键',
+    `parent_id`      varchar(128)    CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '批量执行父任务ID',
     `instruct_id`    varchar(128)    CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '流程ID',
     `name`  varchar(128)    CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '任务名',
     `instruct_name`  varchar(128)    CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '流程名快照',
@@ -107,6 +110,7 @@ CREATE TABLE `asset_protocol`
     `asset_id`       varchar(64)    CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '关联资产ID',
     `protocol`       varchar(32)     CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '协议类型,如 ssh/rdp/vnc',
     `port`           int             NOT NULL DEFAULT 0 COMMENT '端口',
+    `options`        varchar(4096)   CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '协议扩展配置(json)',
     `status`         int  NOT NULL DEFAULT '0' COMMENT '状态',
     `remark`         varchar(1024) CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '描述',
     `created_by`     varchar(128) CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci   NOT NULL DEFAULT '' COMMENT '创建人',
@@ -118,13 +122,57 @@ CREATE TABLE `asset_protocol`
 ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_general_ci COMMENT='资产协议表';
 
 
+-- ssh主机密钥表
+DROP TABLE IF EXISTS `known_host`;
+CREATE TABLE `known_host`
+(
+    `id`             varchar(64)     NOT NULL COMMENT '主键',
+    `host`           varchar(128)    CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '主机地址',
+    `port`           int             NOT NULL DEFAULT 22 COMMENT '端口',
+    `key_type`       varchar(64)     CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '密钥类型',
+    `key_base64`     varchar(4096)   CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '公钥',
+    `fingerprint`    varchar(128)    CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '公钥指纹(SHA256)',
+    `created_by`     varchar(128) CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci   NOT NULL DEFAULT '' COMMENT '创建人',
+    `updated_by`     varchar(128) CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci   NOT NULL DEFAULT '' COMMENT '更新人',
+    `created_at`     datetime                                                        NOT NULL DEFAULT CURRENT_TIMESTAMP COMMENT 'create time',
+    `updated_at`     datetime                                                        NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT 'update time',
+    `deleted`        tinyint                                                         NOT NULL DEFAULT '0' COMMENT '是否删除，0-否，1-是',
+    PRIMARY KEY (`id`),
+    KEY `idx_host_port` (`host`, `port`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_general_ci COMMENT='ssh主机密钥表';
+
+DROP TABLE IF EXISTS `ssh_algorithm`;
+CREATE TABLE `ssh_algorithm`
+(
+    `id`             varchar(64)     NOT NULL COMMENT '主键',
+    `session_id`     varchar(64)     CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '会话ID',
+    `host`           varchar(128)    CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '主机地址',
+    `port`           int             NOT NULL DEFAULT 22 COMMENT '端口',
+    `kex`            varchar(128)    CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '密钥交换算法',
+    `host_key`       varchar(128)    CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '主机密钥算法',
+    `cipher`         varchar(128)    CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '加密算法',
+    `mac`            varchar(128)    CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT 'mac算法',
+    `compression`    varchar(64)     CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '压缩算法',
+    `weak`           varchar(512)    CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '弱算法, 逗号分隔',
+    `created_by`     varchar(128) CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci   NOT NULL DEFAULT '' COMMENT '创建人',
+    `updated_by`     varchar(128) CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci   NOT NULL DEFAULT '' COMMENT '更新人',
+    `created_at`     datetime                                                        NOT NULL DEFAULT CURRENT_TIMESTAMP COMMENT 'create time',
+    `updated_at`     datetime                                                        NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT 'update time',
+    `deleted`        tinyint                                                         NOT NULL DEFAULT '0' COMMENT '是否删除，0-否，1-是',
+    PRIMARY KEY (`id`),
+    KEY `idx_session_id` (`session_id`),
+    KEY `idx_host_port` (`host`, `port`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_general_ci COMMENT='ssh算法协商记录表';
+
+
 -- 资产账号协议表
 DROP TABLE IF EXISTS `asset_account`;
 CREATE TABLE `asset_account`
 (
     `id`                varchar(64)     NOT NULL COMMENT '主键',
     `principal`         varchar(32)     CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '身份',
-    `credential`        varchar(512)    CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '凭证',
+    `credential`        varchar(8192)   CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '凭证,密码/私钥内容/私钥路径',
+    `passphrase`        varchar(512)    CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '私钥密码',
     `asset_id`          varchar(64)     CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '关联资产ID',
     `auth_type`         varchar(32)     CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '凭证类型',
     `address`        varchar(128)    CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '资产地址',
@@ -140,3 +188,46 @@ CREATE TABLE `asset_account`
     `deleted`        tinyint                                                         NOT NULL DEFAULT '0' COMMENT '是否删除，0-否，1-是',
     PRIMARY KEY (`id`)
 ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_general_ci COMMENT='资产账号协议表';
+
+-- 端口转发审计表
+DROP TABLE IF EXISTS `port_forward`;
+CREATE TABLE `port_forward`
+(
+    `id`             varchar(64)     NOT NULL COMMENT '主键',
+    `credential_id`  varchar(64)     CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '凭证ID',
+    `address`        varchar(128)    CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT 'ssh地址',
+    `principal`      varchar(128)    CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT 'ssh账户',
+    `kind`           tinyint         NOT NULL DEFAULT 0 COMMENT '转发类型，0-本地转发，1-远程转发，2-动态转发',
+    `local_addr`     varchar(128)    CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '本地监听地址/本地目标地址',
+    `remote_host`    varchar(128)    CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '远端目标主机/服务端监听地址',
+    `remote_port`    int             NOT NULL DEFAULT 0 COMMENT '远端目标端口/服务端监听端口',
+    `connections`    bigint          NOT NULL DEFAULT 0 COMMENT '累计连接数',
+    `bytes_sent`     bigint          NOT NULL DEFAULT 0 COMMENT '发送字节数',
+    `bytes_received` bigint          NOT NULL DEFAULT 0 COMMENT '接收字节数',
+    `state`          tinyint         NOT NULL DEFAULT 0 COMMENT '状态，0-转发中，1-已关闭',
+    `created_by`     varchar(128) CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci   NOT NULL DEFAULT '' COMMENT '创建人',
+    `updated_by`     varchar(128) CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci   NOT NULL DEFAULT '' COMMENT '更新人',
+    `created_at`     datetime                                                        NOT NULL DEFAULT CURRENT_TIMESTAMP COMMENT 'create time',
+    `updated_at`     datetime                                                        NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT 'update time',
+    `deleted`        tinyint                                                         NOT NULL DEFAULT '0' COMMENT '是否删除，0-否，1-是',
+    PRIMARY KEY (`id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_general_ci COMMENT='端口转发审计表';
+
+-- 动态转发目标审计表
+DROP TABLE IF EXISTS `port_forward_destination`;
+CREATE TABLE `port_forward_destination`
+(
+    `id`             varchar(64)     NOT NULL COMMENT '主键',
+    `forward_id`     varchar(64)     CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '端口转发ID',
+    `peer`           varchar(128)    CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT 'socks客户端地址',
+    `host`           varchar(255)    CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci  NOT NULL DEFAULT '' COMMENT '目标主机',
+    `port`           int             NOT NULL DEFAULT 0 COMMENT '目标端口',
+    `allowed`        tinyint         NOT NULL DEFAULT 0 COMMENT '是否放行，0-拒绝，1-放行',
+    `created_by`     varchar(128) CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci   NOT NULL DEFAULT '' COMMENT '创建人',
+    `updated_by`     varchar(128) CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci   NOT NULL DEFAULT '' COMMENT '更新人',
+    `created_at`     datetime                                                        NOT NULL DEFAULT CURRENT_TIMESTAMP COMMENT 'create time',
+    `updated_at`     datetime                                                        NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT 'update time',
+    `deleted`        tinyint                                                         NOT NULL DEFAULT '0' COMMENT '是否删除，0-否，1-是',
+    PRIMARY KEY (`id`),
+    KEY `idx_forward_id` (`forward_id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_general_ci COMMENT='动态转发目标审计表';